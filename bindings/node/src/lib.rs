@@ -0,0 +1,67 @@
+// Node.js bindings for RSL, so that links can be resolved and cleaned in-process (e.g.,
+// from an Electron app)
+//
+// Build with `npm run build`, which produces `rsl.<platform>.node` and its JavaScript
+// loader.
+
+use std::sync::OnceLock;
+
+use napi::{Env, Error, Result, Task, bindgen_prelude::AsyncTask};
+use napi_derive::napi;
+use rsl::Resolver;
+use tokio::runtime::Runtime;
+
+// The runtime and resolver are shared between calls, so that connections are reused
+fn runtime() -> Result<&'static Runtime> {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    if let Some(runtime) = RUNTIME.get() {
+        return Ok(runtime);
+    }
+    let runtime = Runtime::new().map_err(|e| Error::from_reason(e.to_string()))?;
+    Ok(RUNTIME.get_or_init(|| runtime))
+}
+
+fn resolver() -> Result<&'static Resolver> {
+    static RESOLVER: OnceLock<Resolver> = OnceLock::new();
+    if let Some(resolver) = RESOLVER.get() {
+        return Ok(resolver);
+    }
+    let resolver = Resolver::new().map_err(|e| Error::from_reason(e.to_string()))?;
+    Ok(RESOLVER.get_or_init(|| resolver))
+}
+
+// Resolution runs on libuv's thread pool, blocking on our own runtime, rather than on
+// the JavaScript thread
+pub struct Resolve {
+    url: String,
+}
+
+impl Task for Resolve {
+    type Output = String;
+    type JsValue = String;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let resolver = resolver()?;
+        runtime()?
+            .block_on(resolver.resolve(&self.url))
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        Ok(output)
+    }
+}
+
+// Follow the URL's redirects to its final form, without cleaning it
+#[napi]
+pub fn resolve(url: String) -> AsyncTask<Resolve> {
+    AsyncTask::new(Resolve { url })
+}
+
+// Remove tracking information from an (already resolved) URL
+#[napi]
+pub fn clean(url: String) -> Result<String> {
+    rsl::clean_url(&url)
+        .map(String::from)
+        .map_err(|e| Error::from_reason(e.to_string()))
+}
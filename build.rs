@@ -0,0 +1,15 @@
+use cfg_aliases::cfg_aliases;
+
+fn main() {
+    cfg_aliases! {
+        // The system clipboard on Linux and the BSDs, which may be X11's or Wayland's (or,
+        // under WSL, Windows'), and may have a primary selection
+        linux_clipboard: {
+            all(
+                feature = "clipboard",
+                unix,
+                not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+            )
+        },
+    }
+}
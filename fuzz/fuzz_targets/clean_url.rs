@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// Cleaning must never panic, and must be idempotent; see `rsl::clean_url`
+fuzz_target!(|input: &str| {
+    if let Ok(cleaned) = rsl::clean_url(input) {
+        let again = rsl::clean_url(cleaned.as_str())
+            .unwrap_or_else(|e| panic!("{} cleaned to {}, which failed: {}", input, cleaned, e));
+        assert_eq!(again.as_str(), cleaned.as_str());
+    }
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// Pages come from the network, so parsing them must never panic
+fuzz_target!(|html: &str| {
+    let _ = rsl::resolve::extract_meta_refresh(html);
+});
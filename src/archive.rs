@@ -0,0 +1,117 @@
+use std::error::Error;
+
+use reqwest::header;
+
+// Wayback Machine's Save Page Now endpoint.  A GET request to this prefix, followed by
+// the URL to save, archives the page and redirects to the resulting snapshot:
+//   <https://web.archive.org/save>
+const SAVE_ENDPOINT: &str = "https://web.archive.org/save/";
+const WAYBACK_ORIGIN: &str = "https://web.archive.org";
+
+// Submit a URL to the Wayback Machine, returning the URL of the snapshot
+pub async fn archive(client: &reqwest::Client, url: &str) -> Result<String, Box<dyn Error>> {
+    save(client, SAVE_ENDPOINT, url).await
+}
+
+async fn save(
+    client: &reqwest::Client,
+    endpoint: &str,
+    url: &str,
+) -> Result<String, Box<dyn Error>> {
+    let response = client
+        .get(format!("{}{}", endpoint, url))
+        .send()
+        .await?
+        .error_for_status()?;
+
+    // Depending on the state of the archive, the Save API either redirects us to the
+    // snapshot, or points to it with a relative Content-Location header.  Our client does
+    // not follow redirects automatically, so we read either header.  Without one, all we
+    // have is the URL we asked to save, which is not a snapshot
+    let location = [header::LOCATION, header::CONTENT_LOCATION]
+        .iter()
+        .find_map(|name| response.headers().get(name))
+        .and_then(|v| v.to_str().ok())
+        .ok_or("no snapshot URL in response")?;
+    Ok(snapshot_url(location))
+}
+
+fn snapshot_url(location: &str) -> String {
+    if location.starts_with("http") {
+        location.to_string()
+    } else {
+        format!("{}{}", WAYBACK_ORIGIN, location)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{Mock, MockServer, ResponseTemplate, matchers::path_regex};
+
+    use super::*;
+
+    const SNAPSHOT: &str = "/web/20260101000000/https://example.com/";
+
+    // Save a link with the mock server standing in for Save Page Now
+    async fn save_with(response: ResponseTemplate) -> Result<String, Box<dyn Error>> {
+        let server = MockServer::start().await;
+        Mock::given(path_regex("^/save/"))
+            .respond_with(response)
+            .mount(&server)
+            .await;
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .expect("client");
+        save(
+            &client,
+            &format!("{}/save/", server.uri()),
+            "https://example.com/",
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_save_location() {
+        let response = ResponseTemplate::new(302)
+            .insert_header("Location", format!("{}{}", WAYBACK_ORIGIN, SNAPSHOT));
+        assert_eq!(
+            save_with(response).await.expect("saved"),
+            format!("{}{}", WAYBACK_ORIGIN, SNAPSHOT)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_save_content_location() {
+        let response = ResponseTemplate::new(200).insert_header("Content-Location", SNAPSHOT);
+        assert_eq!(
+            save_with(response).await.expect("saved"),
+            format!("{}{}", WAYBACK_ORIGIN, SNAPSHOT)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_save_no_snapshot() {
+        let result = save_with(ResponseTemplate::new(200)).await;
+        assert_eq!(
+            result.expect_err("no snapshot").to_string(),
+            "no snapshot URL in response"
+        );
+    }
+
+    #[test]
+    fn test_snapshot_url_relative() {
+        assert_eq!(
+            snapshot_url(
+                "/web/20260101000000/https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m"
+            ),
+            "https://web.archive.org/web/20260101000000/https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m"
+        );
+    }
+
+    #[test]
+    fn test_snapshot_url_absolute() {
+        let url = "https://web.archive.org/web/20260101000000/https://example.com/";
+        assert_eq!(snapshot_url(url), url);
+    }
+}
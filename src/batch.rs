@@ -0,0 +1,249 @@
+use std::{
+    io::{self, BufRead},
+    pin::pin,
+    time::Duration,
+};
+
+use futures::stream::{self, Stream, StreamExt};
+
+use crate::{
+    archive, clipboard,
+    exit::ExitCode,
+    history::{self, History},
+    output,
+    process::{self, ProcessError, ProcessOptions, Processed},
+    progress::Progress,
+    redact, report,
+    resolve::Resolver,
+    sink::Sink,
+};
+
+pub const DEFAULT_CONCURRENCY: usize = 8;
+pub const DEFAULT_HOST_FAILURES: u32 = 5;
+pub const DEFAULT_HOST_COOLDOWN: Duration = Duration::from_secs(60);
+
+// Options controlling how a batch of URLs is processed
+pub struct BatchOptions {
+    // Maximum number of URLs to resolve at once
+    pub concurrency: usize,
+    // Only check whether URLs are canonical; do not copy them to the clipboard
+    pub check: bool,
+    // Byte used to terminate each output record (a newline, or NUL with `--null`)
+    pub delimiter: u8,
+    // Submit cleaned URLs to the Wayback Machine
+    pub archive: bool,
+    // Write each result as a JSON object, rather than just the cleaned URL
+    pub json: bool,
+    // Options passed through to each URL's processing
+    pub process: ProcessOptions,
+}
+
+// Read URLs from a reader, separated by `delimiter`, ignoring blank records
+pub fn read_urls(reader: impl BufRead, delimiter: u8) -> io::Result<Vec<String>> {
+    let mut urls = Vec::new();
+    for record in reader.split(delimiter) {
+        let record = String::from_utf8(record?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let record = record.trim();
+        if !record.is_empty() {
+            urls.push(record.to_string());
+        }
+    }
+    Ok(urls)
+}
+
+// Resolve and clean URLs concurrently, yielding each result in input order
+//
+// All URLs share the same resolver (and hence connection pool), and at most
+// `concurrency` are resolved at once.
+pub fn process_all<'a>(
+    resolver: &'a Resolver,
+    urls: &'a [String],
+    concurrency: usize,
+    options: &'a ProcessOptions,
+) -> impl Stream<Item = (&'a String, Result<Processed, ProcessError>)> + 'a {
+    process::process_stream(resolver, stream::iter(urls), concurrency, options)
+}
+
+// The output record for a processed URL
+fn record(processed: &Processed, options: &BatchOptions) -> String {
+    if options.json {
+        processed.to_json().to_string()
+    } else {
+        processed.cleaned.clone()
+    }
+}
+
+// Resolve and clean a batch of URLs concurrently, printing results in input order
+//
+// Results are written to `sink`.  The returned exit code reflects the first failure, if
+// any; otherwise, with `--check`, whether any URL was changed.
+pub async fn run(
+    resolver: &Resolver,
+    urls: &[String],
+    options: &BatchOptions,
+    mut sink: Sink,
+    saved_history: Option<&History>,
+) -> ExitCode {
+    let mut results = pin!(process_all(
+        resolver,
+        urls,
+        options.concurrency,
+        &options.process
+    ));
+
+    let mut progress = Progress::new(urls.len());
+    let mut code = ExitCode::Success;
+    let mut cleaned_urls = Vec::new();
+    while let Some((url, result)) = results.next().await {
+        progress.record(&result);
+        progress.suspend(|| report::report(&result));
+        if let Ok(processed) = &result
+            && processed.verified == Some(false)
+            && matches!(code, ExitCode::Success | ExitCode::Changed)
+        {
+            code = ExitCode::VerifyFailure;
+        }
+        if let Ok(processed) = &result
+            && !processed.threats.is_empty()
+            && matches!(code, ExitCode::Success | ExitCode::Changed)
+        {
+            code = ExitCode::Unsafe;
+        }
+        match result {
+            Ok(processed) if options.check => {
+                if !processed.is_canonical() {
+                    emit(
+                        &progress,
+                        &mut sink,
+                        &record(&processed, options),
+                        options.delimiter,
+                    );
+                    if code == ExitCode::Success {
+                        code = ExitCode::Changed;
+                    }
+                }
+            }
+            Ok(processed) => {
+                if let Some(saved_history) = saved_history {
+                    history::record(saved_history, &processed);
+                }
+                emit(
+                    &progress,
+                    &mut sink,
+                    &record(&processed, options),
+                    options.delimiter,
+                );
+                if options.archive {
+                    match archive::archive(resolver.client(), &processed.cleaned).await {
+                        Ok(snapshot) => progress
+                            .suspend(|| output::info(format_args!("Archived: {}", snapshot))),
+                        Err(e) => progress.suspend(|| {
+                            tracing::warn!(
+                                url = %redact::url(&processed.cleaned),
+                                error = %e,
+                                "could not archive"
+                            )
+                        }),
+                    }
+                }
+                if processed.threats.is_empty() {
+                    let title = processed.title().map(str::to_string);
+                    cleaned_urls.push((processed.cleaned, title));
+                } else {
+                    progress.suspend(|| {
+                        tracing::warn!(
+                            url = %redact::url(&processed.cleaned),
+                            "not copying possibly malicious link to clipboard"
+                        )
+                    });
+                }
+            }
+            Err(e) => {
+                let skipped = e.is_skipped();
+                let label = if skipped { "Skipped" } else { "Error" };
+                progress.eprintln(&format!("{}: {}: {}", label, url, e));
+                if options.json {
+                    let failure = serde_json::json!({
+                        "input": url,
+                        "error": e.to_string(),
+                        "skipped": skipped,
+                    });
+                    emit(
+                        &progress,
+                        &mut sink,
+                        &failure.to_string(),
+                        options.delimiter,
+                    );
+                }
+                if matches!(code, ExitCode::Success | ExitCode::Changed) {
+                    code = ExitCode::from(&e);
+                }
+            }
+        }
+    }
+
+    progress.finish();
+
+    if let Err(e) = sink.finish() {
+        eprintln!("Error: could not write output: {}", e);
+        code = ExitCode::Failure;
+    }
+
+    if !cleaned_urls.is_empty()
+        && let Err(e) = clipboard::copy_links(
+            cleaned_urls
+                .iter()
+                .map(|(url, title)| (url.as_str(), title.as_deref())),
+        )
+    {
+        eprintln!("Error: could not copy to clipboard: {}", e);
+        if matches!(code, ExitCode::Success | ExitCode::Changed) {
+            code = ExitCode::ClipboardFailure;
+        }
+    }
+
+    code
+}
+
+// Write a result to the sink without the progress bar getting in the way, warning if
+// it could not be written
+fn emit(progress: &Progress, sink: &mut Sink, s: &str, delimiter: u8) {
+    if let Err(e) = progress.suspend(|| sink.write_record(s, delimiter)) {
+        progress.suspend(
+            || tracing::warn!(url = %redact::url(s), error = %e, "could not write result"),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_urls() {
+        let input = "https://www.reddit.com/r/AskTheWorld/s/mONZu40JNk\n\n  https://www.facebook.com/share/r/14QeSSeP3nu/  \n";
+        let urls = read_urls(input.as_bytes(), b'\n').expect("read");
+        assert_eq!(
+            urls,
+            [
+                "https://www.reddit.com/r/AskTheWorld/s/mONZu40JNk",
+                "https://www.facebook.com/share/r/14QeSSeP3nu/",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_urls_null_delimited() {
+        let input =
+            "https://www.reddit.com/r/AskTheWorld/s/mONZu40JNk\0https://example.com/a\nb\0\0";
+        let urls = read_urls(input.as_bytes(), b'\0').expect("read");
+        assert_eq!(
+            urls,
+            [
+                "https://www.reddit.com/r/AskTheWorld/s/mONZu40JNk",
+                "https://example.com/a\nb",
+            ]
+        );
+    }
+}
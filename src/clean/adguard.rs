@@ -0,0 +1,351 @@
+use std::{error::Error, fs, io, path::Path};
+
+use regex::Regex;
+use url::Url;
+
+use super::Explanation;
+
+// Error type for loading an AdGuard filter list
+#[derive(Debug, derive_more::Display)]
+pub enum FilterListError {
+    Io(io::Error),
+    #[display("invalid regular expression on line {_0}: {_1}")]
+    Regex(usize, regex::Error),
+}
+
+impl Error for FilterListError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FilterListError::Io(e) => Some(e),
+            FilterListError::Regex(_, e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for FilterListError {
+    fn from(e: io::Error) -> Self {
+        FilterListError::Io(e)
+    }
+}
+
+// Which query parameters a `$removeparam` rule applies to
+#[derive(Clone, Debug)]
+enum ParamMatcher {
+    // `$removeparam`: every parameter
+    All,
+    // `$removeparam=name`: parameters with exactly this name
+    Name(String),
+    // `$removeparam=/regex/`: parameters whose `name=value` matches
+    Regex(Regex),
+    // `$removeparam=~name` or `$removeparam=~/regex/`: every parameter except those
+    // matching
+    Except(Box<ParamMatcher>),
+}
+
+impl ParamMatcher {
+    fn parse(value: Option<&str>, line: usize) -> Result<Self, FilterListError> {
+        let Some(value) = value.filter(|v| !v.is_empty()) else {
+            return Ok(ParamMatcher::All);
+        };
+        if let Some(negated) = value.strip_prefix('~') {
+            return Ok(ParamMatcher::Except(Box::new(Self::parse(
+                Some(negated),
+                line,
+            )?)));
+        }
+        // Regular expressions are delimited by slashes, optionally followed by `i` to
+        // ignore case
+        let regex = value
+            .strip_prefix('/')
+            .and_then(|v| match v.strip_suffix("/i") {
+                Some(pattern) => Some(format!("(?i){}", pattern)),
+                None => v.strip_suffix('/').map(str::to_string),
+            });
+        match regex {
+            Some(pattern) => Regex::new(&pattern)
+                .map(ParamMatcher::Regex)
+                .map_err(|e| FilterListError::Regex(line, e)),
+            None => Ok(ParamMatcher::Name(value.to_string())),
+        }
+    }
+
+    fn matches(&self, name: &str, value: &str) -> bool {
+        match self {
+            ParamMatcher::All => true,
+            ParamMatcher::Name(n) => n == name,
+            ParamMatcher::Regex(regex) => regex.is_match(&format!("{}={}", name, value)),
+            ParamMatcher::Except(matcher) => !matcher.matches(name, value),
+        }
+    }
+}
+
+// A single `$removeparam` rule
+#[derive(Clone, Debug)]
+struct Rule {
+    // From `||host^`; rules without a host apply everywhere
+    host: Option<String>,
+    // From `$domain=a.com|~b.com`
+    domains: Vec<String>,
+    excluded_domains: Vec<String>,
+    params: ParamMatcher,
+    // From `@@`, which prevents matching parameters being removed by other rules
+    exception: bool,
+}
+
+// Whether `host` is `domain`, or one of its subdomains
+pub(super) fn is_subdomain(host: &str, domain: &str) -> bool {
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+impl Rule {
+    // Parse a line from a filter list, returning `None` for comments and for rules
+    // without `$removeparam` (or that we cannot support; see below)
+    fn parse(line: &str, number: usize) -> Result<Option<Self>, FilterListError> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('!') || line.starts_with('[') {
+            return Ok(None);
+        }
+        let (exception, line) = match line.strip_prefix("@@") {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        let Some((pattern, modifiers)) = line.split_once('$') else {
+            return Ok(None);
+        };
+
+        // We only support rules for whole hosts, rather than arbitrary URL patterns
+        let host = match pattern {
+            "" | "*" => None,
+            _ => match pattern
+                .strip_prefix("||")
+                .map(|p| p.strip_suffix('^').unwrap_or(p))
+                .filter(|p| !p.contains(['/', '*', '^', '|']))
+            {
+                Some(host) => Some(host.to_lowercase()),
+                None => return Ok(None),
+            },
+        };
+
+        let mut rule = Rule {
+            host,
+            domains: Vec::new(),
+            excluded_domains: Vec::new(),
+            params: ParamMatcher::All,
+            exception,
+        };
+        let mut removeparam = false;
+        for modifier in split_modifiers(modifiers) {
+            let (name, value) = match modifier.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (modifier.as_str(), None),
+            };
+            match name {
+                "removeparam" => {
+                    removeparam = true;
+                    rule.params = ParamMatcher::parse(value, number)?;
+                }
+                "domain" => {
+                    for domain in value.unwrap_or_default().split('|') {
+                        match domain.strip_prefix('~') {
+                            Some(domain) => rule.excluded_domains.push(domain.to_lowercase()),
+                            None => rule.domains.push(domain.to_lowercase()),
+                        }
+                    }
+                }
+                // Other modifiers (e.g., `$document` or `$important`) concern how the
+                // browser applies the rule, which does not matter to us
+                _ => {}
+            }
+        }
+        Ok(removeparam.then_some(rule))
+    }
+
+    fn applies_to(&self, host: &str) -> bool {
+        self.host.as_ref().is_none_or(|h| is_subdomain(host, h))
+            && (self.domains.is_empty() || self.domains.iter().any(|d| is_subdomain(host, d)))
+            && !self.excluded_domains.iter().any(|d| is_subdomain(host, d))
+    }
+}
+
+// Split modifiers on commas, except where escaped (as in a regular expression)
+fn split_modifiers(s: &str) -> Vec<String> {
+    let mut modifiers = vec![String::new()];
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(',') => modifiers.last_mut().expect("non-empty").push(','),
+                Some(c) => {
+                    let last = modifiers.last_mut().expect("non-empty");
+                    last.push('\\');
+                    last.push(c);
+                }
+                None => modifiers.last_mut().expect("non-empty").push('\\'),
+            },
+            ',' => modifiers.push(String::new()),
+            c => modifiers.last_mut().expect("non-empty").push(c),
+        }
+    }
+    modifiers
+}
+
+// Query parameters to remove, as given by `$removeparam` rules in AdGuard filter lists
+//
+// See <https://adguard.com/kb/general/ad-filtering/create-own-filters/#removeparam-modifier>.
+// Lines that are not `$removeparam` rules are ignored, so existing filter lists can be
+// used as-is.  Unlike cleaners, these rules apply to every host.
+#[derive(Clone, Debug, Default)]
+pub struct RemoveParamFilters {
+    rules: Vec<Rule>,
+}
+
+impl RemoveParamFilters {
+    pub fn from_file(path: &Path) -> Result<Self, FilterListError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(s: &str) -> Result<Self, FilterListError> {
+        let mut rules = Vec::new();
+        for (i, line) in s.lines().enumerate() {
+            rules.extend(Rule::parse(line, i + 1)?);
+        }
+        Ok(RemoveParamFilters { rules })
+    }
+
+    // Add the rules from another filter list
+    pub fn extend(&mut self, other: RemoveParamFilters) {
+        self.rules.extend(other.rules);
+    }
+
+    // Number of `$removeparam` rules loaded
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    // Remove query parameters from `url` according to the rules
+    pub fn apply(&self, url: &mut Url, explanation: &mut Explanation) {
+        if self.rules.is_empty() {
+            return;
+        }
+        let host = url.host_str().unwrap_or_default().to_lowercase();
+        let rules: Vec<_> = self.rules.iter().filter(|r| r.applies_to(&host)).collect();
+        let removed = |k: &str, v: &str| {
+            rules.iter().any(|r| !r.exception && r.params.matches(k, v))
+                && !rules.iter().any(|r| r.exception && r.params.matches(k, v))
+        };
+
+        let (removed, kept): (Vec<_>, Vec<_>) = url
+            .query_pairs()
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .partition(|(k, v)| removed(k, v));
+        if removed.is_empty() {
+            return;
+        }
+
+        let names: Vec<_> = removed.iter().map(|(k, _)| k.as_str()).collect();
+        explanation.step(format!(
+            "removed query parameters: {} ($removeparam)",
+            names.join(", ")
+        ));
+        url.set_query(None);
+        if !kept.is_empty() {
+            url.query_pairs_mut().extend_pairs(kept);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILTERS: &str = r"! Title: Test filters
+||example.com^$removeparam=ref
+$removeparam=/^(utm_[a-z]+|ICID)=/i
+||shop.example.org^$removeparam=~id
+||example.net^$removeparam=/^(a\,b)=/
+$removeparam=src,domain=news.example|~www.news.example
+@@||keep.example.com^$removeparam=ref
+||example.com/path$removeparam=page
+example.com##.banner
+";
+
+    fn clean(url: &str) -> String {
+        let filters = RemoveParamFilters::parse(FILTERS).expect("parsed");
+        let mut url = Url::parse(url).expect("valid url");
+        filters.apply(&mut url, &mut Explanation::default());
+        url.to_string()
+    }
+
+    #[test]
+    fn test_parse() {
+        let filters = RemoveParamFilters::parse(FILTERS).expect("parsed");
+        assert_eq!(filters.len(), 6);
+    }
+
+    #[test]
+    fn test_invalid_regex() {
+        assert!(matches!(
+            RemoveParamFilters::parse("! comment\n$removeparam=/(/"),
+            Err(FilterListError::Regex(2, _))
+        ));
+    }
+
+    #[test]
+    fn test_host() {
+        assert_eq!(
+            clean("https://www.example.com/a?ref=x&page=2"),
+            "https://www.example.com/a?page=2"
+        );
+        assert_eq!(
+            clean("https://notexample.com/a?ref=x"),
+            "https://notexample.com/a?ref=x"
+        );
+    }
+
+    #[test]
+    fn test_regex() {
+        assert_eq!(
+            clean("https://other.org/?UTM_SOURCE=x&icid=y&id=1"),
+            "https://other.org/?id=1"
+        );
+        assert_eq!(
+            clean("https://example.net/?a,b=1&a=2"),
+            "https://example.net/?a=2"
+        );
+    }
+
+    #[test]
+    fn test_negation() {
+        assert_eq!(
+            clean("https://shop.example.org/item?id=1&colour=red&ref=x"),
+            "https://shop.example.org/item?id=1"
+        );
+    }
+
+    #[test]
+    fn test_domains() {
+        assert_eq!(
+            clean("https://m.news.example/story?src=feed"),
+            "https://m.news.example/story"
+        );
+        assert_eq!(
+            clean("https://www.news.example/story?src=feed"),
+            "https://www.news.example/story?src=feed"
+        );
+    }
+
+    #[test]
+    fn test_exception() {
+        assert_eq!(
+            clean("https://keep.example.com/?ref=x"),
+            "https://keep.example.com/?ref=x"
+        );
+    }
+}
@@ -0,0 +1,317 @@
+use std::{collections::BTreeMap, error::Error, fs, io, path::Path};
+
+use percent_encoding::percent_decode_str;
+use regex::Regex;
+use serde::Deserialize;
+use url::Url;
+
+use super::{CleanUrlError, Explanation, UrlCleaner, generic};
+
+// Maximum number of redirections to follow when cleaning a single URL, in case a ruleset
+// redirects in a loop
+const MAX_REDIRECTIONS: usize = 5;
+
+// Error type for loading a ClearURLs ruleset
+#[derive(Debug, derive_more::Display)]
+pub enum ClearUrlsError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    #[display("invalid pattern in provider {_0:?}: {_1}")]
+    Regex(String, regex::Error),
+}
+
+impl Error for ClearUrlsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ClearUrlsError::Io(e) => Some(e),
+            ClearUrlsError::Json(e) => Some(e),
+            ClearUrlsError::Regex(_, e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for ClearUrlsError {
+    fn from(e: io::Error) -> Self {
+        ClearUrlsError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for ClearUrlsError {
+    fn from(e: serde_json::Error) -> Self {
+        ClearUrlsError::Json(e)
+    }
+}
+
+// The layout of ClearURLs' `data.json`, as published at
+//   <https://rules2.clearurls.xyz/data.minify.json>
+//
+// Fields only relevant to the browser extension (e.g., `forceRedirection`) are ignored.
+#[derive(Deserialize)]
+struct RawRules {
+    providers: BTreeMap<String, RawProvider>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawProvider {
+    url_pattern: String,
+    #[serde(default)]
+    complete_provider: bool,
+    #[serde(default)]
+    rules: Vec<String>,
+    #[serde(default)]
+    raw_rules: Vec<String>,
+    #[serde(default)]
+    referral_marketing: Vec<String>,
+    #[serde(default)]
+    exceptions: Vec<String>,
+    #[serde(default)]
+    redirections: Vec<String>,
+}
+
+// A ClearURLs provider, with its patterns compiled
+//
+// As in the extension, all patterns are case-insensitive.
+struct Provider {
+    name: String,
+    url_pattern: Regex,
+    // Matched against the full name of each query parameter
+    rules: Vec<Regex>,
+    // Removed from anywhere in the URL
+    raw_rules: Vec<Regex>,
+    exceptions: Vec<Regex>,
+    // The first capture group is the (percent-encoded) destination URL
+    redirections: Vec<Regex>,
+}
+
+impl Provider {
+    fn compile(name: String, raw: RawProvider) -> Result<Self, ClearUrlsError> {
+        let compile = |pattern: &str| Regex::new(&format!("(?i){}", pattern));
+        let compile_all = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|p| compile(p))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let result = (|| -> Result<Provider, regex::Error> {
+            Ok(Provider {
+                url_pattern: compile(&raw.url_pattern)?,
+                rules: raw
+                    .rules
+                    .iter()
+                    .chain(&raw.referral_marketing)
+                    .map(|rule| compile(&format!("^(?:{})$", rule)))
+                    .collect::<Result<_, _>>()?,
+                raw_rules: compile_all(&raw.raw_rules)?,
+                exceptions: compile_all(&raw.exceptions)?,
+                redirections: compile_all(&raw.redirections)?,
+                name: name.clone(),
+            })
+        })();
+        result.map_err(|e| ClearUrlsError::Regex(name, e))
+    }
+
+    fn matches(&self, url: &str) -> bool {
+        self.url_pattern.is_match(url) && !self.exceptions.iter().any(|e| e.is_match(url))
+    }
+
+    // The destination of a redirection (e.g., `google.com/url?q=...`), if any
+    fn redirect(&self, url: &str) -> Option<Url> {
+        self.redirections.iter().find_map(|redirection| {
+            let target = redirection.captures(url)?.get(1)?.as_str();
+            Url::parse(&percent_decode_str(target).decode_utf8_lossy()).ok()
+        })
+    }
+}
+
+// Cleaner driven by the ClearURLs ruleset, for the long tail of hosts without a dedicated
+// cleaner
+//
+// Every provider whose URL pattern matches is applied in turn, after which known
+// tracking parameters are removed as with `FallbackCleaner`.  Providers marked as
+// "complete" (i.e., blocked outright by the extension) are skipped, as there is nothing
+// sensible for us to clean them to.
+pub struct ClearUrlsCleaner {
+    providers: Vec<Provider>,
+}
+
+impl ClearUrlsCleaner {
+    pub fn from_file(path: &Path) -> Result<Self, ClearUrlsError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(s: &str) -> Result<Self, ClearUrlsError> {
+        let raw: RawRules = serde_json::from_str(s)?;
+        let providers = raw
+            .providers
+            .into_iter()
+            .filter(|(_, provider)| !provider.complete_provider)
+            .map(|(name, provider)| Provider::compile(name, provider))
+            .collect::<Result<_, _>>()?;
+        Ok(ClearUrlsCleaner { providers })
+    }
+
+    // Number of providers loaded from the ruleset
+    pub fn len(&self) -> usize {
+        self.providers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.providers.is_empty()
+    }
+}
+
+impl UrlCleaner for ClearUrlsCleaner {
+    fn name(&self) -> &'static str {
+        "ClearUrlsCleaner"
+    }
+
+    fn shapes(&self) -> &'static [&'static str] {
+        &["/<any path>"]
+    }
+
+    fn clean(&self, url: &mut Url, explanation: &mut Explanation) -> Result<(), CleanUrlError> {
+        let mut matched = false;
+        let mut redirections = 0;
+        let mut providers = self.providers.iter();
+        while let Some(provider) = providers.next() {
+            if !provider.matches(url.as_str()) {
+                continue;
+            }
+            matched = true;
+            explanation.step(format!("matched ClearURLs provider \"{}\"", provider.name));
+
+            // Step 1: skip past redirection pages to their destination, and start again
+            // from there, as the destination may match other providers
+            if redirections < MAX_REDIRECTIONS
+                && let Some(target) = provider.redirect(url.as_str())
+            {
+                explanation.step(format!("followed redirection to {}", target));
+                *url = target;
+                redirections += 1;
+                providers = self.providers.iter();
+                continue;
+            }
+
+            // Step 2: apply raw rules to the URL as a whole
+            for raw_rule in &provider.raw_rules {
+                let replaced = raw_rule.replace_all(url.as_str(), "");
+                if replaced != url.as_str() {
+                    *url = Url::parse(&replaced)?;
+                    explanation.step(format!("removed \"{}\"", raw_rule.as_str()));
+                }
+            }
+
+            // Step 3: remove query parameters matching any of the provider's rules
+            let (removed, kept): (Vec<_>, Vec<_>) = url
+                .query_pairs()
+                .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                .partition(|(k, _)| provider.rules.iter().any(|rule| rule.is_match(k)));
+            if !removed.is_empty() {
+                let names: Vec<_> = removed.iter().map(|(k, _)| k.as_str()).collect();
+                explanation.step(format!("removed query parameters: {}", names.join(", ")));
+                url.set_query(None);
+                if !kept.is_empty() {
+                    url.query_pairs_mut().extend_pairs(kept);
+                }
+            }
+        }
+
+        // Finally, remove anything the ruleset does not know about as with other hosts
+        generic::FallbackCleaner.clean(url, explanation)?;
+        if matched {
+            explanation.rule("ClearURLs provider");
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RULES: &str = r#"{
+        "providers": {
+            "example": {
+                "urlPattern": "^https?://(?:[a-z0-9-]+\\.)*?example\\.com",
+                "rules": ["ref", "campaign_[a-z]+"],
+                "referralMarketing": ["tag"],
+                "rawRules": ["/ref=[^/?]*"],
+                "exceptions": ["^https?://example\\.com/keep"]
+            },
+            "redirector": {
+                "urlPattern": "^https?://out\\.example\\.org",
+                "redirections": ["^https?://out\\.example\\.org/\\?to=([^&]*)"]
+            },
+            "blocked": {
+                "urlPattern": "^https?://ads\\.example\\.net",
+                "completeProvider": true
+            }
+        }
+    }"#;
+
+    fn clean(url: &str) -> String {
+        let cleaner = ClearUrlsCleaner::parse(RULES).expect("parsed");
+        let mut url = Url::parse(url).expect("valid url");
+        cleaner
+            .clean(&mut url, &mut Explanation::default())
+            .expect("cleaned");
+        url.to_string()
+    }
+
+    #[test]
+    fn test_parse() {
+        let cleaner = ClearUrlsCleaner::parse(RULES).expect("parsed");
+        assert_eq!(cleaner.len(), 2);
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        let rules = r#"{"providers": {"bad": {"urlPattern": "("}}}"#;
+        assert!(matches!(
+            ClearUrlsCleaner::parse(rules),
+            Err(ClearUrlsError::Regex(name, _)) if name == "bad"
+        ));
+    }
+
+    #[test]
+    fn test_rules() {
+        assert_eq!(
+            clean("https://www.example.com/item?id=1&REF=x&campaign_src=y&tag=z"),
+            "https://www.example.com/item?id=1"
+        );
+    }
+
+    #[test]
+    fn test_raw_rules() {
+        assert_eq!(
+            clean("https://example.com/item/ref=abc?id=1"),
+            "https://example.com/item?id=1"
+        );
+    }
+
+    #[test]
+    fn test_exceptions() {
+        assert_eq!(
+            clean("https://example.com/keep?ref=x"),
+            "https://example.com/keep?ref=x"
+        );
+    }
+
+    #[test]
+    fn test_redirections() {
+        assert_eq!(
+            clean("https://out.example.org/?to=https%3A%2F%2Fexample.com%2Fitem%3Fref%3Dx"),
+            "https://example.com/item"
+        );
+    }
+
+    #[test]
+    fn test_unmatched_host() {
+        assert_eq!(
+            clean("https://other.org/page?id=1&utm_source=x"),
+            "https://other.org/page?id=1"
+        );
+    }
+}
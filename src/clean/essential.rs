@@ -0,0 +1,81 @@
+use url::Url;
+
+use super::{Explanation, adguard::is_subdomain};
+
+// Query parameters that a site cannot do without, by domain
+//
+// A parameter here is part of which page a link is to (e.g., YouTube's `v`), or of
+// whether it can be opened at all (e.g., Dropbox's `rlkey`), so is never removed by
+// generic cleaning, the fallback, or a tracking parameter list, however aggressive.  A
+// dedicated cleaner knows its platform better, and is left to decide for itself.
+const ESSENTIAL_PARAMS: &[(&str, &[&str])] = &[
+    ("dropbox.com", &["rlkey"]),
+    ("facebook.com", &["story_fbid", "id"]),
+    // Search terms, whether in Search or Maps (e.g., `/maps?q=...`)
+    ("google.com", &["q"]),
+    ("youtube.com", &["v", "t", "list"]),
+    ("youtu.be", &["t"]),
+];
+
+// The essential query parameters on the URL's host, if any
+pub(super) fn essential_params(url: &Url) -> &'static [&'static str] {
+    let Some(host) = url.host_str() else {
+        return &[];
+    };
+    ESSENTIAL_PARAMS
+        .iter()
+        .find(|(domain, _)| is_subdomain(host, domain))
+        .map_or(&[], |(_, params)| params)
+}
+
+// Restore any essential parameters from the original URL that cleaning removed
+pub(super) fn restore_essential_params(
+    url: &mut Url,
+    original_params: &[(String, String)],
+    explanation: &mut Explanation,
+) {
+    let essential = essential_params(url);
+    let present: Vec<String> = url.query_pairs().map(|(k, _)| k.into_owned()).collect();
+    let restore: Vec<_> = original_params
+        .iter()
+        .filter(|(k, _)| essential.contains(&k.as_str()) && !present.contains(k))
+        .collect();
+    if restore.is_empty() {
+        return;
+    }
+
+    let mut pairs = url.query_pairs_mut();
+    for (k, v) in &restore {
+        pairs.append_pair(k, v);
+        explanation.step(format!("kept essential query parameter {}", k));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_essential_params() {
+        let params = |url| essential_params(&Url::parse(url).expect("valid url"));
+        assert_eq!(params("https://www.youtube.com/watch"), ["v", "t", "list"]);
+        assert_eq!(params("https://m.youtube.com/watch"), ["v", "t", "list"]);
+        assert_eq!(params("https://www.dropbox.com/scl/fi/abc/file"), ["rlkey"]);
+        assert!(params("https://example.com/").is_empty());
+        assert!(params("https://notyoutube.com/").is_empty());
+    }
+
+    #[test]
+    fn test_restore_essential_params() {
+        let mut url = Url::parse("https://www.google.com/maps?hl=en").expect("valid url");
+        let original = [
+            ("q".to_string(), "Wellington".to_string()),
+            ("entry".to_string(), "ttu".to_string()),
+        ];
+        restore_essential_params(&mut url, &original, &mut Explanation::default());
+        assert_eq!(
+            url.as_str(),
+            "https://www.google.com/maps?hl=en&q=Wellington"
+        );
+    }
+}
@@ -1,22 +1,39 @@
-use super::{CleanUrlError, UrlCleaner};
+use super::{CleanUrlError, Explanation, UrlCleaner, remove_query, remove_trailing_slash};
 use std::collections::HashMap;
 use url::Url;
 
 pub struct FacebookCleaner;
 
 impl UrlCleaner for FacebookCleaner {
-    fn clean(&self, url: &mut Url) -> Result<(), CleanUrlError> {
+    fn name(&self) -> &'static str {
+        "FacebookCleaner"
+    }
+
+    fn shapes(&self) -> &'static [&'static str] {
+        &[
+            "/<page>/posts/<post ID>",
+            "/groups/<group>/permalink/<post ID>",
+            "/groups/<group>/permalink/<post ID>?comment_id=<comment ID>",
+            "/reel/<reel ID>",
+            "/permalink.php?story_fbid=<story ID>&id=<ID>",
+            "/photo.php?fbid=<photo ID>",
+        ]
+    }
+
+    fn preserved_params(&self) -> &'static [&'static str] {
+        &["story_fbid", "id", "fbid", "comment_id"]
+    }
+
+    fn clean(&self, url: &mut Url, explanation: &mut Explanation) -> Result<(), CleanUrlError> {
         // Step 1: store query parameters before removing them
         let params: HashMap<String, String> = url
             .query_pairs()
             .map(|(k, v)| (k.into_owned(), v.into_owned()))
             .collect();
-        url.set_query(None);
+        remove_query(url, explanation);
 
         // Step 2: remove trailing slash if any (provides no information)
-        url.path_segments_mut()
-            .map_err(|_| CleanUrlError::PathSegmentsError)?
-            .pop_if_empty(); // remove trailing slash if present
+        remove_trailing_slash(url, explanation)?;
 
         let segments: Vec<_> = url
             .path_segments()
@@ -36,6 +53,11 @@ impl UrlCleaner for FacebookCleaner {
         let is_reel = matches!(segments.as_slice(), ["reel", _]);
 
         if is_page_post || is_reel || (is_group_post && !&params.contains_key("comment_id")) {
+            explanation.rule(match (is_page_post, is_reel) {
+                (true, _) => "page post",
+                (false, true) => "reel",
+                (false, false) => "group permalink",
+            });
             return Ok(());
         }
 
@@ -47,34 +69,62 @@ impl UrlCleaner for FacebookCleaner {
             //   (presumably poster's) ID
             //
             // https://www.facebook.com/permalink.php?story_fbid=<story ID>&id=<id>
-            if let Some(story_fbid) = params.get("story_fbid") {
-                url.query_pairs_mut()
-                    .append_pair("story_fbid", story_fbid)
-                    .append_pair("id", params.get("id").expect("must have id"));
-                return Ok(());
+            let Some(story_fbid) = params.get("story_fbid") else {
+                explanation.step("permalink has no story_fbid");
+                return Err(CleanUrlError::UnsupportedUrlPath);
+            };
+            explanation.rule("story permalink");
+            let mut pairs = url.query_pairs_mut();
+            pairs.append_pair("story_fbid", story_fbid);
+            match params.get("id") {
+                Some(id) => {
+                    explanation.step("restored query parameters: story_fbid, id");
+                    pairs.append_pair("id", id);
+                }
+                None => explanation.step("restored query parameter: story_fbid"),
             }
-            unreachable!()
+            return Ok(());
         } else if matches!(segments.as_slice(), ["photo.php"]) {
             // 4.1 b: the permalink is for a photo; we need to add its ID back
             //
             // https://www.facebook.com/photo.php?fbid=<photo ID>
-            url.query_pairs_mut()
-                .append_pair("fbid", params.get("fbid").expect("must have fbid"));
+            let Some(fbid) = params.get("fbid") else {
+                explanation.step("photo has no fbid");
+                return Err(CleanUrlError::UnsupportedUrlPath);
+            };
+            explanation.rule("photo");
+            explanation.step("restored query parameter: fbid");
+            url.query_pairs_mut().append_pair("fbid", fbid);
             return Ok(());
         }
 
         // Case 4.2: the link is a group post permalink containing a comment
         //
         // https://www.facebook.com/groups/<group>/permalink/25654608820855518/?comment_id=<commend ID>
-        if is_group_post && params.contains_key("comment_id") {
-            url.query_pairs_mut().append_pair(
-                "comment_id",
-                params.get("comment_id").expect("100% Rust bug"),
-            );
+        if is_group_post && let Some(comment_id) = params.get("comment_id") {
+            explanation.rule("group permalink with comment");
+            explanation.step("restored query parameter: comment_id");
+            url.query_pairs_mut().append_pair("comment_id", comment_id);
             return Ok(());
         }
 
-        // Probably unreachable?
-        unreachable!("if we got here then there was a case for Facebook that wasn't handled");
+        // Anything else (e.g., a profile or the home page) is not a link to content
+        explanation.step(format!(
+            "path /{} is not a post, reel, story, or photo",
+            segments.join("/")
+        ));
+        Err(CleanUrlError::UnsupportedUrlPath)
+    }
+
+    // Any post, reel, story, or photo is already a full URL, whereas share links
+    // (`/share/...`) must be resolved
+    fn recognises(&self, url: &Url) -> bool {
+        self.clean(&mut url.clone(), &mut Explanation::default())
+            .is_ok()
+    }
+
+    // Facebook's pages have no anchors worth keeping, only leftovers such as `#_=_`
+    fn keeps_fragment(&self, _url: &Url, _fragment: &str) -> bool {
+        false
     }
 }
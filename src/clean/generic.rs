@@ -1,21 +1,228 @@
-use super::{CleanUrlError, UrlCleaner};
+use super::{
+    CleanUrlError, Explanation, UrlCleaner,
+    essential::{essential_params, restore_essential_params},
+    remove_query, remove_trailing_slash,
+};
 use url::Url;
 
 pub struct GenericCleaner;
 
 impl UrlCleaner for GenericCleaner {
+    fn name(&self) -> &'static str {
+        "GenericCleaner"
+    }
+
+    fn shapes(&self) -> &'static [&'static str] {
+        &["/<any path>"]
+    }
+
+    // We remove every query parameter, whether or not the site needs it
+    fn knows_params(&self) -> bool {
+        false
+    }
+
     // A generic cleaner to strip query parameters, such as igsh (for Instragram share
     // links) rcm (for LinkedIn), and utm_* parameters
-    fn clean(&self, url: &mut Url) -> Result<(), CleanUrlError> {
-        // Step 1: remove query parameters
+    fn clean(&self, url: &mut Url, explanation: &mut Explanation) -> Result<(), CleanUrlError> {
+        explanation.rule("any path");
+
+        // Step 1: remove query parameters, other than any the site cannot do without
+        // (see `essential_params`)
         //
         // Importantly, we remove tracking information from the igsh query parameter
+        let params: Vec<(String, String)> = url
+            .query_pairs()
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect();
+        remove_query(url, explanation);
+        restore_essential_params(url, &params, explanation);
+
+        // Step 2: remove trailing slash if any (provides no information)
+        remove_trailing_slash(url, explanation)?;
+
+        Ok(())
+    }
+}
+
+// Query parameters (or prefixes thereof) known to be used only for tracking, which we can
+// safely remove from URLs on any site
+//
+// These are removed before any cleaner runs (see `clean_url_explained`), so must never
+// be needed to identify a page, on any platform.
+pub(super) const TRACKING_PARAMS: &[&str] = &[
+    "_gl",
+    "_hsenc",
+    "_hsmi",
+    "dclid",
+    "fbclid",
+    "gbraid",
+    "gclid",
+    "igsh",
+    "igshid",
+    "li_fat_id",
+    "mc_cid",
+    "mc_eid",
+    "mkt_tok",
+    "msclkid",
+    "ref_src",
+    "si",
+    "srsltid",
+    "ttclid",
+    "twclid",
+    "wbraid",
+    "yclid",
+];
+pub(super) const TRACKING_PARAM_PREFIXES: &[&str] = &["utm_"];
+
+pub(super) fn is_tracking_param(name: &str) -> bool {
+    TRACKING_PARAMS.contains(&name)
+        || TRACKING_PARAM_PREFIXES
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+}
+
+// Query parameters used only for tracking: those built in (see `TRACKING_PARAMS`), and
+// any others we have learnt of since (e.g., from a list downloaded by `rsl update-rules`)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TrackingParams {
+    pub(super) names: Vec<String>,
+    pub(super) prefixes: Vec<String>,
+}
+
+impl TrackingParams {
+    // Add the parameters in a list with one name per line, e.g.:
+    //
+    //     # Comments and blank lines are ignored
+    //     fbclid
+    //     utm_*
+    //
+    // where a trailing `*` matches any parameter with that prefix
+    pub fn parse(list: &str) -> Self {
+        let mut params = TrackingParams::default();
+        for line in list.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.strip_suffix('*') {
+                Some(prefix) if !prefix.is_empty() => params.prefixes.push(prefix.to_string()),
+                Some(_) => {}
+                None => params.names.push(line.to_string()),
+            }
+        }
+        params
+    }
+
+    // The number of parameters (and prefixes) in addition to those built in
+    pub fn len(&self) -> usize {
+        self.names.len() + self.prefixes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        is_tracking_param(name)
+            || self.names.iter().any(|n| n == name)
+            || self.prefixes.iter().any(|prefix| name.starts_with(prefix))
+    }
+}
+
+// Remove every query parameter known to be used for tracking, unless the site cannot do
+// without it (see `essential_params`), as a downloaded list may be overeager
+pub(super) fn remove_tracking_params(
+    url: &mut Url,
+    params: &TrackingParams,
+    explanation: &mut Explanation,
+) {
+    let essential = essential_params(url);
+    let (removed, kept): (Vec<_>, Vec<_>) = url
+        .query_pairs()
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .partition(|(k, _)| params.contains(k) && !essential.contains(&k.as_str()));
+    if removed.is_empty() {
+        return;
+    }
+    let names: Vec<_> = removed.iter().map(|(k, _)| k.as_str()).collect();
+    explanation.step(format!(
+        "removed tracking query parameters: {}",
+        names.join(", ")
+    ));
+    url.set_query(None);
+    if !kept.is_empty() {
+        url.query_pairs_mut().extend_pairs(kept);
+    }
+}
+
+// Query parameters that hold a session identifier, compared case-insensitively (e.g.,
+// `PHPSESSID`)
+//
+// Sites that keep sessions in the URL rather than a cookie (mostly older PHP and Java
+// ones) identify the visitor by these, and the session expires soon after, so a link
+// with one both tracks whoever follows it and stops working.
+const SESSION_PARAMS: &[&str] = &["jsessionid", "phpsessid", "sessionid", "sid"];
+
+// Java servlets may also put the session in a path parameter, e.g.:
+//   <https://example.com/shop/item;jsessionid=0A1B2C3D?id=1>
+const SESSION_PATH_PARAM: &str = ";jsessionid=";
+
+pub(super) fn is_session_param(name: &str) -> bool {
+    SESSION_PARAMS
+        .iter()
+        .any(|param| param.eq_ignore_ascii_case(name))
+}
+
+// Remove session identifiers from the query and path
+pub(super) fn remove_session_ids(url: &mut Url, explanation: &mut Explanation) {
+    let (removed, kept): (Vec<_>, Vec<_>) = url
+        .query_pairs()
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .partition(|(k, _)| is_session_param(k));
+    if !removed.is_empty() {
+        let names: Vec<_> = removed.iter().map(|(k, _)| k.as_str()).collect();
+        explanation.step(format!(
+            "removed session query parameters: {}",
+            names.join(", ")
+        ));
         url.set_query(None);
+        if !kept.is_empty() {
+            url.query_pairs_mut().extend_pairs(kept);
+        }
+    }
+
+    // The path parameter runs to the end of its segment
+    let path = url.path();
+    if let Some(start) = path.to_ascii_lowercase().find(SESSION_PATH_PARAM) {
+        let end = path[start..].find('/').map_or(path.len(), |i| start + i);
+        let path = format!("{}{}", &path[..start], &path[end..]);
+        explanation.step("removed session path parameter: jsessionid");
+        url.set_path(&path);
+    }
+}
+
+// Fallback cleaner for hosts without a dedicated cleaner
+//
+// Unlike `GenericCleaner`, we know nothing about which query parameters the site needs
+// (e.g., YouTube's `v`), so we only remove those known to be used for tracking.
+pub struct FallbackCleaner;
+
+impl UrlCleaner for FallbackCleaner {
+    fn name(&self) -> &'static str {
+        "FallbackCleaner"
+    }
+
+    fn shapes(&self) -> &'static [&'static str] {
+        &["/<any path>"]
+    }
+
+    fn clean(&self, url: &mut Url, explanation: &mut Explanation) -> Result<(), CleanUrlError> {
+        explanation.rule("unsupported host");
+
+        // Step 1: remove known tracking parameters
+        remove_tracking_params(url, &TrackingParams::default(), explanation);
 
         // Step 2: remove trailing slash if any (provides no information)
-        url.path_segments_mut()
-            .map_err(|_| CleanUrlError::PathSegmentsError)?
-            .pop_if_empty();
+        remove_trailing_slash(url, explanation)?;
 
         Ok(())
     }
@@ -0,0 +1,113 @@
+use std::str::FromStr;
+
+use super::ParseOptionError;
+
+// Internationalised domain names
+//
+// The URL parser stores hosts in their ASCII (punycode) form, e.g., `xn--bcher-kva.de`
+// for `bücher.de`, which is what we dispatch on and what we print by default.  Unicode
+// is friendlier to read, but lets a host pass for another: `аpple.com` with a Cyrillic
+// `а` looks just like the real thing.  We cannot catch every such host, but one that
+// mixes scripts which have lookalike letters within a label is almost never genuine, so
+// we warn about those, and never show them in Unicode.
+
+// How to show internationalised hosts in cleaned URLs
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum HostDisplay {
+    // As punycode (e.g., `xn--bcher-kva.de`), which cannot be mistaken for another host
+    #[default]
+    Punycode,
+    // As Unicode (e.g., `bücher.de`), unless the host mixes scripts
+    Unicode,
+}
+
+impl FromStr for HostDisplay {
+    type Err = ParseOptionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "punycode" => Ok(HostDisplay::Punycode),
+            "unicode" => Ok(HostDisplay::Unicode),
+            _ => Err(ParseOptionError(s.to_string())),
+        }
+    }
+}
+
+// Scripts with letters that look like those of the others
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Script {
+    Latin,
+    Greek,
+    Cyrillic,
+    Armenian,
+}
+
+fn script(c: char) -> Option<Script> {
+    match c {
+        'a'..='z' | 'A'..='Z' | '\u{00C0}'..='\u{024F}' | '\u{1E00}'..='\u{1EFF}' => {
+            Some(Script::Latin)
+        }
+        '\u{0370}'..='\u{03FF}' | '\u{1F00}'..='\u{1FFF}' => Some(Script::Greek),
+        '\u{0400}'..='\u{052F}' => Some(Script::Cyrillic),
+        '\u{0530}'..='\u{058F}' => Some(Script::Armenian),
+        _ => None,
+    }
+}
+
+// The Unicode form of an ASCII host
+pub(crate) fn to_unicode(host: &str) -> String {
+    let (unicode, result) = idna::domain_to_unicode(host);
+    if result.is_ok() {
+        unicode
+    } else {
+        host.to_string()
+    }
+}
+
+// The ASCII form of a host, so that domains given in either form match
+pub(super) fn to_ascii(host: &str) -> String {
+    idna::domain_to_ascii(host).unwrap_or_else(|_| host.to_ascii_lowercase())
+}
+
+// Whether any label of a host (in its Unicode form) mixes letters from scripts that
+// look alike, as a spoof would
+pub(crate) fn is_mixed_script(host: &str) -> bool {
+    host.split('.').any(|label| {
+        let mut scripts = label.chars().filter_map(script);
+        scripts
+            .next()
+            .is_some_and(|first| scripts.any(|script| script != first))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_unicode() {
+        assert_eq!(to_unicode("xn--bcher-kva.de"), "bücher.de");
+        assert_eq!(to_unicode("www.reddit.com"), "www.reddit.com");
+        assert_eq!(to_ascii("Bücher.de"), "xn--bcher-kva.de");
+    }
+
+    #[test]
+    fn test_is_mixed_script() {
+        // A Cyrillic "а" among Latin letters
+        assert!(is_mixed_script("\u{0430}pple.com"));
+        // A Greek omicron
+        assert!(is_mixed_script("g\u{03BF}\u{03BF}gle.com"));
+
+        assert!(!is_mixed_script("bücher.de"));
+        assert!(!is_mixed_script("яндекс.рф"));
+        assert!(!is_mixed_script("例え.jp"));
+        // Each label may have its own script
+        assert!(!is_mixed_script("пример.example.com"));
+    }
+}
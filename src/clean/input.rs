@@ -0,0 +1,205 @@
+use std::borrow::Cow;
+
+use linkify::{LinkFinder, LinkKind};
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+// Links copied out of somewhere other than a browser's address bar
+//
+// Links taken from the source of an HTML email (or page) have their `&`s written as
+// `&amp;`, and those taken from a tracking link's query string may have been
+// percent-encoded once or twice over (e.g., `https%253A%252F%252Fexample.com`), neither of
+// which parses as the URL that was meant.  We undo both before parsing, as a link with
+// either is never what its author intended.
+//
+// Share sheets, too, often put some text before the link (e.g., "Check this out!
+// https://..."), in which case we take the first link in the text.
+
+// Maximum number of times to percent-decode an encoded link, as a safeguard
+const MAX_DECODES: usize = 3;
+
+// Named entities that may appear in an encoded link
+const ENTITIES: &[(&str, char)] = &[
+    ("amp", '&'),
+    ("apos", '\''),
+    ("quot", '"'),
+    ("lt", '<'),
+    ("gt", '>'),
+];
+
+// The character an entity (without its `&` and `;`, e.g., `amp` or `#38`) stands for
+fn entity(name: &str) -> Option<char> {
+    let code = match name.strip_prefix('#') {
+        Some(hex) if hex.starts_with(['x', 'X']) => u32::from_str_radix(&hex[1..], 16).ok()?,
+        Some(decimal) => decimal.parse().ok()?,
+        None => return ENTITIES.iter().find(|(n, _)| *n == name).map(|(_, c)| *c),
+    };
+    char::from_u32(code)
+}
+
+// Decode the HTML entities in `s`, leaving any `&` that does not begin one as it is
+fn decode_entities(s: &str) -> Cow<'_, str> {
+    if !s.contains('&') {
+        return Cow::Borrowed(s);
+    }
+    let mut decoded = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('&') {
+        decoded.push_str(&rest[..i]);
+        rest = &rest[i..];
+        match rest[1..]
+            .split_once(';')
+            .and_then(|(name, _)| Some((name.len(), entity(name)?)))
+        {
+            Some((len, c)) => {
+                decoded.push(c);
+                rest = &rest[len + 2..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    Cow::Owned(decoded)
+}
+
+// Whether `s` is a whole link percent-encoded, e.g., `https%3A%2F%2Fexample.com`
+fn is_encoded_link(s: &str) -> bool {
+    ["http%3a", "https%3a"].iter().any(|scheme| {
+        s.get(..scheme.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(scheme))
+    })
+}
+
+// Undo HTML entities and percent-encoding of a whole link, or take the first link from
+// surrounding text (see the note above), so that it may be parsed
+pub fn decode_input(input: &str) -> Cow<'_, str> {
+    let mut decoded = decode_entities(input.trim());
+    for _ in 0..MAX_DECODES {
+        if !is_encoded_link(&decoded) {
+            break;
+        }
+        decoded = Cow::Owned(
+            percent_decode_str(&decoded)
+                .decode_utf8_lossy()
+                .into_owned(),
+        );
+    }
+
+    // A link never contains whitespace, so if there is any, the link is somewhere within
+    if (decoded.contains(char::is_whitespace) || Url::parse(&decoded).is_err())
+        && let Some((start, end)) = find_urls(&decoded).first().copied()
+    {
+        decoded = Cow::Owned(decoded[start..end].to_string());
+    }
+    decoded
+}
+
+// Find the byte ranges of HTTP(S) URLs in text
+//
+// We use a linkifier rather than a naive regex so that trailing punctuation and
+// Markdown syntax (e.g., the closing parenthesis in `[text](url)`) are not included in
+// the URL.
+pub fn find_urls(text: &str) -> Vec<(usize, usize)> {
+    let mut finder = LinkFinder::new();
+    finder.kinds(&[LinkKind::Url]);
+    finder
+        .links(text)
+        .filter(|link| {
+            let s = link.as_str();
+            s.starts_with("https://") || s.starts_with("http://")
+        })
+        .map(|link| (link.start(), link.end()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_entities() {
+        assert_eq!(
+            decode_input("https://example.com/?a=1&amp;b=2&#38;c=3&#x26;d=4"),
+            "https://example.com/?a=1&b=2&c=3&d=4"
+        );
+        // Only entities are decoded
+        assert_eq!(
+            decode_input("https://example.com/?a=1&b=2&c;d"),
+            "https://example.com/?a=1&b=2&c;d"
+        );
+        assert_eq!(
+            decode_input("https://example.com/?q=&amp"),
+            "https://example.com/?q=&amp"
+        );
+    }
+
+    #[test]
+    fn test_decode_percent_encoded() {
+        assert_eq!(
+            decode_input("https%3A%2F%2Fexample.com%2Fa%3Fb%3D1"),
+            "https://example.com/a?b=1"
+        );
+        assert_eq!(
+            decode_input("https%253A%252F%252Fexample.com%252Fa"),
+            "https://example.com/a"
+        );
+        assert_eq!(
+            decode_input("HTTP%3A%2F%2Fexample.com"),
+            "HTTP://example.com"
+        );
+        // Encoding within a link is left alone
+        let url = "https://example.com/a%2520b?next=https%3A%2F%2Fexample.org";
+        assert_eq!(decode_input(url), url);
+    }
+
+    #[test]
+    fn test_extract_from_text() {
+        assert_eq!(
+            decode_input("Check this out! https://www.reddit.com/r/rust/s/abc so funny"),
+            "https://www.reddit.com/r/rust/s/abc"
+        );
+        assert_eq!(
+            decode_input("Look (https://example.com/a?b=1&amp;c=2), and https://example.org/"),
+            "https://example.com/a?b=1&c=2"
+        );
+        // Without a link, there is nothing to take
+        assert_eq!(decode_input("not a link"), "not a link");
+    }
+
+    fn urls(text: &str) -> Vec<&str> {
+        find_urls(text)
+            .into_iter()
+            .map(|(start, end)| &text[start..end])
+            .collect()
+    }
+
+    #[test]
+    fn test_plain_text() {
+        let text = "See https://www.reddit.com/r/AskTheWorld/s/mONZu40JNk for more.";
+        assert_eq!(
+            urls(text),
+            ["https://www.reddit.com/r/AskTheWorld/s/mONZu40JNk"]
+        );
+    }
+
+    #[test]
+    fn test_markdown_link() {
+        let text = "A [post](https://www.facebook.com/share/r/14QeSSeP3nu/) and <https://www.reddit.com/r/AskTheWorld/s/mONZu40JNk>.";
+        assert_eq!(
+            urls(text),
+            [
+                "https://www.facebook.com/share/r/14QeSSeP3nu/",
+                "https://www.reddit.com/r/AskTheWorld/s/mONZu40JNk",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_non_http_schemes() {
+        let text = "Email mailto:someone@example.com or ftp://example.com/file";
+        assert!(urls(text).is_empty());
+    }
+}
@@ -0,0 +1,125 @@
+use url::Url;
+
+use super::{Explanation, adguard::is_subdomain};
+
+// Locale path prefixes
+//
+// Many sites serve the same page under a prefix for each locale (e.g., `/en-us/` and
+// `/de-de/`), so the same page shared from different regions gives different links.  We
+// only touch the sites listed here, which are known to serve the same content whatever
+// the locale, as elsewhere a locale may pick out a different page altogether.
+
+// Sites whose locale prefixes we remove, or, where a page cannot be reached without one,
+// replace with the given locale
+const LOCALE_SITES: &[(&str, Option<&str>)] = &[
+    ("google.com", None),
+    ("microsoft.com", None),
+    ("mozilla.org", None),
+    ("nvidia.com", Some("en-us")),
+];
+
+// Whether a path segment is a locale, in the form `<language>-<region>` (e.g., `en-us`,
+// or `en_US`).  A bare language or region is not enough, as two-letter segments are
+// common enough in paths (e.g., `/tv/`)
+fn is_locale(segment: &str) -> bool {
+    match segment.as_bytes() {
+        [a, b, b'-' | b'_', c, d] => [a, b, c, d].iter().all(|x| x.is_ascii_alphabetic()),
+        _ => false,
+    }
+}
+
+// The number of leading path segments that give the locale: `/en-us/`, or Google's
+// `/intl/en/` (or `/intl/en_uk/`)
+fn locale_segments(segments: &[&str]) -> usize {
+    match segments {
+        ["intl", locale, ..] if is_locale(locale) || locale.len() == 2 => 2,
+        [locale, ..] if is_locale(locale) => 1,
+        _ => 0,
+    }
+}
+
+// Remove (or replace) the locale prefix of the path, if the site is one known to serve
+// the same content across locales
+pub(super) fn normalize_locale(url: &mut Url, explanation: &mut Explanation) {
+    let Some(host) = url.host_str() else {
+        return;
+    };
+    let Some((_, replacement)) = LOCALE_SITES
+        .iter()
+        .find(|(domain, _)| is_subdomain(host, domain))
+    else {
+        return;
+    };
+    let Some(segments) = url.path_segments() else {
+        return;
+    };
+    let segments: Vec<_> = segments.collect();
+    let n = locale_segments(&segments);
+    if n == 0 {
+        return;
+    }
+
+    let locale = segments[..n].join("/");
+    let rest = segments[n..].join("/");
+    let path = match replacement {
+        Some(replacement) if *replacement == locale => return,
+        Some(replacement) => {
+            explanation.step(format!("replaced locale {} with {}", locale, replacement));
+            format!("/{}/{}", replacement, rest)
+        }
+        None => {
+            explanation.step(format!("removed locale {}", locale));
+            format!("/{}", rest)
+        }
+    };
+    url.set_path(&path);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalized(url: &str) -> String {
+        let mut url = Url::parse(url).expect("valid url");
+        normalize_locale(&mut url, &mut Explanation::default());
+        url.to_string()
+    }
+
+    #[test]
+    fn test_normalize_locale() {
+        assert_eq!(
+            normalized("https://learn.microsoft.com/en-us/windows/wsl/install"),
+            "https://learn.microsoft.com/windows/wsl/install"
+        );
+        assert_eq!(
+            normalized("https://learn.microsoft.com/de-DE/windows/wsl/install"),
+            "https://learn.microsoft.com/windows/wsl/install"
+        );
+        assert_eq!(
+            normalized("https://www.google.com/intl/en_uk/chrome/"),
+            "https://www.google.com/chrome/"
+        );
+        assert_eq!(
+            normalized("https://www.google.com/intl/de/gmail/about/"),
+            "https://www.google.com/gmail/about/"
+        );
+        assert_eq!(
+            normalized("https://www.nvidia.com/de-de/geforce/"),
+            "https://www.nvidia.com/en-us/geforce/"
+        );
+    }
+
+    #[test]
+    fn test_unchanged() {
+        for url in [
+            // Not a site we know
+            "https://example.com/en-us/about",
+            // Not a locale
+            "https://www.microsoft.com/tv/",
+            "https://developer.mozilla.org/docs/Web",
+            "https://www.nvidia.com/en-us/geforce/",
+        ] {
+            assert_eq!(normalized(url), url);
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use url::Url;
+
+// Mobile hosts
+//
+// Despite our desktop user agent (see the note in `ResolverBuilder::build`), some links
+// still end up on a site's mobile host (e.g., `m.facebook.com`), whether shared from a
+// phone or because the site decided we were one.  The page is the same, so we use the
+// desktop host, which is what most people expect to see, and lets equivalent links
+// compare equal.
+
+// Labels that mark a mobile host, when they come just before the registrable domain
+const MOBILE_LABELS: &[&str] = &["m", "mbasic", "mobile", "touch"];
+
+// Platforms whose desktop host is the registrable domain itself, rather than `www.`
+// followed by it (the default)
+//
+// A platform whose mobile label comes after a subdomain of its own (e.g., Wikipedia's
+// `en.m.wikipedia.org`) needs no entry, as the subdomain is kept.
+const BARE_DOMAINS: &[&str] = &["twitter.com", "x.com", "tiktok.com"];
+
+// The desktop host for `url`, if it is on a mobile host
+pub(super) fn desktop_host(url: &Url) -> Option<String> {
+    let host = url.host_str()?;
+    let domain = psl::domain_str(host)?;
+    let subdomain = host.strip_suffix(domain)?.strip_suffix('.')?;
+    let (rest, label) = match subdomain.rsplit_once('.') {
+        Some((rest, label)) => (Some(rest), label),
+        None => (None, subdomain),
+    };
+    if !MOBILE_LABELS.contains(&label) {
+        return None;
+    }
+    Some(match rest {
+        Some(rest) => format!("{}.{}", rest, domain),
+        None if BARE_DOMAINS.contains(&domain) => domain.to_string(),
+        None => format!("www.{}", domain),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn desktop(url: &str) -> Option<String> {
+        desktop_host(&Url::parse(url).expect("valid url"))
+    }
+
+    #[test]
+    fn test_desktop_host() {
+        assert_eq!(
+            desktop("https://m.facebook.com/story.php").as_deref(),
+            Some("www.facebook.com")
+        );
+        assert_eq!(
+            desktop("https://mbasic.facebook.com/").as_deref(),
+            Some("www.facebook.com")
+        );
+        assert_eq!(
+            desktop("https://touch.facebook.com/").as_deref(),
+            Some("www.facebook.com")
+        );
+        assert_eq!(
+            desktop("https://mobile.twitter.com/rustlang").as_deref(),
+            Some("twitter.com")
+        );
+        assert_eq!(
+            desktop("https://en.m.wikipedia.org/wiki/Rust").as_deref(),
+            Some("en.wikipedia.org")
+        );
+    }
+
+    #[test]
+    fn test_not_mobile() {
+        assert_eq!(desktop("https://www.facebook.com/"), None);
+        assert_eq!(desktop("https://facebook.com/"), None);
+        // The label has to come just before the registrable domain
+        assert_eq!(desktop("https://m.news.bbc.co.uk/"), None);
+        // And not be the registrable domain itself
+        assert_eq!(desktop("https://m.me/rustlang"), None);
+        assert_eq!(desktop("https://www.mobile.de/"), None);
+    }
+}
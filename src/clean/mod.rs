@@ -1,18 +1,52 @@
 // Structure of `clean` submodule inspired by:
 //   <https://github.com/jakewilliami/citati/tree/8bb1e472/src/source>
 
-use std::error::Error;
+use std::{error::Error, fmt, str::FromStr, sync::Arc};
 
-use url::Url;
+use url::{Position, Url};
 
+mod adguard;
+#[cfg(feature = "serde")]
+mod clearurls;
+mod essential;
 mod facebook;
 mod generic;
+mod idn;
+mod input;
+mod locale;
+mod mobile;
+mod nested;
+mod normalize;
+#[cfg(all(feature = "plugins", not(target_arch = "wasm32")))]
+mod plugin;
 mod reddit;
+mod report;
+mod rules;
+mod wayback;
+
+pub use adguard::{FilterListError, RemoveParamFilters};
+#[cfg(feature = "serde")]
+pub use clearurls::{ClearUrlsCleaner, ClearUrlsError};
+pub use generic::TrackingParams;
+pub use idn::HostDisplay;
+#[cfg(feature = "net")]
+pub(crate) use idn::{is_mixed_script, to_unicode};
+pub use input::{decode_input, find_urls};
+pub use normalize::normalize;
+#[cfg(all(feature = "plugins", not(target_arch = "wasm32")))]
+pub use plugin::{PluginError, WasmCleaner, load_plugins};
+pub use report::{Confidence, Removed, removed_components};
+pub use rules::{CleaningRule, RuleCleaner};
+pub use wayback::WaybackPolicy;
 
 // Error type for clean URL function
 #[derive(Debug, derive_more::Display)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum CleanUrlError {
-    ParseError(url::ParseError),
+    ParseError(
+        #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serialize::display"))]
+        url::ParseError,
+    ),
     PathSegmentsError,
     UnknownDomain,
     UnsupportedUrlScheme,
@@ -35,39 +69,741 @@ impl From<url::ParseError> for CleanUrlError {
     }
 }
 
+// Error type for parsing an option (e.g., a `FragmentPolicy`) from its name
+#[derive(Debug, derive_more::Display)]
+#[display("unknown option \"{_0}\"")]
+pub struct ParseOptionError(pub String);
+
+impl Error for ParseOptionError {}
+
 // Trait for platform-specific URL cleaners
-trait UrlCleaner {
-    // Clean the URL according to platform-specific rules
-    fn clean(&self, url: &mut Url) -> Result<(), CleanUrlError>;
+//
+// Implement this to add support for a new platform, and register it with a
+// `CleanerRegistry`.
+pub trait UrlCleaner: Send + Sync {
+    // Name of the cleaner, as shown by `--explain`
+    fn name(&self) -> &'static str;
+
+    // Human-readable descriptions of the URL shapes accepted by the cleaner, as shown by
+    // `--list-supported`
+    fn shapes(&self) -> &'static [&'static str];
+
+    // Query parameters that the cleaner preserves (when relevant to the URL shape)
+    fn preserved_params(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    // Clean the URL according to platform-specific rules, recording each decision made
+    // in `explanation`
+    fn clean(&self, url: &mut Url, explanation: &mut Explanation) -> Result<(), CleanUrlError>;
+
+    // Whether the URL is already in one of the shapes the cleaner accepts, such that
+    // there is no need to resolve it first (e.g., a full Reddit post URL, rather than a
+    // share link to one).  By default, none are, as a cleaner that accepts any path
+    // cannot tell a page from a link that redirects to one
+    fn recognises(&self, url: &Url) -> bool {
+        let _ = url;
+        false
+    }
+
+    // Whether the cleaner knows which query parameters its platform needs, so that any it
+    // removes are known not to be needed.  A cleaner that removes every parameter
+    // regardless (e.g., `GenericCleaner`) is only guessing, and those it removes that
+    // are not known to be used for tracking are reported; see `CleanedUrl::unknown_params`
+    fn knows_params(&self) -> bool {
+        true
+    }
+
+    // Whether the fragment of a cleaned URL is meaningful (e.g., an anchor within the
+    // page), and so survives cleaning with `FragmentPolicy::Auto`.  By default, any
+    // fragment that does not look like tracking is
+    fn keeps_fragment(&self, url: &Url, fragment: &str) -> bool {
+        let _ = url;
+        !is_tracking_fragment(fragment)
+    }
+}
+
+// Registry of supported domains and the cleaner used for each.  Dispatch, as well as
+// `--list-supported`, is driven by this list
+//
+// Domains are registrable domains (e.g., "reddit.com" rather than "www.reddit.com"),
+// according to the Public Suffix List.
+#[derive(Clone)]
+pub struct CleanerRegistry {
+    cleaners: Vec<(String, Arc<dyn UrlCleaner>)>,
+    // Used for hosts without a registered cleaner (unless cleaning is strict)
+    fallback: Arc<dyn UrlCleaner>,
+}
+
+impl Default for CleanerRegistry {
+    // The built-in cleaners
+    fn default() -> Self {
+        let mut registry = CleanerRegistry::new();
+        registry.register("reddit.com", reddit::RedditCleaner);
+        registry.register("facebook.com", facebook::FacebookCleaner);
+        registry.register("instagram.com", generic::GenericCleaner);
+        registry.register("linkedin.com", generic::GenericCleaner);
+        registry
+    }
+}
+
+impl fmt::Debug for CleanerRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.cleaners.iter().map(|(d, c)| (d, c.name())))
+            .finish()
+    }
+}
+
+impl CleanerRegistry {
+    // An empty registry, without any of the built-in cleaners
+    pub fn new() -> Self {
+        CleanerRegistry {
+            cleaners: Vec::new(),
+            fallback: Arc::new(generic::FallbackCleaner),
+        }
+    }
+
+    // Use `cleaner` for URLs on `domain`, replacing any cleaner already registered for it.
+    // Internationalised domains may be given in Unicode or punycode
+    pub fn register(&mut self, domain: impl Into<String>, cleaner: impl UrlCleaner + 'static) {
+        self.register_arc(domain.into(), Arc::new(cleaner));
+    }
+
+    fn register_arc(&mut self, domain: String, cleaner: Arc<dyn UrlCleaner>) {
+        let domain = idn::to_ascii(&domain);
+        match self.cleaners.iter_mut().find(|(d, _)| *d == domain) {
+            Some(entry) => entry.1 = cleaner,
+            None => self.cleaners.push((domain, cleaner)),
+        }
+    }
+
+    // Use the built-in cleaner called `name` (e.g., "reddit" or "GenericCleaner") for
+    // URLs on `domain`, returning false if there is no such cleaner.  This allows
+    // cleaners to be mapped to additional domains from configuration
+    pub fn register_builtin(&mut self, domain: impl Into<String>, name: &str) -> bool {
+        let name = name.to_lowercase();
+        let name = name.strip_suffix("cleaner").unwrap_or(&name);
+        let cleaner: Arc<dyn UrlCleaner> = match name {
+            "reddit" => Arc::new(reddit::RedditCleaner),
+            "facebook" => Arc::new(facebook::FacebookCleaner),
+            "generic" => Arc::new(generic::GenericCleaner),
+            "fallback" => Arc::new(generic::FallbackCleaner),
+            _ => return false,
+        };
+        self.register_arc(domain.into(), cleaner);
+        true
+    }
+
+    // Use `cleaner` for hosts without a registered cleaner, rather than only removing
+    // known tracking parameters (e.g., a `ClearUrlsCleaner`)
+    pub fn set_fallback(&mut self, cleaner: impl UrlCleaner + 'static) {
+        self.fallback = Arc::new(cleaner);
+    }
+
+    // The cleaner used for hosts without a registered cleaner
+    pub fn fallback(&self) -> &dyn UrlCleaner {
+        self.fallback.as_ref()
+    }
+
+    // The cleaner registered for `domain` (in punycode, as it is in a parsed URL), if any
+    pub fn get(&self, domain: &str) -> Option<&dyn UrlCleaner> {
+        self.cleaners
+            .iter()
+            .find(|(d, _)| d == domain)
+            .map(|(_, cleaner)| cleaner.as_ref())
+    }
+
+    // List every supported domain, in the order in which they were registered
+    pub fn supported_platforms(&self) -> Vec<SupportedPlatform> {
+        self.cleaners
+            .iter()
+            .map(|(domain, cleaner)| SupportedPlatform {
+                domain: domain.clone(),
+                cleaner: cleaner.name(),
+                shapes: cleaner.shapes(),
+                preserved_params: cleaner.preserved_params(),
+            })
+            .collect()
+    }
+}
+
+// Description of a supported platform, derived from the registry
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SupportedPlatform {
+    pub domain: String,
+    pub cleaner: &'static str,
+    pub shapes: &'static [&'static str],
+    pub preserved_params: &'static [&'static str],
+}
+
+impl fmt::Display for SupportedPlatform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} ({})", self.domain, self.cleaner)?;
+        writeln!(f, "  URL shapes:")?;
+        for shape in self.shapes {
+            writeln!(f, "    https://www.{}{}", self.domain, shape)?;
+        }
+        write!(f, "  Preserved query parameters: ")?;
+        if self.preserved_params.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", self.preserved_params.join(", "))
+        }
+    }
+}
+
+// List every domain supported by the built-in cleaners
+pub fn supported_platforms() -> Vec<SupportedPlatform> {
+    CleanerRegistry::default().supported_platforms()
+}
+
+// Record of the decisions made while cleaning a URL: which cleaner was dispatched,
+// which of its rules matched the URL, and each transformation applied.  This is
+// populated even if cleaning fails, which is the main reason it exists
+#[derive(Debug, Default)]
+pub struct Explanation {
+    url: String,
+    cleaner: Option<&'static str>,
+    rule: Option<&'static str>,
+    steps: Vec<String>,
+    removed: Vec<Removed>,
+}
+
+impl Explanation {
+    // Record which of the cleaner's rules matched the URL
+    pub fn rule(&mut self, rule: &'static str) {
+        self.rule = Some(rule);
+    }
+
+    // Record a transformation applied to the URL
+    pub fn step(&mut self, step: impl Into<String>) {
+        self.steps.push(step.into());
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Explanation for {}", self.url)?;
+        writeln!(f, "  cleaner: {}", self.cleaner.unwrap_or("none"))?;
+        writeln!(f, "  rule:    {}", self.rule.unwrap_or("none matched"))?;
+        write!(f, "  steps:")?;
+        if self.steps.is_empty() {
+            write!(f, " none")?;
+        }
+        for (i, step) in self.steps.iter().enumerate() {
+            write!(f, "\n    {}. {}", i + 1, step)?;
+        }
+        if !self.removed.is_empty() {
+            write!(f, "\n  removed:")?;
+        }
+        for removed in &self.removed {
+            write!(f, "\n    - {}", removed)?;
+        }
+        Ok(())
+    }
+}
+
+// User-specified options which override the per-platform cleaning rules
+#[derive(Clone, Debug, Default)]
+pub struct CleanOptions {
+    // Query parameters that should survive cleaning, even if the platform's cleaner
+    // would otherwise remove them
+    pub keep_params: Vec<String>,
+    // Additional query parameters to remove, even if the platform's cleaner would
+    // otherwise preserve them.  This takes precedence over `keep_params`
+    pub strip_params: Vec<String>,
+    // Whether `#` fragments survive cleaning
+    pub fragments: FragmentPolicy,
+    // Reject hosts without a dedicated cleaner, rather than falling back to removing
+    // known tracking parameters
+    pub strict: bool,
+    // Cleaners to dispatch to, by domain
+    pub registry: CleanerRegistry,
+    // `$removeparam` rules from AdGuard filter lists, applied on every host
+    pub filters: RemoveParamFilters,
+    // Query parameters removed from every host before its cleaner runs
+    pub tracking_params: TrackingParams,
+    // Whether to show internationalised hosts as punycode or Unicode
+    pub hosts: HostDisplay,
+    // Domains (and their subdomains) whose session identifiers (e.g., `PHPSESSID`)
+    // survive cleaning, as the link would not work without them
+    pub keep_session_ids: Vec<String>,
+    // Remove a trailing index page (e.g., `index.html`) from the path, which most servers
+    // give for the directory it is in anyway
+    pub drop_index: bool,
+    // Whether to link to the Wayback Machine snapshot of a link to one, or the original
+    pub wayback: WaybackPolicy,
+    // Remove locale prefixes from paths (e.g., `/en-us/`) on sites known to serve the
+    // same content across locales
+    pub strip_locale: bool,
+}
+
+// Policy for handling `#` fragments in cleaned URLs
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum FragmentPolicy {
+    // Always keep fragments
+    Keep,
+    // Always remove fragments
+    Strip,
+    // Keep meaningful anchors (e.g., Wikipedia sections or GitHub line ranges), but
+    // remove fragments used for tracking
+    #[default]
+    Auto,
+}
+
+// Parse a policy from its name (ignoring case), as given on the command line or to the
+// WebAssembly interface
+impl FromStr for FragmentPolicy {
+    type Err = ParseOptionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "keep" => Ok(FragmentPolicy::Keep),
+            "strip" => Ok(FragmentPolicy::Strip),
+            "auto" => Ok(FragmentPolicy::Auto),
+            _ => Err(ParseOptionError(s.to_string())),
+        }
+    }
+}
+
+// Keys that, when they appear as `key=value` in a fragment, indicate that the fragment
+// is used for tracking rather than navigation (e.g., `#Echobox=1700000000`)
+const TRACKING_FRAGMENT_KEYS: &[&str] = &[
+    "Echobox",
+    "at_campaign",
+    "at_medium",
+    "ref",
+    "utm_campaign",
+    "utm_content",
+    "utm_medium",
+    "utm_source",
+    "utm_term",
+    "xtor",
+];
+
+// Remove the query string from a URL, recording the names of the parameters removed
+pub fn remove_query(url: &mut Url, explanation: &mut Explanation) {
+    let names: Vec<_> = url.query_pairs().map(|(k, _)| k.into_owned()).collect();
+    if !names.is_empty() {
+        explanation.step(format!("removed query parameters: {}", names.join(", ")));
+    }
+    url.set_query(None);
+}
+
+// Remove trailing slash if any (provides no information)
+pub fn remove_trailing_slash(
+    url: &mut Url,
+    explanation: &mut Explanation,
+) -> Result<(), CleanUrlError> {
+    if url.path().len() > 1 && url.path().ends_with('/') {
+        explanation.step("removed trailing slash");
+    }
+    // There may be more than one (e.g., `/a//`), and we must remove them all, or else
+    // cleaning the result would remove another
+    while url.path().len() > 1 && url.path().ends_with('/') {
+        url.path_segments_mut()
+            .map_err(|_| CleanUrlError::PathSegmentsError)?
+            .pop();
+    }
+    Ok(())
+}
+
+// The result of cleaning a URL
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CleanedUrl {
+    // The canonical URL
+    pub url: String,
+    // The registrable domain of the URL (e.g., "reddit.com")
+    pub platform: Option<String>,
+    // The kind of content the URL refers to, according to the rule matched by the
+    // cleaner (e.g., "post" or "reel")
+    pub kind: Option<String>,
+    // Names of query parameters removed by cleaning
+    pub removed_params: Vec<String>,
+    // Everything removed by cleaning, in more detail; see `Removed`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub removed: Vec<Removed>,
+    // Names of query parameters removed by a cleaner that does not know whether the site
+    // needs them (see `UrlCleaner::knows_params`), and which are not known to be used for
+    // tracking either
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub unknown_params: Vec<String>,
+    // Whether the input was already in its canonical form
+    pub canonical: bool,
+}
+
+impl CleanedUrl {
+    pub fn as_str(&self) -> &str {
+        &self.url
+    }
+
+    // How sure we are that cleaning removed nothing the site needs
+    pub fn confidence(&self) -> Confidence {
+        Confidence::of(&self.unknown_params)
+    }
+}
+
+impl fmt::Display for CleanedUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.url)
+    }
+}
+
+impl From<CleanedUrl> for String {
+    fn from(cleaned: CleanedUrl) -> Self {
+        cleaned.url
+    }
+}
+
+// Allow comparing a cleaned URL directly with a string, as is often convenient
+impl PartialEq<str> for CleanedUrl {
+    fn eq(&self, other: &str) -> bool {
+        self.url == other
+    }
+}
+
+impl PartialEq<&str> for CleanedUrl {
+    fn eq(&self, other: &&str) -> bool {
+        self.url == *other
+    }
+}
+
+impl PartialEq<String> for CleanedUrl {
+    fn eq(&self, other: &String) -> bool {
+        self.url == *other
+    }
+}
+
+impl PartialEq<CleanedUrl> for &str {
+    fn eq(&self, other: &CleanedUrl) -> bool {
+        *self == other.url
+    }
+}
+
+impl PartialEq<CleanedUrl> for String {
+    fn eq(&self, other: &CleanedUrl) -> bool {
+        *self == other.url
+    }
 }
 
 // Clean URL
-pub fn clean_url(url: &str) -> Result<String, CleanUrlError> {
-    // Step 1: parse URL
-    let mut url = Url::parse(url)?;
+//
+// Cleaning never panics, whatever the input, and is idempotent: cleaning a URL that has
+// already been cleaned (with the same options) returns it unchanged.  Both are checked
+// by property tests below, and by the fuzz targets in `fuzz/`.  The one exception to
+// the latter is if `strip_params` removes a parameter the platform needs (e.g., a
+// photo's `fbid`), as then the result is no longer a URL we recognise
+pub fn clean_url(url: &str) -> Result<CleanedUrl, CleanUrlError> {
+    clean_url_explained(url, &CleanOptions::default(), &mut Explanation::default())
+}
+
+// Clean URL according to the user's options, recording the decisions made in
+// `explanation`
+pub fn clean_url_explained(
+    url: &str,
+    options: &CleanOptions,
+    explanation: &mut Explanation,
+) -> Result<CleanedUrl, CleanUrlError> {
+    explanation.url = url.to_string();
+    let input = url;
+
+    // Step 1: parse URL, once any HTML entities or percent-encoding of the whole link
+    // (e.g., from the source of an email) are undone; see `decode_input`
+    let decoded = decode_input(url);
+    if decoded != url {
+        explanation.step(format!("decoded input to {}", decoded));
+    }
+    let mut url = Url::parse(&decoded)?;
 
     // Step 2: validate scheme
     let scheme = url.scheme();
     if scheme != "https" && scheme != "http" {
+        explanation.step(format!("rejected unsupported scheme \"{}\"", scheme));
         return Err(CleanUrlError::UnsupportedUrlScheme);
     }
 
-    // Step 3: dispatch to defined URL cleaner based on domain name
-    let host = url.host_str().expect("url host is valid");
-    let cleaner: &dyn UrlCleaner = match psl::domain_str(host) {
-        Some(domain) => match domain {
-            "reddit.com" => &reddit::RedditCleaner,
-            "facebook.com" => &facebook::FacebookCleaner,
-            "instagram.com" => &generic::GenericCleaner,
-            "linkedin.com" => &generic::GenericCleaner,
-            _ => return Err(CleanUrlError::UnsupportedUrlHost),
+    // Step 3: unwrap links to redirectors (e.g., `l.facebook.com/l.php?u=...`), so that
+    // what we clean is where the link actually goes.  The destination may itself be a
+    // redirector, or need cleaning of its own.  We then move from the mobile version of
+    // the site, if on one, and tidy the path, so that cleaners see the page as it would
+    // usually be linked
+    //
+    // Links to a snapshot in the Wayback Machine are cleaned in the same way, although
+    // we may link to the snapshot in the end; see `WaybackPolicy`
+    let mut removed = Vec::new();
+    let snapshot = wayback::parse(&url).map(|(timestamp, original)| {
+        explanation.step(format!(
+            "extracted {} from snapshot {}",
+            original, timestamp
+        ));
+        url = original;
+        timestamp
+    });
+    if let Some(timestamp) = &snapshot
+        && options.wayback == WaybackPolicy::Extract
+    {
+        removed.push(Removed::Snapshot {
+            timestamp: timestamp.clone(),
+        });
+    }
+    for _ in 0..nested::MAX_NESTING {
+        let Some(inner) = nested::unwrap(&url) else {
+            break;
+        };
+        explanation.step(format!("unwrapped link to {}", inner));
+        removed.push(Removed::Redirector {
+            host: url.host_str().unwrap_or_default().to_string(),
+        });
+        url = inner;
+    }
+    let unwrapped = url.clone();
+    if let Some(desktop) = mobile::desktop_host(&url) {
+        explanation.step(format!("replaced mobile host with {}", desktop));
+        url.set_host(Some(&desktop))?;
+    }
+    normalize::normalize_path(&mut url, options.drop_index, explanation);
+    if options.strip_locale {
+        locale::normalize_locale(&mut url, explanation);
+    }
+
+    // Step 4: dispatch to defined URL cleaner based on domain name
+    //
+    // Hosts are in punycode, as are the domains in the Public Suffix List and the
+    // registry, so internationalised domains need no special handling here
+    let Some(host) = url.host_str() else {
+        explanation.step("URL has no host");
+        return Err(CleanUrlError::UnknownDomain);
+    };
+    let unicode_host = idn::to_unicode(host);
+    if idn::is_mixed_script(&unicode_host) {
+        explanation.step(format!(
+            "host \"{}\" mixes scripts, so may be impersonating another",
+            unicode_host
+        ));
+        tracing::warn!(host = %unicode_host, "host mixes scripts, so may be impersonating another");
+    }
+    let (cleaner, fallback): (&dyn UrlCleaner, bool) = match psl::domain_str(host) {
+        Some(domain) => match options.registry.get(domain) {
+            Some(cleaner) => (cleaner, false),
+            None if options.strict => {
+                explanation.step(format!("no cleaner for domain \"{}\"", domain));
+                return Err(CleanUrlError::UnsupportedUrlHost);
+            }
+            None => {
+                explanation.step(format!(
+                    "no cleaner for domain \"{}\"; falling back to generic cleaning",
+                    domain
+                ));
+                (options.registry.fallback(), true)
+            }
         },
-        _ => return Err(CleanUrlError::UnknownDomain),
+        _ => {
+            explanation.step(format!("could not determine domain of host \"{}\"", host));
+            return Err(CleanUrlError::UnknownDomain);
+        }
+    };
+    explanation.cleaner = Some(cleaner.name());
+    tracing::debug!(host, cleaner = cleaner.name(), "dispatched cleaner");
+
+    // Step 5: apply cleaner, keeping a copy of the original query parameters
+    let original_params: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+
+    // Parameters used only for tracking are removed first, whichever cleaner is used, so
+    // that a dedicated cleaner that keeps some parameters cannot keep those.  The same
+    // goes for session identifiers, unless the user has said the domain needs them
+    generic::remove_tracking_params(&mut url, &options.tracking_params, explanation);
+    let host = url.host_str().unwrap_or_default();
+    if !options
+        .keep_session_ids
+        .iter()
+        .any(|domain| adguard::is_subdomain(host, domain))
+    {
+        generic::remove_session_ids(&mut url, explanation);
+    }
+
+    // The cleaner runs in its own span, so that subscribers can time each cleaner
+    // (e.g., with `FmtSpan::CLOSE`, as `-vv` does)
+    tracing::debug_span!(
+        "clean",
+        cleaner = cleaner.name(),
+        url = %crate::redact::url(input)
+    )
+    .in_scope(|| cleaner.clean(&mut url, explanation))?;
+    options.filters.apply(&mut url, explanation);
+    // Without a dedicated cleaner, nothing else knows which parameters the site needs, so
+    // we make sure that neither the fallback (e.g., a `ClearUrlsCleaner`) nor a filter
+    // list removed any we know it does
+    if fallback {
+        essential::restore_essential_params(&mut url, &original_params, explanation);
+    }
+
+    // Final step: apply user overrides and return modified URL
+    keep_params(
+        &mut url,
+        &original_params,
+        &options.keep_params,
+        explanation,
+    );
+    strip_params(&mut url, &options.strip_params, explanation);
+    apply_fragment_policy(&mut url, options.fragments, cleaner, explanation);
+    normalize(&mut url);
+
+    let remaining: Vec<_> = url.query_pairs().map(|(k, _)| k.into_owned()).collect();
+    let mut removed_params: Vec<String> = Vec::new();
+    for (k, _) in original_params {
+        if !remaining.contains(&k) && !removed_params.contains(&k) {
+            removed_params.push(k);
+        }
+    }
+    // Parameters removed by a cleaner that does not know which the site needs, but which
+    // are not known to be used for tracking, may have been needed
+    let unknown_params: Vec<String> = if cleaner.knows_params() {
+        Vec::new()
+    } else {
+        removed_params
+            .iter()
+            .filter(|&k| {
+                !options.tracking_params.contains(k)
+                    && !generic::is_session_param(k)
+                    && !options.strip_params.contains(k)
+            })
+            .cloned()
+            .collect()
+    };
+    if !unknown_params.is_empty() {
+        explanation.step(format!(
+            "guessed that unknown query parameters are not needed: {}",
+            unknown_params.join(", ")
+        ));
+    }
+    removed.extend(removed_components(&unwrapped, &url));
+    explanation.removed = removed.clone();
+    // For a snapshot, this is the platform of the original, as is the kind of content
+    let platform = url.host_str().and_then(psl::domain_str).map(str::to_string);
+    let url = match (options.hosts, url.host_str().map(idn::to_unicode)) {
+        (HostDisplay::Unicode, Some(host)) if !idn::is_mixed_script(&host) => format!(
+            "{}{}{}",
+            &url[..Position::BeforeHost],
+            host,
+            &url[Position::AfterHost..]
+        ),
+        _ => url.to_string(),
+    };
+    let url = match snapshot {
+        Some(timestamp) if options.wayback == WaybackPolicy::Wrap => {
+            wayback::snapshot_url(&timestamp, &url)
+        }
+        _ => url,
+    };
+    Ok(CleanedUrl {
+        canonical: url == input,
+        platform,
+        kind: explanation.rule.map(str::to_string),
+        removed_params,
+        removed,
+        unknown_params,
+        url,
+    })
+}
+
+// Whether `url` is already the full URL of a page that its platform's cleaner recognises
+// (see `UrlCleaner::recognises`), such that resolving it would only lead back to the
+// same page
+pub fn is_recognised(url: &Url, options: &CleanOptions) -> bool {
+    url.host_str()
+        .and_then(psl::domain_str)
+        .and_then(|domain| options.registry.get(domain))
+        .is_some_and(|cleaner| cleaner.recognises(url))
+}
+
+// Restore any parameters from the original URL that the user asked to keep, but which
+// the cleaner removed
+fn keep_params(
+    url: &mut Url,
+    original_params: &[(String, String)],
+    keep: &[String],
+    explanation: &mut Explanation,
+) {
+    let present: Vec<String> = url.query_pairs().map(|(k, _)| k.into_owned()).collect();
+    let restore: Vec<_> = original_params
+        .iter()
+        .filter(|(k, _)| keep.contains(k) && !present.contains(k))
+        .collect();
+    if restore.is_empty() {
+        return;
+    }
+
+    let mut pairs = url.query_pairs_mut();
+    for (k, v) in &restore {
+        pairs.append_pair(k, v);
+        explanation.step(format!("kept query parameter {} (--keep-params)", k));
+    }
+}
+
+// Remove any parameters that the user asked to strip
+fn strip_params(url: &mut Url, strip: &[String], explanation: &mut Explanation) {
+    let (stripped, kept): (Vec<_>, Vec<_>) = url
+        .query_pairs()
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .partition(|(k, _)| strip.contains(k));
+    if stripped.is_empty() {
+        return;
+    }
+
+    for (k, _) in &stripped {
+        explanation.step(format!("stripped query parameter {} (--strip-params)", k));
+    }
+
+    url.set_query(None);
+    if !kept.is_empty() {
+        url.query_pairs_mut().extend_pairs(kept);
+    }
+}
+
+// Remove the URL's fragment, if the user's fragment policy (or, by default, the cleaner)
+// says to
+fn apply_fragment_policy(
+    url: &mut Url,
+    policy: FragmentPolicy,
+    cleaner: &dyn UrlCleaner,
+    explanation: &mut Explanation,
+) {
+    let Some(fragment) = url.fragment() else {
+        return;
     };
 
-    // Final step: apply cleaner and return modified URL
-    cleaner.clean(&mut url)?;
-    Ok(url.to_string())
+    let strip = match policy {
+        FragmentPolicy::Keep => false,
+        FragmentPolicy::Strip => true,
+        FragmentPolicy::Auto => !cleaner.keeps_fragment(url, fragment),
+    };
+    if strip {
+        explanation.step(format!("removed fragment #{}", fragment));
+        url.set_fragment(None);
+    }
+}
+
+// Whether a fragment is used for tracking, rather than pointing somewhere in the page
+//
+// This includes `#_=_`, which Facebook adds to the page it sends you back to after
+// logging in with it, and so ends up on links to all sorts of sites.
+fn is_tracking_fragment(fragment: &str) -> bool {
+    fragment == "_=_"
+        || fragment.split('&').any(|pair| {
+            pair.split_once('=')
+                .is_some_and(|(k, _)| TRACKING_FRAGMENT_KEYS.contains(&k))
+        })
 }
 
 #[cfg(test)]
@@ -296,6 +1032,796 @@ mod tests {
         }
     }
 
+    mod registry {
+        use super::*;
+
+        #[test]
+        fn test_every_platform_has_a_shape() {
+            for platform in supported_platforms() {
+                assert!(!platform.shapes.is_empty(), "{}", platform.domain);
+            }
+        }
+
+        #[test]
+        fn test_is_recognised() {
+            let options = CleanOptions::default();
+            let recognised = |url| is_recognised(&Url::parse(url).expect("valid url"), &options);
+            assert!(recognised(
+                "https://www.reddit.com/r/rust/comments/abc123/title/?utm_source=share"
+            ));
+            assert!(recognised(
+                "https://www.facebook.com/groups/123/permalink/456/?comment_id=789"
+            ));
+
+            // Share links must be resolved, as must links on hosts whose cleaner accepts
+            // any path
+            assert!(!recognised("https://www.reddit.com/r/rust/s/AbCdEf"));
+            assert!(!recognised("https://www.facebook.com/share/p/AbCdEf/"));
+            assert!(!recognised("https://www.linkedin.com/posts/abc"));
+            assert!(!recognised("https://example.com/r/rust/comments/abc123"));
+        }
+
+        #[test]
+        fn test_domains_are_unique() {
+            let platforms = supported_platforms();
+            for (i, a) in platforms.iter().enumerate() {
+                for b in &platforms[i + 1..] {
+                    assert_ne!(a.domain, b.domain);
+                }
+            }
+        }
+
+        #[test]
+        fn test_register_override() {
+            let mut registry = CleanerRegistry::default();
+            registry.register("reddit.com", generic::GenericCleaner);
+            assert_eq!(
+                registry.get("reddit.com").map(|c| c.name()),
+                Some("GenericCleaner")
+            );
+            assert_eq!(registry.supported_platforms().len(), 4);
+        }
+
+        #[test]
+        fn test_register_builtin() {
+            let mut registry = CleanerRegistry::new();
+            assert!(registry.register_builtin("threads.net", "generic"));
+            assert!(registry.register_builtin("redd.it", "RedditCleaner"));
+            assert!(!registry.register_builtin("example.com", "unknown"));
+            assert_eq!(
+                registry.get("threads.net").map(|c| c.name()),
+                Some("GenericCleaner")
+            );
+            assert_eq!(
+                registry.get("redd.it").map(|c| c.name()),
+                Some("RedditCleaner")
+            );
+            assert!(registry.get("example.com").is_none());
+        }
+
+        #[test]
+        fn test_custom_cleaner() {
+            struct PathOnly;
+
+            impl UrlCleaner for PathOnly {
+                fn name(&self) -> &'static str {
+                    "PathOnly"
+                }
+
+                fn shapes(&self) -> &'static [&'static str] {
+                    &["/<any path>"]
+                }
+
+                fn clean(
+                    &self,
+                    url: &mut Url,
+                    explanation: &mut Explanation,
+                ) -> Result<(), CleanUrlError> {
+                    explanation.rule("any path");
+                    remove_query(url, explanation);
+                    Ok(())
+                }
+            }
+
+            let mut options = CleanOptions::default();
+            options.registry.register("example.com", PathOnly);
+            let cleaned = clean_url_explained(
+                "https://example.com/a?id=1",
+                &options,
+                &mut Explanation::default(),
+            );
+            assert_eq!(cleaned.expect("cleaned"), "https://example.com/a");
+        }
+
+        #[test]
+        fn test_facebook_preserved_params() {
+            let facebook = supported_platforms()
+                .into_iter()
+                .find(|p| p.domain == "facebook.com")
+                .expect("facebook is supported");
+            assert_eq!(facebook.cleaner, "FacebookCleaner");
+            assert!(facebook.preserved_params.contains(&"comment_id"));
+        }
+    }
+
+    mod options {
+        use super::*;
+
+        fn clean_with(url: &str, options: &CleanOptions) -> Result<CleanedUrl, CleanUrlError> {
+            clean_url_explained(url, options, &mut Explanation::default())
+        }
+
+        #[test]
+        fn test_keep_params() {
+            let options = CleanOptions {
+                keep_params: vec!["context".to_string()],
+                ..Default::default()
+            };
+            let url = "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m/comment/nxfc5ci/?context=3&share_id=8ws3zlfg6lxtYbyGrudio&utm_source=share";
+            let expected =
+                "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m/comment/nxfc5ci?context=3";
+            assert_eq!(clean_with(url, &options).expect("cleaned"), expected);
+        }
+
+        #[test]
+        fn test_keep_params_already_preserved() {
+            let options = CleanOptions {
+                keep_params: vec!["comment_id".to_string()],
+                ..Default::default()
+            };
+            let url = "https://www.facebook.com/groups/vicdeals/permalink/25654608820855518/?comment_id=25654673274182406&rdid=9etJN9mXDU45vGPw";
+            let expected = "https://www.facebook.com/groups/vicdeals/permalink/25654608820855518?comment_id=25654673274182406";
+            assert_eq!(clean_with(url, &options).expect("cleaned"), expected);
+        }
+
+        #[test]
+        fn test_strip_params() {
+            let options = CleanOptions {
+                strip_params: vec!["comment_id".to_string()],
+                ..Default::default()
+            };
+            let url = "https://www.facebook.com/groups/vicdeals/permalink/25654608820855518/?comment_id=25654673274182406&rdid=9etJN9mXDU45vGPw";
+            let expected = "https://www.facebook.com/groups/vicdeals/permalink/25654608820855518";
+            assert_eq!(clean_with(url, &options).expect("cleaned"), expected);
+        }
+
+        #[test]
+        fn test_strip_params_partial() {
+            let options = CleanOptions {
+                strip_params: vec!["id".to_string()],
+                ..Default::default()
+            };
+            let url = "https://www.facebook.com/permalink.php?story_fbid=pfbid02mNMcJYekXP4bnUFkWguBsNddw6GkLHrWZG4ENa23x2h3G2SbbMeJRHByXuxhjKj1l&id=100088004222911";
+            let expected = "https://www.facebook.com/permalink.php?story_fbid=pfbid02mNMcJYekXP4bnUFkWguBsNddw6GkLHrWZG4ENa23x2h3G2SbbMeJRHByXuxhjKj1l";
+            assert_eq!(clean_with(url, &options).expect("cleaned"), expected);
+        }
+
+        #[test]
+        fn test_strip_takes_precedence_over_keep() {
+            let options = CleanOptions {
+                keep_params: vec!["context".to_string()],
+                strip_params: vec!["context".to_string()],
+                ..Default::default()
+            };
+            let url =
+                "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m/comment/nxfc5ci/?context=3";
+            let expected = "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m/comment/nxfc5ci";
+            assert_eq!(clean_with(url, &options).expect("cleaned"), expected);
+        }
+
+        #[test]
+        fn test_keep_params_absent() {
+            let options = CleanOptions {
+                keep_params: vec!["context".to_string()],
+                ..Default::default()
+            };
+            let url = "https://www.instagram.com/p/DS8F57NjS_S/?igsh=MWxidXNpbWV6djIxcQ==";
+            let expected = "https://www.instagram.com/p/DS8F57NjS_S";
+            assert_eq!(clean_with(url, &options).expect("cleaned"), expected);
+        }
+    }
+
+    mod fallback {
+        use super::*;
+
+        #[test]
+        fn test_tracking_params() {
+            let url =
+                "https://www.youtube.com/watch?v=dQw4w9WgXcQ&si=Kx2pP9Tq3bLm&utm_source=share";
+            let expected = "https://www.youtube.com/watch?v=dQw4w9WgXcQ";
+            assert_eq!(clean_url(url).expect("cleaned"), expected);
+        }
+
+        #[test]
+        fn test_trailing_slash() {
+            let url = "https://example.com/blog/post/?fbclid=IwAR0abc";
+            let expected = "https://example.com/blog/post";
+            assert_eq!(clean_url(url).expect("cleaned"), expected);
+        }
+
+        #[cfg(feature = "serde")]
+        #[test]
+        fn test_clearurls() {
+            let rules = r#"{"providers": {"example": {
+                "urlPattern": "^https?://example\\.com",
+                "rules": ["ref"]
+            }}}"#;
+            let mut options = CleanOptions::default();
+            options
+                .registry
+                .set_fallback(ClearUrlsCleaner::parse(rules).expect("parsed"));
+            let cleaned = clean_url_explained(
+                "https://example.com/item?id=1&ref=home&utm_source=x",
+                &options,
+                &mut Explanation::default(),
+            );
+            assert_eq!(cleaned.expect("cleaned"), "https://example.com/item?id=1");
+        }
+
+        #[cfg(feature = "serde")]
+        #[test]
+        fn test_essential_params() {
+            // However aggressive the fallback, a YouTube video keeps its ID and timestamp
+            let rules = r#"{"providers": {"youtube": {
+                "urlPattern": "^https?://(www\\.)?youtube\\.com",
+                "rules": ["v", "t", "feature"]
+            }}}"#;
+            let mut options = CleanOptions {
+                tracking_params: TrackingParams::parse("t\nlist"),
+                ..CleanOptions::default()
+            };
+            options
+                .registry
+                .set_fallback(ClearUrlsCleaner::parse(rules).expect("parsed"));
+            let cleaned = clean_url_explained(
+                "https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=42&feature=share",
+                &options,
+                &mut Explanation::default(),
+            );
+            assert_eq!(
+                cleaned.expect("cleaned"),
+                "https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=42"
+            );
+
+            // As does a Dropbox link on a host given the generic cleaner
+            options
+                .registry
+                .register("dropbox.com", generic::GenericCleaner);
+            let cleaned = clean_url_explained(
+                "https://www.dropbox.com/scl/fi/abc/file.pdf?rlkey=xyz&st=123&dl=0",
+                &options,
+                &mut Explanation::default(),
+            );
+            assert_eq!(
+                cleaned.expect("cleaned"),
+                "https://www.dropbox.com/scl/fi/abc/file.pdf?rlkey=xyz"
+            );
+        }
+
+        #[test]
+        fn test_explain() {
+            let mut explanation = Explanation::default();
+            clean_url_explained(
+                "https://example.com/?gclid=abc",
+                &CleanOptions::default(),
+                &mut explanation,
+            )
+            .expect("cleaned");
+            assert_eq!(explanation.cleaner, Some("FallbackCleaner"));
+            assert_eq!(explanation.rule, Some("unsupported host"));
+        }
+    }
+
+    mod cleaned {
+        use super::*;
+
+        #[test]
+        fn test_details() {
+            let url = "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m/what_comes_to_mind_when_you_think_of_new_zealand/?share_id=l2suzjz-JpaaqZSjbaNmt&utm_source=share&utm_source=ios";
+            let cleaned = clean_url(url).expect("cleaned");
+            assert_eq!(
+                cleaned.url,
+                "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m"
+            );
+            assert_eq!(cleaned.platform.as_deref(), Some("reddit.com"));
+            assert_eq!(cleaned.kind.as_deref(), Some("post with short name"));
+            assert_eq!(cleaned.removed_params, ["share_id", "utm_source"]);
+            assert_eq!(
+                cleaned
+                    .removed
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>(),
+                [
+                    "path segment \"what_comes_to_mind_when_you_think_of_new_zealand\"",
+                    "query parameter share_id=l2… (21 characters)",
+                    "query parameter utm_source=share",
+                    "query parameter utm_source=ios",
+                ]
+            );
+            assert!(!cleaned.canonical);
+            // The Reddit cleaner knows what it removes
+            assert!(cleaned.unknown_params.is_empty());
+            assert_eq!(cleaned.confidence(), Confidence::Known);
+        }
+
+        #[test]
+        fn test_unknown_params() {
+            // The generic cleaner removes every parameter, but only knows that some are
+            // used for tracking
+            let url = "https://www.instagram.com/p/Cabc123/?igsh=MWZ&img_index=2&utm_source=ig";
+            let mut explanation = Explanation::default();
+            let cleaned = clean_url_explained(url, &CleanOptions::default(), &mut explanation)
+                .expect("cleaned");
+            assert_eq!(cleaned, "https://www.instagram.com/p/Cabc123");
+            assert_eq!(cleaned.unknown_params, ["img_index"]);
+            assert_eq!(cleaned.confidence(), Confidence::Guessed);
+            assert!(
+                explanation
+                    .steps
+                    .iter()
+                    .any(|step| step.ends_with("not needed: img_index"))
+            );
+
+            // Unless told to strip it
+            let options = CleanOptions {
+                strip_params: vec!["img_index".to_string()],
+                ..CleanOptions::default()
+            };
+            let cleaned =
+                clean_url_explained(url, &options, &mut Explanation::default()).expect("cleaned");
+            assert_eq!(cleaned.confidence(), Confidence::Known);
+        }
+
+        #[test]
+        fn test_normalized() {
+            let url = "HTTPS://Example.COM:443/%7euser/caf%c3%a9?";
+            let cleaned = clean_url(url).expect("cleaned");
+            assert_eq!(cleaned, "https://example.com/~user/caf%C3%A9");
+            assert_eq!(clean_url(cleaned.as_str()).expect("cleaned"), cleaned.url);
+        }
+
+        #[test]
+        fn test_canonical() {
+            let url = "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m";
+            let cleaned = clean_url(url).expect("cleaned");
+            assert!(cleaned.canonical);
+            assert!(cleaned.removed_params.is_empty());
+        }
+
+        #[cfg(feature = "serde")]
+        #[test]
+        fn test_serde() {
+            let url = "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m/?utm_source=share";
+            let cleaned = clean_url(url).expect("cleaned");
+            let json = serde_json::to_string(&cleaned).expect("serialized");
+            let deserialized: CleanedUrl = serde_json::from_str(&json).expect("deserialized");
+            assert_eq!(deserialized, cleaned);
+        }
+    }
+
+    mod nested {
+        use super::*;
+        use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+
+        #[test]
+        fn test_redirector() {
+            let url = "https://l.facebook.com/l.php?u=https%3A%2F%2Fwww.reddit.com%2Fr%2Frust%2Fcomments%2F1q2rw7m%2Ftitle%2F%3Futm_source%3Dshare&h=AT0abc";
+            let expected = "https://www.reddit.com/r/rust/comments/1q2rw7m";
+            let cleaned = clean_url(url).expect("cleaned");
+            assert_eq!(cleaned, expected);
+            assert_eq!(cleaned.platform.as_deref(), Some("reddit.com"));
+            assert_eq!(
+                cleaned.removed[0],
+                Removed::Redirector {
+                    host: "l.facebook.com".to_string()
+                }
+            );
+        }
+
+        #[test]
+        fn test_nested_redirectors() {
+            let inner =
+                "https://www.google.com/url?url=https%3A%2F%2Fexample.com%2Fa%3Ffbclid%3Dabc";
+            let url = format!(
+                "https://click.example.net/out?dest={}",
+                utf8_percent_encode(inner, NON_ALPHANUMERIC)
+            );
+            let mut explanation = Explanation::default();
+            let cleaned = clean_url_explained(&url, &CleanOptions::default(), &mut explanation);
+            assert_eq!(cleaned.expect("cleaned"), "https://example.com/a");
+            assert_eq!(
+                explanation
+                    .steps
+                    .iter()
+                    .filter(|step| step.starts_with("unwrapped link"))
+                    .count(),
+                2
+            );
+        }
+
+        #[test]
+        fn test_not_redirector() {
+            // A share button's link is to the page doing the sharing, not the page shared
+            let url =
+                "https://www.linkedin.com/sharing/share-offsite?url=https%3A%2F%2Fexample.com%2F";
+            let expected = "https://www.linkedin.com/sharing/share-offsite";
+            assert_eq!(clean_url(url).expect("cleaned"), expected);
+        }
+    }
+
+    mod hosts {
+        use super::*;
+
+        #[test]
+        fn test_mobile() {
+            let url = "https://m.facebook.com/permalink.php?story_fbid=123&id=456&mibextid=abc";
+            let expected = "https://www.facebook.com/permalink.php?story_fbid=123&id=456";
+            assert_eq!(clean_url(url).expect("cleaned"), expected);
+
+            let url = "https://en.m.wikipedia.org/wiki/Rust_(programming_language)";
+            let expected = "https://en.wikipedia.org/wiki/Rust_(programming_language)";
+            assert_eq!(clean_url(url).expect("cleaned"), expected);
+        }
+
+        fn clean_with(url: &str, options: &CleanOptions) -> Result<CleanedUrl, CleanUrlError> {
+            clean_url_explained(url, options, &mut Explanation::default())
+        }
+
+        #[test]
+        fn test_dispatch() {
+            let mut options = CleanOptions::default();
+            options
+                .registry
+                .register("bücher.de", generic::GenericCleaner);
+            let url = "https://www.xn--bcher-kva.de/a/?ref=1";
+            let cleaned = clean_with(url, &options).expect("cleaned");
+            assert_eq!(cleaned, "https://www.xn--bcher-kva.de/a");
+            assert_eq!(cleaned.platform.as_deref(), Some("xn--bcher-kva.de"));
+
+            // Unicode input is converted to punycode before dispatch
+            let url = "https://www.bücher.de/a/?ref=1";
+            assert_eq!(
+                clean_with(url, &options).expect("cleaned"),
+                "https://www.xn--bcher-kva.de/a"
+            );
+        }
+
+        #[test]
+        fn test_unicode() {
+            let options = CleanOptions {
+                hosts: HostDisplay::Unicode,
+                ..CleanOptions::default()
+            };
+            let url = "https://xn--bcher-kva.de/a?utm_source=x";
+            let cleaned = clean_with(url, &options).expect("cleaned");
+            assert_eq!(cleaned, "https://bücher.de/a");
+            assert_eq!(
+                clean_with(cleaned.as_str(), &options).expect("cleaned"),
+                cleaned.url
+            );
+        }
+
+        #[test]
+        fn test_mixed_script() {
+            let options = CleanOptions {
+                hosts: HostDisplay::Unicode,
+                ..CleanOptions::default()
+            };
+            let mut explanation = Explanation::default();
+            let url = "https://\u{0430}pple.com/iphone";
+            let cleaned = clean_url_explained(url, &options, &mut explanation).expect("cleaned");
+            assert!(cleaned.url.starts_with("https://xn--"));
+            assert!(explanation.steps[0].contains("mixes scripts"));
+        }
+    }
+
+    mod wayback {
+        use super::*;
+
+        const URL: &str = "https://web.archive.org/web/20260101000000/https://www.reddit.com/r/rust/comments/1q2rw7m/title/?utm_source=share";
+
+        #[test]
+        fn test_wrap() {
+            let cleaned = clean_url(URL).expect("cleaned");
+            let expected = "https://web.archive.org/web/20260101000000/https://www.reddit.com/r/rust/comments/1q2rw7m";
+            assert_eq!(cleaned, expected);
+            assert_eq!(cleaned.platform.as_deref(), Some("reddit.com"));
+            assert_eq!(clean_url(expected).expect("cleaned"), expected);
+        }
+
+        #[test]
+        fn test_extract() {
+            let options = CleanOptions {
+                wayback: WaybackPolicy::Extract,
+                ..CleanOptions::default()
+            };
+            let cleaned =
+                clean_url_explained(URL, &options, &mut Explanation::default()).expect("cleaned");
+            assert_eq!(cleaned, "https://www.reddit.com/r/rust/comments/1q2rw7m");
+            assert_eq!(
+                cleaned.removed[0],
+                Removed::Snapshot {
+                    timestamp: "20260101000000".to_string()
+                }
+            );
+        }
+    }
+
+    mod fragments {
+        use super::*;
+
+        const URL: &str = "https://www.linkedin.com/posts/robert-a-saigh-7b2b05359_i-have-warned-about-this-before-ai-is-activity-7415393290201534464-seUj";
+
+        fn clean_with(url: &str, fragments: FragmentPolicy) -> Result<CleanedUrl, CleanUrlError> {
+            let options = CleanOptions {
+                fragments,
+                ..Default::default()
+            };
+            clean_url_explained(url, &options, &mut Explanation::default())
+        }
+
+        #[test]
+        fn test_keep() {
+            let url = format!("{}#Echobox=1700000000", URL);
+            assert_eq!(
+                clean_with(&url, FragmentPolicy::Keep).expect("cleaned"),
+                url
+            );
+        }
+
+        #[test]
+        fn test_strip() {
+            let url = format!("{}#comments", URL);
+            assert_eq!(
+                clean_with(&url, FragmentPolicy::Strip).expect("cleaned"),
+                URL
+            );
+        }
+
+        #[test]
+        fn test_auto() {
+            let url = format!("{}?utm_source=share#comments", URL);
+            assert_eq!(
+                clean_with(&url, FragmentPolicy::Auto).expect("cleaned"),
+                format!("{}#comments", URL)
+            );
+
+            let url = format!("{}#Echobox=1700000000", URL);
+            assert_eq!(
+                clean_with(&url, FragmentPolicy::Auto).expect("cleaned"),
+                URL
+            );
+        }
+
+        #[test]
+        fn test_parse() {
+            assert_eq!(
+                "strip".parse::<FragmentPolicy>().ok(),
+                Some(FragmentPolicy::Strip)
+            );
+            assert_eq!(
+                "Auto".parse::<FragmentPolicy>().ok(),
+                Some(FragmentPolicy::Auto)
+            );
+            assert!("drop".parse::<FragmentPolicy>().is_err());
+        }
+
+        #[test]
+        fn test_platform() {
+            // Meaningful anchors are kept
+            for url in [
+                "https://github.com/jakewilliami/rsl/blob/main/src/main.rs#L10-L20",
+                "https://en.wikipedia.org/wiki/URL#History",
+                "https://www.reddit.com/r/rust/comments/1q2rw7m#t1_abc123",
+                "https://old.reddit.com/r/rust/comments/1q2rw7m#thing_t1_abc123",
+            ] {
+                assert_eq!(clean_with(url, FragmentPolicy::Auto).expect("cleaned"), url);
+            }
+
+            // But others are not, where the platform knows better
+            assert_eq!(
+                clean_with(
+                    "https://www.reddit.com/r/rust/comments/1q2rw7m#lightbox",
+                    FragmentPolicy::Auto
+                )
+                .expect("cleaned"),
+                "https://www.reddit.com/r/rust/comments/1q2rw7m"
+            );
+            assert_eq!(
+                clean_with(
+                    "https://www.facebook.com/reel/1309748351194528#_=_",
+                    FragmentPolicy::Auto
+                )
+                .expect("cleaned"),
+                "https://www.facebook.com/reel/1309748351194528"
+            );
+
+            // Unless told otherwise
+            let url = "https://www.reddit.com/r/rust/comments/1q2rw7m#lightbox";
+            assert_eq!(clean_with(url, FragmentPolicy::Keep).expect("cleaned"), url);
+        }
+
+        #[test]
+        fn test_is_tracking_fragment() {
+            assert!(is_tracking_fragment("_=_"));
+            assert!(is_tracking_fragment("Echobox=1700000000"));
+            assert!(is_tracking_fragment("xtor=RSS-1&foo=bar"));
+            assert!(!is_tracking_fragment("History"));
+            assert!(!is_tracking_fragment("L10-L20"));
+            assert!(!is_tracking_fragment(":~:text=share%20link"));
+        }
+    }
+
+    mod explain {
+        use super::*;
+
+        #[test]
+        fn test_dispatch_and_rule() {
+            let mut explanation = Explanation::default();
+            let url = "https://www.facebook.com/groups/vicdeals/permalink/25654608820855518/?comment_id=25654673274182406&rdid=9etJN9mXDU45vGPw";
+            let result = clean_url_explained(url, &CleanOptions::default(), &mut explanation);
+            assert!(result.is_ok());
+            assert_eq!(explanation.cleaner, Some("FacebookCleaner"));
+            assert_eq!(explanation.rule, Some("group permalink with comment"));
+            assert!(
+                explanation
+                    .steps
+                    .contains(&"removed query parameters: comment_id, rdid".to_string())
+            );
+        }
+
+        #[test]
+        fn test_extra_tracking_params() {
+            // The list published for `rsl update-rules` has everything built in, and more
+            // can be added to it
+            let published = TrackingParams::parse(include_str!("../../data/tracking-params.txt"));
+            for name in generic::TRACKING_PARAMS {
+                assert!(published.names.contains(&name.to_string()), "{}", name);
+            }
+            for prefix in generic::TRACKING_PARAM_PREFIXES {
+                assert!(
+                    published.prefixes.contains(&prefix.to_string()),
+                    "{}",
+                    prefix
+                );
+            }
+
+            let options = CleanOptions {
+                tracking_params: TrackingParams::parse("# New\n\nnew_click_id\nnew_*\n*\n"),
+                ..CleanOptions::default()
+            };
+            assert_eq!(options.tracking_params.len(), 2);
+            let cleaned = clean_url_explained(
+                "https://example.com/a?id=1&new_click_id=x&new_source=y&fbclid=z",
+                &options,
+                &mut Explanation::default(),
+            );
+            assert_eq!(cleaned.expect("cleaned"), "https://example.com/a?id=1");
+        }
+
+        #[test]
+        fn test_session_ids() {
+            let url = "https://shop.example.com/item;jsessionid=0A1B2C3D/reviews?PHPSESSID=abc&id=1&sid=2";
+            let mut explanation = Explanation::default();
+            let cleaned = clean_url_explained(url, &CleanOptions::default(), &mut explanation)
+                .expect("cleaned");
+            assert_eq!(cleaned, "https://shop.example.com/item/reviews?id=1");
+            assert_eq!(cleaned.removed_params, ["PHPSESSID", "sid"]);
+            assert!(
+                explanation
+                    .steps
+                    .contains(&"removed session path parameter: jsessionid".to_string())
+            );
+
+            let url = "https://shop.example.com/item;JSESSIONID=0A1B2C3D";
+            assert_eq!(
+                clean_url(url).expect("cleaned"),
+                "https://shop.example.com/item"
+            );
+        }
+
+        #[test]
+        fn test_keep_session_ids() {
+            let options = CleanOptions {
+                keep_session_ids: vec!["example.com".to_string()],
+                ..CleanOptions::default()
+            };
+            let url = "https://shop.example.com/item;jsessionid=0A1B2C3D?sid=2";
+            let cleaned = clean_url_explained(url, &options, &mut Explanation::default());
+            assert_eq!(cleaned.expect("cleaned"), url);
+        }
+
+        #[test]
+        fn test_strip_locale() {
+            let options = CleanOptions {
+                strip_locale: true,
+                ..CleanOptions::default()
+            };
+            // Shared from different regions, the same page cleans to the same link
+            for url in [
+                "https://learn.microsoft.com/en-us/windows/wsl/install",
+                "https://learn.microsoft.com/de-de/windows/wsl/install",
+            ] {
+                let cleaned = clean_url_explained(url, &options, &mut Explanation::default());
+                assert_eq!(
+                    cleaned.expect("cleaned"),
+                    "https://learn.microsoft.com/windows/wsl/install"
+                );
+            }
+
+            // Only when asked to
+            let url = "https://learn.microsoft.com/en-us/windows/wsl/install";
+            assert_eq!(clean_url(url).expect("cleaned"), url);
+        }
+
+        #[test]
+        fn test_path() {
+            let options = CleanOptions {
+                drop_index: true,
+                ..CleanOptions::default()
+            };
+            let url = "https://www.reddit.com//r/rust//comments/1q2rw7m/";
+            let cleaned = clean_url_explained(url, &options, &mut Explanation::default());
+            assert_eq!(
+                cleaned.expect("cleaned"),
+                "https://www.reddit.com/r/rust/comments/1q2rw7m"
+            );
+
+            let url = "https://example.com/blog/./2024/index.php";
+            let cleaned = clean_url_explained(url, &options, &mut Explanation::default());
+            assert_eq!(cleaned.expect("cleaned"), "https://example.com/blog/2024");
+        }
+
+        #[test]
+        fn test_tracking_params_removed_first() {
+            // Facebook's cleaner keeps `story_fbid` and `id`, but `fbclid` is gone before it
+            // runs, whichever host it is on
+            let mut explanation = Explanation::default();
+            let url = "https://www.facebook.com/permalink.php?story_fbid=123&fbclid=abc&id=456";
+            let cleaned = clean_url_explained(url, &CleanOptions::default(), &mut explanation)
+                .expect("cleaned");
+            assert_eq!(
+                cleaned,
+                "https://www.facebook.com/permalink.php?story_fbid=123&id=456"
+            );
+            assert_eq!(cleaned.removed_params, ["fbclid"]);
+            assert_eq!(
+                explanation.steps.first().map(String::as_str),
+                Some("removed tracking query parameters: fbclid")
+            );
+        }
+
+        #[test]
+        fn test_unsupported_path() {
+            let mut explanation = Explanation::default();
+            let result = clean_url_explained(
+                "https://reddit.com/u/spez",
+                &CleanOptions::default(),
+                &mut explanation,
+            );
+            assert!(matches!(result, Err(CleanUrlError::UnsupportedUrlPath)));
+            assert_eq!(explanation.cleaner, Some("RedditCleaner"));
+            assert_eq!(explanation.rule, None);
+            assert!(explanation.to_string().contains("none matched"));
+        }
+
+        #[test]
+        fn test_unsupported_host() {
+            let mut explanation = Explanation::default();
+            let result = clean_url_explained(
+                "https://example.com/",
+                &CleanOptions {
+                    strict: true,
+                    ..Default::default()
+                },
+                &mut explanation,
+            );
+            assert!(matches!(result, Err(CleanUrlError::UnsupportedUrlHost)));
+            assert_eq!(explanation.cleaner, None);
+        }
+    }
+
     mod errors {
         use super::*;
 
@@ -333,8 +1859,16 @@ mod tests {
 
         #[test]
         fn test_unsupported_host() {
+            let options = CleanOptions {
+                strict: true,
+                ..Default::default()
+            };
             assert!(matches!(
-                clean_url("https://example.com/"),
+                clean_url_explained(
+                    "https://example.com/",
+                    &options,
+                    &mut Explanation::default()
+                ),
                 Err(CleanUrlError::UnsupportedUrlHost)
             ));
         }
@@ -359,6 +1893,28 @@ mod tests {
             ));
         }
 
+        #[test]
+        fn test_facebook_unsupported_path() {
+            for url in [
+                "https://www.facebook.com/permalink.php?id=100088004222911",
+                "https://www.facebook.com/photo.php?set=a.301086902050393",
+                "https://www.facebook.com/zuck",
+                "https://www.facebook.com/",
+            ] {
+                assert!(
+                    matches!(clean_url(url), Err(CleanUrlError::UnsupportedUrlPath)),
+                    "{}",
+                    url
+                );
+            }
+        }
+
+        #[test]
+        fn test_facebook_story_without_id() {
+            let url = "https://www.facebook.com/permalink.php?story_fbid=pfbid02mNMcJYekXP4bnUFkWguBsNddw6GkLHrWZG4ENa23x2h3G2SbbMeJRHByXuxhjKj1l";
+            assert_eq!(clean_url(url).expect("cleaned"), url);
+        }
+
         #[test]
         #[ignore]
         fn test_path_segments_error() {
@@ -366,4 +1922,148 @@ mod tests {
             // URL validation during parsing.
         }
     }
+
+    mod properties {
+        use super::*;
+        use proptest::prelude::*;
+
+        const HOSTS: &[&str] = &[
+            "www.reddit.com",
+            "old.reddit.com",
+            "www.facebook.com",
+            "m.facebook.com",
+            "www.instagram.com",
+            "www.linkedin.com",
+            "www.youtube.com",
+            "example.com",
+        ];
+
+        // Path segments and query parameters that the cleaners treat specially, so that
+        // generated URLs exercise every rule rather than only the fallback
+        const SEGMENTS: &[&str] = &[
+            "r",
+            "s",
+            "comments",
+            "comment",
+            "posts",
+            "groups",
+            "permalink",
+            "reel",
+            "permalink.php",
+            "photo.php",
+            "p",
+            "",
+        ];
+        const PARAMS: &[&str] = &[
+            "story_fbid",
+            "id",
+            "fbid",
+            "comment_id",
+            "rdid",
+            "utm_source",
+            "fbclid",
+            "igsh",
+            "v",
+            "Echobox",
+        ];
+
+        // Stripping parameters needed to identify the content leaves a URL we reject; see
+        // `clean_url`
+        const STRIPPABLE: &[&str] = &["id", "comment_id", "rdid", "utm_source", "v"];
+
+        fn segment() -> impl Strategy<Value = String> {
+            prop_oneof![
+                prop::sample::select(SEGMENTS).prop_map(str::to_string),
+                "[a-zA-Z0-9_.%~-]{1,10}",
+            ]
+        }
+
+        fn param() -> impl Strategy<Value = (String, String)> {
+            (
+                prop_oneof![
+                    prop::sample::select(PARAMS).prop_map(str::to_string),
+                    "[a-z_]{1,8}",
+                ],
+                "[a-zA-Z0-9 +=&%./-]{0,10}",
+            )
+        }
+
+        prop_compose! {
+            fn url()(
+                scheme in prop::sample::select(&["http", "https"][..]),
+                host in prop::sample::select(HOSTS),
+                segments in prop::collection::vec(segment(), 0..7),
+                trailing_slashes in 0..3usize,
+                params in prop::collection::vec(param(), 0..5),
+                fragment in prop::option::of("[a-zA-Z0-9=&:~-]{0,12}"),
+            ) -> String {
+                let mut url = Url::parse(&format!("{}://{}/", scheme, host)).expect("valid url");
+                url.set_path(&format!("{}{}", segments.join("/"), "/".repeat(trailing_slashes)));
+                if !params.is_empty() {
+                    url.query_pairs_mut().extend_pairs(params);
+                }
+                url.set_fragment(fragment.as_deref());
+                url.to_string()
+            }
+        }
+
+        fn options() -> impl Strategy<Value = CleanOptions> {
+            (
+                prop::collection::vec(prop::sample::select(PARAMS).prop_map(str::to_string), 0..2),
+                prop::collection::vec(
+                    prop::sample::select(STRIPPABLE).prop_map(str::to_string),
+                    0..2,
+                ),
+                prop::sample::select(
+                    &[
+                        FragmentPolicy::Auto,
+                        FragmentPolicy::Keep,
+                        FragmentPolicy::Strip,
+                    ][..],
+                ),
+                any::<bool>(),
+            )
+                .prop_map(|(keep_params, strip_params, fragments, strict)| {
+                    CleanOptions {
+                        keep_params,
+                        strip_params,
+                        fragments,
+                        strict,
+                        ..Default::default()
+                    }
+                })
+        }
+
+        proptest! {
+            #[test]
+            fn test_never_panics(input in ".*") {
+                let _ = clean_url(&input);
+            }
+
+            #[test]
+            fn test_never_panics_on_urls(input in url(), options in options()) {
+                let _ = clean_url_explained(&input, &options, &mut Explanation::default());
+            }
+
+            #[test]
+            fn test_idempotent(input in url(), options in options()) {
+                let clean =
+                    |url: &str| clean_url_explained(url, &options, &mut Explanation::default());
+                if let Ok(cleaned) = clean(&input) {
+                    let again = clean(cleaned.as_str());
+                    prop_assert!(
+                        again.is_ok(),
+                        "{} cleaned to {}, which failed: {:?}",
+                        input,
+                        cleaned,
+                        again
+                    );
+                    let again = again.expect("cleaned");
+                    prop_assert_eq!(again.as_str(), cleaned.as_str());
+                    prop_assert!(again.canonical);
+                    prop_assert!(again.removed_params.is_empty());
+                }
+            }
+        }
+    }
 }
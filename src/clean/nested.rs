@@ -0,0 +1,99 @@
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+// Links that wrap another link
+//
+// Countless services (search engines, email newsletters, chat apps, Facebook's
+// `l.facebook.com/l.php`) send outbound links through a redirector of their own, so that
+// they can count the click before sending you on, e.g.:
+//   <https://l.facebook.com/l.php?u=https%3A%2F%2Fexample.com%2F&h=AT0...>
+// Rather than a rule for each, we recognise the shape they almost all share: a path
+// named for what it does, with the destination in a query parameter.
+
+// Query parameters that hold the destination of a redirector
+const PARAMS: &[&str] = &["url", "u", "target", "dest", "redirect_uri"];
+
+// Names of redirector paths (the last segment, ignoring any extension, e.g., `l.php`)
+const PATHS: &[&str] = &[
+    "away", "click", "exit", "go", "l", "link", "out", "outbound", "redir", "redirect", "url",
+];
+
+// Maximum number of links to unwrap from a single URL.  Each unwrapped link is shorter
+// than the one it came from, so this is only a safeguard
+pub(super) const MAX_NESTING: usize = 10;
+
+fn is_redirector_path(url: &Url) -> bool {
+    let Some(segment) = url
+        .path_segments()
+        .and_then(|mut s| s.rfind(|s| !s.is_empty()))
+    else {
+        return false;
+    };
+    let name = segment.split('.').next().unwrap_or_default();
+    PATHS.iter().any(|path| path.eq_ignore_ascii_case(name))
+}
+
+// Some redirectors encode their destination twice, so we decode again if we must
+fn parse_destination(value: &str) -> Option<Url> {
+    let url = Url::parse(value)
+        .ok()
+        .or_else(|| Url::parse(&percent_decode_str(value).decode_utf8_lossy()).ok())?;
+    (matches!(url.scheme(), "http" | "https") && url.host_str().is_some()).then_some(url)
+}
+
+// The link wrapped by `url`, if it is a redirector
+pub(super) fn unwrap(url: &Url) -> Option<Url> {
+    if !is_redirector_path(url) {
+        return None;
+    }
+    url.query_pairs()
+        .filter(|(k, _)| PARAMS.contains(&k.as_ref()))
+        .find_map(|(_, v)| parse_destination(&v))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unwrapped(url: &str) -> Option<String> {
+        unwrap(&Url::parse(url).expect("valid url")).map(String::from)
+    }
+
+    #[test]
+    fn test_unwrap() {
+        assert_eq!(
+            unwrapped("https://l.facebook.com/l.php?u=https%3A%2F%2Fexample.com%2Fa&h=AT0abc")
+                .as_deref(),
+            Some("https://example.com/a")
+        );
+        assert_eq!(
+            unwrapped("https://www.google.com/url?sa=t&url=https://example.com/b?id=1").as_deref(),
+            Some("https://example.com/b?id=1")
+        );
+        assert_eq!(
+            unwrapped("https://click.example.net/redirect/?target=http%253A%252F%252Fexample.com")
+                .as_deref(),
+            Some("http://example.com/")
+        );
+    }
+
+    #[test]
+    fn test_not_redirector() {
+        // The parameter has to be on a redirector path
+        assert_eq!(
+            unwrapped("https://example.com/share?url=https://example.org/"),
+            None
+        );
+
+        // And has to hold a web URL
+        assert_eq!(unwrapped("https://example.com/out?url=/home"), None);
+        assert_eq!(
+            unwrapped("https://example.com/out?url=javascript:alert(1)"),
+            None
+        );
+        assert_eq!(
+            unwrapped("https://example.com/out?id=https://example.org/"),
+            None
+        );
+    }
+}
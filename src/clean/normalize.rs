@@ -0,0 +1,178 @@
+use url::Url;
+
+use super::Explanation;
+
+// Names of index pages, which servers give for the directory they are in
+const INDEX_PAGES: &[&str] = &["index.htm", "index.html", "index.php"];
+
+// Syntax-based normalisation, per RFC 3986 § 6.2.2
+//
+// Two URLs can differ textually and still be the same URL (e.g., `%7e` and `~`), which
+// matters when comparing or deduplicating cleaned links.  The URL parser already
+// lower-cases the scheme and host, and drops a port that is the default for the scheme,
+// so what is left is percent-encoding, and an empty query (or fragment).
+pub fn normalize(url: &mut Url) {
+    if url.cannot_be_a_base() {
+        return;
+    }
+
+    let path = normalize_percent_encoding(url.path());
+    if path != url.path() {
+        url.set_path(&path);
+    }
+
+    match url.query().map(normalize_percent_encoding) {
+        Some(query) if query.is_empty() => url.set_query(None),
+        Some(query) if Some(query.as_str()) != url.query() => url.set_query(Some(&query)),
+        _ => {}
+    }
+
+    match url.fragment().map(normalize_percent_encoding) {
+        Some(fragment) if fragment.is_empty() => url.set_fragment(None),
+        Some(fragment) if Some(fragment.as_str()) != url.fragment() => {
+            url.set_fragment(Some(&fragment))
+        }
+        _ => {}
+    }
+}
+
+// Normalise the path of a URL, however the link was put together: repeated slashes are
+// collapsed, and optionally, a trailing index page (e.g., `index.html`) is removed
+//
+// Dot segments (`.` and `..`) are resolved by the URL parser, so need nothing from us.
+pub(super) fn normalize_path(url: &mut Url, drop_index: bool, explanation: &mut Explanation) {
+    if url.cannot_be_a_base() {
+        return;
+    }
+
+    let mut path = url.path().to_string();
+    if path.contains("//") {
+        while path.contains("//") {
+            path = path.replace("//", "/");
+        }
+        explanation.step("collapsed repeated slashes in path");
+    }
+    if drop_index
+        && let Some((dir, page)) = path.rsplit_once('/')
+        && INDEX_PAGES
+            .iter()
+            .any(|index| index.eq_ignore_ascii_case(page))
+    {
+        explanation.step(format!("removed index page {}", page));
+        path = format!("{}/", dir);
+    }
+    if path != url.path() {
+        url.set_path(&path);
+    }
+}
+
+// Decode percent-encoded unreserved characters (letters, digits, `-`, `.`, `_`, and `~`),
+// which mean the same either way, and upper-case the hex digits of the rest
+fn normalize_percent_encoding(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut normalized = String::with_capacity(s.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let decoded = if bytes[i] == b'%' {
+            s.get(i + 1..i + 3)
+                .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        } else {
+            None
+        };
+        match decoded {
+            Some(byte) if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) => {
+                normalized.push(byte as char);
+                i += 3;
+            }
+            Some(byte) => {
+                normalized.push_str(&format!("%{:02X}", byte));
+                i += 3;
+            }
+            None => {
+                // We are always at the start of a character, as percent-encodings are
+                // ASCII
+                let c = s[i..].chars().next().unwrap_or_default();
+                normalized.push(c);
+                i += c.len_utf8();
+            }
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalized(url: &str) -> String {
+        let mut url = Url::parse(url).expect("valid url");
+        normalize(&mut url);
+        url.to_string()
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            normalized("HTTPS://WWW.Example.COM:443/%7euser/a%2fb?q=%e2%9c%93#%41"),
+            "https://www.example.com/~user/a%2Fb?q=%E2%9C%93#A"
+        );
+        assert_eq!(
+            normalized("http://example.com:80/a?"),
+            "http://example.com/a"
+        );
+        assert_eq!(
+            normalized("https://example.com/a#"),
+            "https://example.com/a"
+        );
+    }
+
+    #[test]
+    fn test_equivalent() {
+        assert_eq!(
+            normalized("https://example.com/%41%62c?x=%2d"),
+            normalized("https://example.com/Abc?x=-")
+        );
+    }
+
+    fn path_normalized(url: &str, drop_index: bool) -> String {
+        let mut url = Url::parse(url).expect("valid url");
+        normalize_path(&mut url, drop_index, &mut Explanation::default());
+        url.to_string()
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(
+            path_normalized("https://example.com//a///b/./c/../d", false),
+            "https://example.com/a/b/d"
+        );
+        assert_eq!(
+            path_normalized("https://example.com/a/%2E%2E/b", false),
+            "https://example.com/b"
+        );
+        assert_eq!(
+            path_normalized("https://example.com/blog/Index.HTML?page=2", true),
+            "https://example.com/blog/?page=2"
+        );
+        assert_eq!(
+            path_normalized("https://example.com/blog/index.html", false),
+            "https://example.com/blog/index.html"
+        );
+        assert_eq!(
+            path_normalized("https://example.com/blog/index.html.bak", true),
+            "https://example.com/blog/index.html.bak"
+        );
+    }
+
+    #[test]
+    fn test_unchanged() {
+        for url in [
+            "https://www.reddit.com/r/rust/comments/1q2rw7m",
+            "https://example.com/a%20b?q=%25&r=100%&s=%+1",
+            "https://example.com/caf%C3%A9",
+        ] {
+            assert_eq!(normalized(url), url);
+        }
+    }
+}
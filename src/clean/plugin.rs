@@ -0,0 +1,247 @@
+use std::{error::Error, fs, io, path::Path};
+
+use url::Url;
+use wasmtime::{Engine, Instance, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+use super::{CleanUrlError, Explanation, UrlCleaner};
+
+// Limits on the resources a plugin may use for each call, so that a misbehaving plugin
+// cannot hang or exhaust the memory of the process
+const FUEL: u64 = 50_000_000;
+const MEMORY_LIMIT: usize = 16 * 1024 * 1024;
+
+const PLUGIN_EXTENSION: &str = "wasm";
+
+// Error type for loading a plugin
+#[derive(Debug, derive_more::Display)]
+pub enum PluginError {
+    Io(io::Error),
+    #[display("{_0}: {_1}")]
+    Wasm(String, wasmtime::Error),
+}
+
+impl Error for PluginError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PluginError::Io(e) => Some(e),
+            PluginError::Wasm(_, e) => Some(e.as_ref()),
+        }
+    }
+}
+
+impl From<io::Error> for PluginError {
+    fn from(e: io::Error) -> Self {
+        PluginError::Io(e)
+    }
+}
+
+// Cleaner implemented by a WebAssembly module
+//
+// Plugins are sandboxed: they are given no imports (and hence no access to the file
+// system, network, or clock), and each call runs in a fresh instance with limited fuel
+// and memory.  A plugin must export:
+//
+//   - `memory`: its linear memory
+//   - `alloc(len: i32) -> i32`: allocate `len` bytes for input, returning a pointer
+//   - `domains() -> i64`: the domains it supports, separated by newlines
+//   - `clean(ptr: i32, len: i32) -> i64`: clean the URL at `ptr`, returning the cleaned
+//     URL, or 0 to reject it
+//
+// Strings are returned by packing a pointer into the upper 32 bits of the result, and
+// its length into the lower 32 bits.
+#[derive(Clone)]
+pub struct WasmCleaner {
+    name: &'static str,
+    engine: Engine,
+    module: Module,
+}
+
+impl WasmCleaner {
+    // Load the plugin, returning it along with the domains it supports
+    pub fn from_file(path: &Path) -> Result<(Self, Vec<String>), PluginError> {
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Self::from_bytes(&name, &fs::read(path)?)
+    }
+
+    pub fn from_bytes(name: &str, bytes: &[u8]) -> Result<(Self, Vec<String>), PluginError> {
+        let wasm_error = |e| PluginError::Wasm(name.to_string(), e);
+        let mut config = wasmtime::Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config).map_err(wasm_error)?;
+        let module = Module::new(&engine, bytes).map_err(wasm_error)?;
+
+        // Plugins are given the name of their file, which lives as long as the registry
+        // (i.e., for the rest of the program)
+        let cleaner = WasmCleaner {
+            name: Box::leak(name.to_string().into_boxed_str()),
+            engine,
+            module,
+        };
+        let domains = cleaner
+            .call("domains", None)
+            .map_err(wasm_error)?
+            .unwrap_or_default();
+        let domains = domains
+            .lines()
+            .map(str::trim)
+            .filter(|d| !d.is_empty())
+            .map(str::to_string)
+            .collect();
+        Ok((cleaner, domains))
+    }
+
+    // Call one of the plugin's exported functions in a fresh instance, passing `input`
+    // (if any) and reading back the returned string
+    fn call(&self, function: &str, input: Option<&str>) -> Result<Option<String>, wasmtime::Error> {
+        let limits = StoreLimitsBuilder::new().memory_size(MEMORY_LIMIT).build();
+        let mut store: Store<StoreLimits> = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(FUEL)?;
+
+        let instance = Instance::new(&mut store, &self.module, &[])?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| wasmtime::Error::msg("plugin does not export memory"))?;
+
+        let packed = match input {
+            Some(input) => {
+                let len = i32::try_from(input.len())?;
+                let alloc = instance.get_typed_func::<i32, i32>(&mut store, "alloc")?;
+                let ptr = alloc.call(&mut store, len)?;
+                memory.write(&mut store, usize::try_from(ptr)?, input.as_bytes())?;
+                instance
+                    .get_typed_func::<(i32, i32), i64>(&mut store, function)?
+                    .call(&mut store, (ptr, len))?
+            }
+            None => instance
+                .get_typed_func::<(), i64>(&mut store, function)?
+                .call(&mut store, ())?,
+        };
+        if packed == 0 {
+            return Ok(None);
+        }
+
+        let packed = packed as u64;
+        let (ptr, len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+        // Copy straight out of the plugin's memory, rather than allocating `len` bytes up
+        // front, so that a bogus length can't make us allocate (up to) 4 GiB
+        let output = ptr
+            .checked_add(len)
+            .and_then(|end| memory.data(&store).get(ptr..end))
+            .ok_or_else(|| wasmtime::Error::msg("plugin returned a string out of bounds"))?;
+        Ok(Some(String::from_utf8(output.to_vec())?))
+    }
+}
+
+impl UrlCleaner for WasmCleaner {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn shapes(&self) -> &'static [&'static str] {
+        &["/<any path>"]
+    }
+
+    fn clean(&self, url: &mut Url, explanation: &mut Explanation) -> Result<(), CleanUrlError> {
+        explanation.rule("plugin");
+        match self.call("clean", Some(url.as_str())) {
+            Ok(Some(cleaned)) => {
+                *url = Url::parse(&cleaned)?;
+                explanation.step(format!("plugin cleaned URL to {}", url));
+                Ok(())
+            }
+            Ok(None) => {
+                explanation.step("plugin rejected URL");
+                Err(CleanUrlError::UnsupportedUrlPath)
+            }
+            Err(e) => {
+                tracing::warn!(plugin = self.name, error = %e, "plugin failed");
+                explanation.step(format!("plugin failed: {}", e));
+                Err(CleanUrlError::UnsupportedUrlPath)
+            }
+        }
+    }
+}
+
+// Load every plugin (`*.wasm`) in `dir`, in order of file name.  It is not an error for
+// the directory not to exist
+pub fn load_plugins(dir: &Path) -> Result<Vec<(WasmCleaner, Vec<String>)>, PluginError> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == PLUGIN_EXTENSION) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    paths
+        .iter()
+        .map(|path| WasmCleaner::from_file(path))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Accepts URLs on example.com, and cleans them all to the same URL
+    const PLUGIN: &str = r#"
+        (module
+          (memory (export "memory") 1)
+          (data (i32.const 0) "example.com\nexample.org")
+          (data (i32.const 64) "https://example.com/cleaned")
+          (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+          (func (export "domains") (result i64) (i64.const 23))
+          (func (export "clean") (param i32 i32) (result i64)
+            (i64.or (i64.shl (i64.const 64) (i64.const 32)) (i64.const 27))))
+    "#;
+
+    #[test]
+    fn test_plugin() {
+        let (cleaner, domains) =
+            WasmCleaner::from_bytes("example", PLUGIN.as_bytes()).expect("plugin loaded");
+        assert_eq!(cleaner.name(), "example");
+        assert_eq!(domains, ["example.com", "example.org"]);
+
+        let mut url = Url::parse("https://example.com/a?ref=x").expect("valid url");
+        cleaner
+            .clean(&mut url, &mut Explanation::default())
+            .expect("cleaned");
+        assert_eq!(url.as_str(), "https://example.com/cleaned");
+    }
+
+    #[test]
+    fn test_no_imports() {
+        let plugin = r#"(module (import "env" "f" (func)) (memory (export "memory") 1))"#;
+        assert!(WasmCleaner::from_bytes("imports", plugin.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_fuel() {
+        let plugin = r#"
+            (module
+              (memory (export "memory") 1)
+              (func (export "domains") (result i64) (loop (br 0)) (i64.const 0)))
+        "#;
+        assert!(WasmCleaner::from_bytes("loop", plugin.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_out_of_bounds() {
+        // Claims to return a 4 GiB string, from a 64 KiB memory
+        let plugin = r#"
+            (module
+              (memory (export "memory") 1)
+              (func (export "domains") (result i64) (i64.const 0xffffffff)))
+        "#;
+        assert!(WasmCleaner::from_bytes("oversized", plugin.as_bytes()).is_err());
+    }
+}
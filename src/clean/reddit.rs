@@ -1,17 +1,27 @@
-use super::{CleanUrlError, UrlCleaner};
+use super::{CleanUrlError, Explanation, UrlCleaner, remove_query, remove_trailing_slash};
 use url::Url;
 
 pub struct RedditCleaner;
 
 impl UrlCleaner for RedditCleaner {
-    fn clean(&self, url: &mut Url) -> Result<(), CleanUrlError> {
+    fn name(&self) -> &'static str {
+        "RedditCleaner"
+    }
+
+    fn shapes(&self) -> &'static [&'static str] {
+        &[
+            "/r/<subreddit>/comments/<post ID>",
+            "/r/<subreddit>/comments/<post ID>/<short name>",
+            "/r/<subreddit>/comments/<post ID>/comment/<comment ID>",
+        ]
+    }
+
+    fn clean(&self, url: &mut Url, explanation: &mut Explanation) -> Result<(), CleanUrlError> {
         // Step 1: remove query parameters
-        url.set_query(None);
+        remove_query(url, explanation);
 
         // Step 2: remove trailing slash if any (provides no information)
-        url.path_segments_mut()
-            .map_err(|_| CleanUrlError::PathSegmentsError)?
-            .pop_if_empty(); // remove trailing slash if present
+        remove_trailing_slash(url, explanation)?;
 
         // Step 3: possibly remove trailing path (additional post information)
         let segments: Vec<_> = url
@@ -20,19 +30,37 @@ impl UrlCleaner for RedditCleaner {
             .collect();
 
         // https://www.reddit.com/r/<sub>/comments/<post_id>/<post_short_name> (optional short name)
-        let is_post_with_short_name = matches!(segments.as_slice(), ["r", _, "comments", _, _]);
-        let is_post =
-            is_post_with_short_name || matches!(segments.as_slice(), ["r", _, "comments", _]);
+        //
+        // IDs must be non-empty, or else removing the short name would leave a trailing
+        // slash (and a URL we would reject if cleaned again)
+        let is_post_with_short_name =
+            matches!(segments.as_slice(), ["r", _, "comments", id, _] if !id.is_empty());
+        let is_post = is_post_with_short_name
+            || matches!(segments.as_slice(), ["r", _, "comments", id] if !id.is_empty());
 
         // https://www.reddit.com/r/<sub>/comments/<post_id>/comment/<comment_id>
         let is_comment = matches!(segments.as_slice(), ["r", _, "comments", _, "comment", _]);
 
         if !is_post && !is_comment {
+            explanation.step(format!(
+                "path /{} is neither a post nor a comment",
+                segments.join("/")
+            ));
             return Err(CleanUrlError::UnsupportedUrlPath);
         }
 
+        explanation.rule(match (is_post_with_short_name, is_post) {
+            (true, _) => "post with short name",
+            (false, true) => "post",
+            (false, false) => "comment",
+        });
+
         // If the URL is a post, remove its short name (final segment)
         if is_post_with_short_name {
+            explanation.step(format!(
+                "removed post short name \"{}\"",
+                segments.last().expect("post has short name")
+            ));
             url.path_segments_mut()
                 .map_err(|_| CleanUrlError::PathSegmentsError)?
                 .pop();
@@ -40,4 +68,21 @@ impl UrlCleaner for RedditCleaner {
 
         Ok(())
     }
+
+    // Any post or comment is already a full URL, whereas share links (`/r/<sub>/s/<ID>`)
+    // must be resolved
+    fn recognises(&self, url: &Url) -> bool {
+        self.clean(&mut url.clone(), &mut Explanation::default())
+            .is_ok()
+    }
+
+    // Only anchors to a comment on the page (e.g., `#t1_abc123`, or `#thing_t1_abc123`
+    // on old Reddit) mean anything; the rest are left by the site's own scripts
+    fn keeps_fragment(&self, _url: &Url, fragment: &str) -> bool {
+        fragment
+            .strip_prefix("thing_")
+            .unwrap_or(fragment)
+            .strip_prefix("t1_")
+            .is_some_and(|id| !id.is_empty() && id.bytes().all(|b| b.is_ascii_alphanumeric()))
+    }
 }
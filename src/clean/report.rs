@@ -0,0 +1,209 @@
+use std::fmt;
+
+use url::Url;
+
+// A component of a URL removed (or replaced) by cleaning, so that what was stripped can
+// be shown to the user, or audited
+//
+// Query parameter values may themselves identify someone (e.g., a `share_id`), so all
+// but short values are redacted; see `redact_value`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "component", rename_all = "snake_case")
+)]
+pub enum Removed {
+    // The Wayback Machine snapshot the link was to, when linking to the original instead
+    Snapshot { timestamp: String },
+    // A redirector the link was wrapped in (e.g., `l.facebook.com`)
+    Redirector { host: String },
+    // The host, replaced with another (e.g., a mobile host with the desktop one)
+    Host { from: String, to: String },
+    // A path segment (e.g., a Reddit post's short name)
+    PathSegment { segment: String },
+    // A query parameter, with its value redacted
+    Param { name: String, value: String },
+    // The fragment
+    Fragment { fragment: String },
+}
+
+impl fmt::Display for Removed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Removed::Snapshot { timestamp } => write!(f, "snapshot {}", timestamp),
+            Removed::Redirector { host } => write!(f, "redirector {}", host),
+            Removed::Host { from, to } => write!(f, "host {} (now {})", from, to),
+            Removed::PathSegment { segment } => write!(f, "path segment \"{}\"", segment),
+            Removed::Param { name, value } => write!(f, "query parameter {}={}", name, value),
+            Removed::Fragment { fragment } => write!(f, "fragment #{}", fragment),
+        }
+    }
+}
+
+// How sure we are that cleaning removed nothing the site needs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum Confidence {
+    // Every query parameter removed was known not to be needed, whether by a dedicated
+    // cleaner, or from a list of tracking parameters
+    Known,
+    // Some were removed only because the cleaner removes any it does not know (e.g.,
+    // `GenericCleaner`), so the link may no longer work as intended
+    Guessed,
+}
+
+impl Confidence {
+    // The confidence of cleaning that removed `unknown_params`; see
+    // `CleanedUrl::unknown_params`
+    pub fn of(unknown_params: &[String]) -> Self {
+        if unknown_params.is_empty() {
+            Confidence::Known
+        } else {
+            Confidence::Guessed
+        }
+    }
+}
+
+impl fmt::Display for Confidence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Confidence::Known => write!(f, "known"),
+            Confidence::Guessed => write!(f, "guessed"),
+        }
+    }
+}
+
+// Values at most this long are short enough not to identify anyone (e.g., `share`, or
+// `ios`), and are reported as they are
+const MAX_UNREDACTED_LEN: usize = 8;
+
+// Enough of a value to tell what it was, without recording an identifier
+fn redact_value(value: &str) -> String {
+    if value.chars().count() <= MAX_UNREDACTED_LEN {
+        return value.to_string();
+    }
+    let prefix: String = value.chars().take(2).collect();
+    format!("{}… ({} characters)", prefix, value.chars().count())
+}
+
+// Every component of `before` that is not in `after`, in the order they appear in a URL
+//
+// Repeated path segments and query parameters are counted, so that removing one of two
+// `utm_source` parameters is reported.  Empty path segments (e.g., from a trailing
+// slash) are not.
+pub fn removed_components(before: &Url, after: &Url) -> Vec<Removed> {
+    let mut removed = Vec::new();
+
+    if let (Some(from), Some(to)) = (before.host_str(), after.host_str())
+        && from != to
+    {
+        removed.push(Removed::Host {
+            from: from.to_string(),
+            to: to.to_string(),
+        });
+    }
+
+    let mut remaining: Vec<_> = after.path_segments().into_iter().flatten().collect();
+    for segment in before.path_segments().into_iter().flatten() {
+        match remaining.iter().position(|s| *s == segment) {
+            Some(i) => {
+                remaining.remove(i);
+            }
+            None if !segment.is_empty() => removed.push(Removed::PathSegment {
+                segment: segment.to_string(),
+            }),
+            None => {}
+        }
+    }
+
+    let mut remaining: Vec<_> = after.query_pairs().collect();
+    for (name, value) in before.query_pairs() {
+        match remaining
+            .iter()
+            .position(|(k, v)| *k == name && *v == value)
+        {
+            Some(i) => {
+                remaining.remove(i);
+            }
+            None => removed.push(Removed::Param {
+                value: redact_value(&value),
+                name: name.into_owned(),
+            }),
+        }
+    }
+
+    if let Some(fragment) = before.fragment()
+        && after.fragment() != Some(fragment)
+    {
+        removed.push(Removed::Fragment {
+            fragment: fragment.to_string(),
+        });
+    }
+
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn removed(before: &str, after: &str) -> Vec<Removed> {
+        removed_components(
+            &Url::parse(before).expect("valid url"),
+            &Url::parse(after).expect("valid url"),
+        )
+    }
+
+    #[test]
+    fn test_removed_components() {
+        assert_eq!(
+            removed(
+                "https://m.reddit.com/r/rust/comments/1q2rw7m/title/?share_id=l2suzjz-JpaaqZSjbaNmt&utm_source=share&utm_source=ios#lightbox",
+                "https://www.reddit.com/r/rust/comments/1q2rw7m",
+            ),
+            [
+                Removed::Host {
+                    from: "m.reddit.com".to_string(),
+                    to: "www.reddit.com".to_string()
+                },
+                Removed::PathSegment {
+                    segment: "title".to_string()
+                },
+                Removed::Param {
+                    name: "share_id".to_string(),
+                    value: "l2… (21 characters)".to_string()
+                },
+                Removed::Param {
+                    name: "utm_source".to_string(),
+                    value: "share".to_string()
+                },
+                Removed::Param {
+                    name: "utm_source".to_string(),
+                    value: "ios".to_string()
+                },
+                Removed::Fragment {
+                    fragment: "lightbox".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nothing_removed() {
+        let url = "https://www.facebook.com/permalink.php?story_fbid=1&id=2";
+        assert!(removed(url, url).is_empty());
+        // Reordering is not removal
+        assert!(
+            removed(
+                url,
+                "https://www.facebook.com/permalink.php?id=2&story_fbid=1"
+            )
+            .is_empty()
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use regex::Regex;
+use url::Url;
+
+use super::{CleanUrlError, Explanation, UrlCleaner, generic, remove_trailing_slash};
+
+// A declarative cleaning rule for a single domain, as given in the configuration file
+//
+// Example:
+//
+//     [rules."example.com"]
+//     strip = ["ref"]
+//     keep = ["id"]
+//     path_patterns = ["/p/*"]
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct CleaningRule {
+    // Query parameters to remove, in addition to known tracking parameters
+    pub strip: Vec<String>,
+    // If given, the only query parameters to keep; all others are removed
+    pub keep: Vec<String>,
+    // If given, the paths accepted for this domain, where `*` matches within a single
+    // path segment and `**` matches any number of segments
+    pub path_patterns: Vec<String>,
+}
+
+// Cleaner compiled from a user-defined `CleaningRule`
+pub struct RuleCleaner {
+    rule: CleaningRule,
+    paths: Vec<Regex>,
+    // Descriptions for `--list-supported`.  Rules are compiled once at startup, so it is
+    // harmless to leak these in order to satisfy `UrlCleaner`'s signatures
+    shapes: &'static [&'static str],
+    preserved_params: &'static [&'static str],
+}
+
+// Convert a path glob to an anchored regular expression
+fn compile_glob(pattern: &str) -> Regex {
+    let pattern = pattern.trim_end_matches('/');
+    let mut regex = String::from("^");
+    for (i, part) in pattern.split("**").enumerate() {
+        if i > 0 {
+            regex.push_str(".*");
+        }
+        let parts: Vec<_> = part.split('*').map(regex::escape).collect();
+        regex.push_str(&parts.join("[^/]*"));
+    }
+    regex.push_str("/?$");
+    Regex::new(&regex).expect("escaped glob is a valid regex")
+}
+
+fn leak(strings: &[String]) -> &'static [&'static str] {
+    let strings: Vec<&'static str> = strings
+        .iter()
+        .map(|s| &*Box::leak(s.clone().into_boxed_str()))
+        .collect();
+    Box::leak(strings.into_boxed_slice())
+}
+
+impl RuleCleaner {
+    pub fn new(rule: CleaningRule) -> Self {
+        let shapes: &'static [&'static str] = if rule.path_patterns.is_empty() {
+            &["/<any path>"]
+        } else {
+            leak(&rule.path_patterns)
+        };
+        RuleCleaner {
+            paths: rule.path_patterns.iter().map(|p| compile_glob(p)).collect(),
+            shapes,
+            preserved_params: leak(&rule.keep),
+            rule,
+        }
+    }
+}
+
+impl UrlCleaner for RuleCleaner {
+    fn name(&self) -> &'static str {
+        "RuleCleaner"
+    }
+
+    fn shapes(&self) -> &'static [&'static str] {
+        self.shapes
+    }
+
+    fn preserved_params(&self) -> &'static [&'static str] {
+        self.preserved_params
+    }
+
+    fn clean(&self, url: &mut Url, explanation: &mut Explanation) -> Result<(), CleanUrlError> {
+        // Step 1: validate path
+        if !self.paths.is_empty() {
+            if !self.paths.iter().any(|p| p.is_match(url.path())) {
+                explanation.step(format!(
+                    "path {} does not match any of {}",
+                    url.path(),
+                    self.rule.path_patterns.join(", ")
+                ));
+                return Err(CleanUrlError::UnsupportedUrlPath);
+            }
+            explanation.rule("user-defined path pattern");
+        } else {
+            explanation.rule("user-defined rule");
+        }
+
+        // Step 2: remove query parameters which are not explicitly kept
+        let (removed, kept): (Vec<_>, Vec<_>) = url
+            .query_pairs()
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .partition(|(k, _)| {
+                if self.rule.keep.is_empty() {
+                    self.rule.strip.contains(k) || generic::is_tracking_param(k)
+                } else {
+                    !self.rule.keep.contains(k) || self.rule.strip.contains(k)
+                }
+            });
+        if !removed.is_empty() {
+            let names: Vec<_> = removed.iter().map(|(k, _)| k.as_str()).collect();
+            explanation.step(format!("removed query parameters: {}", names.join(", ")));
+            url.set_query(None);
+            if !kept.is_empty() {
+                url.query_pairs_mut().extend_pairs(kept);
+            }
+        }
+
+        // Step 3: remove trailing slash if any (provides no information)
+        remove_trailing_slash(url, explanation)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clean(rule: CleaningRule, url: &str) -> Result<String, CleanUrlError> {
+        let mut url = Url::parse(url).expect("valid url");
+        RuleCleaner::new(rule).clean(&mut url, &mut Explanation::default())?;
+        Ok(url.to_string())
+    }
+
+    #[test]
+    fn test_strip() {
+        let rule = CleaningRule {
+            strip: vec!["ref".to_string()],
+            ..Default::default()
+        };
+        let cleaned = clean(rule, "https://example.com/a/?id=1&ref=x&utm_source=y");
+        assert_eq!(cleaned.expect("cleaned"), "https://example.com/a?id=1");
+    }
+
+    #[test]
+    fn test_keep() {
+        let rule = CleaningRule {
+            keep: vec!["id".to_string()],
+            ..Default::default()
+        };
+        let cleaned = clean(rule, "https://example.com/a?id=1&page=2");
+        assert_eq!(cleaned.expect("cleaned"), "https://example.com/a?id=1");
+    }
+
+    #[test]
+    fn test_path_patterns() {
+        let rule = CleaningRule {
+            path_patterns: vec!["/p/*".to_string(), "/docs/**".to_string()],
+            ..Default::default()
+        };
+        assert!(clean(rule.clone(), "https://example.com/p/123").is_ok());
+        assert!(clean(rule.clone(), "https://example.com/p/123/").is_ok());
+        assert!(clean(rule.clone(), "https://example.com/docs/a/b").is_ok());
+        assert!(matches!(
+            clean(rule.clone(), "https://example.com/p/123/comments"),
+            Err(CleanUrlError::UnsupportedUrlPath)
+        ));
+        assert!(matches!(
+            clean(rule, "https://example.com/q/123"),
+            Err(CleanUrlError::UnsupportedUrlPath)
+        ));
+    }
+}
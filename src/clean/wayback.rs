@@ -0,0 +1,127 @@
+use std::str::FromStr;
+
+use url::Url;
+
+use super::ParseOptionError;
+
+// Links to snapshots in the Wayback Machine, e.g.:
+//   <https://web.archive.org/web/20260101000000/https://www.reddit.com/r/rust/comments/1q2rw7m/title/?utm_source=share>
+//
+// The original URL is embedded in the path, tracking and all, so we clean that, and
+// either link to it directly, or to its snapshot; see `WaybackPolicy`.
+
+const WAYBACK_HOST: &str = "web.archive.org";
+
+// What to do with links to snapshots in the Wayback Machine
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum WaybackPolicy {
+    // Clean the original URL, and link to its snapshot
+    #[default]
+    Wrap,
+    // Clean the original URL, and link to it directly
+    Extract,
+}
+
+impl FromStr for WaybackPolicy {
+    type Err = ParseOptionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "wrap" => Ok(WaybackPolicy::Wrap),
+            "extract" => Ok(WaybackPolicy::Extract),
+            _ => Err(ParseOptionError(s.to_string())),
+        }
+    }
+}
+
+// A snapshot's timestamp (e.g., `20260101000000`, possibly with a modifier, such as
+// `id_`), and the original URL, if `url` is a link to one
+pub(super) fn parse(url: &Url) -> Option<(String, Url)> {
+    if url.host_str() != Some(WAYBACK_HOST) {
+        return None;
+    }
+    let rest = url.path().strip_prefix("/web/")?;
+    let (timestamp, original) = rest.split_once('/')?;
+    let digits = timestamp.bytes().take_while(u8::is_ascii_digit).count();
+    let modifier = &timestamp[digits..];
+    if digits == 0
+        || !modifier
+            .bytes()
+            .all(|b| b.is_ascii_alphabetic() || b == b'_')
+    {
+        return None;
+    }
+
+    // The original's query and fragment are the link's own, as the Wayback Machine does
+    // not encode them.  Its `//` is sometimes collapsed to `/`, too
+    let mut original = original.to_string();
+    for scheme in ["http:/", "https:/"] {
+        if original.starts_with(scheme) && !original[scheme.len()..].starts_with('/') {
+            original.insert(scheme.len(), '/');
+        }
+    }
+    if let Some(query) = url.query() {
+        original = format!("{}?{}", original, query);
+    }
+    if let Some(fragment) = url.fragment() {
+        original = format!("{}#{}", original, fragment);
+    }
+    let original = Url::parse(&original).ok()?;
+    matches!(original.scheme(), "http" | "https").then(|| (timestamp.to_string(), original))
+}
+
+// The link to the snapshot taken at `timestamp` of `url`
+pub(super) fn snapshot_url(timestamp: &str, url: &str) -> String {
+    format!("https://{}/web/{}/{}", WAYBACK_HOST, timestamp, url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(url: &str) -> Option<(String, String)> {
+        parse(&Url::parse(url).expect("valid url"))
+            .map(|(timestamp, original)| (timestamp, original.to_string()))
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parsed("https://web.archive.org/web/20260101000000/https://example.com/a?b=1#c"),
+            Some((
+                "20260101000000".to_string(),
+                "https://example.com/a?b=1#c".to_string()
+            ))
+        );
+        assert_eq!(
+            parsed("https://web.archive.org/web/2026id_/http:/example.com/"),
+            Some(("2026id_".to_string(), "http://example.com/".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_not_snapshot() {
+        assert_eq!(
+            parsed("https://web.archive.org/save/https://example.com/"),
+            None
+        );
+        assert_eq!(
+            parsed("https://web.archive.org/web/*/https://example.com/"),
+            None
+        );
+        assert_eq!(
+            parsed("https://web.archive.org/web/2026/ftp://example.com/"),
+            None
+        );
+        assert_eq!(
+            parsed("https://archive.org/web/2026/https://example.com/"),
+            None
+        );
+    }
+}
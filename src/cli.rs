@@ -0,0 +1,796 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, IsTerminal},
+    path::PathBuf,
+    time::Duration,
+};
+
+use clap::{ArgAction, ColorChoice, Parser, Subcommand, crate_authors, crate_name, crate_version};
+use url::Url;
+
+use crate::{
+    batch::{self, DEFAULT_CONCURRENCY, DEFAULT_HOST_COOLDOWN, DEFAULT_HOST_FAILURES},
+    clean::{
+        CleanOptions, CleanerRegistry, FragmentPolicy, HostDisplay, RemoveParamFilters,
+        WaybackPolicy,
+    },
+    clipboard::{self, Backend as ClipboardBackend, Selection},
+    config::Config,
+    exit::{EXIT_CODES_HELP, ExitCode},
+    logging::{LogFormat, LogLevel},
+    process::ProcessOptions,
+    resolve::{CookieJar, IpFamily, Resolver},
+    sink::Sink,
+    tabular::Column,
+    update,
+};
+
+#[derive(Parser)]
+#[command(
+    name = crate_name!(),
+    author = crate_authors!(", "),
+    version = crate_version!(),
+    after_help = EXIT_CODES_HELP,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true,
+)]
+/// Resolve share link to canonical form
+pub struct Cli {
+    /// URLs to resolve
+    ///
+    /// If no URLs are given, they are read from standard input, one per line.
+    #[arg(
+        action = ArgAction::Append,
+        num_args = 0..,
+        value_name = "URL",
+    )]
+    pub urls: Vec<String>,
+
+    /// Read URLs from FILE, one per line
+    #[arg(
+        short = 'f',
+        long = "file",
+        action = ArgAction::Set,
+        value_name = "FILE",
+    )]
+    pub file: Option<PathBuf>,
+
+    /// Read URLs from a column of CSV input, writing back each row with added
+    /// `resolved` and `cleaned` columns
+    #[arg(
+        long = "csv",
+        action = ArgAction::SetTrue,
+        conflicts_with_all = ["urls", "interactive", "null", "tsv"],
+    )]
+    pub csv: bool,
+
+    /// As --csv, but for tab-separated input
+    #[arg(
+        long = "tsv",
+        action = ArgAction::SetTrue,
+        conflicts_with_all = ["urls", "interactive", "null"],
+    )]
+    pub tsv: bool,
+
+    /// With --csv or --tsv, the column holding URLs, by header name or 1-based index
+    /// [default: the column named "url", or else the first]
+    #[arg(
+        long = "column",
+        action = ArgAction::Set,
+        value_name = "COLUMN",
+    )]
+    pub column: Option<Column>,
+
+    /// Print each result as a JSON object (one per line), with details such as the
+    /// platform and removed query parameters
+    #[arg(
+        long = "json",
+        action = ArgAction::SetTrue,
+        conflicts_with_all = ["interactive", "csv", "tsv"],
+    )]
+    pub json: bool,
+
+    /// Use NUL rather than newline to delimit URLs read from input and written to output
+    #[arg(
+        short = '0',
+        long = "null",
+        action = ArgAction::SetTrue,
+        conflicts_with = "interactive",
+    )]
+    pub null: bool,
+
+    /// Write results to FILE rather than standard output
+    ///
+    /// The file is written atomically: results only appear at FILE once the run
+    /// completes, and an interrupted run leaves any existing file untouched.
+    #[arg(
+        short = 'o',
+        long = "output",
+        action = ArgAction::Set,
+        value_name = "FILE",
+        conflicts_with = "interactive",
+    )]
+    pub output: Option<PathBuf>,
+
+    /// With --output, add results to the end of FILE rather than replacing it
+    #[arg(
+        long = "append",
+        action = ArgAction::SetTrue,
+        requires = "output",
+    )]
+    pub append: bool,
+
+    /// Maximum number of URLs to resolve concurrently
+    #[arg(
+        short = 'j',
+        long = "concurrency",
+        action = ArgAction::Set,
+        value_name = "N",
+        default_value_t = DEFAULT_CONCURRENCY,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..),
+    )]
+    pub concurrency: usize,
+
+    /// Maximum number of requests to have in flight to any one host [default: 4]
+    #[arg(
+        long = "host-concurrency",
+        action = ArgAction::Set,
+        value_name = "N",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..),
+    )]
+    pub host_concurrency: Option<usize>,
+
+    /// Only print cleaned URLs and errors (no warnings or progress)
+    #[arg(
+        short = 'q',
+        long = "quiet",
+        action = ArgAction::SetTrue,
+    )]
+    pub quiet: bool,
+
+    /// Print each step taken on standard error (-vv for more detail)
+    #[arg(
+        short = 'v',
+        long = "verbose",
+        action = ArgAction::Count,
+        conflicts_with = "quiet",
+    )]
+    pub verbose: u8,
+
+    /// Minimum level of log messages to show (overrides RUST_LOG)
+    #[arg(
+        long = "log-level",
+        action = ArgAction::Set,
+        value_name = "LEVEL",
+        value_enum,
+    )]
+    pub log_level: Option<LogLevel>,
+
+    /// Format of log messages written to standard error
+    #[arg(
+        long = "log-format",
+        action = ArgAction::Set,
+        value_name = "FORMAT",
+        value_enum,
+        default_value_t = LogFormat::Text,
+    )]
+    pub log_format: LogFormat,
+
+    /// Show only the scheme and host of URLs in log messages
+    #[arg(
+        long = "redact-urls",
+        action = ArgAction::SetTrue,
+    )]
+    pub redact_urls: bool,
+
+    /// How to copy results: the system clipboard (by whichever means works, or OSC 52 over
+    /// SSH or in tmux), Wayland, X11, an OSC 52 escape sequence (which asks the terminal to
+    /// set its clipboard), or not at all [default: auto]
+    #[arg(
+        long = "clipboard-backend",
+        action = ArgAction::Set,
+        value_name = "BACKEND",
+        value_enum,
+    )]
+    pub clipboard_backend: Option<ClipboardBackend>,
+
+    /// Copy results with an OSC 52 escape sequence; shorthand for `--clipboard-backend osc52`
+    #[arg(
+        long = "osc52",
+        action = ArgAction::SetTrue,
+        conflicts_with = "clipboard_backend",
+    )]
+    pub osc52: bool,
+
+    /// Copy results as rich text too, so that pasting them into a document or chat gives
+    /// links titled with their page's title (where known), rather than bare URLs
+    #[arg(
+        long = "rich",
+        action = ArgAction::SetTrue,
+    )]
+    pub rich: bool,
+
+    /// Which selection to copy results to: the clipboard, the X11 primary selection
+    /// (pasted with a middle click), or both [default: clipboard]
+    #[arg(
+        long = "selection",
+        action = ArgAction::Set,
+        value_name = "SELECTION",
+        value_enum,
+    )]
+    pub selection: Option<Selection>,
+
+    /// Save a snapshot of each cleaned URL to the Wayback Machine, printing the snapshot URL
+    #[arg(
+        long = "archive",
+        action = ArgAction::SetTrue,
+        conflicts_with = "check",
+    )]
+    pub archive: bool,
+
+    /// Follow redirects to the final URL, but do not remove any query parameters
+    #[arg(
+        long = "expand-only",
+        action = ArgAction::SetTrue,
+    )]
+    pub expand_only: bool,
+
+    /// Do not resolve URLs over the network; only clean them as given, warning about
+    /// shortened links that cannot be expanded
+    #[arg(
+        long = "offline",
+        action = ArgAction::SetTrue,
+        conflicts_with_all = ["expand_only", "verify", "archive"],
+    )]
+    pub offline: bool,
+
+    /// Resolve URLs even if they are already full URLs of posts (or other pages) on a
+    /// supported platform, e.g., to check whether they are dead
+    #[arg(
+        long = "always-resolve",
+        action = ArgAction::SetTrue,
+        conflicts_with = "offline",
+    )]
+    pub always_resolve: bool,
+
+    /// Use the URL we were redirected to, even if the page gives a different canonical URL
+    #[arg(
+        long = "no-canonical",
+        action = ArgAction::SetTrue,
+    )]
+    pub no_canonical: bool,
+
+    /// Resolve links with a client that mimics Chrome's TLS and HTTP/2 fingerprints, for
+    /// sites that serve bot checks to anything else
+    #[cfg(feature = "impersonate")]
+    #[arg(
+        long = "impersonate",
+        action = ArgAction::SetTrue,
+    )]
+    pub impersonate: bool,
+
+    /// Keep plain `http` links as they are, rather than upgrading them to `https` where
+    /// the site supports it
+    #[arg(
+        long = "no-https-upgrade",
+        action = ArgAction::SetTrue,
+    )]
+    pub no_https_upgrade: bool,
+
+    /// Refuse to follow redirects off the site (registrable domain) of the URL being
+    /// resolved, so that no other site sees the request [default: `same_site` in the
+    /// configuration file]
+    #[arg(
+        long = "same-site",
+        action = ArgAction::SetTrue,
+    )]
+    pub same_site: bool,
+
+    /// Allow resolution to request URLs on private networks (e.g., `localhost`, or
+    /// `192.168.0.1`) or unusual ports, which are refused by default [default:
+    /// `allow_private_networks` in the configuration file]
+    #[arg(
+        long = "allow-private-networks",
+        action = ArgAction::SetTrue,
+    )]
+    pub allow_private_networks: bool,
+
+    /// Keep cookies set while resolving in this file, so that they are sent again for later
+    /// links and runs [default: `cookie_jar` in the configuration file, if set]
+    #[arg(long = "cookie-jar", value_name = "FILE")]
+    pub cookie_jar: Option<PathBuf>,
+
+    /// Only connect to servers over IPv4
+    #[arg(
+        short = '4',
+        long = "ipv4",
+        action = ArgAction::SetTrue,
+        conflicts_with = "ipv6",
+    )]
+    pub ipv4: bool,
+
+    /// Only connect to servers over IPv6
+    #[arg(
+        short = '6',
+        long = "ipv6",
+        action = ArgAction::SetTrue,
+    )]
+    pub ipv6: bool,
+
+    /// Do not use (or add to) the cache of previously resolved links
+    #[arg(
+        long = "no-cache",
+        action = ArgAction::SetTrue,
+    )]
+    pub no_cache: bool,
+
+    /// Resolve links on the domains in `browser_domains` (see the configuration file) with
+    /// headless Chromium, for sites that only redirect in JavaScript
+    #[cfg(feature = "browser")]
+    #[arg(
+        long = "browser",
+        action = ArgAction::SetTrue,
+    )]
+    pub browser: bool,
+
+    /// Re-fetch each cleaned URL to check that it still works
+    ///
+    /// Warns (and exits with 6) if the cleaned URL does not return a successful status,
+    /// or redirects somewhere other than the resolved URL.
+    #[arg(
+        long = "verify",
+        action = ArgAction::SetTrue,
+        conflicts_with = "expand_only",
+    )]
+    pub verify: bool,
+
+    /// Screen where each link goes for signs that it is malicious, and do not copy it to
+    /// the clipboard if there are any
+    ///
+    /// Checks for hosts spelled with lookalike letters, credentials in the URL, and
+    /// top-level domains widely used for phishing; with --safe-browsing-key, also looks
+    /// the link up in Google Safe Browsing.  Warns (and exits with 7) if any are found.
+    #[arg(
+        long = "safety-check",
+        action = ArgAction::SetTrue,
+    )]
+    pub safety_check: bool,
+
+    /// Google Safe Browsing API key, used by --safety-check
+    #[arg(
+        long = "safe-browsing-key",
+        action = ArgAction::Set,
+        value_name = "KEY",
+        env = "RSL_SAFE_BROWSING_KEY",
+        hide_env_values = true,
+    )]
+    pub safe_browsing_key: Option<String>,
+
+    /// Query parameters to keep, even if they would otherwise be removed
+    #[arg(
+        long = "keep-params",
+        action = ArgAction::Append,
+        value_name = "NAME,...",
+        value_delimiter = ',',
+        conflicts_with = "expand_only",
+    )]
+    pub keep_params: Vec<String>,
+
+    /// Additional query parameters to remove, even if they would otherwise be preserved
+    #[arg(
+        long = "strip-params",
+        action = ArgAction::Append,
+        value_name = "NAME,...",
+        value_delimiter = ',',
+        conflicts_with = "expand_only",
+    )]
+    pub strip_params: Vec<String>,
+
+    /// Whether to keep `#` fragments in cleaned URLs [default: auto]
+    ///
+    /// With "auto", meaningful anchors (e.g., Wikipedia sections or GitHub line ranges)
+    /// are kept, but tracking fragments (e.g., "#Echobox=...") are removed.
+    #[arg(
+        long = "fragments",
+        action = ArgAction::Set,
+        value_name = "POLICY",
+        value_enum,
+        conflicts_with = "expand_only",
+    )]
+    pub fragments: Option<FragmentPolicy>,
+
+    /// How to show internationalised hosts in cleaned URLs [default: punycode]
+    ///
+    /// With "unicode", hosts such as "xn--bcher-kva.de" are shown as "bücher.de", except
+    /// those mixing lookalike scripts (e.g., a Cyrillic "а" in "аpple.com"), which are
+    /// left in punycode.  A warning is printed for such hosts either way.
+    #[arg(
+        long = "hosts",
+        action = ArgAction::Set,
+        value_name = "FORM",
+        value_enum,
+        conflicts_with = "expand_only",
+    )]
+    pub hosts: Option<HostDisplay>,
+
+    /// Whether to link to the Wayback Machine snapshot of a link to one, or the original
+    /// [default: wrap]
+    ///
+    /// Either way, the original URL embedded in the snapshot's link is cleaned.
+    #[arg(
+        long = "wayback",
+        action = ArgAction::Set,
+        value_name = "POLICY",
+        value_enum,
+        conflicts_with = "expand_only",
+    )]
+    pub wayback: Option<WaybackPolicy>,
+
+    /// Remove locale prefixes (e.g., "/en-us/" or "/intl/en/") from the paths of cleaned
+    /// URLs, on sites known to serve the same content whatever the locale
+    #[arg(
+        long = "strip-locale",
+        action = ArgAction::SetTrue,
+        conflicts_with = "expand_only",
+    )]
+    pub strip_locale: bool,
+
+    /// Remove a trailing index page (e.g., "index.html" or "index.php") from cleaned URLs
+    #[arg(
+        long = "drop-index",
+        action = ArgAction::SetTrue,
+        conflicts_with = "expand_only",
+    )]
+    pub drop_index: bool,
+
+    /// Reject URLs on hosts without a dedicated cleaner, rather than only removing known
+    /// tracking parameters
+    #[arg(
+        long = "strict",
+        action = ArgAction::SetTrue,
+        conflicts_with = "expand_only",
+    )]
+    pub strict: bool,
+
+    /// Path to configuration file [default: <config dir>/rsl/config.toml]
+    #[arg(
+        long = "config",
+        action = ArgAction::Set,
+        value_name = "FILE",
+        env = "RSL_CONFIG",
+    )]
+    pub config: Option<PathBuf>,
+
+    /// Send requests through this proxy (`http://`, `https://`, or `socks5://`), rather
+    /// than those given by `HTTP_PROXY`, `HTTPS_PROXY`, and `ALL_PROXY`.  Hosts listed in
+    /// `NO_PROXY` are still requested directly [default: `proxy` in the configuration
+    /// file]
+    #[arg(long = "proxy", value_name = "URL")]
+    pub proxy: Option<String>,
+
+    /// Explain which cleaner and rule were applied to each URL, and how it was changed
+    #[arg(
+        long = "explain",
+        action = ArgAction::SetTrue,
+    )]
+    pub explain: bool,
+
+    /// Print every hop (HTTP status, URL, and redirect type) taken while resolving each URL
+    #[arg(
+        long = "trace",
+        action = ArgAction::SetTrue,
+    )]
+    pub trace: bool,
+
+    /// List supported domains, the URL shapes accepted for each, and preserved parameters
+    #[arg(
+        long = "list-supported",
+        action = ArgAction::SetTrue,
+        exclusive = true,
+    )]
+    pub list_supported: bool,
+
+    /// When to use colour in output
+    #[arg(
+        long = "color",
+        action = ArgAction::Set,
+        value_name = "WHEN",
+        value_enum,
+        default_value_t = ColorChoice::Auto,
+    )]
+    pub color: ColorChoice,
+
+    /// Read URLs interactively, reusing the same HTTP client between requests
+    #[arg(
+        short = 'i',
+        long = "interactive",
+        action = ArgAction::SetTrue,
+        conflicts_with_all = ["urls", "file"],
+    )]
+    pub interactive: bool,
+
+    /// Check whether the URL is already canonical, without copying it to the clipboard
+    ///
+    /// Exits with 0 if the URL is already canonical, or 2 (printing the canonical
+    /// form) if cleaning would change it.
+    #[arg(
+        long = "check",
+        action = ArgAction::SetTrue,
+        conflicts_with = "interactive",
+    )]
+    pub check: bool,
+
+    /// Record processed URLs in a local history database
+    #[arg(
+        long = "save-history",
+        action = ArgAction::SetTrue,
+        value_parser = clap::builder::BoolishValueParser::new(),
+        env = "RSL_SAVE_HISTORY",
+    )]
+    pub save_history: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Query URLs previously processed with --save-history
+    History {
+        /// Only show entries where any URL contains TERM
+        #[arg(
+            short = 's',
+            long = "search",
+            action = ArgAction::Set,
+            value_name = "TERM",
+        )]
+        search: Option<String>,
+
+        /// Only show the N most recent entries
+        #[arg(
+            short = 'n',
+            long = "last",
+            action = ArgAction::Set,
+            value_name = "N",
+        )]
+        last: Option<usize>,
+    },
+
+    /// Summarise URLs previously processed with --save-history
+    Stats,
+
+    /// Download the latest list of tracking parameters to remove from every link, so that
+    /// newly found ones are removed without waiting for a new release
+    UpdateRules,
+
+    /// Resolve and clean supported links in text or Markdown files
+    Rewrite {
+        /// Files to rewrite [default: read text from standard input, and copy the result
+        /// to the clipboard]
+        #[arg(
+            action = ArgAction::Append,
+            num_args = 1..,
+            value_name = "FILE",
+        )]
+        files: Vec<PathBuf>,
+
+        /// Modify files in place rather than printing to standard output
+        #[arg(
+            long = "in-place",
+            action = ArgAction::SetTrue,
+            requires = "files",
+        )]
+        in_place: bool,
+    },
+
+    /// Resolve and clean two URLs, and report whether they point at the same resource
+    Compare {
+        /// First URL to compare
+        #[arg(value_name = "URL1")]
+        a: String,
+
+        /// Second URL to compare
+        #[arg(value_name = "URL2")]
+        b: String,
+    },
+
+    /// Keep hold of copied text on X11 once RSL has exited (used internally)
+    #[command(hide = true)]
+    HoldClipboard {
+        #[arg(value_enum)]
+        held: clipboard::Held,
+    },
+
+    /// Resolve and clean a list of URLs, printing each unique canonical URL once
+    Dedupe {
+        /// URLs to deduplicate
+        ///
+        /// If no URLs are given, they are read from standard input, one per line.
+        #[arg(
+            action = ArgAction::Append,
+            num_args = 0..,
+            value_name = "URL",
+        )]
+        urls: Vec<String>,
+
+        /// Prefix each URL with the number of inputs that mapped to it
+        #[arg(
+            short = 'c',
+            long = "count",
+            action = ArgAction::SetTrue,
+        )]
+        count: bool,
+    },
+}
+
+// Options given on the command line are combined with those in the configuration file,
+// with the command line taking precedence
+impl Cli {
+    // The proxy to resolve links through, exiting if it is not a valid URL
+    pub fn proxy(&self, config: &Config) -> Option<Url> {
+        self.proxy.as_ref().or(config.proxy.as_ref()).map(|proxy| {
+            Url::parse(proxy).unwrap_or_else(|e| {
+                eprintln!("Error: invalid proxy {}: {}", proxy, e);
+                ExitCode::Failure.exit();
+            })
+        })
+    }
+
+    // How each URL is to be processed, given the cleaning rules the configuration file
+    // describes
+    pub fn process_options(
+        &self,
+        config: &Config,
+        registry: CleanerRegistry,
+        filters: RemoveParamFilters,
+    ) -> ProcessOptions {
+        ProcessOptions {
+            expand_only: self.expand_only,
+            verify: self.verify,
+            safety_check: self.safety_check,
+            safe_browsing_key: self
+                .safe_browsing_key
+                .clone()
+                .or_else(|| config.safe_browsing_key.clone()),
+            offline: self.offline,
+            always_resolve: self.always_resolve,
+            clean: CleanOptions {
+                keep_params: [config.keep_params.clone(), self.keep_params.clone()].concat(),
+                strip_params: [config.strip_params.clone(), self.strip_params.clone()].concat(),
+                fragments: self.fragments.or(config.fragments).unwrap_or_default(),
+                hosts: self.hosts.or(config.hosts).unwrap_or_default(),
+                keep_session_ids: config.keep_session_ids.clone(),
+                drop_index: self.drop_index || config.drop_index,
+                wayback: self.wayback.or(config.wayback).unwrap_or_default(),
+                strip_locale: self.strip_locale || config.strip_locale,
+                strict: self.strict,
+                registry,
+                filters,
+                // Without a downloaded list, we use the one built in
+                tracking_params: update::default_path()
+                    .map(|path| update::load(&path))
+                    .unwrap_or_default(),
+            },
+        }
+    }
+
+    // The resolver to process links with, exiting if it could not be built
+    pub fn resolver(&self, config: &Config, proxy: Option<Url>) -> Resolver {
+        let builder = config.request_strategies.iter().fold(
+            Resolver::builder()
+                .prefer_canonical(!self.no_canonical)
+                .https_upgrade(!self.no_https_upgrade)
+                .same_site(self.same_site || config.same_site)
+                .allow_private_networks(
+                    self.allow_private_networks || config.allow_private_networks,
+                )
+                .request_strategy(config.request_strategy.unwrap_or_default()),
+            |builder, (domain, strategy)| builder.domain_request_strategy(domain, *strategy),
+        );
+        let builder = config
+            .domains
+            .iter()
+            .fold(builder, |builder, (domain, policy)| {
+                builder.domain_policy(domain, policy.clone())
+            });
+        #[cfg(feature = "browser")]
+        let builder = if self.browser {
+            builder.url_resolver(crate::resolve::BrowserResolver::new(
+                config.browser_domains.clone(),
+            ))
+        } else {
+            builder
+        };
+        let builder = match &config.accept_language {
+            Some(accept_language) => builder.accept_language(accept_language),
+            None => builder,
+        };
+        let builder = match config.max_retry_after {
+            Some(seconds) => builder.max_retry_after(Duration::from_secs(seconds)),
+            None => builder,
+        };
+        let builder = match self.cookie_jar.as_ref().or(config.cookie_jar.as_ref()) {
+            Some(path) => builder.cookie_jar(CookieJar::persistent(path).unwrap_or_else(|e| {
+                eprintln!("Error: could not load cookie jar: {}", e);
+                ExitCode::Failure.exit();
+            })),
+            None => builder,
+        };
+        #[cfg(feature = "impersonate")]
+        let builder = if self.impersonate {
+            builder.url_resolver(
+                crate::resolve::ImpersonatingResolver::new().unwrap_or_else(|e| {
+                    eprintln!("Error: {}", e);
+                    ExitCode::Failure.exit();
+                }),
+            )
+        } else {
+            builder
+        };
+        let builder = builder.ip_family(match (self.ipv4, self.ipv6) {
+            (true, _) => IpFamily::V4,
+            (_, true) => IpFamily::V6,
+            _ => IpFamily::Any,
+        });
+        let builder = match self.host_concurrency {
+            Some(n) => builder.max_requests_per_host(n),
+            None => builder,
+        };
+        // In a batch, stop wasting time on (and hammering) hosts that keep failing
+        let builder = match config.host_failures.unwrap_or(DEFAULT_HOST_FAILURES) {
+            0 => builder,
+            failures => builder.circuit_breaker(
+                failures,
+                config
+                    .host_cooldown
+                    .map_or(DEFAULT_HOST_COOLDOWN, Duration::from_secs),
+            ),
+        };
+        let builder = if self.no_cache {
+            builder
+        } else {
+            builder.cache(config.cache())
+        };
+        let builder = match proxy {
+            Some(proxy) => builder.proxy(proxy),
+            None => builder,
+        };
+        builder.build().unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            ExitCode::Failure.exit();
+        })
+    }
+
+    // Collect URLs from the command line, a file, or standard input (in that order of
+    // preference)
+    pub fn read_urls(&self) -> io::Result<Vec<String>> {
+        let mut urls = self.urls.clone();
+        if let Some(file) = &self.file {
+            urls.extend(batch::read_urls(
+                BufReader::new(File::open(file)?),
+                self.delimiter(),
+            )?);
+        }
+
+        // Only read from standard input if it is not a terminal, otherwise we would block
+        // waiting for input that the user does not know we want
+        if urls.is_empty() && self.file.is_none() && !io::stdin().is_terminal() {
+            urls = batch::read_urls(io::stdin().lock(), self.delimiter())?;
+        }
+
+        Ok(urls)
+    }
+
+    // Open the destination for results, exiting if it could not be created
+    pub fn sink(&self) -> Sink {
+        Sink::new(self.output.as_deref(), self.append).unwrap_or_else(|e| {
+            eprintln!("Error: could not open output file: {}", e);
+            ExitCode::Failure.exit();
+        })
+    }
+
+    // Byte used to delimit URLs in input and output
+    pub fn delimiter(&self) -> u8 {
+        if self.null { b'\0' } else { b'\n' }
+    }
+}
@@ -0,0 +1,19 @@
+use super::{ClipboardError, ClipboardProvider};
+
+// The macOS pasteboard (`NSPasteboard`), which, unlike X11's clipboard, keeps what is
+// copied once we exit
+pub(super) struct MacOs;
+
+impl ClipboardProvider for MacOs {
+    fn set_text(&mut self, text: &str) -> Result<(), ClipboardError> {
+        arboard::Clipboard::new()
+            .and_then(|mut ctx| ctx.set_text(text))
+            .map_err(|e| ClipboardError::System("macOS", e.to_string()))
+    }
+
+    fn set_html(&mut self, html: &str, text: &str) -> Result<(), ClipboardError> {
+        arboard::Clipboard::new()
+            .and_then(|mut ctx| ctx.set_html(html, Some(text)))
+            .map_err(|e| ClipboardError::System("macOS", e.to_string()))
+    }
+}
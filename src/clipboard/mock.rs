@@ -0,0 +1,61 @@
+use super::{ClipboardError, ClipboardProvider};
+
+// A clipboard that only records what is put on it, for testing
+#[derive(Default)]
+pub(super) struct Mock {
+    pub(super) text: Option<String>,
+    pub(super) html: Option<String>,
+    pub(super) primary: Option<String>,
+    // Whether it cannot take rich text
+    plain: bool,
+    // Whether it cannot take anything
+    failing: bool,
+}
+
+impl Mock {
+    pub(super) fn plain() -> Self {
+        Mock {
+            plain: true,
+            ..Mock::default()
+        }
+    }
+
+    pub(super) fn failing() -> Self {
+        Mock {
+            failing: true,
+            ..Mock::default()
+        }
+    }
+
+    fn check(&self) -> Result<(), ClipboardError> {
+        if self.failing {
+            Err(ClipboardError::Unsupported("the mock clipboard"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl ClipboardProvider for Mock {
+    fn set_text(&mut self, text: &str) -> Result<(), ClipboardError> {
+        self.check()?;
+        self.text = Some(text.to_string());
+        Ok(())
+    }
+
+    fn set_html(&mut self, html: &str, text: &str) -> Result<(), ClipboardError> {
+        self.check()?;
+        if self.plain {
+            return Err(ClipboardError::Unsupported("rich text"));
+        }
+        self.html = Some(html.to_string());
+        self.text = Some(text.to_string());
+        Ok(())
+    }
+
+    fn set_primary(&mut self, text: &str) -> Result<(), ClipboardError> {
+        self.check()?;
+        self.primary = Some(text.to_string());
+        Ok(())
+    }
+}
@@ -0,0 +1,571 @@
+use std::{
+    env,
+    error::Error,
+    io,
+    sync::atomic::{AtomicBool, AtomicU8, Ordering},
+};
+
+#[cfg(all(feature = "clipboard", target_os = "macos"))]
+mod macos;
+#[cfg(test)]
+mod mock;
+mod osc52;
+#[cfg(linux_clipboard)]
+mod wayland;
+#[cfg(all(feature = "clipboard", windows))]
+mod windows;
+#[cfg(all(feature = "clipboard", target_os = "linux"))]
+mod wsl;
+#[cfg(linux_clipboard)]
+mod x11;
+
+#[cfg(linux_clipboard)]
+pub use x11::hold;
+
+static BACKEND: AtomicU8 = AtomicU8::new(Backend::Auto as u8);
+static RICH: AtomicBool = AtomicBool::new(false);
+static SELECTION: AtomicU8 = AtomicU8::new(Selection::Clipboard as u8);
+
+// How to copy results
+//
+// By default, results are copied with OSC 52 over SSH or in tmux (see `use_osc52`), and
+// otherwise to the system clipboard, by whichever means works.  The others force one
+// backend, for when that guess is wrong (e.g., under XWayland, where both X11 and Wayland
+// are available, but only one is what the user pastes from), or disable copying entirely
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    Auto,
+    Wayland,
+    X11,
+    Osc52,
+    None,
+}
+
+// Which X11 selection to copy to: the clipboard (pasted with Ctrl-V), the primary
+// selection (pasted with a middle click), or both.  Only X11 and Wayland have a primary
+// selection, so elsewhere, only the clipboard is used
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Selection {
+    #[default]
+    Clipboard,
+    Primary,
+    Both,
+}
+
+impl Selection {
+    fn clipboard(self) -> bool {
+        matches!(self, Selection::Clipboard | Selection::Both)
+    }
+
+    fn primary(self) -> bool {
+        matches!(self, Selection::Primary | Selection::Both)
+    }
+}
+
+// What a clipboard holder (`rsl hold-clipboard`; see `x11::hand_off`) is to hold
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Held {
+    Text,
+    Primary,
+    Html,
+}
+
+// Reasons that results could not be copied
+#[derive(Debug, derive_more::Display)]
+pub enum ClipboardError {
+    #[display("could not write to the terminal: {_0}")]
+    Osc52(io::Error),
+    #[cfg(feature = "clipboard")]
+    #[display("{_0} clipboard: {_1}")]
+    System(&'static str, String),
+    #[cfg(linux_clipboard)]
+    #[display("primary selection: {_0}")]
+    Primary(String),
+    #[cfg(linux_clipboard)]
+    #[display("could not start clipboard holder: {_0}")]
+    Holder(io::Error),
+    // Either there is no such clipboard on this platform, or RSL was built without the
+    // `clipboard` feature
+    #[display("{_0} is not available on this system")]
+    Unsupported(&'static str),
+}
+
+impl Error for ClipboardError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ClipboardError::Osc52(e) => Some(e),
+            #[cfg(linux_clipboard)]
+            ClipboardError::Holder(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+// Trait for the ways of copying text
+//
+// Each platform's clipboard (and OSC 52, which asks the terminal to set its own) is a
+// provider; `provider` picks which to use.  Only plain text is required: a provider that
+// cannot take rich text, or has no primary selection, says so, and the caller decides
+// what to do instead.
+pub trait ClipboardProvider {
+    // Put `text` on the clipboard
+    fn set_text(&mut self, text: &str) -> Result<(), ClipboardError>;
+
+    // Put `html` on the clipboard, with `text` as its plain text alternative, for
+    // anything pasting plain text
+    fn set_html(&mut self, html: &str, text: &str) -> Result<(), ClipboardError> {
+        let _ = (html, text);
+        Err(ClipboardError::Unsupported("rich text"))
+    }
+
+    // Put `text` in the primary selection (see `Selection`)
+    fn set_primary(&mut self, text: &str) -> Result<(), ClipboardError> {
+        let _ = text;
+        Err(ClipboardError::Unsupported("the primary selection"))
+    }
+}
+
+impl ClipboardProvider for Box<dyn ClipboardProvider> {
+    fn set_text(&mut self, text: &str) -> Result<(), ClipboardError> {
+        (**self).set_text(text)
+    }
+
+    fn set_html(&mut self, html: &str, text: &str) -> Result<(), ClipboardError> {
+        (**self).set_html(html, text)
+    }
+
+    fn set_primary(&mut self, text: &str) -> Result<(), ClipboardError> {
+        (**self).set_primary(text)
+    }
+}
+
+// Providers tried in turn, until one works; on Linux, we cannot tell which clipboard
+// the user pastes from until we try (see `methods`)
+#[cfg(any(test, linux_clipboard))]
+struct Fallback<P>(Vec<P>);
+
+#[cfg(any(test, linux_clipboard))]
+impl<P: ClipboardProvider> Fallback<P> {
+    fn try_each(
+        &mut self,
+        mut f: impl FnMut(&mut P) -> Result<(), ClipboardError>,
+    ) -> Result<(), ClipboardError> {
+        let mut result = Err(ClipboardError::Unsupported("a clipboard"));
+        for provider in &mut self.0 {
+            result = f(provider);
+            match &result {
+                Ok(()) => break,
+                Err(e) => tracing::debug!(error = %e, "could not copy; trying next clipboard"),
+            }
+        }
+        result
+    }
+}
+
+#[cfg(any(test, linux_clipboard))]
+impl<P: ClipboardProvider> ClipboardProvider for Fallback<P> {
+    fn set_text(&mut self, text: &str) -> Result<(), ClipboardError> {
+        self.try_each(|provider| provider.set_text(text))
+    }
+
+    fn set_html(&mut self, html: &str, text: &str) -> Result<(), ClipboardError> {
+        self.try_each(|provider| provider.set_html(html, text))
+    }
+
+    fn set_primary(&mut self, text: &str) -> Result<(), ClipboardError> {
+        self.try_each(|provider| provider.set_primary(text))
+    }
+}
+
+// How to copy results (`--clipboard-backend`)
+pub fn set_backend(backend: Backend) {
+    BACKEND.store(backend as u8, Ordering::Relaxed);
+}
+
+fn backend() -> Backend {
+    match BACKEND.load(Ordering::Relaxed) {
+        0 => Backend::Auto,
+        1 => Backend::Wayland,
+        2 => Backend::X11,
+        3 => Backend::Osc52,
+        _ => Backend::None,
+    }
+}
+
+// Which selection to copy to (`--selection`)
+pub fn set_selection(selection: Selection) {
+    SELECTION.store(selection as u8, Ordering::Relaxed);
+}
+
+fn selection() -> Selection {
+    match SELECTION.load(Ordering::Relaxed) {
+        0 => Selection::Clipboard,
+        1 => Selection::Primary,
+        _ => Selection::Both,
+    }
+}
+
+// Whether to copy links as rich text (`--rich`), as well as plain text; see `copy_links`
+pub fn set_rich(rich: bool) {
+    RICH.store(rich, Ordering::Relaxed);
+}
+
+// Whether the `auto` backend should copy with an OSC 52 escape sequence rather than the
+// system clipboard
+//
+// Over SSH, the system clipboard (if there is one) is the remote machine's, which is of
+// no use to anyone, and in tmux, the terminal's clipboard may be on another machine
+// altogether.  OSC 52 asks the terminal itself to set its clipboard, wherever it is
+fn use_osc52() -> bool {
+    ["SSH_CLIENT", "SSH_TTY", "TMUX"]
+        .iter()
+        .any(|var| env::var_os(var).is_some())
+}
+
+// The provider for a backend, if results are to be copied at all
+fn provider(backend: Backend) -> Result<Option<Box<dyn ClipboardProvider>>, ClipboardError> {
+    let provider: Box<dyn ClipboardProvider> = match backend {
+        Backend::None => return Ok(None),
+        Backend::Osc52 => Box::new(osc52::Osc52::new()),
+        Backend::Auto if use_osc52() => Box::new(osc52::Osc52::new()),
+        Backend::Auto => match system() {
+            Some(system) => system,
+            None => return Ok(None),
+        },
+        Backend::Wayland => wayland()?,
+        Backend::X11 => x11()?,
+    };
+    Ok(Some(provider))
+}
+
+#[cfg(all(feature = "clipboard", target_os = "macos"))]
+fn system() -> Option<Box<dyn ClipboardProvider>> {
+    Some(Box::new(macos::MacOs))
+}
+
+#[cfg(all(feature = "clipboard", windows))]
+fn system() -> Option<Box<dyn ClipboardProvider>> {
+    Some(Box::new(windows::Windows))
+}
+
+#[cfg(linux_clipboard)]
+fn system() -> Option<Box<dyn ClipboardProvider>> {
+    system_for(methods(|var| env::var_os(var).is_some()))
+}
+
+// Without a display (e.g., on a headless server, or over SSH without tmux), there is no
+// clipboard to copy to, so results are only printed, as without the `clipboard` feature
+#[cfg(linux_clipboard)]
+fn system_for(methods: Vec<Method>) -> Option<Box<dyn ClipboardProvider>> {
+    if methods.is_empty() {
+        return None;
+    }
+    let providers = methods
+        .into_iter()
+        .map(|method| -> Box<dyn ClipboardProvider> {
+            match method {
+                #[cfg(target_os = "linux")]
+                Method::Wsl => Box::new(wsl::Wsl),
+                Method::Wayland => Box::new(wayland::Wayland),
+                Method::X11 => Box::new(x11::X11),
+            }
+        })
+        .collect();
+    Some(Box::new(Fallback(providers)))
+}
+
+// Without the `clipboard` feature (e.g., on a server without X11), results are only
+// printed, unless copied with OSC 52, or a system clipboard was asked for explicitly
+#[cfg(not(any(
+    all(feature = "clipboard", any(target_os = "macos", windows)),
+    linux_clipboard
+)))]
+fn system() -> Option<Box<dyn ClipboardProvider>> {
+    None
+}
+
+#[cfg(linux_clipboard)]
+fn wayland() -> Result<Box<dyn ClipboardProvider>, ClipboardError> {
+    Ok(Box::new(wayland::Wayland))
+}
+
+#[cfg(linux_clipboard)]
+fn x11() -> Result<Box<dyn ClipboardProvider>, ClipboardError> {
+    Ok(Box::new(x11::X11))
+}
+
+#[cfg(not(linux_clipboard))]
+fn wayland() -> Result<Box<dyn ClipboardProvider>, ClipboardError> {
+    Err(ClipboardError::Unsupported("the Wayland clipboard"))
+}
+
+#[cfg(not(linux_clipboard))]
+fn x11() -> Result<Box<dyn ClipboardProvider>, ClipboardError> {
+    Err(ClipboardError::Unsupported("the X11 clipboard"))
+}
+
+#[cfg(not(linux_clipboard))]
+pub fn hold(_held: Held) -> Result<(), Box<dyn Error>> {
+    Err(ClipboardError::Unsupported("the primary selection").into())
+}
+
+// Ways to put text on the system clipboard on Linux (and the BSDs, which have no WSL);
+// see `methods`
+#[cfg(linux_clipboard)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Method {
+    #[cfg(target_os = "linux")]
+    Wsl,
+    Wayland,
+    X11,
+}
+
+// The clipboards to try, in order, given which environment variables are set
+//
+// X11 and Wayland may both be available (under XWayland), and we cannot tell which the
+// user pastes from, so we try Wayland first, as XWayland passes its clipboard on to X11
+// programs anyway.  Under WSL, the Windows clipboard is the one that matters, even with
+// an X11 or Wayland display available (e.g., with WSLg)
+#[cfg(linux_clipboard)]
+fn methods(is_set: impl Fn(&str) -> bool) -> Vec<Method> {
+    #[cfg(target_os = "linux")]
+    if is_set("WSL_DISTRO_NAME") {
+        return vec![Method::Wsl];
+    }
+    let mut methods = Vec::new();
+    if is_set("WAYLAND_DISPLAY") {
+        methods.push(Method::Wayland);
+    }
+    if is_set("DISPLAY") {
+        methods.push(Method::X11);
+    }
+    methods
+}
+
+pub fn copy(s: &str) -> Result<(), ClipboardError> {
+    match provider(backend())? {
+        Some(mut provider) => copy_text(&mut provider, s, selection()),
+        None => Ok(()),
+    }
+}
+
+fn copy_text(
+    provider: &mut impl ClipboardProvider,
+    s: &str,
+    selection: Selection,
+) -> Result<(), ClipboardError> {
+    if selection.clipboard() {
+        provider.set_text(s)?;
+    }
+    if selection.primary() {
+        provider.set_primary(s)?;
+    }
+    Ok(())
+}
+
+// Copy links, one per line, along with the titles of their pages, if known
+//
+// With `--rich`, the links are also copied as HTML, so that pasting them somewhere that
+// understands it (e.g., a document or chat) gives a link titled with its page's title,
+// rather than a bare URL.  Anything pasting plain text gets the URLs as usual
+pub fn copy_links<'a>(
+    links: impl IntoIterator<Item = (&'a str, Option<&'a str>)>,
+) -> Result<(), ClipboardError> {
+    let links: Vec<_> = links.into_iter().collect();
+    match provider(backend())? {
+        Some(mut provider) => copy_links_with(
+            &mut provider,
+            &links,
+            selection(),
+            RICH.load(Ordering::Relaxed),
+        ),
+        None => Ok(()),
+    }
+}
+
+fn copy_links_with(
+    provider: &mut impl ClipboardProvider,
+    links: &[(&str, Option<&str>)],
+    selection: Selection,
+    rich: bool,
+) -> Result<(), ClipboardError> {
+    let text = links
+        .iter()
+        .map(|(url, _)| *url)
+        .collect::<Vec<_>>()
+        .join("\n");
+    if !rich {
+        return copy_text(provider, &text, selection);
+    }
+    let html = links
+        .iter()
+        .map(|(url, title)| anchor(url, *title))
+        .collect::<Vec<_>>()
+        .join("<br>\n");
+    if selection.clipboard() {
+        tracing::debug!("copying as rich text");
+        if let Err(e) = provider.set_html(&html, &text) {
+            tracing::debug!(error = %e, "could not copy rich text; copying plain text instead");
+            provider.set_text(&text)?;
+        }
+    }
+    // Middle-click pastes are almost always into a terminal or text field, so the
+    // primary selection only gets the text
+    if selection.primary() {
+        provider.set_primary(&text)?;
+    }
+    Ok(())
+}
+
+// An HTML link to `url`, with its title as the text, or the URL itself if there is none
+fn anchor(url: &str, title: Option<&str>) -> String {
+    format!(
+        "<a href=\"{}\">{}</a>",
+        escape_html(url),
+        escape_html(title.unwrap_or(url))
+    )
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mock::Mock, *};
+
+    #[cfg(linux_clipboard)]
+    #[test]
+    fn test_methods() {
+        fn env(vars: &'static [&'static str]) -> impl Fn(&str) -> bool {
+            move |var| vars.iter().any(|v| *v == var)
+        }
+
+        assert_eq!(methods(env(&["DISPLAY"])), [Method::X11]);
+        assert_eq!(
+            methods(env(&["WAYLAND_DISPLAY", "DISPLAY"])),
+            [Method::Wayland, Method::X11]
+        );
+        #[cfg(target_os = "linux")]
+        assert_eq!(
+            methods(env(&["WSL_DISTRO_NAME", "WAYLAND_DISPLAY", "DISPLAY"])),
+            [Method::Wsl]
+        );
+        assert!(methods(env(&[])).is_empty());
+    }
+
+    #[cfg(linux_clipboard)]
+    #[test]
+    fn test_system_without_display() {
+        assert!(system_for(Vec::new()).is_none());
+        assert!(system_for(vec![Method::X11]).is_some());
+    }
+
+    #[test]
+    fn test_fallback() {
+        let mut clipboards = Fallback(vec![Mock::failing(), Mock::default(), Mock::default()]);
+        clipboards.set_text("https://example.com/").expect("copied");
+        assert_eq!(clipboards.0[0].text, None);
+        assert_eq!(
+            clipboards.0[1].text.as_deref(),
+            Some("https://example.com/")
+        );
+        assert_eq!(clipboards.0[2].text, None);
+
+        let mut clipboards = Fallback(vec![Mock::failing(), Mock::failing()]);
+        assert!(clipboards.set_text("https://example.com/").is_err());
+        assert!(
+            Fallback::<Mock>(Vec::new())
+                .set_text("https://example.com/")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_copy_text() {
+        let mut clipboard = Mock::default();
+        copy_text(&mut clipboard, "https://example.com/", Selection::Primary).expect("copied");
+        assert_eq!(clipboard.text, None);
+        assert_eq!(clipboard.primary.as_deref(), Some("https://example.com/"));
+
+        let mut clipboard = Mock::default();
+        copy_text(&mut clipboard, "https://example.com/", Selection::Both).expect("copied");
+        assert_eq!(clipboard.text.as_deref(), Some("https://example.com/"));
+        assert_eq!(clipboard.primary.as_deref(), Some("https://example.com/"));
+
+        assert!(
+            copy_text(
+                &mut Mock::failing(),
+                "https://example.com/",
+                Selection::Clipboard
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_copy_links() {
+        let links = [
+            ("https://example.com/a", Some("A & B")),
+            ("https://example.com/b", None),
+        ];
+        let mut clipboard = Mock::default();
+        copy_links_with(&mut clipboard, &links, Selection::Both, true).expect("copied");
+        assert_eq!(
+            clipboard.html.as_deref(),
+            Some(
+                "<a href=\"https://example.com/a\">A &amp; B</a><br>\n<a href=\"https://example.com/b\">https://example.com/b</a>"
+            )
+        );
+        assert_eq!(
+            clipboard.text.as_deref(),
+            Some("https://example.com/a\nhttps://example.com/b")
+        );
+        assert_eq!(
+            clipboard.primary.as_deref(),
+            Some("https://example.com/a\nhttps://example.com/b")
+        );
+
+        // Without rich text, only the text is copied
+        let mut clipboard = Mock::default();
+        copy_links_with(&mut clipboard, &links, Selection::Clipboard, false).expect("copied");
+        assert_eq!(clipboard.html, None);
+        assert_eq!(
+            clipboard.text.as_deref(),
+            Some("https://example.com/a\nhttps://example.com/b")
+        );
+    }
+
+    #[test]
+    fn test_copy_links_plain_only() {
+        // A clipboard that cannot take rich text gets the text instead
+        let mut clipboard = Mock::plain();
+        copy_links_with(
+            &mut clipboard,
+            &[("https://example.com/", Some("Example"))],
+            Selection::Clipboard,
+            true,
+        )
+        .expect("copied");
+        assert_eq!(clipboard.html, None);
+        assert_eq!(clipboard.text.as_deref(), Some("https://example.com/"));
+    }
+
+    #[test]
+    fn test_anchor() {
+        assert_eq!(
+            anchor("https://example.com/?a=1&b=2", Some("Fish & <Chips>")),
+            "<a href=\"https://example.com/?a=1&amp;b=2\">Fish &amp; &lt;Chips&gt;</a>"
+        );
+        assert_eq!(
+            anchor("https://example.com/", None),
+            "<a href=\"https://example.com/\">https://example.com/</a>"
+        );
+    }
+}
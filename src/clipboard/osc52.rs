@@ -0,0 +1,85 @@
+use std::{
+    env,
+    fs::OpenOptions,
+    io::{self, Write},
+};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+
+use super::{ClipboardError, ClipboardProvider, Selection};
+
+// The terminal's clipboard, set with an OSC 52 escape sequence (see `use_osc52`)
+//
+// As this needs no system clipboard, it works even when RSL is built without the
+// `clipboard` feature.
+pub(super) struct Osc52 {
+    tmux: bool,
+}
+
+impl Osc52 {
+    pub(super) fn new() -> Self {
+        Osc52 {
+            tmux: env::var_os("TMUX").is_some(),
+        }
+    }
+
+    // Write the sequence for `s` to the terminal.  Standard output may well be piped
+    // elsewhere, so we write to the terminal directly if we can, and standard error if not
+    fn write(&self, s: &str, selection: Selection) -> Result<(), ClipboardError> {
+        tracing::debug!("copying with OSC 52");
+        let sequence = sequence(s, selection, self.tmux);
+        match OpenOptions::new().write(true).open("/dev/tty") {
+            Ok(mut tty) => tty.write_all(sequence.as_bytes()),
+            Err(_) => io::stderr().write_all(sequence.as_bytes()),
+        }
+        .map_err(ClipboardError::Osc52)
+    }
+}
+
+impl ClipboardProvider for Osc52 {
+    fn set_text(&mut self, text: &str) -> Result<(), ClipboardError> {
+        self.write(text, Selection::Clipboard)
+    }
+
+    fn set_primary(&mut self, text: &str) -> Result<(), ClipboardError> {
+        self.write(text, Selection::Primary)
+    }
+}
+
+// The escape sequence asking the terminal to put `s` on its clipboard, primary selection,
+// or both.  Within tmux, this must be wrapped so that tmux passes it on to the terminal
+// rather than swallowing it
+fn sequence(s: &str, selection: Selection, tmux: bool) -> String {
+    let targets = match selection {
+        Selection::Clipboard => "c",
+        Selection::Primary => "p",
+        Selection::Both => "pc",
+    };
+    let osc52 = format!("\x1b]52;{};{}\x07", targets, STANDARD.encode(s));
+    if tmux {
+        format!("\x1bPtmux;\x1b{}\x1b\\", osc52)
+    } else {
+        osc52
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequence() {
+        assert_eq!(
+            sequence("hi", Selection::Clipboard, false),
+            "\x1b]52;c;aGk=\x07"
+        );
+        assert_eq!(
+            sequence("hi", Selection::Both, false),
+            "\x1b]52;pc;aGk=\x07"
+        );
+        assert_eq!(
+            sequence("hi", Selection::Clipboard, true),
+            "\x1bPtmux;\x1b\x1b]52;c;aGk=\x07\x1b\\"
+        );
+    }
+}
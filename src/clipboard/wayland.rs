@@ -0,0 +1,52 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use super::{ClipboardError, ClipboardProvider};
+
+// The Wayland clipboard and primary selection, set with `wl-copy`
+//
+// As on X11, a Wayland selection belongs to the program that set it, but `wl-copy` forks
+// to keep hold of it until something else is copied, so it outlives us.  It can only
+// offer one type at a time, so cannot give rich text a plain text alternative; rich text
+// is left to XWayland, if there is one (see `methods`).
+pub(super) struct Wayland;
+
+impl Wayland {
+    fn wl_copy(&self, text: &str, primary: bool) -> Result<(), String> {
+        let mut command = Command::new("wl-copy");
+        if primary {
+            command.arg("--primary");
+        }
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("could not run wl-copy: {}", e))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(text.as_bytes())
+                .map_err(|e| e.to_string())?;
+        }
+        // `wl-copy` exits once it has forked, so this does not wait for something else
+        // to be copied
+        let status = child.wait().map_err(|e| e.to_string())?;
+        if !status.success() {
+            return Err(format!("wl-copy exited with {}", status));
+        }
+        Ok(())
+    }
+}
+
+impl ClipboardProvider for Wayland {
+    fn set_text(&mut self, text: &str) -> Result<(), ClipboardError> {
+        self.wl_copy(text, false)
+            .map_err(|e| ClipboardError::System("Wayland", e))
+    }
+
+    fn set_primary(&mut self, text: &str) -> Result<(), ClipboardError> {
+        self.wl_copy(text, true).map_err(ClipboardError::Primary)
+    }
+}
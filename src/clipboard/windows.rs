@@ -0,0 +1,18 @@
+use super::{ClipboardError, ClipboardProvider};
+
+// The Windows clipboard
+pub(super) struct Windows;
+
+impl ClipboardProvider for Windows {
+    fn set_text(&mut self, text: &str) -> Result<(), ClipboardError> {
+        arboard::Clipboard::new()
+            .and_then(|mut ctx| ctx.set_text(text))
+            .map_err(|e| ClipboardError::System("Windows", e.to_string()))
+    }
+
+    fn set_html(&mut self, html: &str, text: &str) -> Result<(), ClipboardError> {
+        arboard::Clipboard::new()
+            .and_then(|mut ctx| ctx.set_html(html, Some(text)))
+            .map_err(|e| ClipboardError::System("Windows", e.to_string()))
+    }
+}
@@ -0,0 +1,12 @@
+use super::{ClipboardError, ClipboardProvider};
+
+// The Windows clipboard, from within WSL, where `clipboard_anywhere` copies to it by way
+// of Windows' own tools
+pub(super) struct Wsl;
+
+impl ClipboardProvider for Wsl {
+    fn set_text(&mut self, text: &str) -> Result<(), ClipboardError> {
+        clipboard_anywhere::set_clipboard(text)
+            .map_err(|e| ClipboardError::System("Windows", e.to_string()))
+    }
+}
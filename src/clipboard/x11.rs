@@ -0,0 +1,120 @@
+use std::{
+    env,
+    error::Error,
+    io::{self, BufRead, BufReader, Read, Write},
+    os::unix::process::CommandExt,
+    process::{Command, Stdio},
+};
+
+use arboard::{LinuxClipboardKind, SetExtLinux};
+
+use super::{ClipboardError, ClipboardProvider, Held};
+
+// The X11 clipboard and primary selection
+//
+// An X11 selection belongs to the program that set it, so is emptied as soon as that
+// program exits, unless a clipboard manager takes it over first.  So whatever we copy is
+// handed off to a holder (see `hand_off`), which keeps it until something else is copied.
+pub(super) struct X11;
+
+impl ClipboardProvider for X11 {
+    fn set_text(&mut self, text: &str) -> Result<(), ClipboardError> {
+        hand_off(Held::Text, text)
+    }
+
+    fn set_html(&mut self, html: &str, text: &str) -> Result<(), ClipboardError> {
+        hand_off(Held::Html, &format!("{}\0{}", html, text))
+    }
+
+    fn set_primary(&mut self, text: &str) -> Result<(), ClipboardError> {
+        hand_off(Held::Primary, text)
+    }
+}
+
+// Hand `contents` off to a holder: another RSL process, running `rsl hold-clipboard`,
+// which takes ownership of the selection and keeps it until something else is copied
+//
+// `arboard` sets the selection from within the process that calls it, so what it copies
+// would be lost as soon as we exit.  Forking would be simpler, but is unsafe with the
+// async runtime's threads running, so we start a fresh process, in its own process group
+// so that it outlives the terminal's foreground job.  It tells us whether it managed to
+// take the selection before it settles down to wait
+fn hand_off(held: Held, contents: &str) -> Result<(), ClipboardError> {
+    let error = |e: &str| match held {
+        Held::Primary => ClipboardError::Primary(e.to_string()),
+        Held::Text | Held::Html => ClipboardError::System("X11", e.to_string()),
+    };
+    let name = match held {
+        Held::Text => "text",
+        Held::Primary => "primary",
+        Held::Html => "html",
+    };
+    let mut child = Command::new(env::current_exe().map_err(ClipboardError::Holder)?)
+        .args(["hold-clipboard", name])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .process_group(0)
+        .spawn()
+        .map_err(ClipboardError::Holder)?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(contents.as_bytes())
+            .map_err(ClipboardError::Holder)?;
+    }
+    let mut status = String::new();
+    if let Some(stdout) = child.stdout.take() {
+        BufReader::new(stdout)
+            .read_line(&mut status)
+            .map_err(ClipboardError::Holder)?;
+    }
+    // Reap the holder once something else is copied, so that it does not linger (e.g.,
+    // in the REPL, which copies many times over)
+    std::thread::spawn(move || child.wait());
+
+    match status.trim_end() {
+        "ok" => Ok(()),
+        "" => Err(error("clipboard holder exited unexpectedly")),
+        e => Err(error(e)),
+    }
+}
+
+// Hold the selection as a clipboard holder (see `hand_off`), reading what to hold from
+// standard input: the text, or for `Html`, the HTML and its plain text alternative,
+// separated by a NUL
+pub fn hold(held: Held) -> Result<(), Box<dyn Error>> {
+    let mut contents = String::new();
+    io::stdin().read_to_string(&mut contents)?;
+    let (kind, html, text) = match held {
+        Held::Text => (LinuxClipboardKind::Clipboard, None, contents.as_str()),
+        Held::Primary => (LinuxClipboardKind::Primary, None, contents.as_str()),
+        Held::Html => match contents.split_once('\0') {
+            Some((html, text)) => (LinuxClipboardKind::Clipboard, Some(html), text),
+            None => (LinuxClipboardKind::Clipboard, None, contents.as_str()),
+        },
+    };
+    let set = |ctx: &mut arboard::Clipboard, wait: bool| {
+        let set = ctx.set().clipboard(kind);
+        let set = if wait { set.wait() } else { set };
+        match html {
+            Some(html) => set.html(html, Some(text)),
+            None => set.text(text),
+        }
+    };
+
+    let mut stdout = io::stdout();
+    let mut ctx =
+        match arboard::Clipboard::new().and_then(|mut ctx| set(&mut ctx, false).map(|()| ctx)) {
+            Ok(ctx) => ctx,
+            Err(e) => {
+                writeln!(stdout, "{}", e)?;
+                return Err(e.into());
+            }
+        };
+    writeln!(stdout, "ok")?;
+    stdout.flush()?;
+
+    // Keep hold of the selection until something else takes it over
+    set(&mut ctx, true)?;
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use url::Url;
+
+use crate::{
+    clean,
+    exit::ExitCode,
+    output,
+    process::{self, ProcessOptions},
+    report,
+    resolve::Resolver,
+};
+
+// Resolve and clean two URLs, reporting whether they point at the same canonical
+// resource
+//
+// Both canonical URLs are printed to standard output.  Exits with 0 if they are
+// equivalent, or 2 if they differ.
+pub async fn run(resolver: &Resolver, a: &str, b: &str, options: &ProcessOptions) -> ExitCode {
+    let (a_result, b_result) = tokio::join!(
+        process::process(resolver, a, options),
+        process::process(resolver, b, options),
+    );
+    report::report(&a_result);
+    report::report(&b_result);
+
+    let (a_processed, b_processed) = match (a_result, b_result) {
+        (Ok(a_processed), Ok(b_processed)) => (a_processed, b_processed),
+        (a_result, b_result) => {
+            // Report errors in reverse, so that the exit code reflects the first URL's
+            // error if both failed
+            let mut code = ExitCode::Failure;
+            for (url, result) in [(b, b_result), (a, a_result)] {
+                if let Err(e) = result {
+                    eprintln!("Error: {}: {}", url, e);
+                    code = ExitCode::from(&e);
+                }
+            }
+            return code;
+        }
+    };
+
+    println!("{}", a_processed.cleaned);
+    println!("{}", b_processed.cleaned);
+
+    if equivalent(&a_processed.cleaned, &b_processed.cleaned) {
+        output::info("Same resource");
+        ExitCode::Success
+    } else {
+        output::info("Different resources");
+        ExitCode::Changed
+    }
+}
+
+// Whether two cleaned URLs refer to the same resource
+pub fn equivalent(a: &str, b: &str) -> bool {
+    canonical_key(a) == canonical_key(b)
+}
+
+// A key identifying the resource a cleaned URL points at
+//
+// Cleaned URLs are already fairly canonical (and normalised; see `clean::normalize`),
+// but we additionally ignore differences that do not change the resource: a trailing
+// slash on the path, the order of query parameters, and the fragment.
+pub fn canonical_key(url: &str) -> String {
+    let Ok(mut url) = Url::parse(url) else {
+        return url.to_string();
+    };
+    clean::normalize(&mut url);
+
+    let path = url.path().trim_end_matches('/').to_string();
+    url.set_path(&path);
+
+    let mut pairs: Vec<_> = url
+        .query_pairs()
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+    pairs.sort();
+    url.set_query(None);
+    if !pairs.is_empty() {
+        url.query_pairs_mut().extend_pairs(pairs);
+    }
+
+    url.set_fragment(None);
+    url.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_equivalent() {
+        assert!(equivalent(
+            "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m",
+            "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m",
+        ));
+        assert!(!equivalent(
+            "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m",
+            "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7n",
+        ));
+    }
+
+    #[test]
+    fn test_equivalent_ignores_insignificant_differences() {
+        assert!(equivalent(
+            "https://www.facebook.com/permalink.php?story_fbid=1&id=2",
+            "https://WWW.facebook.com/permalink.php/?id=2&story_fbid=1#top",
+        ));
+    }
+
+    #[test]
+    fn test_equivalent_normalized() {
+        assert!(equivalent(
+            "https://example.com/%7Euser/caf%c3%a9?",
+            "HTTPS://example.com:443/~user/caf%C3%A9",
+        ));
+    }
+}
@@ -0,0 +1,494 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::Deserialize;
+
+use crate::clean::{
+    CleanerRegistry, CleaningRule, ClearUrlsCleaner, ClearUrlsError, FilterListError,
+    FragmentPolicy, HostDisplay, RemoveParamFilters, RuleCleaner, WaybackPolicy,
+};
+use crate::clipboard::{Backend as ClipboardBackend, Selection};
+use crate::resolve::{DomainPolicy, RequestStrategy, ResolveCache};
+
+const CONFIG_FILE_NAME: &str = "config.toml";
+#[cfg(feature = "plugins")]
+const PLUGINS_DIR_NAME: &str = "plugins";
+const RULES_DIR_NAME: &str = "rules.d";
+
+// Error type for loading the configuration file
+#[derive(Debug, derive_more::Display)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    ClearUrls(ClearUrlsError),
+    FilterList(FilterListError),
+    #[cfg(feature = "plugins")]
+    Plugin(crate::clean::PluginError),
+    #[display("unknown cleaner {_0:?}")]
+    UnknownCleaner(String),
+    #[display("invalid rule file {}: {_1}", _0.display())]
+    RuleFile(PathBuf, Box<dyn Error + Send + Sync>),
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::Io(e) => Some(e),
+            ConfigError::Parse(e) => Some(e),
+            ConfigError::ClearUrls(e) => Some(e),
+            ConfigError::FilterList(e) => Some(e),
+            #[cfg(feature = "plugins")]
+            ConfigError::Plugin(e) => Some(e),
+            ConfigError::UnknownCleaner(_) => None,
+            ConfigError::RuleFile(_, e) => Some(e.as_ref()),
+        }
+    }
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(e: io::Error) -> Self {
+        ConfigError::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(e: toml::de::Error) -> Self {
+        ConfigError::Parse(e)
+    }
+}
+
+// User configuration, read from `config.toml` in the platform's configuration directory
+// (e.g., `~/.config/rsl/config.toml` on Linux).  Options given on the command line are
+// combined with those given here
+//
+// Example:
+//
+//     keep_params = ["context"]
+//     strip_params = ["comment_id"]
+//     fragments = "keep"
+//
+//     [cleaners]
+//     "old.reddit.com" = "reddit"
+//
+//     [rules."example.com"]
+//     strip = ["ref"]
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // Query parameters to keep, even if they would otherwise be removed
+    pub keep_params: Vec<String>,
+    // Additional query parameters to remove, even if a cleaner would preserve them
+    pub strip_params: Vec<String>,
+    // Whether `#` fragments survive cleaning
+    pub fragments: Option<FragmentPolicy>,
+    // Whether to show internationalised hosts as punycode or Unicode
+    pub hosts: Option<HostDisplay>,
+    // Domains whose links need their session identifiers (e.g., `PHPSESSID`), which are
+    // otherwise removed
+    pub keep_session_ids: Vec<String>,
+    // Remove a trailing index page (e.g., `index.html`) from cleaned URLs
+    pub drop_index: bool,
+    // Whether to link to the Wayback Machine snapshot of a link to one, or the original
+    pub wayback: Option<WaybackPolicy>,
+    // Remove locale prefixes (e.g., `/en-us/`) from paths on sites known to serve the
+    // same content across locales
+    pub strip_locale: bool,
+    // Google Safe Browsing API key, used by `--safety-check`
+    pub safe_browsing_key: Option<String>,
+    // How to copy results: `auto`, `wayland`, `x11`, `osc52`, or `none`
+    pub clipboard_backend: Option<ClipboardBackend>,
+    // Which selection to copy results to: the clipboard, the X11 primary selection, or
+    // both
+    pub selection: Option<Selection>,
+    // Additional domains to support, or built-in domains to override, mapped to the name
+    // of the built-in cleaner to use for them
+    pub cleaners: BTreeMap<String, String>,
+    // Declarative cleaning rules for additional domains, which take precedence over
+    // `cleaners`
+    pub rules: BTreeMap<String, CleaningRule>,
+    // Directory of further cleaning rules, one file per domain (by default, `rules.d` in
+    // the configuration directory).  Rules in `rules` take precedence over these
+    pub rules_dir: Option<PathBuf>,
+    // Path to a ClearURLs ruleset (`data.json`), used to clean hosts without a dedicated
+    // cleaner
+    pub clearurls: Option<PathBuf>,
+    // Paths to AdGuard filter lists, whose `$removeparam` rules are applied on every host
+    pub filter_lists: Vec<PathBuf>,
+    // Directory from which to load WebAssembly cleaner plugins (by default, `plugins` in
+    // the configuration directory).  Ignored unless built with the `plugins` feature
+    pub plugins_dir: Option<PathBuf>,
+    // Domains (and their subdomains) whose links are resolved in a headless browser when
+    // `--browser` is given.  Ignored unless built with the `browser` feature
+    pub browser_domains: Vec<String>,
+    // How to request each URL while following redirects (`get`, `head`, or `range`)
+    pub request_strategy: Option<RequestStrategy>,
+    // Request strategies for particular domains (and their subdomains), overriding
+    // `request_strategy`
+    pub request_strategies: BTreeMap<String, RequestStrategy>,
+    // How to resolve URLs on particular domains (and their subdomains), where they need
+    // different handling from the rest
+    pub domains: BTreeMap<String, DomainPolicy>,
+    // Languages to ask sites for, as an `Accept-Language` header (by default, `en`)
+    pub accept_language: Option<String>,
+    // How long to cache each resolution for, in seconds (by default, a day)
+    pub cache_ttl: Option<u64>,
+    // The longest to wait when a server asks us to retry later, in seconds (by default,
+    // a minute)
+    pub max_retry_after: Option<u64>,
+    // How many times in a row a host may fail (or rate-limit us) before we stop resolving
+    // its URLs for a while (by default, 5; 0 never stops)
+    pub host_failures: Option<u32>,
+    // How long to stop resolving a failing host's URLs for, in seconds (by default, a
+    // minute)
+    pub host_cooldown: Option<u64>,
+    // Whether to refuse to follow redirects off the site of the URL being resolved
+    pub same_site: bool,
+    // Whether to allow resolution to request URLs on private networks, or unusual ports
+    pub allow_private_networks: bool,
+    // Proxy to send requests through (`http://`, `https://`, or `socks5://`), rather than
+    // those given by the environment
+    pub proxy: Option<String>,
+    // Whether to also keep the cache on disk, so that it survives between runs
+    pub disk_cache: bool,
+    // File in which to keep cookies between links and runs (by default, they are
+    // forgotten once each link is resolved)
+    pub cookie_jar: Option<PathBuf>,
+}
+
+impl Config {
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(clap::crate_name!()).join(CONFIG_FILE_NAME))
+    }
+
+    // Load configuration from the given path, or the default path if none is given.  It
+    // is not an error for the default configuration file not to exist
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        match path {
+            Some(path) => Self::from_file(path),
+            None => match Self::default_path() {
+                Some(path) if path.exists() => Self::from_file(&path),
+                _ => Ok(Config::default()),
+            },
+        }
+    }
+
+    // The built-in cleaners, with any added or overridden by plugins, by the `cleaners`
+    // table, or by rules (from `rules_dir`, then the `rules` table)
+    pub fn registry(&self) -> Result<CleanerRegistry, ConfigError> {
+        let mut registry = CleanerRegistry::default();
+        #[cfg(feature = "plugins")]
+        {
+            let dir = self
+                .plugins_dir
+                .clone()
+                .or_else(|| Self::default_path().map(|path| path.with_file_name(PLUGINS_DIR_NAME)));
+            if let Some(dir) = dir {
+                let plugins = crate::clean::load_plugins(&dir).map_err(ConfigError::Plugin)?;
+                for (cleaner, domains) in plugins {
+                    for domain in domains {
+                        registry.register(domain, cleaner.clone());
+                    }
+                }
+            }
+        }
+        for (domain, cleaner) in &self.cleaners {
+            if !registry.register_builtin(domain.as_str(), cleaner) {
+                return Err(ConfigError::UnknownCleaner(cleaner.clone()));
+            }
+        }
+        let rules_dir = self
+            .rules_dir
+            .clone()
+            .or_else(|| Self::default_path().map(|path| path.with_file_name(RULES_DIR_NAME)));
+        if let Some(dir) = rules_dir {
+            for (domain, rule) in load_rules_dir(&dir)? {
+                registry.register(domain, RuleCleaner::new(rule));
+            }
+        }
+        for (domain, rule) in &self.rules {
+            registry.register(domain.as_str(), RuleCleaner::new(rule.clone()));
+        }
+        if let Some(path) = &self.clearurls {
+            let rules = ClearUrlsCleaner::from_file(path).map_err(ConfigError::ClearUrls)?;
+            registry.set_fallback(rules);
+        }
+        Ok(registry)
+    }
+
+    // The `$removeparam` rules from every filter list in `filter_lists`
+    pub fn filters(&self) -> Result<RemoveParamFilters, ConfigError> {
+        let mut filters = RemoveParamFilters::default();
+        for path in &self.filter_lists {
+            filters.extend(RemoveParamFilters::from_file(path).map_err(ConfigError::FilterList)?);
+        }
+        Ok(filters)
+    }
+
+    // The resolution cache described by `cache_ttl` and `disk_cache`.  If the disk cache
+    // could not be opened, we warn and carry on with only the in-memory cache
+    pub fn cache(&self) -> ResolveCache {
+        let memory = || match self.cache_ttl {
+            Some(ttl) => ResolveCache::new().ttl(Duration::from_secs(ttl)),
+            None => ResolveCache::new(),
+        };
+        if !self.disk_cache {
+            return memory();
+        }
+        let Some(path) = ResolveCache::default_path() else {
+            tracing::warn!("could not find a cache directory");
+            return memory();
+        };
+        memory().persist(&path).unwrap_or_else(|e| {
+            tracing::warn!(error = %e, "could not open disk cache");
+            memory()
+        })
+    }
+
+    fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    fn parse(s: &str) -> Result<Self, ConfigError> {
+        Ok(toml::from_str(s)?)
+    }
+}
+
+// Load the cleaning rule in each file in `dir`, named for the domain it applies to, in
+// order of file name, e.g.:
+//
+//     ~/.config/rsl/rules.d/example.com.toml
+//     ~/.config/rsl/rules.d/example.org.json
+//
+// Each holds a single rule, as would be given in the `rules` table.  This way, rules for
+// a site can be shared (e.g., across an organisation) by copying a file, without
+// editing anyone's configuration.  It is not an error for the directory not to exist
+fn load_rules_dir(dir: &Path) -> Result<Vec<(String, CleaningRule)>, ConfigError> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|ext| ext == "toml" || ext == "json")
+        {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    let mut rules = Vec::new();
+    for path in paths {
+        let Some(domain) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        let contents = fs::read_to_string(&path)?;
+        let rule: Result<CleaningRule, Box<dyn Error + Send + Sync>> =
+            if path.extension().is_some_and(|ext| ext == "json") {
+                serde_json::from_str(&contents).map_err(Into::into)
+            } else {
+                toml::from_str(&contents).map_err(Into::into)
+            };
+        let rule = rule.map_err(|e| ConfigError::RuleFile(path.clone(), e))?;
+        rules.push((domain.to_string(), rule));
+    }
+    Ok(rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty() {
+        let config = Config::parse("").expect("parsed");
+        assert!(config.keep_params.is_empty());
+        assert!(config.strip_params.is_empty());
+    }
+
+    #[test]
+    fn test_params() {
+        let config = Config::parse(
+            r#"
+            keep_params = ["context"]
+            strip_params = ["comment_id", "id"]
+            fragments = "strip"
+            hosts = "unicode"
+            keep_session_ids = ["intranet.example.com"]
+            drop_index = true
+            wayback = "extract"
+            strip_locale = true
+            selection = "both"
+            clipboard_backend = "x11"
+            "#,
+        )
+        .expect("parsed");
+        assert_eq!(config.keep_params, ["context"]);
+        assert_eq!(config.strip_params, ["comment_id", "id"]);
+        assert_eq!(config.fragments, Some(FragmentPolicy::Strip));
+        assert_eq!(config.hosts, Some(HostDisplay::Unicode));
+        assert_eq!(config.keep_session_ids, ["intranet.example.com"]);
+        assert!(config.drop_index);
+        assert_eq!(config.wayback, Some(WaybackPolicy::Extract));
+        assert!(config.strip_locale);
+        assert_eq!(config.selection, Some(Selection::Both));
+        assert_eq!(config.clipboard_backend, Some(ClipboardBackend::X11));
+    }
+
+    #[test]
+    fn test_request_strategies() {
+        let config = Config::parse(
+            r#"
+            request_strategy = "head"
+
+            [request_strategies]
+            "facebook.com" = "range"
+            "#,
+        )
+        .expect("parsed");
+        assert_eq!(config.request_strategy, Some(RequestStrategy::Head));
+        assert_eq!(
+            config.request_strategies.get("facebook.com"),
+            Some(&RequestStrategy::Range)
+        );
+    }
+
+    #[test]
+    fn test_domains() {
+        let config = Config::parse(
+            r#"
+            [domains."facebook.com"]
+            max_redirects = 10
+            meta_refresh = false
+            request_strategy = "get"
+            user_agent = "facebookexternalhit/1.1"
+            headers = { "Sec-Fetch-Site" = "none" }
+            "#,
+        )
+        .expect("parsed");
+        assert_eq!(
+            config.domains.get("facebook.com"),
+            Some(&DomainPolicy {
+                max_redirects: Some(10),
+                meta_refresh: Some(false),
+                request_strategy: Some(RequestStrategy::Get),
+                user_agent: Some("facebookexternalhit/1.1".to_string()),
+                headers: BTreeMap::from([("Sec-Fetch-Site".to_string(), "none".to_string())]),
+            })
+        );
+        assert!(Config::parse("[domains.\"facebook.com\"]\nnope = 1").is_err());
+    }
+
+    #[test]
+    fn test_cleaners() {
+        let config = Config::parse(
+            r#"
+            [cleaners]
+            "threads.net" = "generic"
+            "reddit.com" = "fallback"
+            "#,
+        )
+        .expect("parsed");
+        let registry = config.registry().expect("registry");
+        assert_eq!(
+            registry.get("threads.net").map(|c| c.name()),
+            Some("GenericCleaner")
+        );
+        assert_eq!(
+            registry.get("reddit.com").map(|c| c.name()),
+            Some("FallbackCleaner")
+        );
+
+        let config = Config::parse("cleaners = { \"example.com\" = \"nope\" }").expect("parsed");
+        assert!(matches!(
+            config.registry(),
+            Err(ConfigError::UnknownCleaner(_))
+        ));
+    }
+
+    #[test]
+    fn test_rules() {
+        let config = Config::parse(
+            r#"
+            [rules."example.com"]
+            strip = ["ref"]
+            keep = ["id"]
+            path_patterns = ["/p/*"]
+            "#,
+        )
+        .expect("parsed");
+        assert_eq!(config.rules["example.com"].path_patterns, ["/p/*"]);
+        let registry = config.registry().expect("registry");
+        assert_eq!(
+            registry.get("example.com").map(|c| c.name()),
+            Some("RuleCleaner")
+        );
+    }
+
+    #[test]
+    fn test_rules_dir() {
+        let dir = std::env::temp_dir().join(format!("rsl-rules-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("created");
+        fs::write(dir.join("example.com.toml"), "strip = [\"ref\"]\n").expect("written");
+        fs::write(dir.join("example.org.json"), r#"{"keep": ["id"]}"#).expect("written");
+        fs::write(dir.join("README.md"), "Not a rule").expect("written");
+
+        // The `rules` table takes precedence
+        let config = Config::parse(&format!(
+            r#"
+            rules_dir = {:?}
+
+            [cleaners]
+            "example.org" = "generic"
+
+            [rules."example.com"]
+            path_patterns = ["/p/*"]
+            "#,
+            dir.display().to_string()
+        ))
+        .expect("parsed");
+        let registry = config.registry().expect("registry");
+        let clean = |url: &str| {
+            crate::clean::clean_url_explained(
+                url,
+                &crate::clean::CleanOptions {
+                    registry: registry.clone(),
+                    ..Default::default()
+                },
+                &mut crate::clean::Explanation::default(),
+            )
+            .map(String::from)
+        };
+        assert_eq!(
+            clean("https://example.org/a?id=1&page=2").expect("cleaned"),
+            "https://example.org/a?id=1"
+        );
+        assert_eq!(
+            clean("https://example.com/p/1?ref=x").expect("cleaned"),
+            "https://example.com/p/1?ref=x"
+        );
+
+        fs::write(dir.join("example.net.toml"), "strip = \"ref\"\n").expect("written");
+        assert!(matches!(
+            config.registry(),
+            Err(ConfigError::RuleFile(path, _)) if path.ends_with("example.net.toml")
+        ));
+        fs::remove_dir_all(&dir).expect("removed");
+    }
+
+    #[test]
+    fn test_unknown_field() {
+        assert!(matches!(
+            Config::parse("strip = [\"ref\"]"),
+            Err(ConfigError::Parse(_))
+        ));
+    }
+}
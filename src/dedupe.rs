@@ -0,0 +1,137 @@
+use std::{collections::HashMap, pin::pin};
+
+use futures::StreamExt;
+
+use crate::{
+    batch,
+    compare::canonical_key,
+    exit::ExitCode,
+    history::{self, History},
+    process::ProcessOptions,
+    progress::Progress,
+    report,
+    resolve::Resolver,
+};
+
+// Options controlling how a list of URLs is deduplicated
+pub struct DedupeOptions {
+    // Maximum number of URLs to resolve at once
+    pub concurrency: usize,
+    // Prefix each unique URL with the number of inputs that mapped to it
+    pub count: bool,
+    // Options passed through to each URL's processing
+    pub process: ProcessOptions,
+}
+
+// Resolve and clean a list of URLs, printing each unique canonical URL in the order
+// that it was first seen
+//
+// Inputs that fail to process are passed through unchanged (after reporting the
+// error), so that no links are lost from the list.
+pub async fn run(
+    resolver: &Resolver,
+    urls: &[String],
+    options: &DedupeOptions,
+    saved_history: Option<&History>,
+) -> ExitCode {
+    // Links are often repeated verbatim, so we only process each distinct input once
+    let mut occurrences: HashMap<&str, usize> = HashMap::new();
+    let mut inputs = Vec::new();
+    for url in urls {
+        let n = occurrences.entry(url).or_default();
+        if *n == 0 {
+            inputs.push(url.clone());
+        }
+        *n += 1;
+    }
+
+    let mut results = pin!(batch::process_all(
+        resolver,
+        &inputs,
+        options.concurrency,
+        &options.process
+    ));
+
+    let mut progress = Progress::new(inputs.len());
+    let mut code = ExitCode::Success;
+    let mut canonical = Vec::new();
+    while let Some((url, result)) = results.next().await {
+        progress.record(&result);
+        progress.suspend(|| report::report(&result));
+        let n = occurrences[url.as_str()];
+        match result {
+            Ok(processed) => {
+                if let Some(saved_history) = saved_history {
+                    history::record(saved_history, &processed);
+                }
+                canonical.push((processed.cleaned, n));
+            }
+            Err(e) => {
+                progress.eprintln(&format!("Error: {}: {}", url, e));
+                if code == ExitCode::Success {
+                    code = ExitCode::from(&e);
+                }
+                canonical.push((url.clone(), n));
+            }
+        }
+    }
+    progress.finish();
+
+    for (url, n) in group(canonical) {
+        if options.count {
+            println!("{}\t{}", n, url);
+        } else {
+            println!("{}", url);
+        }
+    }
+
+    code
+}
+
+// Merge equivalent URLs, summing their counts and preserving the order in which each
+// was first seen
+fn group(urls: impl IntoIterator<Item = (String, usize)>) -> Vec<(String, usize)> {
+    let mut groups: Vec<(String, usize)> = Vec::new();
+    let mut index = HashMap::new();
+    for (url, n) in urls {
+        let key = canonical_key(&url);
+        match index.get(&key) {
+            Some(&i) => groups[i].1 += n,
+            None => {
+                index.insert(key, groups.len());
+                groups.push((url, n));
+            }
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group() {
+        let urls = [
+            (
+                "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m".to_string(),
+                2,
+            ),
+            ("https://www.instagram.com/p/DS8F57NjS_S".to_string(), 1),
+            (
+                "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m/".to_string(),
+                1,
+            ),
+        ];
+        assert_eq!(
+            group(urls),
+            [
+                (
+                    "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m".to_string(),
+                    3
+                ),
+                ("https://www.instagram.com/p/DS8F57NjS_S".to_string(), 1),
+            ]
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use crate::{clean::CleanUrlError, process::ProcessError};
+
+// Exit codes returned by the CLI, so that scripts can distinguish failure modes
+//
+// This is documented for users in `EXIT_CODES_HELP`; any changes here must also be
+// reflected there (and in the README).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExitCode {
+    // The URL was processed successfully (or with `--check`, was already canonical)
+    Success = 0,
+    // An error occurred that does not fit into any of the other categories
+    Failure = 1,
+    // With `--check`, cleaning changed the input URL (or with `compare`, the URLs
+    // differ)
+    Changed = 2,
+    // The input could not be parsed as an HTTP(S) URL
+    InvalidInput = 3,
+    // The URL's domain, host, or path is not supported by any cleaner
+    Unsupported = 4,
+    // The URL could not be resolved (network error, timeout, too many redirects, etc.)
+    ResolveFailure = 5,
+    // With `--verify`, a cleaned URL no longer worked
+    VerifyFailure = 6,
+    // With `--safety-check`, a link may be malicious
+    Unsafe = 7,
+    // Results were printed, but could not be copied to the clipboard
+    ClipboardFailure = 8,
+}
+
+pub const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0  Success (with --check: the input is already canonical)
+  1  Unspecified error
+  2  With --check: cleaning changed the input; with compare: the URLs differ
+  3  Invalid input URL
+  4  Unsupported domain, host, or path
+  5  Failed to resolve URL
+  6  With --verify: a cleaned URL no longer works
+  7  With --safety-check: a link may be malicious
+  8  Results could not be copied to the clipboard";
+
+impl ExitCode {
+    pub fn exit(self) -> ! {
+        std::process::exit(self as i32)
+    }
+}
+
+impl From<&CleanUrlError> for ExitCode {
+    fn from(e: &CleanUrlError) -> Self {
+        match e {
+            CleanUrlError::ParseError(_) | CleanUrlError::UnsupportedUrlScheme => {
+                ExitCode::InvalidInput
+            }
+            CleanUrlError::UnknownDomain
+            | CleanUrlError::UnsupportedUrlHost
+            | CleanUrlError::UnsupportedUrlPath => ExitCode::Unsupported,
+            CleanUrlError::PathSegmentsError => ExitCode::Failure,
+        }
+    }
+}
+
+impl From<&ProcessError> for ExitCode {
+    fn from(e: &ProcessError) -> Self {
+        match e {
+            ProcessError::InvalidInput(_) => ExitCode::InvalidInput,
+            ProcessError::Resolve(_) => ExitCode::ResolveFailure,
+            ProcessError::Clean(e, _) => e.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolve::ResolveError;
+
+    #[test]
+    fn test_codes_are_distinct() {
+        let codes = [
+            ExitCode::Success,
+            ExitCode::Failure,
+            ExitCode::Changed,
+            ExitCode::InvalidInput,
+            ExitCode::Unsupported,
+            ExitCode::ResolveFailure,
+            ExitCode::VerifyFailure,
+            ExitCode::Unsafe,
+            ExitCode::ClipboardFailure,
+        ];
+        for (i, a) in codes.iter().enumerate() {
+            for b in &codes[i + 1..] {
+                assert_ne!(*a as i32, *b as i32);
+            }
+        }
+    }
+
+    #[test]
+    fn test_clean_errors() {
+        assert_eq!(
+            ExitCode::from(&CleanUrlError::UnsupportedUrlScheme),
+            ExitCode::InvalidInput
+        );
+        assert_eq!(
+            ExitCode::from(&CleanUrlError::UnsupportedUrlHost),
+            ExitCode::Unsupported
+        );
+        assert_eq!(
+            ExitCode::from(&CleanUrlError::UnsupportedUrlPath),
+            ExitCode::Unsupported
+        );
+    }
+
+    #[test]
+    fn test_process_errors() {
+        let e = ProcessError::InvalidInput(url::ParseError::EmptyHost);
+        assert_eq!(ExitCode::from(&e), ExitCode::InvalidInput);
+
+        let e = ProcessError::Resolve(ResolveError::Timeout);
+        assert_eq!(ExitCode::from(&e), ExitCode::ResolveFailure);
+
+        let e = ProcessError::Clean(CleanUrlError::UnknownDomain, Box::default());
+        assert_eq!(ExitCode::from(&e), ExitCode::Unsupported);
+    }
+}
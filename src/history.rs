@@ -0,0 +1,421 @@
+use std::{
+    error::Error,
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use rusqlite::{Connection, params};
+
+use crate::{exit::ExitCode, process::Processed};
+
+const HISTORY_FILE_NAME: &str = "history.sqlite3";
+
+// Error type for reading from and writing to the history database
+#[derive(Debug, derive_more::Display)]
+pub enum HistoryError {
+    Io(io::Error),
+    Sqlite(rusqlite::Error),
+    NoDataDir,
+}
+
+impl Error for HistoryError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            HistoryError::Io(e) => Some(e),
+            HistoryError::Sqlite(e) => Some(e),
+            HistoryError::NoDataDir => None,
+        }
+    }
+}
+
+impl From<io::Error> for HistoryError {
+    fn from(e: io::Error) -> Self {
+        HistoryError::Io(e)
+    }
+}
+
+impl From<rusqlite::Error> for HistoryError {
+    fn from(e: rusqlite::Error) -> Self {
+        HistoryError::Sqlite(e)
+    }
+}
+
+// A single row from the history database
+pub struct Record {
+    pub timestamp: String,
+    pub input: String,
+    pub resolved: String,
+    pub cleaned: String,
+}
+
+// Local, opt-in store of every URL processed by RSL, so that links cleaned in the past
+// can be recovered later
+pub struct History {
+    conn: Connection,
+}
+
+impl History {
+    // Default location of the history database, under the platform's data directory
+    // (e.g., `~/.local/share/rsl/history.sqlite3` on Linux)
+    pub fn default_path() -> Result<PathBuf, HistoryError> {
+        let data_dir = dirs::data_dir().ok_or(HistoryError::NoDataDir)?;
+        Ok(data_dir.join(clap::crate_name!()).join(HISTORY_FILE_NAME))
+    }
+
+    pub fn open_default() -> Result<Self, HistoryError> {
+        Self::open(&Self::default_path()?)
+    }
+
+    pub fn open(path: &Path) -> Result<Self, HistoryError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    fn from_connection(conn: Connection) -> Result<Self, HistoryError> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS history (
+                id             INTEGER PRIMARY KEY AUTOINCREMENT,
+                input          TEXT NOT NULL,
+                resolved       TEXT NOT NULL,
+                cleaned        TEXT NOT NULL,
+                timestamp      INTEGER NOT NULL,
+                platform       TEXT,
+                params_removed INTEGER,
+                latency_ms     INTEGER
+            );",
+        )?;
+        Ok(History { conn })
+    }
+
+    // Record a successfully processed URL
+    pub fn record(&self, processed: &Processed) -> Result<(), HistoryError> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+        self.conn.execute(
+            "INSERT INTO history
+                (input, resolved, cleaned, timestamp, platform, params_removed, latency_ms)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                processed.input,
+                processed.resolved,
+                processed.cleaned,
+                timestamp,
+                processed.platform(),
+                processed.removed_params().len() as i64,
+                processed.elapsed.as_millis() as i64,
+            ],
+        )?;
+        Ok(())
+    }
+
+    // Query history in chronological order, optionally filtering to entries containing
+    // `search` in any URL, and limiting to the most recent `last` entries
+    pub fn query(
+        &self,
+        search: Option<&str>,
+        last: Option<usize>,
+    ) -> Result<Vec<Record>, HistoryError> {
+        // SQLite treats a negative limit as no limit
+        let limit = last.map(|n| n as i64).unwrap_or(-1);
+        let pattern = format!("%{}%", escape_like(search.unwrap_or_default()));
+
+        let mut stmt = self.conn.prepare(
+            "SELECT datetime(timestamp, 'unixepoch', 'localtime'), input, resolved, cleaned
+             FROM history
+             WHERE input LIKE ?1 ESCAPE '\\' OR resolved LIKE ?1 ESCAPE '\\'
+                OR cleaned LIKE ?1 ESCAPE '\\'
+             ORDER BY id DESC
+             LIMIT ?2",
+        )?;
+        let mut records = stmt
+            .query_map(params![pattern, limit], |row| {
+                Ok(Record {
+                    timestamp: row.get(0)?,
+                    input: row.get(1)?,
+                    resolved: row.get(2)?,
+                    cleaned: row.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // We selected the most recent entries first so that the limit applies to them,
+        // but we want to display them chronologically
+        records.reverse();
+        Ok(records)
+    }
+
+    // Summarise history.  Entries whose platform is not known are counted as `unknown`
+    pub fn stats(&self) -> Result<Stats, HistoryError> {
+        let (total, params_removed, average_latency_ms) = self.conn.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(params_removed), 0), AVG(latency_ms) FROM history",
+            [],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, Option<f64>>(2)?,
+                ))
+            },
+        )?;
+
+        let mut stmt = self.conn.prepare(
+            "SELECT COALESCE(platform, 'unknown'), COUNT(*)
+             FROM history
+             GROUP BY 1
+             ORDER BY 2 DESC, 1 ASC",
+        )?;
+        let per_platform = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as usize))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Stats {
+            total: total as usize,
+            per_platform,
+            params_removed: params_removed as usize,
+            average_latency: average_latency_ms.map(|ms| Duration::from_millis(ms as u64)),
+        })
+    }
+}
+
+// Summary statistics over the history database
+pub struct Stats {
+    pub total: usize,
+    pub per_platform: Vec<(String, usize)>,
+    pub params_removed: usize,
+    pub average_latency: Option<Duration>,
+}
+
+impl std::fmt::Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "URLs cleaned:                {}", self.total)?;
+        writeln!(f, "Tracking parameters removed: {}", self.params_removed)?;
+        match self.average_latency {
+            Some(latency) => {
+                writeln!(f, "Average resolution latency:  {} ms", latency.as_millis())?
+            }
+            None => writeln!(f, "Average resolution latency:  n/a")?,
+        }
+
+        if !self.per_platform.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "Per platform:")?;
+            let width = self
+                .per_platform
+                .iter()
+                .map(|(platform, _)| platform.len())
+                .max()
+                .unwrap_or_default();
+            for (platform, count) in &self.per_platform {
+                writeln!(f, "  {:<width$}  {}", platform, count, width = width)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Escape `term` for use in a `LIKE ... ESCAPE '\'` pattern, so that it is matched
+// literally: URLs are full of `%` (percent-encoding) and `_`
+fn escape_like(term: &str) -> String {
+    let mut escaped = String::with_capacity(term.len());
+    for c in term.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// Record a processed URL, warning rather than failing if it could not be saved
+pub fn record(history: &History, processed: &Processed) {
+    if let Err(e) = history.record(processed) {
+        tracing::warn!(error = %e, "could not save to history");
+    }
+}
+
+// Print the entries matching `search` for `rsl history`, one per line
+pub fn list(search: Option<&str>, last: Option<usize>) -> ExitCode {
+    match History::open_default().and_then(|h| h.query(search, last)) {
+        Ok(records) => {
+            for record in records {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    record.timestamp, record.input, record.resolved, record.cleaned
+                );
+            }
+            ExitCode::Success
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::Failure
+        }
+    }
+}
+
+// Print statistics for `rsl stats`
+pub fn show_stats() -> ExitCode {
+    match History::open_default().and_then(|h| h.stats()) {
+        Ok(stats) => {
+            print!("{}", stats);
+            ExitCode::Success
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::Failure
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> History {
+        History::from_connection(Connection::open_in_memory().expect("in-memory database"))
+            .expect("history")
+    }
+
+    fn processed(input: &str, cleaned: &str) -> Processed {
+        Processed {
+            input: input.to_string(),
+            resolved: input.to_string(),
+            cleaned: cleaned.to_string(),
+            kind: None,
+            elapsed: Duration::from_millis(100),
+            hops: Vec::new(),
+            verified: None,
+            unknown_params: Vec::new(),
+            removed: Vec::new(),
+            explanation: None,
+            threats: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_record_and_query() {
+        let history = history();
+        history
+            .record(&processed(
+                "https://www.reddit.com/r/AskTheWorld/s/mONZu40JNk",
+                "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m",
+            ))
+            .expect("recorded");
+        history
+            .record(&processed(
+                "https://www.facebook.com/share/r/14QeSSeP3nu/",
+                "https://www.facebook.com/reel/1309748351194528",
+            ))
+            .expect("recorded");
+
+        let records = history.query(None, None).expect("queried");
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[0].cleaned,
+            "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m"
+        );
+        assert_eq!(
+            records[1].cleaned,
+            "https://www.facebook.com/reel/1309748351194528"
+        );
+    }
+
+    #[test]
+    fn test_search() {
+        let history = history();
+        history
+            .record(&processed("https://a.example/1", "https://reddit.com/1"))
+            .expect("recorded");
+        history
+            .record(&processed("https://b.example/2", "https://facebook.com/2"))
+            .expect("recorded");
+
+        let records = history.query(Some("facebook"), None).expect("queried");
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].input, "https://b.example/2");
+    }
+
+    #[test]
+    fn test_search_literal() {
+        let history = history();
+        history
+            .record(&processed(
+                "https://a.example/caf%C3%A9",
+                "https://a.example/1",
+            ))
+            .expect("recorded");
+        history
+            .record(&processed("https://b.example/a_b", "https://b.example/2"))
+            .expect("recorded");
+        history
+            .record(&processed("https://c.example/axb", "https://c.example/3"))
+            .expect("recorded");
+
+        // Neither `%` nor `_` is a wildcard
+        let records = history.query(Some("%C3"), None).expect("queried");
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].input, "https://a.example/caf%C3%A9");
+        let records = history.query(Some("a_b"), None).expect("queried");
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].input, "https://b.example/a_b");
+        assert_eq!(history.query(Some("%"), None).expect("queried").len(), 1);
+    }
+
+    #[test]
+    fn test_last() {
+        let history = history();
+        for i in 0..5 {
+            let url = format!("https://www.reddit.com/{}", i);
+            history.record(&processed(&url, &url)).expect("recorded");
+        }
+
+        let records = history.query(None, Some(2)).expect("queried");
+        let inputs: Vec<_> = records.iter().map(|r| r.input.as_str()).collect();
+        assert_eq!(
+            inputs,
+            ["https://www.reddit.com/3", "https://www.reddit.com/4"]
+        );
+    }
+
+    #[test]
+    fn test_stats() {
+        let history = history();
+        history
+            .record(&processed(
+                "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m?utm_source=share&utm_term=1",
+                "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m",
+            ))
+            .expect("recorded");
+        history
+            .record(&processed(
+                "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m/comment/nxfc5ci",
+                "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m/comment/nxfc5ci",
+            ))
+            .expect("recorded");
+        history
+            .record(&processed(
+                "https://www.facebook.com/reel/1605919000854039/?rdid=VxhE0u0GlwyGLnFD",
+                "https://www.facebook.com/reel/1605919000854039",
+            ))
+            .expect("recorded");
+
+        let stats = history.stats().expect("stats");
+        assert_eq!(stats.total, 3);
+        assert_eq!(stats.params_removed, 3);
+        assert_eq!(stats.average_latency, Some(Duration::from_millis(100)));
+        assert_eq!(
+            stats.per_platform,
+            [
+                ("reddit.com".to_string(), 2),
+                ("facebook.com".to_string(), 1)
+            ]
+        );
+    }
+}
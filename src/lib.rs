@@ -0,0 +1,37 @@
+//! Resolve share links to their canonical form, without tracking.
+//!
+//! This library provides the logic behind the `rsl` command line tool: [`Resolver`]
+//! follows a share link's chain of redirects to its final form, and [`clean_url`]
+//! removes tracking information from the result according to per-platform rules (see
+//! [`clean::supported_platforms`]).  [`process()`] combines the two, and
+//! [`process_stream`] does so concurrently for a stream of URLs.
+//!
+//! Resolution requires the `net` feature (enabled by default).  Without it, only the
+//! offline cleaning rules are available; e.g., when compiling to WebAssembly, along with
+//! a small JavaScript interface for use in the browser.
+
+#[cfg(feature = "net")]
+pub mod archive;
+pub mod clean;
+#[cfg(feature = "net")]
+pub mod process;
+pub mod redact;
+#[cfg(feature = "net")]
+pub mod resolve;
+#[cfg(feature = "net")]
+pub mod safety;
+#[cfg(feature = "serde")]
+mod serialize;
+#[cfg(feature = "net")]
+pub mod verify;
+#[cfg(target_arch = "wasm32")]
+mod wasm;
+
+pub use clean::{CleanOptions, CleanUrlError, CleanedUrl, CleanerRegistry, UrlCleaner, clean_url};
+#[cfg(feature = "net")]
+pub use process::{ProcessError, ProcessOptions, Processed, process, process_stream};
+#[cfg(feature = "net")]
+pub use resolve::{
+    CookieJar, IpFamily, RequestStrategy, Resolution, ResolveCache, ResolveError, Resolver,
+    ResolverBuilder, UrlResolver,
+};
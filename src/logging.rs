@@ -0,0 +1,93 @@
+use std::io;
+
+use tracing_subscriber::{EnvFilter, filter::LevelFilter, fmt::format::FmtSpan};
+
+use crate::output::{self, Verbosity};
+
+// Minimum level of log messages to show (`--log-level`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Off => LevelFilter::OFF,
+            LogLevel::Error => LevelFilter::ERROR,
+            LogLevel::Warn => LevelFilter::WARN,
+            LogLevel::Info => LevelFilter::INFO,
+            LogLevel::Debug => LevelFilter::DEBUG,
+            LogLevel::Trace => LevelFilter::TRACE,
+        }
+    }
+}
+
+// Format of log messages written to standard error
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogFormat {
+    // Human-readable text
+    #[default]
+    Text,
+    // One JSON object per line, for log collectors
+    Json,
+}
+
+// Install the global subscriber for `tracing` events (warnings, request timing, retry
+// attempts, cleaner dispatch, etc.)
+//
+// An explicit `--log-level` takes precedence over `RUST_LOG`, which in turn takes
+// precedence over the level implied by `--quiet` or `-vv`.
+//
+// With `-v`, RSL's own info events are shown, which mark each step of the pipeline
+// (resolving, verifying, and so on).  With `-vv`, its debug events are shown too, along
+// with the timing of each span (resolution, each retry attempt, and each cleaner) as it
+// closes.
+pub fn init(verbosity: Verbosity, level: Option<LogLevel>, format: LogFormat) {
+    let filter = match level {
+        Some(level) => EnvFilter::default().add_directive(LevelFilter::from(level).into()),
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| {
+            let filter = EnvFilter::default().add_directive(default_level(verbosity).into());
+            match verbosity {
+                Verbosity::Debug => {
+                    filter.add_directive("rsl=debug".parse().expect("valid directive"))
+                }
+                Verbosity::Verbose => {
+                    filter.add_directive("rsl=info".parse().expect("valid directive"))
+                }
+                _ => filter,
+            }
+        }),
+    };
+    let span_events = if verbosity >= Verbosity::Debug {
+        FmtSpan::CLOSE
+    } else {
+        FmtSpan::NONE
+    };
+
+    let builder = tracing_subscriber::fmt()
+        .with_writer(io::stderr)
+        .with_env_filter(filter)
+        .with_span_events(span_events);
+    match format {
+        LogFormat::Text => builder
+            .without_time()
+            .with_target(false)
+            .with_ansi(output::color())
+            .init(),
+        LogFormat::Json => builder.json().init(),
+    }
+}
+
+// By default, we only show warnings, unless running quietly
+fn default_level(verbosity: Verbosity) -> LevelFilter {
+    match verbosity {
+        Verbosity::Quiet => LevelFilter::ERROR,
+        _ => LevelFilter::WARN,
+    }
+}
@@ -1,44 +1,219 @@
-use clap::{ArgAction, Parser, crate_authors, crate_name, crate_version};
+use std::{
+    fs::File,
+    io::{self, IsTerminal},
+};
 
-mod clean;
+use clap::{CommandFactory, Parser, error::ErrorKind};
+
+use batch::BatchOptions;
+use cli::{Cli, Command};
+use clipboard::{Backend as ClipboardBackend, ClipboardError};
+use config::Config;
+use dedupe::DedupeOptions;
+use exit::ExitCode;
+use history::History;
+use output::Verbosity;
+use process::ProcessOptions;
+use resolve::Resolver;
+use tabular::TableOptions;
+
+use rsl::{archive, clean, process, redact, resolve};
+
+mod batch;
+mod cli;
 mod clipboard;
-mod resolve;
-
-#[derive(Parser)]
-#[command(
-    name = crate_name!(),
-    author = crate_authors!(", "),
-    version = crate_version!(),
-)]
-/// Resolve share link to canonical form
-struct Cli {
-    /// URL to resolve
-    #[arg(
-        action = ArgAction::Set,
-        num_args = 1,
-        value_name = "URL",
-    )]
-    url: String,
-}
+mod compare;
+mod config;
+mod dedupe;
+mod exit;
+mod history;
+mod logging;
+mod output;
+mod progress;
+mod repl;
+mod report;
+mod rewrite;
+mod sink;
+mod tabular;
+mod update;
 
 #[tokio::main]
 async fn main() {
     // TODO: option to remove scheme and subdomains
     // TODO: option to ignore input validation; just follow redirects and remove query parameters
     let cli = Cli::parse();
+    // A clipboard holder needs nothing else set up; see `clipboard::hand_off`
+    if let Some(Command::HoldClipboard { held }) = &cli.command {
+        match clipboard::hold(*held) {
+            Ok(()) => ExitCode::Success.exit(),
+            Err(_) => ExitCode::Failure.exit(),
+        }
+    }
+    let verbosity = Verbosity::from_flags(cli.quiet, cli.verbose);
+    output::init(verbosity, cli.color);
+    redact::set_enabled(cli.redact_urls);
+    logging::init(verbosity, cli.log_level, cli.log_format);
+    report::set_explain(cli.explain);
+    report::set_trace(cli.trace);
+    clipboard::set_rich(cli.rich);
+
+    let config = Config::load(cli.config.as_deref()).unwrap_or_else(|e| {
+        eprintln!("Error: could not load configuration: {}", e);
+        ExitCode::Failure.exit();
+    });
+    clipboard::set_backend(
+        cli.clipboard_backend
+            .or(cli.osc52.then_some(ClipboardBackend::Osc52))
+            .or(config.clipboard_backend)
+            .unwrap_or_default(),
+    );
+    clipboard::set_selection(cli.selection.or(config.selection).unwrap_or_default());
+    let (registry, filters) = config
+        .registry()
+        .and_then(|registry| Ok((registry, config.filters()?)))
+        .unwrap_or_else(|e| {
+            eprintln!("Error: invalid configuration: {}", e);
+            ExitCode::Failure.exit();
+        });
+
+    if cli.list_supported {
+        let platforms: Vec<_> = registry
+            .supported_platforms()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        println!("{}", platforms.join("\n\n"));
+        ExitCode::Success.exit();
+    }
+
+    if let Some(Command::History { search, last }) = &cli.command {
+        history::list(search.as_deref(), *last).exit();
+    }
+
+    if let Some(Command::Stats) = &cli.command {
+        history::show_stats().exit();
+    }
+
+    let proxy = cli.proxy(&config);
+
+    if let Some(Command::UpdateRules) = &cli.command {
+        update::run(proxy.as_ref()).await.exit();
+    }
+
+    // History is opt-in, and failing to open it should not prevent the URL from being
+    // processed
+    let history = if cli.save_history {
+        History::open_default()
+            .inspect_err(|e| tracing::warn!(error = %e, "could not open history"))
+            .ok()
+    } else {
+        None
+    };
+
+    let process_options = cli.process_options(&config, registry, filters);
+    let resolver = cli.resolver(&config, proxy);
+    let code = run(&cli, &resolver, process_options, history.as_ref()).await;
 
-    match resolve::resolve(&cli.url)
-        .await
-        .and_then(|resolved_url| clean::clean_url(&resolved_url).map_err(|e| e.into()))
-    {
-        Ok(url) => {
-            clipboard::copy(&url);
-            println!("{}", url);
-            std::process::exit(0);
+    // Cookies are saved once we are done, rather than as each is set
+    if let Err(e) = resolver.save_cookies() {
+        eprintln!("Error: could not save cookie jar: {}", e);
+    }
+    code.exit();
+}
+
+// Run the command (or process the URLs) given on the command line with `resolver`
+async fn run(
+    cli: &Cli,
+    resolver: &Resolver,
+    process_options: ProcessOptions,
+    history: Option<&History>,
+) -> ExitCode {
+    if cli.interactive {
+        if let Err(e) = repl::run(resolver, &process_options, history).await {
+            eprintln!("Error: {}", e);
+            return ExitCode::Failure;
         }
+        return ExitCode::Success;
+    }
+
+    if let Some(Command::Rewrite { files, in_place }) = &cli.command {
+        if let Err(e) = rewrite::run(resolver, files, *in_place, &process_options, history).await {
+            // The rewritten text has been printed by now, so all that failed was copying it
+            if e.is::<ClipboardError>() {
+                eprintln!("Error: could not copy to clipboard: {}", e);
+                return ExitCode::ClipboardFailure;
+            }
+            eprintln!("Error: {}", e);
+            return ExitCode::Failure;
+        }
+        return ExitCode::Success;
+    }
+
+    if let Some(Command::Compare { a, b }) = &cli.command {
+        return compare::run(resolver, a, b, &process_options).await;
+    }
+
+    if let Some(Command::Dedupe { urls, count }) = &cli.command {
+        let urls = if urls.is_empty() && !io::stdin().is_terminal() {
+            match batch::read_urls(io::stdin().lock(), b'\n') {
+                Ok(urls) => urls,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    return ExitCode::Failure;
+                }
+            }
+        } else {
+            urls.clone()
+        };
+        let options = DedupeOptions {
+            concurrency: cli.concurrency,
+            count: *count,
+            process: process_options,
+        };
+        return dedupe::run(resolver, &urls, &options, history).await;
+    }
+
+    if cli.csv || cli.tsv {
+        let sink = cli.sink();
+        let options = TableOptions {
+            delimiter: if cli.tsv { b'\t' } else { b',' },
+            column: cli.column.clone(),
+            concurrency: cli.concurrency,
+            process: process_options,
+        };
+        let result = match &cli.file {
+            Some(file) => match File::open(file) {
+                Ok(file) => tabular::run(resolver, file, &options, sink, history).await,
+                Err(e) => Err(e.into()),
+            },
+            None => tabular::run(resolver, io::stdin(), &options, sink, history).await,
+        };
+        return result.unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            ExitCode::Failure
+        });
+    }
+
+    let urls = match cli.read_urls() {
+        Ok(urls) => urls,
         Err(e) => {
             eprintln!("Error: {}", e);
-            std::process::exit(1);
+            return ExitCode::Failure;
         }
+    };
+    if urls.is_empty() {
+        Cli::command()
+            .error(ErrorKind::MissingRequiredArgument, "no URLs given")
+            .exit();
     }
+
+    let options = BatchOptions {
+        concurrency: cli.concurrency,
+        check: cli.check,
+        delimiter: cli.delimiter(),
+        archive: cli.archive,
+        json: cli.json,
+        process: process_options,
+    };
+    batch::run(resolver, &urls, &options, cli.sink(), history).await
 }
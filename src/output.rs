@@ -0,0 +1,162 @@
+use std::{
+    env,
+    fmt::Display,
+    io::{self, IsTerminal},
+    sync::atomic::{AtomicBool, AtomicU8, Ordering},
+};
+
+use anstyle::{AnsiColor, Style};
+use clap::ColorChoice;
+
+// How much RSL should print to standard error
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    // Only print the cleaned URL (and errors)
+    Quiet = 0,
+    // Print warnings and progress
+    Normal = 1,
+    // Additionally print each step taken (`-v`)
+    Verbose = 2,
+    // Additionally print details of each step (`-vv`)
+    Debug = 3,
+}
+
+// Output settings are global, as they are set once from the command line and consulted
+// wherever anything is printed
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+static COLOR: AtomicBool = AtomicBool::new(false);
+
+const REMOVED_STYLE: Style = Style::new()
+    .fg_color(Some(anstyle::Color::Ansi(AnsiColor::Red)))
+    .strikethrough();
+const STEP_STYLE: Style = Style::new().dimmed();
+
+impl Verbosity {
+    pub fn from_flags(quiet: bool, verbose: u8) -> Self {
+        match (quiet, verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, 0) => Verbosity::Normal,
+            (false, 1) => Verbosity::Verbose,
+            (false, _) => Verbosity::Debug,
+        }
+    }
+}
+
+pub fn init(verbosity: Verbosity, color: ColorChoice) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+    let color = match color {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        // Respect <https://no-color.org>
+        ColorChoice::Auto => io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none(),
+    };
+    COLOR.store(color, Ordering::Relaxed);
+}
+
+pub fn verbosity() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => Verbosity::Quiet,
+        1 => Verbosity::Normal,
+        2 => Verbosity::Verbose,
+        _ => Verbosity::Debug,
+    }
+}
+
+pub fn color() -> bool {
+    COLOR.load(Ordering::Relaxed)
+}
+
+// Print supplementary information to standard error, unless running quietly
+pub fn info(msg: impl Display) {
+    if verbosity() >= Verbosity::Normal {
+        eprintln!("{}", msg);
+    }
+}
+
+// Print a step in the pipeline to standard error with `-v`
+pub fn step(msg: impl Display) {
+    if verbosity() >= Verbosity::Verbose {
+        eprintln!("{}", styled(&msg.to_string(), STEP_STYLE));
+    }
+}
+
+// Print details of a step to standard error with `-vv`
+pub fn detail(msg: impl Display) {
+    if verbosity() >= Verbosity::Debug {
+        eprintln!("{}", styled(&format!("  {}", msg), STEP_STYLE));
+    }
+}
+
+fn styled(s: &str, style: Style) -> String {
+    if color() {
+        format!("{style}{s}{style:#}")
+    } else {
+        s.to_string()
+    }
+}
+
+// Render `url` with each of the `removed` query parameters highlighted
+//
+// If colour is disabled, removed parameters are instead wrapped in square brackets so
+// that they remain distinguishable.
+pub fn highlight_removed(url: &str, removed: &[String]) -> String {
+    let Some((base, query)) = url.split_once('?') else {
+        return url.to_string();
+    };
+    let (query, fragment) = match query.split_once('#') {
+        Some((query, fragment)) => (query, Some(fragment)),
+        None => (query, None),
+    };
+
+    let pairs: Vec<String> = query
+        .split('&')
+        .map(|pair| {
+            let key = pair.split('=').next().unwrap_or_default();
+            let is_removed = url::form_urlencoded::parse(key.as_bytes())
+                .next()
+                .is_some_and(|(k, _)| removed.iter().any(|r| *r == k));
+            match (is_removed, color()) {
+                (false, _) => pair.to_string(),
+                (true, true) => styled(pair, REMOVED_STYLE),
+                (true, false) => format!("[{}]", pair),
+            }
+        })
+        .collect();
+
+    let mut highlighted = format!("{}?{}", base, pairs.join("&"));
+    if let Some(fragment) = fragment {
+        highlighted.push('#');
+        highlighted.push_str(fragment);
+    }
+    highlighted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verbosity_from_flags() {
+        assert_eq!(Verbosity::from_flags(true, 0), Verbosity::Quiet);
+        assert_eq!(Verbosity::from_flags(false, 0), Verbosity::Normal);
+        assert_eq!(Verbosity::from_flags(false, 1), Verbosity::Verbose);
+        assert_eq!(Verbosity::from_flags(false, 2), Verbosity::Debug);
+        assert_eq!(Verbosity::from_flags(false, 7), Verbosity::Debug);
+    }
+
+    #[test]
+    fn test_highlight_removed_without_color() {
+        let url = "https://www.linkedin.com/posts/x?utm_source=share&id=1&rcm=ACoAAG";
+        let removed = ["utm_source".to_string(), "rcm".to_string()];
+        assert_eq!(
+            highlight_removed(url, &removed),
+            "https://www.linkedin.com/posts/x?[utm_source=share]&id=1&[rcm=ACoAAG]"
+        );
+    }
+
+    #[test]
+    fn test_highlight_removed_no_query() {
+        let url = "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m";
+        assert_eq!(highlight_removed(url, &[]), url);
+    }
+}
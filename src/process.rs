@@ -0,0 +1,542 @@
+use std::{error::Error, time::Duration};
+
+use futures::{Stream, StreamExt};
+use reqwest::StatusCode;
+use url::Url;
+
+use crate::{
+    clean::{self, CleanOptions, CleanUrlError, CleanedUrl, Confidence, Explanation, Removed},
+    redact,
+    resolve::{self, Hop, Resolution, ResolveError, Resolver, shortener_for},
+    safety::{self, Threat},
+    verify,
+};
+
+// The result of resolving and cleaning a single URL
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Processed {
+    pub input: String,
+    pub resolved: String,
+    pub cleaned: String,
+    // The kind of content the URL refers to (e.g., "post"), if known
+    pub kind: Option<String>,
+    // Time taken to resolve the URL
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            rename = "elapsed_ms",
+            serialize_with = "crate::serialize::millis",
+            deserialize_with = "crate::serialize::from_millis"
+        )
+    )]
+    pub elapsed: Duration,
+    // Every hop taken while resolving the URL; see `Resolution`
+    #[cfg_attr(feature = "serde", serde(skip_deserializing))]
+    pub hops: Vec<Hop>,
+    // With `--verify`, whether the cleaned URL still works
+    pub verified: Option<bool>,
+    // Query parameters removed by cleaning that may have been needed; see
+    // `CleanedUrl::unknown_params`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub unknown_params: Vec<String>,
+    // Everything removed by cleaning; see `CleanedUrl::removed`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub removed: Vec<Removed>,
+    // How the resolved URL was cleaned (for `--explain`), unless it was only expanded
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub explanation: Option<Explanation>,
+    // With `--safety-check`, reasons to think the resolved URL may be malicious
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub threats: Vec<Threat>,
+}
+
+impl Processed {
+    // Whether the input was already in its canonical form
+    pub fn is_canonical(&self) -> bool {
+        self.input == self.cleaned
+    }
+
+    // The HTTP status of the page the URL led to, if it was resolved
+    pub fn status(&self) -> Option<StatusCode> {
+        resolve::final_status(&self.hops)
+    }
+
+    // Whether the URL led to a dead link (e.g., a 404, or a removed post); see
+    // `resolve::is_dead`
+    pub fn is_dead(&self) -> bool {
+        resolve::is_dead(&self.hops)
+    }
+
+    // The title of the page the URL led to, if known (currently, only from oEmbed)
+    pub fn title(&self) -> Option<&str> {
+        self.hops.iter().rev().find_map(|hop| hop.title.as_deref())
+    }
+
+    // The registrable domain of the cleaned URL (e.g., "reddit.com")
+    pub fn platform(&self) -> Option<String> {
+        let url = Url::parse(&self.cleaned).ok()?;
+        psl::domain_str(url.host_str()?).map(str::to_string)
+    }
+
+    // Details of the cleaned URL, as would be returned by `clean_url`
+    pub fn cleaned_url(&self) -> CleanedUrl {
+        CleanedUrl {
+            url: self.cleaned.clone(),
+            platform: self.platform(),
+            kind: self.kind.clone(),
+            removed_params: self.removed_params(),
+            removed: self.removed.clone(),
+            unknown_params: self.unknown_params.clone(),
+            canonical: self.is_canonical(),
+        }
+    }
+
+    // How sure we are that cleaning removed nothing the site needs
+    pub fn confidence(&self) -> Confidence {
+        Confidence::of(&self.unknown_params)
+    }
+
+    // The result as a JSON object, for `--json`: its fields, along with what can be
+    // worked out from them
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut json = serde_json::to_value(self).unwrap_or_default();
+        json["platform"] = serde_json::json!(self.platform());
+        json["removed_params"] = serde_json::json!(self.removed_params());
+        json["confidence"] = serde_json::json!(self.confidence());
+        json["canonical"] = serde_json::json!(self.is_canonical());
+        json["status"] = serde_json::json!(self.status().map(|status| status.as_u16()));
+        json["dead"] = serde_json::json!(self.is_dead());
+        json
+    }
+
+    // Names of query parameters present in the resolved URL but removed by cleaning
+    pub fn removed_params(&self) -> Vec<String> {
+        let (Ok(resolved), Ok(cleaned)) = (Url::parse(&self.resolved), Url::parse(&self.cleaned))
+        else {
+            return Vec::new();
+        };
+        let kept: Vec<_> = cleaned.query_pairs().map(|(k, _)| k).collect();
+        resolved
+            .query_pairs()
+            .map(|(k, _)| k)
+            .filter(|k| !kept.contains(k))
+            .map(|k| k.into_owned())
+            .collect()
+    }
+}
+
+// Options controlling how URLs are processed
+#[derive(Clone, Debug, Default)]
+pub struct ProcessOptions {
+    // Follow redirects, but do not clean the resolved URL
+    pub expand_only: bool,
+    // Re-fetch the cleaned URL to check that cleaning did not break it
+    pub verify: bool,
+    // Screen the resolved URL for signs that it is malicious; see `safety::screen`
+    pub safety_check: bool,
+    // With `safety_check`, also look the resolved URL up in Google Safe Browsing, with
+    // this API key
+    pub safe_browsing_key: Option<String>,
+    // Do not touch the network: clean each URL as given, without resolving it
+    pub offline: bool,
+    // Resolve URLs even if they are already full URLs of pages their platform's cleaner
+    // recognises (see `clean::is_recognised`), which would otherwise be cleaned as given
+    pub always_resolve: bool,
+    // User overrides for cleaning rules
+    pub clean: CleanOptions,
+}
+
+// Error type for the full resolve-and-clean pipeline, distinguishing at which stage
+// processing failed
+#[derive(Debug, derive_more::Display)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ProcessError {
+    InvalidInput(
+        #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serialize::display"))]
+        url::ParseError,
+    ),
+    Resolve(ResolveError),
+    // Cleaning failed, with the decisions made up to that point
+    #[display("{_0}")]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serialize::first"))]
+    Clean(CleanUrlError, Box<Explanation>),
+}
+
+impl ProcessError {
+    // Whether the URL was not attempted at all, as its host kept failing
+    pub fn is_skipped(&self) -> bool {
+        matches!(self, ProcessError::Resolve(ResolveError::CircuitOpen(_)))
+    }
+}
+
+impl Error for ProcessError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ProcessError::InvalidInput(e) => Some(e),
+            ProcessError::Resolve(e) => Some(e),
+            ProcessError::Clean(e, _) => Some(e),
+        }
+    }
+}
+
+// Resolve and clean a URL using the given resolver
+#[tracing::instrument(skip_all, fields(url = %redact::url(input)))]
+pub async fn process(
+    resolver: &Resolver,
+    input: &str,
+    options: &ProcessOptions,
+) -> Result<Processed, ProcessError> {
+    // Step 1: validate input before we touch the network, once any HTML entities or
+    // percent-encoding of the whole link are undone; see `clean::decode_input`
+    let url = Url::parse(&clean::decode_input(input)).map_err(ProcessError::InvalidInput)?;
+    tracing::debug!(host = url.host_str().unwrap_or_default(), "parsed input");
+
+    // Step 2: follow redirects to the URL's final form.  Offline, we can only clean the
+    // URL as given, which is of little use if it is a shortened link
+    let (resolved, hops, elapsed) = if options.offline {
+        if let Some(shortener) = shortener_for(&url) {
+            tracing::warn!(
+                url = %redact::url(input),
+                shortener = shortener.domain,
+                "shortened link cannot be expanded offline"
+            );
+        }
+        (url.to_string(), Vec::new(), Duration::ZERO)
+    } else if !options.expand_only
+        && !options.always_resolve
+        && clean::is_recognised(&url, &options.clean)
+    {
+        // The URL is already in its full form (e.g., a Reddit post rather than a share
+        // link), so resolving it would only take a round trip to get back here.  With
+        // `--expand-only`, we resolve it anyway, as seeing where it goes is the point
+        tracing::info!(url = %redact::url(input), "needs no resolving");
+        (url.to_string(), Vec::new(), Duration::ZERO)
+    } else {
+        tracing::info!(url = %redact::url(input), "resolving");
+        let Resolution {
+            url: resolved,
+            hops,
+            elapsed,
+        } = resolver
+            .resolve_traced(url.as_str())
+            .await
+            .map_err(ProcessError::Resolve)?;
+        tracing::info!(
+            url = %redact::url(&resolved),
+            elapsed_ms = elapsed.as_millis() as u64,
+            "resolved"
+        );
+        if resolve::is_dead(&hops) {
+            tracing::warn!(
+                url = %redact::url(&resolved),
+                status = resolve::final_status(&hops).map(|status| status.as_u16()),
+                "link is dead"
+            );
+        }
+        (resolved, hops, elapsed)
+    };
+
+    // Step 3: optionally screen where the link goes for signs that it is malicious,
+    // before anyone is tempted to open it
+    let threats = if options.safety_check {
+        screen(resolver, &resolved, options).await
+    } else {
+        Vec::new()
+    };
+
+    // In expand-only mode, we deliberately leave the resolved URL as-is; e.g., to
+    // inspect the true destination of a suspicious link without altering it
+    if options.expand_only {
+        return Ok(Processed {
+            input: input.to_string(),
+            cleaned: resolved.clone(),
+            resolved,
+            kind: None,
+            elapsed,
+            hops,
+            verified: None,
+            unknown_params: Vec::new(),
+            removed: Vec::new(),
+            explanation: None,
+            threats,
+        });
+    }
+
+    // Step 4: remove tracking information
+    let mut explanation = Explanation::default();
+    let cleaned = match clean::clean_url_explained(&resolved, &options.clean, &mut explanation) {
+        Ok(cleaned) => cleaned,
+        Err(e) => return Err(ProcessError::Clean(e, Box::new(explanation))),
+    };
+    let mut processed = Processed {
+        input: input.to_string(),
+        resolved,
+        cleaned: cleaned.url,
+        kind: cleaned.kind,
+        elapsed,
+        hops,
+        verified: None,
+        unknown_params: cleaned.unknown_params,
+        removed: cleaned.removed,
+        explanation: Some(explanation),
+        threats,
+    };
+
+    tracing::debug!(url = %redact::url(&processed.cleaned), "cleaned");
+    if !processed.unknown_params.is_empty() {
+        tracing::warn!(
+            url = %redact::url(&processed.cleaned),
+            params = processed.unknown_params.join(", "),
+            "removed query parameters that may be needed"
+        );
+    }
+
+    // Step 5: optionally check that the cleaned URL still works.  If we had to guess which
+    // parameters the site needs, we also check that it is the same page, as far as we
+    // can tell from its title
+    if options.verify && !options.offline {
+        tracing::info!(url = %redact::url(&processed.cleaned), "verifying");
+        let title = match processed.confidence() {
+            Confidence::Guessed => processed.hops.last().and_then(|hop| hop.title.as_deref()),
+            Confidence::Known => None,
+        };
+        let result = verify::verify(resolver, &processed.resolved, &processed.cleaned, title).await;
+        if let Err(e) = &result {
+            tracing::warn!(
+                url = %redact::url(&processed.cleaned),
+                error = %e,
+                "cleaning may have broken link"
+            );
+        }
+        processed.verified = Some(result.is_ok());
+    }
+
+    Ok(processed)
+}
+
+// Screen a resolved URL with `safety::screen`, and, given an API key (and the network),
+// Google Safe Browsing, warning of any threats found
+async fn screen(resolver: &Resolver, resolved: &str, options: &ProcessOptions) -> Vec<Threat> {
+    let mut threats = Url::parse(resolved)
+        .map(|url| safety::screen(&url))
+        .unwrap_or_default();
+    if let Some(key) = &options.safe_browsing_key
+        && !options.offline
+    {
+        tracing::info!(url = %redact::url(resolved), "checking with Safe Browsing");
+        match safety::safe_browsing(resolver.client(), key, resolved).await {
+            Ok(listed) => threats.extend(listed),
+            Err(e) => tracing::warn!(
+                url = %redact::url(resolved),
+                error = %e,
+                "could not check link with Safe Browsing"
+            ),
+        }
+    }
+    for threat in &threats {
+        tracing::warn!(url = %redact::url(resolved), %threat, "link may be malicious");
+    }
+    threats
+}
+
+// Resolve and clean a stream of URLs concurrently, yielding each input alongside its
+// result, in input order
+//
+// At most `concurrency` URLs are in flight at once, and no more inputs are pulled from
+// `inputs` until the consumer takes a result, so arbitrarily long streams can be piped
+// through without buffering them in memory.
+pub fn process_stream<'a, I>(
+    resolver: &'a Resolver,
+    inputs: impl Stream<Item = I> + 'a,
+    concurrency: usize,
+    options: &'a ProcessOptions,
+) -> impl Stream<Item = (I, Result<Processed, ProcessError>)> + 'a
+where
+    I: AsRef<str> + 'a,
+{
+    // Futures are polled concurrently, but yield their results in the order that they
+    // were started, so output lines up with input
+    inputs
+        .map(move |input| async move {
+            let result = process(resolver, input.as_ref(), options).await;
+            (input, result)
+        })
+        .buffered(concurrency.max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn processed(resolved: &str, cleaned: &str) -> Processed {
+        Processed {
+            input: resolved.to_string(),
+            resolved: resolved.to_string(),
+            cleaned: cleaned.to_string(),
+            kind: None,
+            elapsed: Duration::ZERO,
+            hops: Vec::new(),
+            verified: None,
+            unknown_params: Vec::new(),
+            removed: clean::removed_components(
+                &Url::parse(resolved).expect("valid url"),
+                &Url::parse(cleaned).expect("valid url"),
+            ),
+            explanation: None,
+            threats: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_platform() {
+        let p = processed(
+            "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m",
+            "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m",
+        );
+        assert_eq!(p.platform().as_deref(), Some("reddit.com"));
+    }
+
+    #[test]
+    fn test_removed_params() {
+        let p = processed(
+            "https://www.facebook.com/groups/vicdeals/permalink/25654608820855518/?comment_id=25654673274182406&rdid=9etJN9mXDU45vGPw",
+            "https://www.facebook.com/groups/vicdeals/permalink/25654608820855518?comment_id=25654673274182406",
+        );
+        assert_eq!(p.removed_params(), ["rdid"]);
+        assert_eq!(
+            p.removed,
+            [Removed::Param {
+                name: "rdid".to_string(),
+                value: "9e… (16 characters)".to_string()
+            }]
+        );
+        #[cfg(feature = "serde")]
+        {
+            assert_eq!(p.to_json()["removed"][0]["component"], "param");
+            assert_eq!(p.to_json()["confidence"], "known");
+        }
+
+        let p = Processed {
+            unknown_params: vec!["rdid".to_string()],
+            ..p
+        };
+        assert_eq!(p.confidence(), Confidence::Guessed);
+        #[cfg(feature = "serde")]
+        assert_eq!(p.to_json()["unknown_params"][0], "rdid");
+    }
+
+    #[tokio::test]
+    async fn test_removed_redirector() {
+        // The redirector is unwrapped by the cleaner, which no diff of the resolved and
+        // cleaned URLs could tell
+        let resolver = Resolver::new().expect("resolver");
+        let options = ProcessOptions {
+            offline: true,
+            ..ProcessOptions::default()
+        };
+        let processed = process(
+            &resolver,
+            "https://l.facebook.com/l.php?u=https%3A%2F%2Fexample.com%2Fa&h=AT0abc",
+            &options,
+        )
+        .await
+        .expect("processed");
+        assert_eq!(processed.cleaned, "https://example.com/a");
+        assert_eq!(
+            processed.removed,
+            [Removed::Redirector {
+                host: "l.facebook.com".to_string()
+            }]
+        );
+        #[cfg(feature = "serde")]
+        assert_eq!(processed.to_json()["removed"][0]["component"], "redirector");
+    }
+
+    #[tokio::test]
+    async fn test_explanation() {
+        let resolver = Resolver::new().expect("resolver");
+        let mut options = ProcessOptions {
+            offline: true,
+            ..ProcessOptions::default()
+        };
+        let processed = process(&resolver, "https://example.com/a?utm_source=x", &options)
+            .await
+            .expect("processed");
+        assert!(
+            processed
+                .explanation
+                .expect("explained")
+                .to_string()
+                .contains("utm_source")
+        );
+
+        // The decisions made are kept even if cleaning fails
+        options.clean.strict = true;
+        match process(&resolver, "https://example.com/a", &options).await {
+            Err(ProcessError::Clean(CleanUrlError::UnsupportedUrlHost, explanation)) => {
+                assert!(explanation.to_string().contains("no cleaner for domain"))
+            }
+            _ => panic!("expected cleaning to fail"),
+        }
+
+        // Expanding a URL involves no cleaning to explain
+        options.expand_only = true;
+        let processed = process(&resolver, "https://example.com/a", &options)
+            .await
+            .expect("processed");
+        assert!(processed.explanation.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_recognised_not_resolved() {
+        // A full post URL is cleaned without touching the network
+        let resolver = Resolver::new().expect("resolver");
+        let input = "https://www.reddit.com/r/rust/comments/abc123/title/?share_id=xyz";
+        let processed = process(&resolver, input, &ProcessOptions::default())
+            .await
+            .expect("processed");
+        assert!(processed.hops.is_empty());
+        assert_eq!(processed.resolved, input);
+        assert_eq!(
+            processed.cleaned,
+            "https://www.reddit.com/r/rust/comments/abc123"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_encoded_input() {
+        // As copied out of the source of an email
+        let resolver = Resolver::new().expect("resolver");
+        for input in [
+            "https://www.reddit.com/r/rust/comments/abc123/title/?share_id=xyz&amp;utm_source=share",
+            "https%253A%252F%252Fwww.reddit.com%252Fr%252Frust%252Fcomments%252Fabc123%252Ftitle%252F",
+        ] {
+            let processed = process(&resolver, input, &ProcessOptions::default())
+                .await
+                .expect("processed");
+            assert_eq!(processed.input, input);
+            assert_eq!(
+                processed.cleaned,
+                "https://www.reddit.com/r/rust/comments/abc123"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_process_stream_order() {
+        // Invalid inputs fail before touching the network
+        let resolver = Resolver::new().expect("resolver");
+        let inputs = futures::stream::iter(["a", "b", "c"]);
+        let results: Vec<_> = process_stream(&resolver, inputs, 2, &ProcessOptions::default())
+            .collect()
+            .await;
+        let inputs: Vec<_> = results.iter().map(|(input, _)| *input).collect();
+        assert_eq!(inputs, ["a", "b", "c"]);
+        assert!(
+            results
+                .iter()
+                .all(|(_, r)| matches!(r, Err(ProcessError::InvalidInput(_))))
+        );
+    }
+}
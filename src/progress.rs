@@ -0,0 +1,121 @@
+use std::io::{self, IsTerminal};
+
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+use crate::{
+    output::{self, Verbosity},
+    process::{ProcessError, Processed},
+};
+
+const TEMPLATE: &str = "{spinner} [{elapsed_precise}] {wide_bar} {pos}/{len} {msg}";
+
+// Progress reporting for batch runs, drawn on standard error
+//
+// We keep track of how far each URL got through the pipeline: it was either cleaned;
+// resolved but could not be cleaned (e.g., an unsupported host); it failed before
+// resolution completed; or it was skipped, as its host kept failing.
+pub struct Progress {
+    bar: Option<ProgressBar>,
+    enabled: bool,
+    cleaned: usize,
+    resolved: usize,
+    failed: usize,
+    skipped: usize,
+    // Of those cleaned, how many led to dead links
+    dead: usize,
+}
+
+impl Progress {
+    // Progress is only reported for batches of more than one URL, and is suppressed with
+    // `--quiet`.  The bar itself is only drawn when standard error is a terminal, and
+    // not when `--verbose` output would interleave with it
+    pub fn new(len: usize) -> Self {
+        let verbosity = output::verbosity();
+        let enabled = verbosity >= Verbosity::Normal && len > 1;
+        let draw = enabled && verbosity == Verbosity::Normal && io::stderr().is_terminal();
+        let bar = draw.then(|| {
+            let bar = ProgressBar::with_draw_target(Some(len as u64), ProgressDrawTarget::stderr());
+            bar.set_style(
+                ProgressStyle::with_template(TEMPLATE)
+                    .expect("valid progress template")
+                    .progress_chars("=> "),
+            );
+            bar
+        });
+
+        Progress {
+            bar,
+            enabled,
+            cleaned: 0,
+            resolved: 0,
+            failed: 0,
+            skipped: 0,
+            dead: 0,
+        }
+    }
+
+    // Update progress with the result of processing a single URL
+    pub fn record(&mut self, result: &Result<Processed, ProcessError>) {
+        match result {
+            Ok(processed) => {
+                self.cleaned += 1;
+                if processed.is_dead() {
+                    self.dead += 1;
+                }
+            }
+            Err(ProcessError::Clean(..)) => self.resolved += 1,
+            Err(e) if e.is_skipped() => self.skipped += 1,
+            Err(_) => self.failed += 1,
+        }
+
+        if let Some(bar) = &self.bar {
+            bar.set_message(self.status());
+            bar.inc(1);
+        }
+    }
+
+    // Run `f` (e.g., writing a result to standard output) without the progress bar
+    // getting in the way
+    pub fn suspend<R>(&self, f: impl FnOnce() -> R) -> R {
+        match &self.bar {
+            Some(bar) => bar.suspend(f),
+            None => f(),
+        }
+    }
+
+    // Print a message to standard error above the progress bar
+    pub fn eprintln(&self, msg: &str) {
+        self.suspend(|| eprintln!("{}", msg));
+    }
+
+    // Remove the progress bar and print a final summary
+    pub fn finish(self) {
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+        }
+        if self.enabled {
+            eprintln!(
+                "Processed {} URLs: {}",
+                self.cleaned + self.resolved + self.failed + self.skipped,
+                self.status()
+            );
+        }
+    }
+
+    fn status(&self) -> String {
+        let dead = if self.dead > 0 {
+            format!(" ({} dead)", self.dead)
+        } else {
+            String::new()
+        };
+        let status = format!(
+            "{} cleaned{}, {} resolved but not cleaned, {} failed",
+            self.cleaned, dead, self.resolved, self.failed
+        );
+        if self.skipped > 0 {
+            format!("{}, {} skipped", status, self.skipped)
+        } else {
+            status
+        }
+    }
+}
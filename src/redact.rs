@@ -0,0 +1,43 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Redaction is global, as it is set once (e.g., from `--redact-urls`) and consulted
+// wherever a URL is logged
+static REDACT: AtomicBool = AtomicBool::new(false);
+
+// Whether to redact URLs in log messages and spans (`--redact-urls`), so that logs
+// collected from a service embedding RSL do not reveal what its users were looking at
+pub fn set_enabled(redact: bool) {
+    REDACT.store(redact, Ordering::Relaxed);
+}
+
+// `url` as it should appear in logs: if redaction is enabled, only its scheme and host
+// are kept, as the path and query of a share link identify the content (and often the
+// person who shared it)
+pub fn url(url: &str) -> String {
+    if !REDACT.load(Ordering::Relaxed) {
+        return url.to_string();
+    }
+    match url::Url::parse(url) {
+        Ok(url) => format!(
+            "{}://{}/…",
+            url.scheme(),
+            url.host_str().unwrap_or_default()
+        ),
+        Err(_) => "…".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact() {
+        let link = "https://www.reddit.com/r/AskTheWorld/s/mONZu40JNk";
+        assert_eq!(url(link), link);
+        set_enabled(true);
+        assert_eq!(url(link), "https://www.reddit.com/…");
+        assert_eq!(url("not a url"), "…");
+        set_enabled(false);
+    }
+}
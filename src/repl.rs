@@ -0,0 +1,121 @@
+use std::{
+    error::Error,
+    io::{self, IsTerminal, Write},
+};
+
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::{
+    clipboard,
+    history::{self, History},
+    process::{self, ProcessOptions, Processed},
+    report,
+    resolve::Resolver,
+};
+
+const PROMPT: &str = "rsl> ";
+
+const HELP: &str = "\
+Enter a URL to resolve and clean it, or one of the following commands:
+  :diff     toggle showing the difference between input and cleaned URLs
+  :json     toggle JSON output
+  :explain  toggle explaining how each URL was cleaned
+  :history  list URLs resolved during this session
+  :help     show this message
+  :quit     exit the REPL";
+
+// Run the interactive REPL, reading URLs from standard input until EOF or `:quit`
+//
+// Unlike invoking the binary once per link, the REPL keeps a single HTTP client (and
+// hence its connection pool) warm between requests, which makes resolving many links
+// during a research session much faster.
+pub async fn run(
+    resolver: &Resolver,
+    options: &ProcessOptions,
+    saved_history: Option<&History>,
+) -> Result<(), Box<dyn Error>> {
+    let mut history: Vec<Processed> = Vec::new();
+    let mut show_diff = false;
+    let mut show_json = false;
+
+    // Only print a prompt if a human is on the other end
+    let is_tty = io::stdin().is_terminal();
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+
+    loop {
+        if is_tty {
+            print!("{}", PROMPT);
+            io::stdout().flush()?;
+        }
+
+        let Some(line) = lines.next_line().await? else {
+            break;
+        };
+
+        match line.trim() {
+            "" => continue,
+            ":q" | ":quit" | ":exit" => break,
+            ":h" | ":help" => println!("{}", HELP),
+            ":diff" => {
+                show_diff = !show_diff;
+                eprintln!("diff output {}", if show_diff { "on" } else { "off" });
+            }
+            ":json" => {
+                show_json = !show_json;
+                eprintln!("JSON output {}", if show_json { "on" } else { "off" });
+            }
+            ":explain" => {
+                report::set_explain(!report::explain());
+                eprintln!(
+                    "explanations {}",
+                    if report::explain() { "on" } else { "off" }
+                );
+            }
+            ":history" => {
+                for (i, processed) in history.iter().enumerate() {
+                    if show_json {
+                        println!("{}", processed.to_json());
+                    } else {
+                        println!("{:>4}  {}", i + 1, processed.cleaned);
+                    }
+                }
+            }
+            cmd if cmd.starts_with(':') => {
+                eprintln!("Unknown command: {} (try :help)", cmd);
+            }
+            url => {
+                let result = process::process(resolver, url, options).await;
+                report::report(&result);
+                match result {
+                    Ok(processed) => {
+                        if let Some(saved_history) = saved_history {
+                            history::record(saved_history, &processed);
+                        }
+
+                        if processed.threats.is_empty() {
+                            let link = (processed.cleaned.as_str(), processed.title());
+                            if let Err(e) = clipboard::copy_links([link]) {
+                                eprintln!("Error: could not copy to clipboard: {}", e);
+                            }
+                        } else {
+                            eprintln!("Not copied to clipboard, as the link may be malicious");
+                        }
+                        if show_json {
+                            println!("{}", processed.to_json());
+                        } else if show_diff {
+                            println!("- {}", processed.input);
+                            println!("+ {}", processed.cleaned);
+                        } else {
+                            println!("{}", processed.cleaned);
+                        }
+
+                        history.push(processed);
+                    }
+                    Err(e) => eprintln!("Error: {}", e),
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
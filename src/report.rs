@@ -0,0 +1,73 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{
+    output,
+    process::{ProcessError, Processed},
+};
+
+// Like the other output settings (see `output`), these are set once from the command line
+// (or toggled in the REPL), and consulted for every result
+static EXPLAIN: AtomicBool = AtomicBool::new(false);
+static TRACE: AtomicBool = AtomicBool::new(false);
+
+// Whether to explain how each URL was cleaned (`--explain`)
+pub fn set_explain(explain: bool) {
+    EXPLAIN.store(explain, Ordering::Relaxed);
+}
+
+pub fn explain() -> bool {
+    EXPLAIN.load(Ordering::Relaxed)
+}
+
+// Whether to print the full redirect chain for each URL (`--trace`)
+pub fn set_trace(trace: bool) {
+    TRACE.store(trace, Ordering::Relaxed);
+}
+
+pub fn trace() -> bool {
+    TRACE.load(Ordering::Relaxed)
+}
+
+// Print what was asked for about a result to standard error, before the result itself:
+// with `--trace`, the redirect chain it followed, if it was resolved; with `--explain`,
+// how it was cleaned, even if cleaning failed; and with `-v`, what cleaning removed
+pub fn report(result: &Result<Processed, ProcessError>) {
+    match result {
+        Ok(processed) => {
+            // Unless the URL was only expanded, show what cleaning removed
+            if processed.explanation.is_some() {
+                let removed = processed.removed_params();
+                output::step(format_args!(
+                    "Cleaned to {} (removed {})",
+                    processed.cleaned,
+                    output::highlight_removed(&processed.resolved, &removed)
+                ));
+                output::detail(format_args!("removed parameters: {}", removed.join(", ")));
+            }
+            if trace() && !processed.hops.is_empty() {
+                eprintln!("{}", redirect_chain(processed));
+            }
+            if explain()
+                && let Some(explanation) = &processed.explanation
+            {
+                eprintln!("{}", explanation);
+            }
+        }
+        Err(ProcessError::Clean(_, explanation)) if explain() => eprintln!("{}", explanation),
+        Err(_) => {}
+    }
+}
+
+fn redirect_chain(processed: &Processed) -> String {
+    let mut chain = format!("Redirect chain for {}:", processed.input);
+    for (i, hop) in processed.hops.iter().enumerate() {
+        chain.push_str(&format!(
+            "\n  {}. {} in {} ms",
+            i + 1,
+            hop,
+            hop.elapsed.as_millis()
+        ));
+    }
+    chain.push_str(&format!("\n  Total: {} ms", processed.elapsed.as_millis()));
+    chain
+}
@@ -0,0 +1,108 @@
+use std::{error::Error, time::Duration};
+
+use chromiumoxide::{Browser, BrowserConfig};
+use futures::{StreamExt, future::BoxFuture};
+use url::Url;
+
+use super::{DEFAULT_TIMEOUT, ResolveError, ResolveOutput, UrlResolver, matches_domain};
+use crate::redact;
+
+// Resolves links by loading them in headless Chromium, for hosts whose redirects only
+// happen in JavaScript, or behind a cookie challenge that plain requests cannot pass
+//
+// Launching a browser is slow and heavy, so this only ever handles the domains it is
+// given (and their subdomains), and is never added to a `Resolver` by default.
+// Chromium (or Chrome) must be installed.
+pub struct BrowserResolver {
+    domains: Vec<String>,
+    timeout: Duration,
+}
+
+impl BrowserResolver {
+    pub fn new(domains: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        BrowserResolver {
+            domains: domains.into_iter().map(Into::into).collect(),
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    // How long to wait for the page to finish navigating, including launching the browser
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn is_allowed(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        self.domains
+            .iter()
+            .any(|domain| matches_domain(host, domain))
+    }
+
+    // Load the page, wait for it (and any redirects it makes) to settle, and read the URL
+    // the browser ended up on
+    async fn navigate(&self, url: &Url) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let config = BrowserConfig::builder().build()?;
+        let (mut browser, mut handler) = Browser::launch(config).await?;
+        let events = tokio::spawn(async move { while handler.next().await.is_some() {} });
+
+        let result: Result<_, Box<dyn Error + Send + Sync>> = async {
+            let page = browser.new_page(url.as_str()).await?;
+            page.wait_for_navigation().await?;
+            Ok(page.url().await?.unwrap_or_else(|| url.to_string()))
+        }
+        .await;
+
+        let _ = browser.close().await;
+        let _ = browser.wait().await;
+        events.abort();
+        result
+    }
+}
+
+impl UrlResolver for BrowserResolver {
+    fn name(&self) -> &'static str {
+        "BrowserResolver"
+    }
+
+    fn resolve<'a>(
+        &'a self,
+        _client: &'a reqwest::Client,
+        url: &'a Url,
+    ) -> Option<BoxFuture<'a, ResolveOutput>> {
+        if !self.is_allowed(url) {
+            return None;
+        }
+        Some(Box::pin(async move {
+            match tokio::time::timeout(self.timeout, self.navigate(url)).await {
+                Ok(Ok(resolved)) => Ok(resolved),
+                Ok(Err(e)) => {
+                    tracing::debug!(
+                        url = %redact::url(url.as_str()),
+                        error = %e,
+                        "browser navigation failed"
+                    );
+                    Err(ResolveError::Api(self.name()))
+                }
+                Err(_) => Err(ResolveError::Timeout),
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_allowed() {
+        let resolver = BrowserResolver::new(["Example.com"]);
+        let allowed = |url| resolver.is_allowed(&Url::parse(url).expect("valid url"));
+        assert!(allowed("https://example.com/a"));
+        assert!(allowed("https://www.example.com/a"));
+        assert!(!allowed("https://notexample.com/a"));
+        assert!(!allowed("https://example.com.evil.org/a"));
+    }
+}
@@ -0,0 +1,144 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use reqwest::StatusCode;
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::{Deserialize, Serialize};
+
+use crate::resolve::{Hop, HopKind};
+
+// How hops are stored on disk (as JSON), as `Hop` itself is only serialisable with the
+// `serde` feature, and not deserialisable at all
+#[derive(Serialize, Deserialize)]
+struct StoredHop {
+    status: u16,
+    url: String,
+    kind: String,
+    #[serde(default)]
+    elapsed_ms: u64,
+    title: Option<String>,
+}
+
+const HOP_KINDS: [HopKind; 11] = [
+    HopKind::Start,
+    HopKind::HttpRedirect,
+    HopKind::MetaRefresh,
+    HopKind::RefreshHeader,
+    HopKind::Api,
+    HopKind::Canonical,
+    HopKind::OgUrl,
+    HopKind::OEmbed,
+    HopKind::HttpsUpgrade,
+    HopKind::Unavailable,
+    HopKind::Locale,
+];
+
+impl From<&Hop> for StoredHop {
+    fn from(hop: &Hop) -> Self {
+        StoredHop {
+            status: hop.status.as_u16(),
+            url: hop.url.clone(),
+            kind: hop.kind.to_string(),
+            elapsed_ms: hop.elapsed.as_millis() as u64,
+            title: hop.title.clone(),
+        }
+    }
+}
+
+impl StoredHop {
+    fn into_hop(self) -> Option<Hop> {
+        Some(Hop {
+            status: StatusCode::from_u16(self.status).ok()?,
+            url: self.url,
+            kind: HOP_KINDS
+                .into_iter()
+                .find(|kind| kind.to_string() == self.kind)?,
+            elapsed: Duration::from_millis(self.elapsed_ms),
+            title: self.title,
+        })
+    }
+}
+
+fn unix_time(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+// The SQLite database a `ResolveCache` persists entries to (see `ResolveCache::persist`)
+pub(super) struct Disk(Mutex<Connection>);
+
+impl Disk {
+    pub(super) fn new(conn: Connection) -> rusqlite::Result<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS cache (
+                url     TEXT PRIMARY KEY,
+                hops    TEXT NOT NULL,
+                expires INTEGER NOT NULL
+            );",
+        )?;
+        conn.execute(
+            "DELETE FROM cache WHERE expires <= ?1",
+            params![unix_time(SystemTime::now())],
+        )?;
+        Ok(Disk(Mutex::new(conn)))
+    }
+
+    // The stored hops for `url`, and when they expire, if they have not already
+    pub(super) fn get(&self, url: &str) -> Option<(Vec<Hop>, SystemTime)> {
+        let conn = self.0.lock().ok()?;
+        let (json, expires): (String, i64) = conn
+            .query_row(
+                "SELECT hops, expires FROM cache WHERE url = ?1 AND expires > ?2",
+                params![url, unix_time(SystemTime::now())],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .inspect_err(|e| tracing::warn!(error = %e, "could not read from cache"))
+            .ok()??;
+        let hops: Vec<StoredHop> = serde_json::from_str(&json).ok()?;
+        let hops = hops
+            .into_iter()
+            .map(StoredHop::into_hop)
+            .collect::<Option<_>>()?;
+        Some((hops, UNIX_EPOCH + Duration::from_secs(expires as u64)))
+    }
+
+    pub(super) fn insert(&self, url: &str, hops: &[Hop], expires: SystemTime) {
+        let Ok(conn) = self.0.lock() else {
+            return;
+        };
+        let hops: Vec<StoredHop> = hops.iter().map(StoredHop::from).collect();
+        let Ok(json) = serde_json::to_string(&hops) else {
+            return;
+        };
+        if let Err(e) = conn.execute(
+            "INSERT OR REPLACE INTO cache (url, hops, expires) VALUES (?1, ?2, ?3)",
+            params![url, json, unix_time(expires)],
+        ) {
+            tracing::warn!(error = %e, "could not write to cache");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hop_kinds() {
+        for kind in HOP_KINDS {
+            let hop = Hop {
+                status: StatusCode::OK,
+                url: String::new(),
+                kind,
+                elapsed: Duration::ZERO,
+                title: None,
+            };
+            let stored = StoredHop::from(&hop).into_hop().expect("hop");
+            assert_eq!(stored.kind, kind);
+        }
+    }
+}
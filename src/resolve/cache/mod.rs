@@ -0,0 +1,265 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+#[cfg(feature = "disk-cache")]
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "disk-cache")]
+use rusqlite::Connection;
+
+use super::Hop;
+
+#[cfg(feature = "disk-cache")]
+mod disk;
+
+#[cfg(feature = "disk-cache")]
+const CACHE_FILE_NAME: &str = "cache.sqlite3";
+const DEFAULT_CAPACITY: usize = 1024;
+const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+// Cache of resolved redirect chains, keyed by the input URL
+//
+// Share links are often resolved again and again (e.g., when the same link is pasted
+// twice, or appears in several files), and where they lead rarely changes, so there is
+// no need to go back to the network each time.  Recently used results are kept in
+// memory, and optionally (with the `disk-cache` feature) in a SQLite database on disk so
+// that they survive between runs.  Entries expire after the cache's TTL, and only
+// successful resolutions are cached.
+pub struct ResolveCache {
+    ttl: Duration,
+    capacity: usize,
+    memory: Mutex<Lru>,
+    #[cfg(feature = "disk-cache")]
+    disk: Option<disk::Disk>,
+}
+
+// Least-recently-used map of URLs to their hops and when they expire
+#[derive(Default)]
+struct Lru {
+    entries: HashMap<String, (Vec<Hop>, SystemTime)>,
+    // Keys, least recently used first
+    order: VecDeque<String>,
+}
+
+impl Lru {
+    fn get(&mut self, url: &str) -> Option<(Vec<Hop>, SystemTime)> {
+        let entry = self.entries.get(url)?.clone();
+        self.touch(url);
+        Some(entry)
+    }
+
+    fn insert(&mut self, url: &str, hops: Vec<Hop>, expires: SystemTime, capacity: usize) {
+        if self
+            .entries
+            .insert(url.to_string(), (hops, expires))
+            .is_some()
+        {
+            self.touch(url);
+        } else {
+            self.order.push_back(url.to_string());
+        }
+        while self.entries.len() > capacity
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.entries.remove(&oldest);
+        }
+    }
+
+    fn remove(&mut self, url: &str) {
+        self.entries.remove(url);
+        self.order.retain(|key| key != url);
+    }
+
+    fn touch(&mut self, url: &str) {
+        if let Some(i) = self.order.iter().position(|key| key == url) {
+            let key = self.order.remove(i).unwrap_or_default();
+            self.order.push_back(key);
+        }
+    }
+}
+
+impl fmt::Debug for ResolveCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("ResolveCache");
+        debug
+            .field("ttl", &self.ttl)
+            .field("capacity", &self.capacity);
+        #[cfg(feature = "disk-cache")]
+        debug.field("persistent", &self.disk.is_some());
+        debug.finish()
+    }
+}
+
+impl Default for ResolveCache {
+    fn default() -> Self {
+        ResolveCache::new()
+    }
+}
+
+impl ResolveCache {
+    // An in-memory cache, holding up to 1024 entries for a day each
+    pub fn new() -> Self {
+        ResolveCache {
+            ttl: DEFAULT_TTL,
+            capacity: DEFAULT_CAPACITY,
+            memory: Mutex::default(),
+            #[cfg(feature = "disk-cache")]
+            disk: None,
+        }
+    }
+
+    // How long resolutions are cached for
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    // Maximum number of entries to keep in memory.  The disk cache is not limited
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    // Default location of the disk cache, under the platform's cache directory (e.g.,
+    // `~/.cache/rsl/cache.sqlite3` on Linux)
+    #[cfg(feature = "disk-cache")]
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::cache_dir().map(|dir| dir.join(env!("CARGO_PKG_NAME")).join(CACHE_FILE_NAME))
+    }
+
+    // Also store entries in the SQLite database at `path`, creating it if needed
+    #[cfg(feature = "disk-cache")]
+    pub fn persist(self, path: &Path) -> rusqlite::Result<Self> {
+        if let Some(parent) = path.parent() {
+            // If this fails, so will opening the database, with a clearer error
+            let _ = fs::create_dir_all(parent);
+        }
+        self.with_connection(Connection::open(path)?)
+    }
+
+    #[cfg(feature = "disk-cache")]
+    fn with_connection(mut self, conn: Connection) -> rusqlite::Result<Self> {
+        self.disk = Some(disk::Disk::new(conn)?);
+        Ok(self)
+    }
+
+    // The cached hops for `url`, if any have not expired
+    pub fn get(&self, url: &str) -> Option<Vec<Hop>> {
+        let now = SystemTime::now();
+        if let Ok(mut memory) = self.memory.lock()
+            && let Some((hops, expires)) = memory.get(url)
+        {
+            if expires > now {
+                return Some(hops);
+            }
+            memory.remove(url);
+        }
+
+        #[cfg(feature = "disk-cache")]
+        if let Some((hops, expires)) = self.disk.as_ref().and_then(|disk| disk.get(url)) {
+            if let Ok(mut memory) = self.memory.lock() {
+                memory.insert(url, hops.clone(), expires, self.capacity);
+            }
+            return Some(hops);
+        }
+        None
+    }
+
+    // Cache the hops taken to resolve `url`
+    pub fn insert(&self, url: &str, hops: &[Hop]) {
+        let expires = SystemTime::now() + self.ttl;
+        if let Ok(mut memory) = self.memory.lock() {
+            memory.insert(url, hops.to_vec(), expires, self.capacity);
+        }
+
+        #[cfg(feature = "disk-cache")]
+        if let Some(disk) = &self.disk {
+            disk.insert(url, hops, expires);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::StatusCode;
+
+    use super::{super::HopKind, *};
+
+    fn hops(url: &str) -> Vec<Hop> {
+        vec![
+            Hop {
+                status: StatusCode::MOVED_PERMANENTLY,
+                url: "https://example.com/s/abc".to_string(),
+                kind: HopKind::Start,
+                elapsed: Duration::ZERO,
+                title: None,
+            },
+            Hop {
+                status: StatusCode::OK,
+                url: url.to_string(),
+                kind: HopKind::HttpRedirect,
+                elapsed: Duration::ZERO,
+                title: Some("Title".to_string()),
+            },
+        ]
+    }
+
+    fn final_url(cache: &ResolveCache, url: &str) -> Option<String> {
+        cache.get(url).map(|hops| crate::resolve::final_url(&hops))
+    }
+
+    #[test]
+    fn test_memory() {
+        let cache = ResolveCache::new();
+        assert!(cache.get("https://example.com/s/abc").is_none());
+        cache.insert(
+            "https://example.com/s/abc",
+            &hops("https://example.com/post"),
+        );
+        assert_eq!(
+            final_url(&cache, "https://example.com/s/abc").as_deref(),
+            Some("https://example.com/post")
+        );
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let cache = ResolveCache::new().capacity(2);
+        cache.insert("a", &hops("https://example.com/a"));
+        cache.insert("b", &hops("https://example.com/b"));
+        assert!(cache.get("a").is_some());
+        cache.insert("c", &hops("https://example.com/c"));
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("c").is_some());
+    }
+
+    #[test]
+    fn test_expires() {
+        let cache = ResolveCache::new().ttl(Duration::ZERO);
+        cache.insert("a", &hops("https://example.com/a"));
+        assert!(cache.get("a").is_none());
+    }
+
+    #[cfg(feature = "disk-cache")]
+    #[test]
+    fn test_disk() {
+        let conn = Connection::open_in_memory().expect("in-memory database");
+        let cache = ResolveCache::new().with_connection(conn).expect("cache");
+        cache.insert("a", &hops("https://example.com/a"));
+
+        // Forget the in-memory entry, so that it must come from the database
+        cache.memory.lock().expect("lock").remove("a");
+        let cached = cache.get("a").expect("cached");
+        assert_eq!(cached.len(), 2);
+        assert_eq!(cached[0].status, StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(cached[1].kind, HopKind::HttpRedirect);
+        assert_eq!(cached[1].title.as_deref(), Some("Title"));
+    }
+}
@@ -0,0 +1,169 @@
+use url::Url;
+
+use super::{HopKind, html, same_site};
+use crate::redact;
+
+// The canonical URL advertised in the page's head, e.g.:
+// <link rel="canonical" href="https://example.com/post/1">
+fn extract_canonical(html: &str) -> Option<String> {
+    html::tags(html, "link").into_iter().find_map(|tag| {
+        let rel = html::attribute(tag, "rel")?;
+        rel.split_ascii_whitespace()
+            .any(|rel| rel.eq_ignore_ascii_case("canonical"))
+            .then(|| html::attribute(tag, "href"))?
+    })
+}
+
+// The Open Graph URL of the page, e.g.:
+// <meta property="og:url" content="https://example.com/post/1">
+fn extract_og_url(html: &str) -> Option<String> {
+    html::tags(html, "meta").into_iter().find_map(|tag| {
+        let property = html::attribute(tag, "property")?;
+        property
+            .eq_ignore_ascii_case("og:url")
+            .then(|| html::attribute(tag, "content"))?
+    })
+}
+
+// Resolve a URL given by the page, as long as it is a plausible canonical form of it
+//
+// We ignore URLs on other sites, and those pointing at the home page from elsewhere, as
+// some sites use these for every page behind a login wall.
+fn plausible(page: &Url, url: Option<String>) -> Option<Url> {
+    let url = page.join(&url?).ok()?;
+    if url.path() == "/" && page.path() != "/" {
+        return None;
+    }
+    same_site(&url, page).then_some(url)
+}
+
+// Whether two URLs are the same but for a trailing slash
+fn equivalent(a: &Url, b: &Url) -> bool {
+    a.host_str() == b.host_str()
+        && a.path().trim_end_matches('/') == b.path().trim_end_matches('/')
+        && a.query() == b.query()
+}
+
+// The canonical URL of `page`, and where we found it
+//
+// Many platforms land us on locale- or session-specific URLs while advertising the true
+// canonical form in the head, either as a `<link rel="canonical">` or as the page's
+// `og:url` (Facebook and Instagram often give only the latter).  In order of precedence,
+// we use:
+//
+//   1. `<link rel="canonical">`, the standard way to declare a canonical URL
+//   2. `<meta property="og:url">`
+//   3. the URL we were redirected to (i.e., `None`)
+//
+// If the page gives both and they disagree, we warn, as one of them is likely stale.
+pub(super) fn canonical_url(page: &Url, html: &str) -> Option<(Url, HopKind)> {
+    let link = plausible(page, extract_canonical(html));
+    let og_url = plausible(page, extract_og_url(html));
+    match (link, og_url) {
+        (Some(link), Some(og_url)) => {
+            if !equivalent(&link, &og_url) {
+                tracing::warn!(
+                    url = %redact::url(page.as_str()),
+                    canonical = %redact::url(link.as_str()),
+                    og_url = %redact::url(og_url.as_str()),
+                    "page's canonical link and og:url disagree; using canonical link"
+                );
+            }
+            Some((link, HopKind::Canonical))
+        }
+        (Some(link), None) => Some((link, HopKind::Canonical)),
+        (None, Some(og_url)) => Some((og_url, HopKind::OgUrl)),
+        (None, None) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canonical(page: &str, html: &str) -> Option<(String, HopKind)> {
+        let page = Url::parse(page).expect("valid url");
+        canonical_url(&page, html).map(|(url, kind)| (url.to_string(), kind))
+    }
+
+    #[test]
+    fn test_canonical_url() {
+        let html = r#"<head><link rel="stylesheet" href="/a.css"><link rel="canonical" href="https://www.example.com/post/1"></head>"#;
+        assert_eq!(
+            canonical("https://www.example.com/en-gb/post/1?session=x", html),
+            Some((
+                "https://www.example.com/post/1".to_string(),
+                HopKind::Canonical
+            ))
+        );
+    }
+
+    #[test]
+    fn test_relative() {
+        let html = r#"<link href="/post/1" rel="canonical">"#;
+        assert_eq!(
+            canonical("https://www.example.com/en-gb/post/1", html),
+            Some((
+                "https://www.example.com/post/1".to_string(),
+                HopKind::Canonical
+            ))
+        );
+    }
+
+    #[test]
+    fn test_og_url() {
+        let html = r#"<meta property="og:title" content="Post"><meta property="og:url" content="https://www.instagram.com/p/DS8F57NjS_S/">"#;
+        assert_eq!(
+            canonical("https://www.instagram.com/p/DS8F57NjS_S/?igsh=x", html),
+            Some((
+                "https://www.instagram.com/p/DS8F57NjS_S/".to_string(),
+                HopKind::OgUrl
+            ))
+        );
+    }
+
+    #[test]
+    fn test_precedence() {
+        let html = r#"
+            <meta property="og:url" content="https://www.example.com/post/1?from=og">
+            <link rel="canonical" href="https://www.example.com/post/1">
+        "#;
+        assert_eq!(
+            canonical("https://www.example.com/en-gb/post/1", html),
+            Some((
+                "https://www.example.com/post/1".to_string(),
+                HopKind::Canonical
+            ))
+        );
+    }
+
+    #[test]
+    fn test_equivalent() {
+        let url = |s| Url::parse(s).expect("valid url");
+        assert!(equivalent(
+            &url("https://www.example.com/post/1/"),
+            &url("https://www.example.com/post/1")
+        ));
+        assert!(!equivalent(
+            &url("https://www.example.com/post/1?a=1"),
+            &url("https://www.example.com/post/1")
+        ));
+    }
+
+    #[test]
+    fn test_ignored() {
+        // Another site
+        let html = r#"<link rel="canonical" href="https://other.example.org/post/1">"#;
+        assert_eq!(canonical("https://www.example.com/post/1", html), None);
+
+        // The home page
+        let html = r#"<meta property="og:url" content="https://www.example.com/">"#;
+        assert_eq!(canonical("https://www.example.com/post/1", html), None);
+
+        // No canonical link
+        assert_eq!(
+            canonical("https://www.example.com/post/1", "<head></head>"),
+            None
+        );
+    }
+}
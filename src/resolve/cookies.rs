@@ -0,0 +1,234 @@
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use cookie_store::{CookieStore, RawCookie};
+use reqwest::header::HeaderValue;
+use url::Url;
+
+// Error type for loading and saving a persistent cookie jar
+#[derive(Debug, derive_more::Display)]
+pub enum CookieJarError {
+    Io(io::Error),
+    #[display("invalid cookie jar: {_0}")]
+    Json(cookie_store::Error),
+}
+
+impl Error for CookieJarError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CookieJarError::Io(e) => Some(e),
+            CookieJarError::Json(e) => Some(e.as_ref()),
+        }
+    }
+}
+
+impl From<io::Error> for CookieJarError {
+    fn from(e: io::Error) -> Self {
+        CookieJarError::Io(e)
+    }
+}
+
+// The cookies set while resolving one link (see `scoped`)
+tokio::task_local! {
+    static SESSION: Mutex<CookieStore>;
+}
+
+// Resolve a link with its own cookies, which an in-memory jar keeps only until it is
+// resolved, so that one link cannot influence how another resolves
+pub(super) async fn scoped<F: Future>(future: F) -> F::Output {
+    SESSION.scope(Mutex::default(), future).await
+}
+
+// Cookies set while resolving, which are sent back on later requests
+//
+// Some share links (e.g., Facebook's and LinkedIn's) set cookies on the first hop, and
+// only give the real redirect once those cookies are presented.  By default cookies are
+// kept only while resolving the link that set them (see `scoped`), but the jar can be
+// saved to a file so that cookies (e.g., consent choices) are remembered between links
+// and runs.
+#[derive(Debug, Default)]
+pub struct CookieJar {
+    store: Mutex<CookieStore>,
+    path: Option<PathBuf>,
+}
+
+impl CookieJar {
+    // An empty, in-memory jar
+    pub fn new() -> Self {
+        CookieJar::default()
+    }
+
+    // A jar backed by the JSON file at `path`, which is loaded now (if it exists), and
+    // saved with `save` (or when the jar is dropped).  Session cookies are not saved
+    pub fn persistent(path: impl Into<PathBuf>) -> Result<Self, CookieJarError> {
+        let path = path.into();
+        let store = match File::open(&path) {
+            Ok(file) => cookie_store::serde::json::load(BufReader::new(file))
+                .map_err(CookieJarError::Json)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => CookieStore::default(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(CookieJar {
+            store: Mutex::new(store),
+            path: Some(path),
+        })
+    }
+
+    // Save the jar to its file, if it has one
+    pub fn save(&self) -> Result<(), CookieJarError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let store = self.store.lock().unwrap_or_else(|e| e.into_inner());
+        Self::write(&store, path)
+    }
+
+    // Write `store` to a temporary file alongside `path`, and then move it into place, so
+    // that an interrupted save never leaves a truncated jar (as with the CLI's output)
+    fn write(store: &CookieStore, path: &Path) -> Result<(), CookieJarError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let name = path
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file path"))?;
+        let tmp = path.with_file_name(format!(
+            ".{}.{}.tmp",
+            name.to_string_lossy(),
+            std::process::id()
+        ));
+
+        let result = File::create(&tmp)
+            .map_err(CookieJarError::from)
+            .and_then(|file| {
+                let mut writer = BufWriter::new(file);
+                cookie_store::serde::json::save(store, &mut writer)
+                    .map_err(CookieJarError::Json)?;
+                writer.flush()?;
+                writer.get_ref().sync_all()?;
+                Ok(fs::rename(&tmp, path)?)
+            });
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        result
+    }
+
+    // The store to use for the link being resolved: its own, unless the jar is kept
+    // between links
+    fn with_store<T>(&self, f: impl FnOnce(&mut CookieStore) -> T) -> Option<T> {
+        if self.path.is_none() && SESSION.try_with(|_| ()).is_ok() {
+            return SESSION.with(|store| Some(f(&mut *store.lock().ok()?)));
+        }
+        Some(f(&mut *self.store.lock().ok()?))
+    }
+}
+
+impl Drop for CookieJar {
+    fn drop(&mut self) {
+        if let Err(e) = self.save() {
+            tracing::warn!(error = %e, "could not save cookie jar");
+        }
+    }
+}
+
+impl reqwest::cookie::CookieStore for CookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        let cookies = cookie_headers
+            .filter_map(|value| value.to_str().ok())
+            .filter_map(|value| RawCookie::parse(value.to_string()).ok());
+        self.with_store(|store| store.store_response_cookies(cookies, url));
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        let cookies = self.with_store(|store| {
+            store
+                .get_request_values(url)
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect::<Vec<_>>()
+                .join("; ")
+        })?;
+        if cookies.is_empty() {
+            None
+        } else {
+            HeaderValue::from_str(&cookies).ok()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::cookie::CookieStore as _;
+
+    use super::*;
+
+    fn set(jar: &CookieJar, url: &Url, cookie: &'static str) {
+        jar.set_cookies(&mut [HeaderValue::from_static(cookie)].iter(), url);
+    }
+
+    // The cookies sent to `url`, in a stable order
+    fn sent(jar: &CookieJar, url: &str) -> Vec<String> {
+        let url = Url::parse(url).expect("valid url");
+        let mut cookies: Vec<_> = jar
+            .cookies(&url)
+            .and_then(|value| value.to_str().ok().map(str::to_string))
+            .map(|value| value.split("; ").map(str::to_string).collect())
+            .unwrap_or_default();
+        cookies.sort();
+        cookies
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let jar = CookieJar::new();
+        let url = Url::parse("https://www.facebook.com/share/abc/").expect("valid url");
+        assert!(sent(&jar, url.as_str()).is_empty());
+
+        set(&jar, &url, "datr=abc; Domain=.facebook.com; Path=/");
+        set(&jar, &url, "locale=en_GB; Path=/share");
+        assert_eq!(sent(&jar, url.as_str()), ["datr=abc", "locale=en_GB"]);
+
+        // Cookies only go back to the domain (and path) that set them
+        assert_eq!(sent(&jar, "https://m.facebook.com/story.php"), ["datr=abc"]);
+        assert!(sent(&jar, "https://example.com/").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_scoped() {
+        let jar = CookieJar::new();
+        let url = Url::parse("https://www.facebook.com/share/abc/").expect("valid url");
+
+        // Each link keeps its cookies to itself
+        scoped(async {
+            set(&jar, &url, "datr=abc");
+            assert_eq!(sent(&jar, url.as_str()), ["datr=abc"]);
+        })
+        .await;
+        scoped(async { assert!(sent(&jar, url.as_str()).is_empty()) }).await;
+    }
+
+    #[tokio::test]
+    async fn test_persistent() {
+        let path = std::env::temp_dir().join(format!("rsl-cookies-{}.json", std::process::id()));
+        let url = Url::parse("https://www.linkedin.com/").expect("valid url");
+
+        // Kept between links, too, and saved once we are done
+        let jar = CookieJar::persistent(&path).expect("jar");
+        scoped(async { set(&jar, &url, "session=abc") }).await;
+        scoped(async { set(&jar, &url, "consent=yes; Max-Age=3600") }).await;
+        assert_eq!(sent(&jar, url.as_str()), ["consent=yes", "session=abc"]);
+        assert!(!path.exists());
+        jar.save().expect("saved");
+
+        // Only the persistent cookie survives
+        let reloaded = CookieJar::persistent(&path).expect("jar");
+        assert_eq!(sent(&reloaded, url.as_str()), ["consent=yes"]);
+        drop((jar, reloaded));
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,108 @@
+use futures::future::BoxFuture;
+use url::Url;
+
+use super::{ResolveError, ResolveOutput, UrlResolver};
+
+const OEMBED_POST: &str = "https://www.facebook.com/plugins/post/oembed.json/";
+const OEMBED_VIDEO: &str = "https://www.facebook.com/plugins/video/oembed.json/";
+
+// Resolves Facebook share links (`/share/<code>`, `/share/p/<code>`, etc.) via the oEmbed
+// endpoint
+//
+// The embed markup that oEmbed returns links to the canonical permalink of the post or
+// video, so we get it from a single small JSON response rather than having to convince
+// Facebook that we are a real browser (see `ResolverBuilder::build`).
+pub struct FacebookResolver;
+
+// The oEmbed endpoint for the share link, if it is one.  Videos (`/share/v/`) and reels
+// (`/share/r/`) have their own endpoint
+fn oembed_endpoint(url: &Url) -> Option<&'static str> {
+    let host = url.host_str().unwrap_or_default();
+    if host != "facebook.com" && !host.ends_with(".facebook.com") {
+        return None;
+    }
+    let segments: Vec<_> = url
+        .path_segments()
+        .map(|s| s.filter(|s| !s.is_empty()).collect())
+        .unwrap_or_default();
+    match segments.as_slice() {
+        ["share", "v" | "r", _] => Some(OEMBED_VIDEO),
+        ["share", _] | ["share", _, _] => Some(OEMBED_POST),
+        _ => None,
+    }
+}
+
+// Extract the permalink from the `data-href` attribute of the embed markup, e.g.:
+// <div class="fb-post" data-href="https://www.facebook.com/..." data-width="500">
+fn extract_data_href(html: &str) -> Option<String> {
+    let start = html.find("data-href=\"")? + "data-href=\"".len();
+    let end = html[start..].find('"')?;
+    Some(html[start..start + end].replace("&amp;", "&"))
+}
+
+impl UrlResolver for FacebookResolver {
+    fn name(&self) -> &'static str {
+        "FacebookResolver"
+    }
+
+    fn resolve<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        url: &'a Url,
+    ) -> Option<BoxFuture<'a, ResolveOutput>> {
+        let endpoint = oembed_endpoint(url)?;
+        Some(Box::pin(async move {
+            let endpoint = Url::parse_with_params(endpoint, [("url", url.as_str())])?;
+            let response = client.get(endpoint).send().await?;
+            let status = response.status();
+            if !status.is_success() {
+                return Err(ResolveError::Http(status));
+            }
+            let body = response.text().await?;
+            let permalink = serde_json::from_str::<serde_json::Value>(&body)
+                .ok()
+                .and_then(|oembed| extract_data_href(oembed["html"].as_str()?))
+                .ok_or(ResolveError::Api(self.name()))?;
+            Ok(Url::parse(&permalink)?.to_string())
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_oembed_endpoint() {
+        let endpoint = |url| oembed_endpoint(&Url::parse(url).expect("valid url"));
+        assert_eq!(
+            endpoint("https://www.facebook.com/share/p/187BayNfDu/"),
+            Some(OEMBED_POST)
+        );
+        assert_eq!(
+            endpoint("https://www.facebook.com/share/17cLnKdQth/"),
+            Some(OEMBED_POST)
+        );
+        assert_eq!(
+            endpoint("https://www.facebook.com/share/r/14QeSSeP3nu/"),
+            Some(OEMBED_VIDEO)
+        );
+        assert_eq!(
+            endpoint("https://www.facebook.com/reel/1309748351194528"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_extract_data_href() {
+        let html = r#"<div id="fb-root"></div><div class="fb-post" data-href="https://www.facebook.com/photo.php?fbid=1279617124197361&amp;set=a.301086902050393" data-width="552"></div>"#;
+        assert_eq!(
+            extract_data_href(html),
+            Some(
+                "https://www.facebook.com/photo.php?fbid=1279617124197361&set=a.301086902050393"
+                    .to_string()
+            )
+        );
+        assert_eq!(extract_data_href("<div></div>"), None);
+    }
+}
@@ -0,0 +1,159 @@
+// Minimal helpers for picking tags and attributes out of the pages we fetch.  We only
+// ever need a handful of tags from the `<head>`, so this is deliberately not a full HTML
+// parser
+
+use encoding_rs::{Encoding, UTF_8};
+
+// Every `<name ...>` tag in `html` (excluding the closing `>`)
+pub(super) fn tags<'a>(html: &'a str, name: &str) -> Vec<&'a str> {
+    let lower = html.to_ascii_lowercase();
+    let open = format!("<{}", name.to_ascii_lowercase());
+    let mut tags = Vec::new();
+    let mut offset = 0;
+    while let Some(start) = lower[offset..].find(&open).map(|i| offset + i) {
+        let after = start + open.len();
+        // Make sure we matched the whole tag name (e.g., `<link`, not `<linkage`)
+        if !lower[after..].starts_with(|c: char| c.is_ascii_whitespace() || c == '/' || c == '>') {
+            offset = after;
+            continue;
+        }
+        let Some(end) = lower[after..].find('>').map(|i| after + i) else {
+            break;
+        };
+        tags.push(&html[start..end]);
+        offset = end;
+    }
+    tags
+}
+
+// The value of the attribute `name` in `tag`, with quotes removed and common entities
+// decoded
+pub(super) fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let name = name.to_ascii_lowercase();
+    let mut offset = 0;
+    while let Some(start) = lower[offset..].find(&name).map(|i| offset + i) {
+        offset = start + name.len();
+
+        // The name must stand alone (e.g., `href`, not `data-href`), and be followed by
+        // `=` (possibly surrounded by whitespace)
+        let preceded = lower[..start]
+            .chars()
+            .next_back()
+            .is_some_and(|c| !c.is_ascii_whitespace());
+        let rest = tag[offset..].trim_start();
+        let Some(value) = rest.strip_prefix('=').filter(|_| !preceded) else {
+            continue;
+        };
+        let value = value.trim_start();
+        let value = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => value[1..].split(quote).next()?,
+            _ => value.split_ascii_whitespace().next()?,
+        };
+        return Some(decode_entities(value));
+    }
+    None
+}
+
+// Decode a page in its character encoding: that given by its `Content-Type` header, or
+// else by its byte order mark or `<meta charset>`, or else UTF-8
+//
+// Older sites in particular are often not UTF-8, and decoding them as if they were would
+// mangle any non-ASCII characters in the URLs we find in them.
+pub(super) fn decode(body: &[u8], content_type: Option<&str>) -> String {
+    let encoding = content_type
+        .and_then(content_type_charset)
+        .or_else(|| meta_charset(body))
+        .unwrap_or(UTF_8);
+    // This prefers a byte order mark, if there is one, to `encoding`
+    let (html, _, _) = encoding.decode(body);
+    html.into_owned()
+}
+
+// The encoding in a `Content-Type` header, e.g., `text/html; charset=ISO-8859-1`
+fn content_type_charset(content_type: &str) -> Option<&'static Encoding> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("charset")
+            .then(|| Encoding::for_label(value.trim().trim_matches('"').as_bytes()))?
+    })
+}
+
+// The encoding given in the page itself, by either of:
+// <meta charset="windows-1252">
+// <meta http-equiv="Content-Type" content="text/html; charset=windows-1252">
+fn meta_charset(body: &[u8]) -> Option<&'static Encoding> {
+    // The tags themselves are ASCII, which any encoding a page could declare this way
+    // leaves alone
+    let html = String::from_utf8_lossy(body);
+    let encoding = tags(&html, "meta").into_iter().find_map(|tag| {
+        attribute(tag, "charset")
+            .and_then(|label| Encoding::for_label(label.as_bytes()))
+            .or_else(|| content_type_charset(&attribute(tag, "content")?))
+    })?;
+    // A page cannot really be UTF-16 if we could read this, so browsers take it to mean
+    // UTF-8
+    Some(encoding.output_encoding())
+}
+
+fn decode_entities(s: &str) -> String {
+    s.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tags() {
+        let html = r#"<head><LINK rel="a"><linkage><link rel='b'/></head>"#;
+        assert_eq!(tags(html, "link"), [r#"<LINK rel="a""#, "<link rel='b'/"]);
+    }
+
+    #[test]
+    fn test_attribute() {
+        let tag = r#"<link data-href="x" HREF = 'https://example.com/?a=1&amp;b=2' rel=alternate"#;
+        assert_eq!(
+            attribute(tag, "href"),
+            Some("https://example.com/?a=1&b=2".to_string())
+        );
+        assert_eq!(attribute(tag, "rel"), Some("alternate".to_string()));
+        assert_eq!(attribute(tag, "type"), None);
+    }
+
+    #[test]
+    fn test_decode() {
+        let latin1 = b"<title>Caf\xe9</title>";
+        assert_eq!(
+            decode(latin1, Some("text/html; charset=ISO-8859-1")),
+            "<title>Caf\u{e9}</title>"
+        );
+        assert_eq!(
+            decode(latin1, Some("text/html")),
+            "<title>Caf\u{fffd}</title>"
+        );
+
+        let meta = b"<meta charset=\"windows-1252\"><title>Caf\xe9</title>";
+        assert_eq!(
+            decode(meta, None),
+            "<meta charset=\"windows-1252\"><title>Caf\u{e9}</title>"
+        );
+        let meta = b"<meta http-equiv=Content-Type content='text/html; charset=shift_jis'>\x93\xfa";
+        assert!(decode(meta, Some("text/html")).ends_with('\u{65e5}'));
+
+        // The header wins over the page
+        assert_eq!(
+            decode(meta, Some("text/html; charset=utf-8")),
+            "<meta http-equiv=Content-Type content='text/html; charset=shift_jis'>\u{fffd}\u{fffd}"
+        );
+        assert_eq!(
+            decode("<title>日本</title>".as_bytes(), None),
+            "<title>日本</title>"
+        );
+    }
+}
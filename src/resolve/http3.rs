@@ -0,0 +1,110 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+use reqwest::header;
+use url::Url;
+
+// HTTP/3, for the hosts that support it
+//
+// reqwest can only speak HTTP/3 to a host it already knows supports it, so we do as
+// browsers do: hosts advertise HTTP/3 with an `Alt-Svc` header on an ordinary response,
+// and from then on we try their URLs over HTTP/3 first.  If that fails, we forget that
+// the host supports it, and fall back to HTTP/1.1 or HTTP/2 as usual.  HTTP/3 runs over
+// QUIC, which copes better with lossy (e.g., mobile) networks, and some CDNs' bot
+// detection is less suspicious of it.
+#[derive(Clone, Debug)]
+pub(super) struct Http3 {
+    pub(super) client: reqwest::Client,
+    hosts: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Http3 {
+    pub(super) fn new(client: reqwest::Client) -> Self {
+        Http3 {
+            client,
+            hosts: Arc::default(),
+        }
+    }
+
+    fn host(url: &Url) -> Option<String> {
+        // Only HTTPS on the default port, as we do not track which port `Alt-Svc` gave
+        if url.scheme() != "https" || url.port().is_some() {
+            return None;
+        }
+        url.host_str().map(str::to_ascii_lowercase)
+    }
+
+    // Whether the host of `url` has told us that it supports HTTP/3
+    pub(super) fn supports(&self, url: &Url) -> bool {
+        let Some(host) = Self::host(url) else {
+            return false;
+        };
+        self.hosts.lock().is_ok_and(|hosts| hosts.contains(&host))
+    }
+
+    // Remember whether the host of `url` supports HTTP/3, from its response's `Alt-Svc`
+    // header, if any
+    pub(super) fn remember(&self, url: &Url, response: &reqwest::Response) {
+        let Some(host) = Self::host(url) else {
+            return;
+        };
+        let Some(alt_svc) = response
+            .headers()
+            .get(header::ALT_SVC)
+            .and_then(|value| value.to_str().ok())
+        else {
+            return;
+        };
+        let Ok(mut hosts) = self.hosts.lock() else {
+            return;
+        };
+        if advertises_h3(alt_svc) {
+            hosts.insert(host);
+        } else {
+            hosts.remove(&host);
+        }
+    }
+
+    // Stop using HTTP/3 for the host of `url`, as it did not work
+    pub(super) fn forget(&self, url: &Url) {
+        if let Some(host) = Self::host(url)
+            && let Ok(mut hosts) = self.hosts.lock()
+        {
+            hosts.remove(&host);
+        }
+    }
+}
+
+// Whether an `Alt-Svc` header advertises HTTP/3, e.g.:
+// Alt-Svc: h3=":443"; ma=86400, h3-29=":443"; ma=86400
+fn advertises_h3(alt_svc: &str) -> bool {
+    alt_svc
+        .split(',')
+        .any(|alternative| alternative.trim_start().starts_with("h3="))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_advertises_h3() {
+        assert!(advertises_h3(r#"h3=":443"; ma=86400"#));
+        assert!(advertises_h3(r#"h2=":443", h3=":443"; ma=2592000"#));
+        assert!(!advertises_h3(r#"h3-29=":443"; ma=86400"#));
+        assert!(!advertises_h3("clear"));
+    }
+
+    #[test]
+    fn test_host() {
+        let host = |url| Http3::host(&Url::parse(url).expect("valid url"));
+        assert_eq!(
+            host("https://WWW.Facebook.com/a").as_deref(),
+            Some("www.facebook.com")
+        );
+        assert_eq!(host("http://www.facebook.com/a"), None);
+        assert_eq!(host("https://www.facebook.com:8443/a"), None);
+    }
+}
@@ -0,0 +1,146 @@
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use url::Url;
+use wreq_util::Emulation;
+
+use super::{
+    DEFAULT_TIMEOUT, HEAD_END, MAX_BODY_SIZE, MAX_META_REFRESHES, MAX_REDIRECTS, ResolveError,
+    ResolveOutput, UrlResolver, extract_meta_refresh, find_head_end, html, matches_domain,
+};
+use crate::redact;
+
+// Resolves links with an HTTP client that looks like Chrome all the way down: not only
+// its user agent and headers, but its TLS handshake (JA3/JA4) and HTTP/2 settings too
+//
+// Some hosts fingerprint connections, and serve a bot interstitial to anything that
+// does not look like a real browser, however convincing its user agent.  reqwest cannot
+// change how its TLS handshake looks, so this uses `wreq` (which builds on BoringSSL, as
+// Chrome does) instead.  It handles every link unless restricted with `domains`.
+pub struct ImpersonatingResolver {
+    client: wreq::Client,
+    domains: Vec<String>,
+}
+
+impl ImpersonatingResolver {
+    pub fn new() -> wreq::Result<Self> {
+        Self::with_timeout(DEFAULT_TIMEOUT)
+    }
+
+    // As with `new`, but waiting at most `timeout` for each request
+    pub fn with_timeout(timeout: Duration) -> wreq::Result<Self> {
+        let client = wreq::Client::builder()
+            .emulation(Emulation::Chrome131)
+            .redirect(wreq::redirect::Policy::limited(MAX_REDIRECTS))
+            .cookie_store(true)
+            .timeout(timeout)
+            .build()?;
+        Ok(ImpersonatingResolver {
+            client,
+            domains: Vec::new(),
+        })
+    }
+
+    // Only handle links on these domains (and their subdomains)
+    pub fn domains(mut self, domains: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.domains = domains.into_iter().map(Into::into).collect();
+        self
+    }
+
+    fn handles(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        matches!(url.scheme(), "http" | "https")
+            && (self.domains.is_empty()
+                || self
+                    .domains
+                    .iter()
+                    .any(|domain| matches_domain(host, domain)))
+    }
+
+    // Follow redirects (including meta refreshes) from `url` to its final form
+    async fn follow(&self, url: &Url) -> Result<String, wreq::Error> {
+        let mut url = url.clone();
+        for _ in 0..=MAX_META_REFRESHES {
+            let mut response = self.client.get(url.as_str()).send().await?;
+            let resolved = response.url().clone();
+            let content_type = response
+                .headers()
+                .get(wreq::header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string);
+
+            // As in `Resolver::follow_redirects`, we only need the `<head>`
+            let mut body = Vec::new();
+            while body.len() < MAX_BODY_SIZE
+                && let Some(chunk) = response.chunk().await?
+            {
+                let from = body.len().saturating_sub(HEAD_END.len() - 1);
+                body.extend_from_slice(&chunk);
+                if let Some(end) = find_head_end(&body, from) {
+                    body.truncate(end);
+                    break;
+                }
+            }
+            let html = html::decode(&body, content_type.as_deref());
+            match extract_meta_refresh(&html)
+                .and_then(|target| resolved.join(&target).ok())
+                .filter(|target| matches!(target.scheme(), "http" | "https"))
+            {
+                Some(target) => url = target,
+                None => return Ok(resolved.to_string()),
+            }
+        }
+        Ok(url.to_string())
+    }
+}
+
+impl UrlResolver for ImpersonatingResolver {
+    fn name(&self) -> &'static str {
+        "ImpersonatingResolver"
+    }
+
+    fn resolve<'a>(
+        &'a self,
+        _client: &'a reqwest::Client,
+        url: &'a Url,
+    ) -> Option<BoxFuture<'a, ResolveOutput>> {
+        if !self.handles(url) {
+            return None;
+        }
+        Some(Box::pin(async move {
+            self.follow(url).await.map_err(|e| {
+                tracing::debug!(
+                    url = %redact::url(url.as_str()),
+                    error = %e,
+                    "impersonated request failed"
+                );
+                if e.is_timeout() {
+                    ResolveError::Timeout
+                } else {
+                    ResolveError::Api(self.name())
+                }
+            })
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handles() {
+        let resolver = ImpersonatingResolver::new().expect("resolver");
+        let handles = |resolver: &ImpersonatingResolver, url| {
+            resolver.handles(&Url::parse(url).expect("valid url"))
+        };
+        assert!(handles(&resolver, "https://example.com/a"));
+        assert!(!handles(&resolver, "ftp://example.com/a"));
+
+        let resolver = resolver.domains(["facebook.com"]);
+        assert!(handles(&resolver, "https://www.facebook.com/share/abc"));
+        assert!(!handles(&resolver, "https://example.com/a"));
+    }
+}
@@ -0,0 +1,135 @@
+use std::fmt;
+
+use url::Url;
+
+use super::{matches_domain, same_site};
+
+// A login wall (or checkpoint) that resolution ended on, rather than the page we wanted
+//
+// Platforms send logged-out visitors (and requests they suspect of being bots) here,
+// and put the page they were trying to reach in a query parameter, so that they can be
+// sent on after logging in.  That page is what the share link was for, so we report it
+// rather than the login URL, which is the same whatever was shared.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Interstitial {
+    // The login page's URL
+    pub url: String,
+    // The page we would have been sent on to, if the login page said
+    pub destination: Option<String>,
+}
+
+impl fmt::Display for Interstitial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.destination {
+            Some(destination) => write!(f, "{}, on the way to {}", self.url, destination),
+            None => write!(f, "{}", self.url),
+        }
+    }
+}
+
+// Login walls we know of: the domain, the path prefixes of its login pages, and the
+// query parameters (in order of preference) that may give the destination
+const LOGIN_WALLS: &[(&str, &[&str], &[&str])] = &[
+    (
+        "facebook.com",
+        &["/login", "/checkpoint"],
+        &["next", "redirect"],
+    ),
+    ("instagram.com", &["/accounts/login"], &["next"]),
+    (
+        "linkedin.com",
+        &["/authwall", "/login", "/uas/login"],
+        &["sessionRedirect", "session_redirect", "redirect"],
+    ),
+    ("x.com", &["/i/flow/login"], &["redirect_after_login"]),
+    ("twitter.com", &["/i/flow/login"], &["redirect_after_login"]),
+];
+
+// Whether `path` is `prefix`, or a page under it (e.g., `/login.php`, or `/login/`)
+fn matches_path(path: &str, prefix: &str) -> bool {
+    path.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(['/', '.']))
+}
+
+// The login wall that `url` is, if any
+pub(super) fn detect(url: &Url) -> Option<Interstitial> {
+    let host = url.host_str()?;
+    let (_, _, params) = LOGIN_WALLS.iter().find(|(domain, paths, _)| {
+        matches_domain(host, domain) && paths.iter().any(|path| matches_path(url.path(), path))
+    })?;
+
+    // The destination is often relative (e.g., Instagram's `next=/p/abc/`), and must be on
+    // the same site, or any link could claim to be a login wall for somewhere else
+    let destination = params.iter().find_map(|param| {
+        let (_, value) = url.query_pairs().find(|(name, _)| name == param)?;
+        let destination = url.join(&value).ok()?;
+        same_site(&destination, url).then(|| destination.to_string())
+    });
+    Some(Interstitial {
+        url: url.to_string(),
+        destination,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interstitial(url: &str) -> Option<Interstitial> {
+        detect(&Url::parse(url).expect("valid url"))
+    }
+
+    fn destination(url: &str) -> Option<String> {
+        interstitial(url).and_then(|interstitial| interstitial.destination)
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            destination(
+                "https://www.facebook.com/login/?next=https%3A%2F%2Fwww.facebook.com%2Fgroups%2F123%2Fposts%2F456%2F"
+            )
+            .as_deref(),
+            Some("https://www.facebook.com/groups/123/posts/456/")
+        );
+        assert_eq!(
+            destination("https://www.instagram.com/accounts/login/?next=%2Fp%2FCabc123%2F")
+                .as_deref(),
+            Some("https://www.instagram.com/p/Cabc123/")
+        );
+        assert_eq!(
+            destination(
+                "https://www.linkedin.com/authwall?trk=gf&sessionRedirect=https%3A%2F%2Fwww.linkedin.com%2Fposts%2Fabc"
+            )
+            .as_deref(),
+            Some("https://www.linkedin.com/posts/abc")
+        );
+        assert_eq!(
+            destination(
+                "https://www.facebook.com/login.php?next=https%3A%2F%2Fwww.facebook.com%2F123"
+            )
+            .as_deref(),
+            Some("https://www.facebook.com/123")
+        );
+    }
+
+    #[test]
+    fn test_no_destination() {
+        let wall = interstitial("https://www.facebook.com/checkpoint/block/").expect("login wall");
+        assert_eq!(wall.destination, None);
+
+        // A destination on another site is not trusted
+        assert_eq!(
+            destination("https://www.facebook.com/login/?next=https%3A%2F%2Fevil.example%2F"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_not_login_wall() {
+        assert_eq!(interstitial("https://www.facebook.com/loginsights/"), None);
+        assert_eq!(interstitial("https://www.facebook.com/groups/123/"), None);
+        assert_eq!(interstitial("https://example.com/login"), None);
+    }
+}
@@ -0,0 +1,3265 @@
+use std::{
+    cell::Cell,
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use backon::{ExponentialBuilder, Retryable};
+use futures::future::{self, BoxFuture, Either};
+use reqwest::{
+    StatusCode,
+    header::{self, HeaderValue},
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::{Instrument, field};
+use ua_generator::ua;
+use url::Url;
+
+use crate::redact;
+
+#[cfg(feature = "browser")]
+mod browser;
+mod cache;
+mod canonical;
+mod cookies;
+mod facebook;
+mod html;
+#[cfg(feature = "http3")]
+mod http3;
+#[cfg(feature = "impersonate")]
+mod impersonate;
+mod interstitial;
+mod oembed;
+mod policy;
+mod reddit;
+mod shorteners;
+mod ssrf;
+mod unavailable;
+
+#[cfg(feature = "browser")]
+pub use browser::BrowserResolver;
+pub use cache::ResolveCache;
+pub use cookies::{CookieJar, CookieJarError};
+pub use facebook::FacebookResolver;
+#[cfg(feature = "impersonate")]
+pub use impersonate::ImpersonatingResolver;
+pub use interstitial::Interstitial;
+pub use policy::DomainPolicy;
+pub use reddit::RedditResolver;
+pub use shorteners::{Shortener, ShortenerBehaviour, shortener_for};
+
+type ResolveOutput = Result<String, ResolveError>;
+
+const DEFAULT_ACCEPT_LANGUAGE: &str = "en";
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_REDIRECTS: usize = 20;
+const MAX_META_REFRESHES: usize = 5;
+const MAX_BODY_SIZE: usize = 256 * 1024;
+const MAX_REQUESTS_PER_HOST: usize = 4;
+const MAX_RETRIES: usize = 3;
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+// Reasons that a URL could not be resolved
+#[derive(Debug, derive_more::Display)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ResolveError {
+    #[display("invalid URL: {_0}")]
+    InvalidUrl(
+        #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serialize::display"))]
+        url::ParseError,
+    ),
+    #[display("request timed out")]
+    Timeout,
+    // DNS resolution or connection failure
+    #[display("could not connect: {_0}")]
+    Connect(
+        #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serialize::display"))]
+        reqwest::Error,
+    ),
+    #[display("too many redirects")]
+    TooManyRedirects,
+    #[display("too many meta refresh redirects")]
+    MetaRefreshLoop,
+    // The server was unavailable (5xx) or rate-limited us (429)
+    #[display("server returned HTTP {_0}")]
+    Http(
+        #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serialize::display"))]
+        StatusCode,
+    ),
+    // As with `Http`, but the server told us (with `Retry-After`) how long to wait before
+    // trying again
+    #[display("server returned HTTP {_0}, asking us to retry in {} s", _1.as_secs())]
+    RetryAfter(
+        #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serialize::display"))]
+        StatusCode,
+        #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serialize::millis"))] Duration,
+    ),
+    // We were sent to a login page (or other checkpoint) instead of the page itself
+    #[display("ended on a login wall at {_0}")]
+    Interstitial(Interstitial),
+    // The host has failed repeatedly, so we did not try it again until it has had time
+    // to recover (see `ResolverBuilder::circuit_breaker`)
+    #[display("skipped, as {_0} has failed repeatedly")]
+    CircuitOpen(String),
+    // We were redirected to another site, which we were told not to follow (see
+    // `ResolverBuilder::same_site`)
+    #[display("refused to follow redirect to another site: {_0}")]
+    OffSite(String),
+    // We were redirected to a URL that is not on the web (e.g., `javascript:` or `data:`),
+    // which we must not follow, nor hand back as if it were a link
+    #[display("refused to follow redirect to a {_0}: URL")]
+    UnsupportedScheme(String),
+    // The URL is on a private network (or an unusual port), which we were not allowed to
+    // request (see `ResolverBuilder::allow_private_networks`)
+    #[display("refused to request {_0}, as it is on a private network or an unusual port")]
+    PrivateNetwork(String),
+    // A `UrlResolver` (e.g., a platform's API, or a headless browser) did not give us a
+    // URL we could use
+    #[display("{_0} could not resolve URL")]
+    Api(&'static str),
+    #[display("request failed: {_0}")]
+    Request(
+        #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serialize::display"))]
+        reqwest::Error,
+    ),
+}
+
+impl ResolveError {
+    // Whether the failure may be transient, such that it is worth trying again
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ResolveError::Timeout | ResolveError::Connect(_) | ResolveError::RetryAfter(..) => true,
+            ResolveError::Http(status) => {
+                *status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+            }
+            // The connection failed partway through (e.g., it was reset, or closed before
+            // the response was complete), rather than the request being malformed or the
+            // response undecodable
+            ResolveError::Request(e) => e.is_request() || e.is_body(),
+            ResolveError::InvalidUrl(_)
+            | ResolveError::TooManyRedirects
+            | ResolveError::MetaRefreshLoop
+            | ResolveError::Interstitial(_)
+            | ResolveError::CircuitOpen(_)
+            | ResolveError::OffSite(_)
+            | ResolveError::PrivateNetwork(_)
+            | ResolveError::UnsupportedScheme(_)
+            | ResolveError::Api(_) => false,
+        }
+    }
+}
+
+impl Error for ResolveError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ResolveError::InvalidUrl(e) => Some(e),
+            ResolveError::Connect(e) | ResolveError::Request(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<url::ParseError> for ResolveError {
+    fn from(e: url::ParseError) -> Self {
+        ResolveError::InvalidUrl(e)
+    }
+}
+
+// reqwest's errors include the URL that was requested, which would then appear in our
+// logs and messages whether or not URLs are redacted (`redact::url`), so we drop it; the
+// URL is always logged alongside the error anyway
+impl From<reqwest::Error> for ResolveError {
+    fn from(e: reqwest::Error) -> Self {
+        if ssrf::is_private_address(&e) {
+            ResolveError::PrivateNetwork(e.url().map(Url::to_string).unwrap_or_default())
+        } else if e.is_timeout() {
+            ResolveError::Timeout
+        } else if e.is_connect() {
+            ResolveError::Connect(e.without_url())
+        } else {
+            ResolveError::Request(e.without_url())
+        }
+    }
+}
+
+// How a hop in the redirect chain was reached
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum HopKind {
+    // The URL we were asked to resolve
+    Start,
+    // An HTTP redirect (3xx status with a Location header)
+    HttpRedirect,
+    // A `<meta http-equiv="refresh">` tag in the previous page
+    MetaRefresh,
+    // A `Refresh` header in the previous response
+    RefreshHeader,
+    // A platform's API, rather than a request to the URL itself; see `UrlResolver`
+    Api,
+    // The `<link rel="canonical">` of the previous page
+    Canonical,
+    // The `<meta property="og:url">` of the previous page
+    OgUrl,
+    // The canonical URL given by the oEmbed endpoint advertised in the previous page
+    OEmbed,
+    // The `https` form of a plain `http` URL
+    HttpsUpgrade,
+    // The previous page, which said that its content has been removed
+    Unavailable,
+    // The previous URL without its locale subdomain; see `without_locale_subdomain`
+    Locale,
+}
+
+impl HopKind {
+    // Whether the hop was a request of its own, rather than something read from the
+    // previous page
+    fn is_request(self) -> bool {
+        match self {
+            HopKind::Start
+            | HopKind::HttpRedirect
+            | HopKind::MetaRefresh
+            | HopKind::RefreshHeader
+            | HopKind::Api
+            | HopKind::HttpsUpgrade => true,
+            HopKind::Canonical
+            | HopKind::OgUrl
+            | HopKind::OEmbed
+            | HopKind::Unavailable
+            | HopKind::Locale => false,
+        }
+    }
+}
+
+// A single request made while resolving a URL
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Hop {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serialize::display"))]
+    pub status: StatusCode,
+    pub url: String,
+    pub kind: HopKind,
+    // Time taken for the server to respond (zero for hops read from the previous page,
+    // such as its canonical link)
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "elapsed_ms", serialize_with = "crate::serialize::millis")
+    )]
+    pub elapsed: Duration,
+    // The title of the page, if known (currently, only from oEmbed)
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub title: Option<String>,
+}
+
+impl fmt::Display for HopKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HopKind::Start => write!(f, "start"),
+            HopKind::HttpRedirect => write!(f, "HTTP redirect"),
+            HopKind::MetaRefresh => write!(f, "meta refresh"),
+            HopKind::RefreshHeader => write!(f, "Refresh header"),
+            HopKind::Api => write!(f, "API"),
+            HopKind::Canonical => write!(f, "canonical link"),
+            HopKind::OgUrl => write!(f, "og:url"),
+            HopKind::OEmbed => write!(f, "oEmbed"),
+            HopKind::HttpsUpgrade => write!(f, "HTTPS upgrade"),
+            HopKind::Unavailable => write!(f, "content unavailable"),
+            HopKind::Locale => write!(f, "locale removed"),
+        }
+    }
+}
+
+impl fmt::Display for Hop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} ({})", self.status.as_u16(), self.url, self.kind)?;
+        if let Some(title) = &self.title {
+            write!(f, " {:?}", title)?;
+        }
+        Ok(())
+    }
+}
+
+// The result of resolving a URL: where it led, and how it got there
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Resolution {
+    // The URL's final form (that of the last hop)
+    pub url: String,
+    // Every hop taken, in order, starting with the URL itself
+    pub hops: Vec<Hop>,
+    // Time taken overall, including any retries
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "elapsed_ms", serialize_with = "crate::serialize::millis")
+    )]
+    pub elapsed: Duration,
+}
+
+impl Resolution {
+    fn new(hops: Vec<Hop>, elapsed: Duration) -> Self {
+        Resolution {
+            url: final_url(&hops),
+            hops,
+            elapsed,
+        }
+    }
+
+    // The HTTP status of the final page; see `final_status`
+    pub fn status(&self) -> Option<StatusCode> {
+        final_status(&self.hops)
+    }
+
+    // Whether the URL led to a dead link; see `is_dead`
+    pub fn is_dead(&self) -> bool {
+        is_dead(&self.hops)
+    }
+}
+
+// The URL of the last hop in a redirect chain
+pub fn final_url(hops: &[Hop]) -> String {
+    hops.last().map(|hop| hop.url.clone()).unwrap_or_default()
+}
+
+// The HTTP status of the final page in a redirect chain: that of the last request made,
+// rather than of, e.g., the canonical link it gave
+pub fn final_status(hops: &[Hop]) -> Option<StatusCode> {
+    hops.iter()
+        .rev()
+        .find(|hop| hop.kind.is_request())
+        .map(|hop| hop.status)
+}
+
+// Whether a redirect chain ended on a dead link: a page that was not found (404) or is
+// gone (410), or that says its content has been removed
+pub fn is_dead(hops: &[Hop]) -> bool {
+    hops.iter().any(|hop| hop.kind == HopKind::Unavailable)
+        || matches!(
+            final_status(hops),
+            Some(StatusCode::NOT_FOUND | StatusCode::GONE)
+        )
+}
+
+// How to request each URL in a redirect chain
+//
+// Following a chain of HTTP redirects only needs the headers of each response, but a
+// page may also redirect with a meta refresh (or give its canonical URL) in its HTML,
+// which needs the body.  With `Get`, the final page's body is read from the response we
+// already have; the others save downloading anything (e.g., megabytes of Facebook HTML,
+// or a video) when the final page is not HTML, at the cost of a second request when it
+// is.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum RequestStrategy {
+    // GET every URL (the default)
+    #[default]
+    Get,
+    // HEAD every URL, and GET the final page only if it is HTML.  Servers that reject
+    // HEAD are sent a GET instead
+    Head,
+    // As with `Head`, but with a GET for only the first byte (`Range: bytes=0-0`), for
+    // servers that mishandle HEAD
+    Range,
+}
+
+// Whether `host` is `domain` or one of its subdomains
+fn matches_domain(host: &str, domain: &str) -> bool {
+    host.eq_ignore_ascii_case(domain)
+        || host.len() > domain.len()
+            && host.is_char_boundary(host.len() - domain.len())
+            && host[host.len() - domain.len()..].eq_ignore_ascii_case(domain)
+            && host[..host.len() - domain.len()].ends_with('.')
+}
+
+// How long the server asked us to wait before trying again, from its `Retry-After`
+// header (either a number of seconds, or a date)
+fn retry_after(headers: &header::HeaderMap) -> Option<Duration> {
+    let value = headers.get(header::RETRY_AFTER)?.to_str().ok()?.trim();
+    match value.parse::<u64>() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => {
+            let date = httpdate::parse_http_date(value).ok()?;
+            Some(date.duration_since(SystemTime::now()).unwrap_or_default())
+        }
+    }
+}
+
+// The URL on the site's `www` host, if it is on a locale subdomain (e.g.,
+// `de-de.facebook.com`), which some sites redirect to based on where they think we are
+fn without_locale_subdomain(url: &Url) -> Option<Url> {
+    let (label, site) = url.host_str()?.split_once('.')?;
+    let is_locale = matches!(
+        label.as_bytes(),
+        [a, b, b'-', c, d] if [a, b, c, d].iter().all(|x| x.is_ascii_alphabetic())
+    );
+    // The rest must be the site itself, so that we do not lose another subdomain
+    if !is_locale || psl::domain_str(site) != Some(site) {
+        return None;
+    }
+    let mut url = url.clone();
+    url.set_host(Some(&format!("www.{}", site))).ok()?;
+    Some(url)
+}
+
+// Whether the response is (or may be) an HTML page, in which case it may redirect with
+// a meta refresh
+fn is_html(response: &reqwest::Response) -> bool {
+    response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_none_or(|content_type| {
+            let content_type = content_type.to_ascii_lowercase();
+            content_type.starts_with("text/html") || content_type.starts_with("application/xhtml")
+        })
+}
+
+// Read the start of the page's body, up to the end of its `<head>` (which is where meta
+// refreshes, canonical links, and oEmbed endpoints live), or at most `limit` bytes
+//
+// Pages can be huge (Facebook's are several megabytes), so we stream the body rather
+// than reading all of it, and stop as soon as we have what we need.
+async fn read_head(mut response: reqwest::Response, limit: usize) -> Result<String, ResolveError> {
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let mut body = Vec::new();
+    while body.len() < limit
+        && let Some(chunk) = response.chunk().await?
+    {
+        // The end tag may straddle chunks, so search from just before this one
+        let from = body.len().saturating_sub(HEAD_END.len() - 1);
+        body.extend_from_slice(&chunk);
+        if let Some(end) = find_head_end(&body, from) {
+            body.truncate(end);
+            break;
+        }
+    }
+    body.truncate(limit);
+    Ok(html::decode(&body, content_type.as_deref()))
+}
+
+const HEAD_END: &[u8] = b"</head>";
+
+// Read the response's body, or at most `limit` bytes of it
+async fn read_body(mut response: reqwest::Response, limit: usize) -> Result<Vec<u8>, ResolveError> {
+    let mut body = Vec::new();
+    while body.len() < limit
+        && let Some(chunk) = response.chunk().await?
+    {
+        body.extend_from_slice(&chunk);
+    }
+    body.truncate(limit);
+    Ok(body)
+}
+
+// The offset just past the first `</head>` in `body` at or after `from`
+fn find_head_end(body: &[u8], from: usize) -> Option<usize> {
+    body.get(from..)?
+        .windows(HEAD_END.len())
+        .position(|window| window.eq_ignore_ascii_case(HEAD_END))
+        .map(|i| from + i + HEAD_END.len())
+}
+
+// Which IP family to connect to servers over
+//
+// By default, we connect over either (trying IPv6 and IPv4 addresses in parallel, as
+// per "Happy Eyeballs"), and if we cannot connect at all, retry over IPv4 alone, as
+// networks with broken IPv6 can otherwise leave requests hanging until they time out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IpFamily {
+    #[default]
+    Any,
+    V4,
+    V6,
+}
+
+// Limits on the number of requests in flight to each host, shared by every clone of a
+// `Resolver`
+//
+// Batches are often dominated by a few platforms, so without these, a high concurrency
+// would mean hammering (and being rate-limited by) each of them in turn.
+#[derive(Clone, Debug)]
+struct HostLimits {
+    max: usize,
+    hosts: Arc<Mutex<HashMap<String, Arc<Semaphore>>>>,
+}
+
+impl HostLimits {
+    fn new(max: usize) -> Self {
+        HostLimits {
+            max,
+            hosts: Arc::default(),
+        }
+    }
+
+    // Wait until we may make a request to the host of `url`.  The request may be made
+    // until the returned permit is dropped
+    async fn acquire(&self, url: &Url) -> Option<OwnedSemaphorePermit> {
+        let host = url.host_str()?.to_ascii_lowercase();
+        let semaphore = {
+            let mut hosts = self.hosts.lock().ok()?;
+            hosts
+                .entry(host)
+                .or_insert_with(|| Arc::new(Semaphore::new(self.max)))
+                .clone()
+        };
+        semaphore.acquire_owned().await.ok()
+    }
+}
+
+// Circuit breaker, which stops us from making requests to a host that has failed (or
+// rate-limited us) several times in a row, until it has had time to recover
+//
+// In a long batch, a host that is down or throttling us would otherwise have every one
+// of its URLs retried with backoff, slowing the whole run (and making the throttling
+// worse).  Once the cooldown has passed, we try the host again; another failure opens
+// the circuit again straight away, while a success resets it.
+#[derive(Clone, Debug)]
+struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    hosts: Arc<Mutex<HashMap<String, Circuit>>>,
+}
+
+#[derive(Debug, Default)]
+struct Circuit {
+    // Consecutive failures
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    fn new(threshold: u32, cooldown: Duration) -> Self {
+        CircuitBreaker {
+            threshold: threshold.max(1),
+            cooldown,
+            hosts: Arc::default(),
+        }
+    }
+
+    // Whether we may make requests to the host of `url`
+    fn check(&self, url: &Url) -> Result<(), ResolveError> {
+        let Some(host) = url.host_str() else {
+            return Ok(());
+        };
+        let Ok(hosts) = self.hosts.lock() else {
+            return Ok(());
+        };
+        match hosts.get(&host.to_ascii_lowercase()) {
+            Some(Circuit {
+                open_until: Some(until),
+                ..
+            }) if *until > Instant::now() => Err(ResolveError::CircuitOpen(host.to_string())),
+            _ => Ok(()),
+        }
+    }
+
+    // Record whether resolving a URL on the host of `url` failed in a way that suggests
+    // the host is in trouble (i.e., with an error worth retrying)
+    fn record(&self, url: &Url, failed: bool) {
+        let Some(host) = url.host_str() else {
+            return;
+        };
+        let Ok(mut hosts) = self.hosts.lock() else {
+            return;
+        };
+        let host = host.to_ascii_lowercase();
+        if !failed {
+            hosts.remove(&host);
+            return;
+        }
+        let circuit = hosts.entry(host).or_default();
+        circuit.failures += 1;
+        if circuit.failures >= self.threshold {
+            tracing::warn!(
+                host = %url.host_str().unwrap_or_default(),
+                failures = circuit.failures,
+                cooldown_secs = self.cooldown.as_secs(),
+                "host keeps failing; skipping it for a while"
+            );
+            circuit.open_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+}
+
+// Whether two URLs belong to the same site (registrable domain), so that a page cannot
+// point us somewhere else entirely
+fn same_site(a: &Url, b: &Url) -> bool {
+    let site = |url: &Url| url.host_str().and_then(psl::domain_str).map(str::to_string);
+    matches!(a.scheme(), "http" | "https") && site(a).is_some() && site(a) == site(b)
+}
+
+// Resolves URLs on a particular platform using its API, rather than by following
+// redirects (e.g., `RedditResolver`)
+//
+// APIs are generally faster than loading the platform's HTML pages, and less likely to
+// trip bot detection.  A resolver that fails is not fatal, as we fall back to following
+// redirects as usual.
+pub trait UrlResolver: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    // Resolve `url`, or return `None` if the resolver does not handle URLs of its shape
+    fn resolve<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        url: &'a Url,
+    ) -> Option<BoxFuture<'a, ResolveOutput>>;
+}
+
+impl fmt::Debug for dyn UrlResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+// The resolvers used unless configured otherwise
+fn default_url_resolvers() -> Vec<Arc<dyn UrlResolver>> {
+    vec![Arc::new(RedditResolver), Arc::new(FacebookResolver)]
+}
+
+// Resolves URLs to their final form.  This includes HTTP _and_ JS redirects; the latter
+// handled by `extract_meta_refresh`
+//
+// A resolver holds a single HTTP client, built once in `ResolverBuilder::build`, which
+// every hop, retry, and API request is made with.  Reusing the resolver between requests
+// (as the REPL and batch mode do) keeps a warm connection pool, and the same (randomly
+// chosen) user agent throughout, so a site sees one consistent browser.
+#[derive(Clone, Debug)]
+pub struct Resolver {
+    client: reqwest::Client,
+    // A client restricted to IPv4, for retrying if we could not connect at all (e.g., on a
+    // network with broken IPv6).  Only used if no IP family was chosen
+    ipv4_fallback: Option<reqwest::Client>,
+    #[cfg(feature = "http3")]
+    http3: Option<http3::Http3>,
+    max_redirects: usize,
+    url_resolvers: Vec<Arc<dyn UrlResolver>>,
+    oembed: bool,
+    prefer_canonical: bool,
+    request_strategy: RequestStrategy,
+    domain_policies: policy::DomainPolicies,
+    max_body_size: usize,
+    cache: Option<Arc<ResolveCache>>,
+    cookie_jar: Option<Arc<CookieJar>>,
+    host_limits: HostLimits,
+    circuit_breaker: Option<CircuitBreaker>,
+    https_upgrade: bool,
+    same_site: bool,
+    allow_private_networks: bool,
+    // Whether requests may go through a proxy, given to the builder or by the environment
+    proxied: bool,
+    race: bool,
+    max_retries: usize,
+    max_retry_after: Duration,
+}
+
+// Builder for a `Resolver`, e.g.:
+//
+//     Resolver::builder()
+//         .timeout(Duration::from_secs(10))
+//         .max_redirects(5)
+//         .build()
+#[derive(Clone, Debug)]
+pub struct ResolverBuilder {
+    timeout: Duration,
+    max_redirects: usize,
+    user_agent: Option<String>,
+    accept_language: String,
+    proxy: Option<Url>,
+    url_resolvers: Vec<Arc<dyn UrlResolver>>,
+    oembed: bool,
+    prefer_canonical: bool,
+    request_strategy: RequestStrategy,
+    domain_policies: policy::DomainPolicies,
+    max_body_size: usize,
+    cache: Option<Arc<ResolveCache>>,
+    cookies: bool,
+    cookie_jar: Option<Arc<CookieJar>>,
+    max_requests_per_host: usize,
+    circuit_breaker: Option<(u32, Duration)>,
+    ip_family: IpFamily,
+    #[cfg(feature = "http3")]
+    http3: bool,
+    https_upgrade: bool,
+    same_site: bool,
+    allow_private_networks: bool,
+    race: bool,
+    max_retries: usize,
+    max_retry_after: Duration,
+}
+
+impl Default for ResolverBuilder {
+    fn default() -> Self {
+        ResolverBuilder {
+            timeout: DEFAULT_TIMEOUT,
+            max_redirects: MAX_REDIRECTS,
+            user_agent: None,
+            accept_language: DEFAULT_ACCEPT_LANGUAGE.to_string(),
+            proxy: None,
+            url_resolvers: default_url_resolvers(),
+            oembed: true,
+            prefer_canonical: true,
+            request_strategy: RequestStrategy::default(),
+            domain_policies: policy::DomainPolicies::default(),
+            max_body_size: MAX_BODY_SIZE,
+            cache: None,
+            cookies: true,
+            cookie_jar: None,
+            max_requests_per_host: MAX_REQUESTS_PER_HOST,
+            circuit_breaker: None,
+            ip_family: IpFamily::default(),
+            #[cfg(feature = "http3")]
+            http3: true,
+            https_upgrade: true,
+            same_site: false,
+            allow_private_networks: false,
+            race: true,
+            max_retries: MAX_RETRIES,
+            max_retry_after: MAX_RETRY_AFTER,
+        }
+    }
+}
+
+impl ResolverBuilder {
+    // Maximum time to wait for each request
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // Maximum number of redirects to follow before giving up
+    pub fn max_redirects(mut self, max_redirects: usize) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    // Languages to ask for, as an `Accept-Language` header (by default, `en`).  Some sites
+    // redirect to a translation, on a locale subdomain or path, if we do not say
+    pub fn accept_language(mut self, accept_language: impl Into<String>) -> Self {
+        self.accept_language = accept_language.into();
+        self
+    }
+
+    // Maximum number of times to retry a resolution that failed in a way that may not
+    // happen again (see `ResolveError::is_retryable`); by default, three
+    pub fn max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    // User agent to send with each request.  By default, a random (desktop) browser
+    // user agent is used
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    // Send every request through this proxy (`http`, `https`, or `socks5`), except to the
+    // hosts excluded by `NO_PROXY`.  Otherwise, the proxies given by `HTTP_PROXY`,
+    // `HTTPS_PROXY`, and `ALL_PROXY` are used, as by most HTTP clients.  Unless private
+    // networks are allowed, each link's host is looked up (and vetted) before it is sent
+    // through either; see `ssrf::resolves_publicly`
+    pub fn proxy(mut self, proxy: Url) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    // Whether to follow redirects at the same time as asking a platform's API, and take
+    // whichever answers first (the default), rather than only following redirects if the
+    // API fails.  This is faster, at the cost of a request that is often not needed
+    pub fn race(mut self, enabled: bool) -> Self {
+        self.race = enabled;
+        self
+    }
+
+    // Resolve URLs using platforms' APIs where possible (the default), or always follow
+    // redirects.  Disabling this also removes any resolvers added with `url_resolver`
+    pub fn api_resolution(mut self, enabled: bool) -> Self {
+        self.url_resolvers = if enabled {
+            default_url_resolvers()
+        } else {
+            Vec::new()
+        };
+        self
+    }
+
+    // Maximum number of bytes to read from each page when looking for a meta refresh or
+    // canonical URL (256 KiB by default).  We stop early at the end of the `<head>`
+    pub fn max_body_size(mut self, max_body_size: usize) -> Self {
+        self.max_body_size = max_body_size;
+        self
+    }
+
+    // Cache successful resolutions, so that resolving the same URL again does not go
+    // back to the network; see `ResolveCache`.  Nothing is cached by default
+    pub fn cache(mut self, cache: ResolveCache) -> Self {
+        self.cache = Some(Arc::new(cache));
+        self
+    }
+
+    // Maximum number of requests to have in flight to any one host at once (by default,
+    // 4).  This applies across every resolution made with the resolver (e.g., a whole
+    // batch), however many are run concurrently
+    pub fn max_requests_per_host(mut self, max_requests_per_host: usize) -> Self {
+        self.max_requests_per_host = max_requests_per_host.max(1);
+        self
+    }
+
+    // Stop resolving URLs on a host for `cooldown` once it has failed (timed out, could
+    // not be connected to, or returned a server error or 429) `failures` times in a row,
+    // failing them with `ResolveError::CircuitOpen` instead.  Off by default
+    pub fn circuit_breaker(mut self, failures: u32, cooldown: Duration) -> Self {
+        self.circuit_breaker = Some((failures, cooldown));
+        self
+    }
+
+    // Whether to upgrade a final `http` URL to `https`, where the site supports it (the
+    // default)
+    pub fn https_upgrade(mut self, enabled: bool) -> Self {
+        self.https_upgrade = enabled;
+        self
+    }
+
+    // Whether to only follow redirects within the site (registrable domain) of the URL
+    // being resolved, failing with `ResolveError::OffSite` rather than sending a request
+    // anywhere else (off by default).  This keeps a tracker we were bounced to from seeing
+    // the request, at the cost of not resolving links that legitimately leave the site,
+    // such as those on link shorteners
+    pub fn same_site(mut self, enabled: bool) -> Self {
+        self.same_site = enabled;
+        self
+    }
+
+    // Whether to request URLs on private networks (e.g., `localhost`, `192.168.0.1`, or a
+    // name that resolves to one), or on ports other than the standard ones for HTTP and
+    // HTTPS.  This is off by default, so that a malicious link cannot have us probe the
+    // network we run in; see `ssrf`
+    pub fn allow_private_networks(mut self, enabled: bool) -> Self {
+        self.allow_private_networks = enabled;
+        self
+    }
+
+    // The longest we will wait before retrying when a server asks us to (with
+    // `Retry-After`); longer waits are cut short (by default, to a minute)
+    pub fn max_retry_after(mut self, max_retry_after: Duration) -> Self {
+        self.max_retry_after = max_retry_after;
+        self
+    }
+
+    // Whether to use HTTP/3 with hosts that advertise it (the default); see `Http3`
+    #[cfg(feature = "http3")]
+    pub fn http3(mut self, enabled: bool) -> Self {
+        self.http3 = enabled;
+        self
+    }
+
+    // Which IP family to connect over (by default, either)
+    pub fn ip_family(mut self, ip_family: IpFamily) -> Self {
+        self.ip_family = ip_family;
+        self
+    }
+
+    // Whether to keep cookies set while resolving, and send them back on later requests
+    // (the default).  Unless a jar is given with `cookie_jar`, cookies are kept in memory
+    // only while resolving the link that set them
+    pub fn cookies(mut self, enabled: bool) -> Self {
+        self.cookies = enabled;
+        self
+    }
+
+    // Keep cookies in the given jar (e.g., a persistent one, which keeps them between
+    // links; see `CookieJar::persistent`)
+    pub fn cookie_jar(mut self, jar: CookieJar) -> Self {
+        self.cookies = true;
+        self.cookie_jar = Some(Arc::new(jar));
+        self
+    }
+
+    // Whether to look for an oEmbed endpoint on the final page, and prefer the canonical
+    // URL it gives over the URL we were redirected to (the default)
+    pub fn oembed(mut self, enabled: bool) -> Self {
+        self.oembed = enabled;
+        self
+    }
+
+    // Whether to prefer the canonical URL given by the final page (its
+    // `<link rel="canonical">`, or else its `og:url`) over the URL we were redirected to
+    // (the default)
+    pub fn prefer_canonical(mut self, enabled: bool) -> Self {
+        self.prefer_canonical = enabled;
+        self
+    }
+
+    // How to request URLs, unless overridden for their domain; see `RequestStrategy`
+    pub fn request_strategy(mut self, strategy: RequestStrategy) -> Self {
+        self.request_strategy = strategy;
+        self
+    }
+
+    // How to request URLs on `domain` (and its subdomains).  Where several domains
+    // match, the most specific wins
+    pub fn domain_request_strategy(
+        self,
+        domain: impl Into<String>,
+        strategy: RequestStrategy,
+    ) -> Self {
+        self.domain_policy(
+            domain,
+            DomainPolicy {
+                request_strategy: Some(strategy),
+                ..DomainPolicy::default()
+            },
+        )
+    }
+
+    // How to resolve URLs on `domain` (and its subdomains), overriding the rest of the
+    // configuration; see `DomainPolicy`.  Where several domains' policies give the same
+    // setting, the most specific domain's wins
+    pub fn domain_policy(mut self, domain: impl Into<String>, policy: DomainPolicy) -> Self {
+        self.domain_policies.add(domain, policy);
+        self
+    }
+
+    // Add a resolver, which takes precedence over those already added (including the
+    // built-in resolvers)
+    pub fn url_resolver(mut self, resolver: impl UrlResolver + 'static) -> Self {
+        self.url_resolvers.insert(0, Arc::new(resolver));
+        self
+    }
+
+    // Create a resolver whose client mimics a real browser.  Redirects are not followed
+    // automatically by the client; see `Resolver::follow_redirects`
+    pub fn build(self) -> reqwest::Result<Resolver> {
+        let user_agent = self.user_agent.unwrap_or_else(|| {
+            // We generate a random user agent in the interest of privacy.  The best crate
+            // for doing this I found from brief research was:
+            //   <https://github.com/spider-rs/ua_generator>
+            //
+            // However, there are some other contenstants:
+            //   <https://github.com/Vrajs16/fake_user_agent>
+            //   <https://github.com/TrixSec/rand_agents>
+            //
+            // They all seem to be imitating this mature Python library which does the
+            // same:
+            //   <https://github.com/fake-useragent/fake-useragent>
+            //
+            // NOTE: we need a non-mobile user agent, as some servers will add unwanted
+            // subdomains into the URL if requesting from a mobile device.  As such, we
+            // generate a user agent with the `Desktop` `FormFactor`.
+            //   <github.com/spider-rs/ua_generator/blob/57cb3019/ua_generator/src/ua.rs#L312C8-L317>
+            //   <https://docs.rs/ua_generator/latest/ua_generator/ua/fn.spoof_by.html>
+            //
+            // TODO: it is not yet possible to generate a Desktop-only user agent, so
+            //   we use Chrome for now.  See spider-rs/ua_generator#7:
+            //   <https://github.com/spider-rs/ua_generator/issues/7>
+            //
+            // ua::spoof_by(
+            //     None,                          // OS
+            //     Some(ua::FormFactor::Desktop), // Form factor
+            //     None,                          // Browser
+            //     None,                          // RNG
+            // )
+            ua::spoof_chrome_ua().to_string()
+        });
+
+        let cookie_jar = self.cookies.then(|| self.cookie_jar.unwrap_or_default());
+        let proxy = self
+            .proxy
+            .as_ref()
+            .map(|proxy| {
+                reqwest::Proxy::all(proxy.as_str())
+                    .map(|proxy| proxy.no_proxy(reqwest::NoProxy::from_env()))
+            })
+            .transpose()?;
+        let proxy_hosts = ssrf::proxy_hosts(self.proxy.as_ref());
+        let client_builder = |local_address: Option<IpAddr>| {
+            let mut client = reqwest::Client::builder();
+            if let Some(cookie_jar) = &cookie_jar {
+                client = client.cookie_provider(cookie_jar.clone());
+            }
+            if let Some(proxy) = &proxy {
+                client = client.proxy(proxy.clone());
+            }
+            if !self.allow_private_networks {
+                client = client.dns_resolver(Arc::new(ssrf::PublicDns {
+                    proxies: proxy_hosts.clone(),
+                }));
+            }
+            client
+                .local_address(local_address)
+                .redirect(reqwest::redirect::Policy::none())
+                .user_agent(&user_agent)
+                .default_headers({
+                    // We must specify some headers to convince Facebook that we are real.
+                    //
+                    // We seem to be able to use the deault headers, as long as we specify
+                    // Accept, Sec-Fetch-Mode, and Cache-Control.  It seems that
+                    // Accept-Encoding, DNT, Connection, Upgrade-Insecure-Requests,
+                    // Sec-Fetch-Dest, and Sec-Fetch-Site are not required.  (reqwest sets
+                    // Accept-Encoding itself, and decodes gzip, Brotli, and zstd bodies, which
+                    // are often much smaller than uncompressed HTML.)
+                    let mut headers = reqwest::header::HeaderMap::new();
+                    headers.insert(
+                        header::ACCEPT,
+                        concat!(
+                            "text/html,",
+                            "application/xhtml+xml,application/xml;",
+                            "q=0.9,image/webp,*/*;q=0.8",
+                        )
+                        .parse()
+                        .unwrap(),
+                    );
+                    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("max-age=0"));
+                    headers.insert("Sec-Fetch-Mode", HeaderValue::from_static("navigate"));
+                    // As with mobile user agents (see above), without Accept-Language some
+                    // sites guess our locale, and send us to a translation of the page
+                    if let Ok(accept_language) = HeaderValue::from_str(&self.accept_language) {
+                        headers.insert(header::ACCEPT_LANGUAGE, accept_language);
+                    }
+                    headers
+                })
+                .timeout(self.timeout)
+                // Keep enough connections open to reuse one for each request we allow at once
+                .pool_max_idle_per_host(self.max_requests_per_host)
+        };
+        let client = |local_address: Option<IpAddr>| client_builder(local_address).build();
+
+        #[cfg(feature = "http3")]
+        let http3 = match self.ip_family {
+            _ if !self.http3 => None,
+            IpFamily::Any => Some(None),
+            IpFamily::V4 => Some(Some(Ipv4Addr::UNSPECIFIED.into())),
+            IpFamily::V6 => Some(Some(Ipv6Addr::UNSPECIFIED.into())),
+        }
+        .map(|local_address| {
+            client_builder(local_address)
+                .http3_prior_knowledge()
+                .build()
+        })
+        .transpose()?
+        .map(http3::Http3::new);
+
+        // Binding to the unspecified address of a family restricts connections to it
+        let client_v4 = || client(Some(Ipv4Addr::UNSPECIFIED.into()));
+        let (client, ipv4_fallback) = match self.ip_family {
+            IpFamily::Any => (client(None)?, Some(client_v4()?)),
+            IpFamily::V4 => (client_v4()?, None),
+            IpFamily::V6 => (client(Some(Ipv6Addr::UNSPECIFIED.into()))?, None),
+        };
+
+        Ok(Resolver {
+            client,
+            ipv4_fallback,
+            #[cfg(feature = "http3")]
+            http3,
+            max_redirects: self.max_redirects,
+            url_resolvers: self.url_resolvers,
+            oembed: self.oembed,
+            prefer_canonical: self.prefer_canonical,
+            request_strategy: self.request_strategy,
+            domain_policies: self.domain_policies,
+            max_body_size: self.max_body_size,
+            cache: self.cache,
+            cookie_jar,
+            host_limits: HostLimits::new(self.max_requests_per_host),
+            circuit_breaker: self
+                .circuit_breaker
+                .map(|(threshold, cooldown)| CircuitBreaker::new(threshold, cooldown)),
+            https_upgrade: self.https_upgrade,
+            same_site: self.same_site,
+            allow_private_networks: self.allow_private_networks,
+            proxied: !proxy_hosts.is_empty(),
+            race: self.race,
+            max_retries: self.max_retries,
+            max_retry_after: self.max_retry_after,
+        })
+    }
+}
+
+impl Resolver {
+    // Create a resolver with the default configuration
+    pub fn new() -> reqwest::Result<Self> {
+        Self::builder().build()
+    }
+
+    pub fn builder() -> ResolverBuilder {
+        ResolverBuilder::default()
+    }
+
+    // The underlying HTTP client, for making other requests (e.g., archiving) with the
+    // same configuration
+    pub fn client(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    // Save the cookie jar, if it is kept in a file (see `CookieJar::persistent`).  This is
+    // also done when the resolver is dropped
+    pub fn save_cookies(&self) -> Result<(), CookieJarError> {
+        self.cookie_jar.as_ref().map_or(Ok(()), |jar| jar.save())
+    }
+
+    // Resolve a URL to its final form
+    pub async fn resolve(&self, url: &str) -> ResolveOutput {
+        Ok(self.resolve_traced(url).await?.url)
+    }
+
+    // Resolve a URL, returning every hop taken along the way (the last of which is the
+    // URL's final form), and the time taken; see `Resolution`
+    //
+    // This runs in a `resolve` span recording the number of hops taken and the time
+    // taken overall, with a child span for each attempt (see `resolve_with_retries`)
+    #[tracing::instrument(
+        name = "resolve",
+        skip_all,
+        fields(url = %redact::url(url), hops = field::Empty, elapsed_ms = field::Empty)
+    )]
+    pub async fn resolve_traced(&self, url: &str) -> Result<Resolution, ResolveError> {
+        let span = tracing::Span::current();
+        let start = Instant::now();
+        if let Some(hops) = self.cache.as_ref().and_then(|cache| cache.get(url)) {
+            tracing::debug!("cache hit");
+            span.record("hops", hops.len());
+            return Ok(Resolution::new(hops, start.elapsed()));
+        }
+
+        let breaker = self.circuit_breaker.as_ref().zip(Url::parse(url).ok());
+        if let Some((breaker, url)) = &breaker {
+            breaker.check(url)?;
+        }
+
+        let result = cookies::scoped(self.resolve_any(url)).await;
+        if let Some((breaker, url)) = &breaker {
+            breaker.record(url, result.as_ref().is_err_and(ResolveError::is_retryable));
+        }
+        if let Ok(hops) = &result {
+            span.record("hops", hops.len());
+            if let Some(cache) = &self.cache {
+                cache.insert(url, hops);
+            }
+        }
+        let elapsed = start.elapsed();
+        span.record("elapsed_ms", elapsed.as_millis() as u64);
+        result.map(|hops| Resolution::new(hops, elapsed))
+    }
+
+    // Resolve `url` with a platform's API, if one handles it, or else by following
+    // redirects
+    //
+    // Unless told otherwise (see `ResolverBuilder::race`), we do both at once, and take
+    // whichever answers first, as either may be slow: APIs are rate-limited, and some
+    // platforms serve bot checks to plain requests.  Dropping the loser cancels it.
+    async fn resolve_any(&self, url: &str) -> Result<Vec<Hop>, ResolveError> {
+        if !self.race {
+            return match self.resolve_api(url).await {
+                Some(hop) => Ok(vec![hop]),
+                None => self.resolve_with_retries(url).await,
+            };
+        }
+
+        let api = std::pin::pin!(self.resolve_api(url));
+        let redirects = std::pin::pin!(self.resolve_with_retries(url));
+        match future::select(api, redirects).await {
+            Either::Left((Some(hop), _)) => Ok(vec![hop]),
+            Either::Left((None, redirects)) => redirects.await,
+            Either::Right((Ok(hops), _)) => Ok(hops),
+            // The API may yet succeed where following redirects failed
+            Either::Right((Err(e), api)) => api.await.map(|hop| vec![hop]).ok_or(e),
+        }
+    }
+
+    async fn resolve_with_retries(&self, url: &str) -> Result<Vec<Hop>, ResolveError> {
+        // This may not be strictly needed,* but to increase robustness of the core
+        // resolver function, we implement expontentail backoff.
+        //
+        // The best API I could find from some quick research was in this project:
+        //   <https://github.com/ihrwein/backoff>
+        //
+        // The alternatives I found from a Google search were:
+        //   <https://github.com/jimmycuadra/retry>
+        //   <https://github.com/yoshuawuyts/exponential-backoff>
+        //
+        // But the APIs were clunky and the packages immature.  Unfortunately,
+        // the backoff library was abandomed, but I found a replacement that didn't
+        // come up in my Google search (which shows how obsolete traditional search engines
+        // are, as LLMs would understand the intent of what I was asking, not just searching
+        // literally for Rust crates called "backoff"):
+        //   <https://github.com/Xuanwo/backon>
+        //   <https://github.com/ihrwein/backoff/issues/66>
+        //
+        // This backon crate implements ExponentialBackoff, which we build with default
+        // parameters.  We default to three retries before exiting (see `max_retries`):
+        //   <https://docs.rs/backon/latest/backon/struct.ExponentialBuilder.html>
+        //
+        // I only added this when implementing support for Facebook, while I was trying to
+        // debug an issue where some of my test cases were failing non-deterministically.  I
+        // assumed this was due to hitting some 429 response, so I implemented exponential
+        // backoff.  Turns out it was the ransomiser picking the user agents selecting mobile
+        // user agents, and then Facebook responding with a mobile URL!
+        //
+        // If we could not connect at all, we retry over IPv4 alone; see `IpFamily`.
+        let mut attempt = 0;
+        let ipv4_only = Cell::new(false);
+        (|| {
+            attempt += 1;
+            let client = match &self.ipv4_fallback {
+                Some(ipv4_fallback) if ipv4_only.get() => ipv4_fallback,
+                _ => &self.client,
+            };
+            self.resolve_chain(client, url)
+                .instrument(tracing::debug_span!(
+                    "attempt",
+                    attempt,
+                    ipv4_only = ipv4_only.get()
+                ))
+        })
+        .retry(ExponentialBuilder::default().with_max_times(self.max_retries))
+        .when(ResolveError::is_retryable)
+        // If the server told us how long to wait, we do that (within reason) rather than
+        // backing off, as long as we have retries left
+        .adjust(|e, delay| match (e, delay) {
+            (ResolveError::RetryAfter(_, retry_after), Some(_)) => {
+                Some((*retry_after).min(self.max_retry_after))
+            }
+            _ => delay,
+        })
+        .notify(|e, delay| {
+            if matches!(e, ResolveError::Connect(_) | ResolveError::Timeout) {
+                ipv4_only.set(true);
+            }
+            tracing::warn!(
+                error = %e,
+                retry_in_ms = delay.as_millis() as u64,
+                "retrying resolution"
+            )
+        })
+        .await
+    }
+
+    // Resolve `url` with the first API resolver that handles it, if any succeeds
+    async fn resolve_api(&self, url: &str) -> Option<Hop> {
+        let url = Url::parse(url).ok()?;
+        for resolver in &self.url_resolvers {
+            let Some(resolution) = resolver.resolve(&self.client, &url) else {
+                continue;
+            };
+            let start = Instant::now();
+            match resolution.await {
+                Ok(resolved) => {
+                    tracing::debug!(
+                        url = %redact::url(url.as_str()),
+                        resolver = resolver.name(),
+                        "resolved via API"
+                    );
+                    return Some(Hop {
+                        status: StatusCode::OK,
+                        url: resolved,
+                        kind: HopKind::Api,
+                        elapsed: start.elapsed(),
+                        title: None,
+                    });
+                }
+                Err(e) => tracing::debug!(
+                    url = %redact::url(url.as_str()),
+                    resolver = resolver.name(),
+                    error = %e,
+                    "API resolution failed; following redirects instead"
+                ),
+            }
+        }
+        None
+    }
+
+    // The strategy to use for requesting `url`: one given for its domain, or else the
+    // cheapest that works for a known shortener, or else the default
+    fn request_strategy_for(&self, url: &Url) -> RequestStrategy {
+        self.domain_policies
+            .get(url, |policy| policy.request_strategy)
+            .or_else(|| shortener_for(url).and_then(Shortener::request_strategy))
+            .unwrap_or(self.request_strategy)
+    }
+
+    // Request `url` with the given strategy, falling back to a plain GET if the server
+    // does not support what we asked for
+    async fn request(
+        client: &reqwest::Client,
+        url: &Url,
+        strategy: RequestStrategy,
+        headers: header::HeaderMap,
+    ) -> Result<reqwest::Response, ResolveError> {
+        let get = || client.get(url.clone()).headers(headers.clone());
+        let probe = match strategy {
+            RequestStrategy::Get => return Ok(get().send().await?),
+            RequestStrategy::Head => client.head(url.clone()).headers(headers.clone()),
+            RequestStrategy::Range => {
+                get().header(header::RANGE, HeaderValue::from_static("bytes=0-0"))
+            }
+        };
+        let response = probe.send().await?;
+        match response.status() {
+            StatusCode::METHOD_NOT_ALLOWED
+            | StatusCode::NOT_IMPLEMENTED
+            | StatusCode::RANGE_NOT_SATISFIABLE => {
+                tracing::debug!(
+                    url = %redact::url(url.as_str()),
+                    status = response.status().as_u16(),
+                    ?strategy,
+                    "request rejected; retrying with GET"
+                );
+                Ok(get().send().await?)
+            }
+            _ => Ok(response),
+        }
+    }
+
+    // As with `request`, but over HTTP/3 if the host has told us it supports it, falling
+    // back to `client` if that fails
+    async fn send(
+        &self,
+        client: &reqwest::Client,
+        url: &Url,
+        strategy: RequestStrategy,
+    ) -> Result<reqwest::Response, ResolveError> {
+        #[cfg(feature = "http3")]
+        if let Some(http3) = &self.http3
+            && http3.supports(url)
+        {
+            match Self::request(
+                &http3.client,
+                url,
+                strategy,
+                self.domain_policies.headers(url),
+            )
+            .await
+            {
+                Ok(response) => return Ok(response),
+                Err(e) => {
+                    tracing::debug!(
+                        url = %redact::url(url.as_str()),
+                        error = %e,
+                        "HTTP/3 request failed; falling back"
+                    );
+                    http3.forget(url);
+                }
+            }
+        }
+
+        let response =
+            Self::request(client, url, strategy, self.domain_policies.headers(url)).await?;
+        #[cfg(feature = "http3")]
+        if let Some(http3) = &self.http3 {
+            http3.remember(url, &response);
+        }
+        Ok(response)
+    }
+
+    // Follow redirects from `url`, recording each hop taken, and upgrade the final URL to
+    // HTTPS if need be (see `upgrade_to_https`)
+    async fn resolve_chain(
+        &self,
+        client: &reqwest::Client,
+        url: &str,
+    ) -> Result<Vec<Hop>, ResolveError> {
+        let mut hsts_hosts = HashSet::new();
+        let mut hops = self.follow_redirects(client, url, &mut hsts_hosts).await?;
+
+        // Cleaning a login page's URL would give the same result whatever was shared, so
+        // we report the page the share link was really for instead
+        if let Some(interstitial) = hops
+            .last()
+            .and_then(|hop| Url::parse(&hop.url).ok())
+            .and_then(|url| interstitial::detect(&url))
+        {
+            return Err(ResolveError::Interstitial(interstitial));
+        }
+
+        // Despite our Accept-Language, we may end up on a translation of the page
+        if let Some(last) = hops.last()
+            && let Some(url) = Url::parse(&last.url)
+                .ok()
+                .and_then(|url| without_locale_subdomain(&url))
+        {
+            hops.push(Hop {
+                status: last.status,
+                url: url.to_string(),
+                kind: HopKind::Locale,
+                elapsed: Duration::ZERO,
+                title: None,
+            });
+        }
+
+        if self.https_upgrade
+            && let Some(hop) = self.upgrade_to_https(client, &hops, &hsts_hosts).await
+        {
+            hops.push(hop);
+        }
+        Ok(hops)
+    }
+
+    // If the final URL is plain `http`, the `https` form of it, so that cleaned links do
+    // not send recipients over an insecure connection just because the share link did
+    //
+    // If a host told us (with HSTS) to only use HTTPS, we take its word for it.
+    // Otherwise, we check that the `https` URL serves the page itself, rather than an
+    // error or a redirect elsewhere (such as back to `http`).
+    async fn upgrade_to_https(
+        &self,
+        client: &reqwest::Client,
+        hops: &[Hop],
+        hsts_hosts: &HashSet<String>,
+    ) -> Option<Hop> {
+        let last = hops.last()?;
+        let mut https = Url::parse(&last.url).ok()?;
+        // A non-default port would not serve HTTPS too
+        if https.scheme() != "http" || https.port().is_some() {
+            return None;
+        }
+        https.set_scheme("https").ok()?;
+        let host = https.host_str()?.to_ascii_lowercase();
+
+        if hsts_hosts.contains(&host) {
+            return Some(Hop {
+                status: last.status,
+                url: https.to_string(),
+                kind: HopKind::HttpsUpgrade,
+                elapsed: Duration::ZERO,
+                title: None,
+            });
+        }
+
+        let _permit = self.host_limits.acquire(&https).await;
+        let start = Instant::now();
+        let strategy = self.request_strategy_for(&https);
+        let headers = self.domain_policies.headers(&https);
+        let status = match Self::request(client, &https, strategy, headers).await {
+            Ok(response) => response.status(),
+            Err(e) => {
+                tracing::debug!(
+                    url = %redact::url(https.as_str()),
+                    error = %e,
+                    "could not upgrade to HTTPS"
+                );
+                return None;
+            }
+        };
+        if !status.is_success() {
+            tracing::debug!(
+                url = %redact::url(https.as_str()),
+                status = status.as_u16(),
+                "could not upgrade to HTTPS"
+            );
+            return None;
+        }
+        Some(Hop {
+            status,
+            url: https.to_string(),
+            kind: HopKind::HttpsUpgrade,
+            elapsed: start.elapsed(),
+            title: None,
+        })
+    }
+
+    // Follow redirects from `url`, recording each hop taken, and each host that asked us
+    // to only use HTTPS
+    //
+    // We follow HTTP redirects ourselves, rather than letting reqwest do it, so that we
+    // can see (and report with `--trace`) every URL the link bounces through.
+    async fn follow_redirects(
+        &self,
+        client: &reqwest::Client,
+        url: &str,
+        hsts_hosts: &mut HashSet<String>,
+    ) -> Result<Vec<Hop>, ResolveError> {
+        let mut hops: Vec<Hop> = Vec::new();
+        let mut url = Url::parse(url)?;
+        let start_url = url.clone();
+        let mut kind = HopKind::Start;
+        let mut meta_refreshes = 0;
+        let max_redirects = self
+            .domain_policies
+            .get(&url, |policy| policy.max_redirects)
+            .unwrap_or(self.max_redirects);
+
+        loop {
+            if hops.len() > max_redirects {
+                return Err(ResolveError::TooManyRedirects);
+            }
+
+            if !self.allow_private_networks && !ssrf::is_allowed(&url) {
+                tracing::warn!(
+                    url = %redact::url(url.as_str()),
+                    "refusing to request URL on a private network"
+                );
+                return Err(ResolveError::PrivateNetwork(url.to_string()));
+            }
+
+            // Through a proxy, the proxy looks up the host rather than `PublicDns`, so we
+            // check it ourselves first; see `ssrf::resolves_publicly`
+            if !self.allow_private_networks && self.proxied && !ssrf::resolves_publicly(&url).await
+            {
+                tracing::warn!(
+                    url = %redact::url(url.as_str()),
+                    "refusing to request URL through a proxy, as its host may be private"
+                );
+                return Err(ResolveError::PrivateNetwork(url.to_string()));
+            }
+
+            // Refuse to send a request off the original site, if asked.  A host with no
+            // registrable domain (e.g., an IP address) is only the same site as itself
+            if self.same_site
+                && kind != HopKind::Start
+                && !same_site(&url, &start_url)
+                && url.host_str() != start_url.host_str()
+            {
+                tracing::warn!(
+                    url = %redact::url(url.as_str()),
+                    "refusing to follow redirect to another site"
+                );
+                return Err(ResolveError::OffSite(url.to_string()));
+            }
+
+            // Make the request, once the host is not already busy with our others.  We hold
+            // the permit until we have finished with the response
+            let permit = self.host_limits.acquire(&url).await;
+            let start = Instant::now();
+            let strategy = self.request_strategy_for(&url);
+            let response = self.send(client, &url, strategy).await?;
+            tracing::debug!(
+                url = %redact::url(url.as_str()),
+                hop = hops.len() + 1,
+                status = response.status().as_u16(),
+                kind = %kind,
+                ?strategy,
+                elapsed_ms = start.elapsed().as_millis() as u64,
+                "request"
+            );
+            hops.push(Hop {
+                status: response.status(),
+                url: url.to_string(),
+                kind,
+                elapsed: start.elapsed(),
+                title: None,
+            });
+
+            // Browsers ignore HSTS headers sent over plain HTTP, so we do too
+            if url.scheme() == "https"
+                && response
+                    .headers()
+                    .contains_key(header::STRICT_TRANSPORT_SECURITY)
+                && let Some(host) = url.host_str()
+            {
+                hsts_hosts.insert(host.to_ascii_lowercase());
+            }
+
+            // Follow HTTP redirects
+            if response.status().is_redirection()
+                && let Some(location) = response
+                    .headers()
+                    .get(header::LOCATION)
+                    .and_then(|v| v.to_str().ok())
+            {
+                // Handle relative URLs
+                url = redirect_target(&url, location)?;
+                kind = HopKind::HttpRedirect;
+                continue;
+            }
+
+            // We have not reached the real destination if the server is unavailable or
+            // rate-limiting us
+            let status = response.status();
+            if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+                return Err(match retry_after(response.headers()) {
+                    Some(delay) => ResolveError::RetryAfter(status, delay),
+                    None => ResolveError::Http(status),
+                });
+            }
+
+            // Some pages redirect with a `Refresh` header, rather than (or as well as) a meta
+            // refresh, which we count towards the same limit.  As it is a header, there is
+            // no need to read the page
+            let follow_refresh = self
+                .domain_policies
+                .get(&url, |policy| policy.meta_refresh)
+                .unwrap_or(true);
+            if follow_refresh
+                && let Some(refresh) = response
+                    .headers()
+                    .get(header::REFRESH)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(refresh_url)
+            {
+                meta_refreshes += 1;
+                if meta_refreshes > MAX_META_REFRESHES {
+                    return Err(ResolveError::MetaRefreshLoop);
+                }
+                url = redirect_target(&url, &refresh)?;
+                kind = HopKind::RefreshHeader;
+                continue;
+            }
+
+            // Check for meta refresh redirects in the HTML; we may need to follow a redirect.
+            // If we only have the headers so far, there is no need to download anything
+            // unless the page is HTML
+            let response = match strategy {
+                RequestStrategy::Get => response,
+                _ if is_html(&response) => {
+                    client
+                        .get(url.clone())
+                        .headers(self.domain_policies.headers(&url))
+                        .send()
+                        .await?
+                }
+                _ => return Ok(hops),
+            };
+            let html = read_head(response, self.max_body_size).await?;
+            // The oEmbed endpoint below (which is often on the same host) takes its own
+            drop(permit);
+            if follow_refresh && let Some(meta_url) = extract_meta_refresh(&html) {
+                meta_refreshes += 1;
+                if meta_refreshes > MAX_META_REFRESHES {
+                    return Err(ResolveError::MetaRefreshLoop);
+                }
+
+                url = redirect_target(&url, &meta_url)?;
+                kind = HopKind::MetaRefresh;
+                continue;
+            }
+
+            // A removed post is no more canonical than the page saying so, and the page
+            // will not advertise anything else we could use
+            if unavailable::is_unavailable(&url, &html) {
+                hops.push(Hop {
+                    status,
+                    url: url.to_string(),
+                    kind: HopKind::Unavailable,
+                    elapsed: Duration::ZERO,
+                    title: None,
+                });
+                return Ok(hops);
+            }
+
+            // The page may advertise its canonical form, which is often cleaner than the
+            // URL we were redirected to; see `canonical::canonical_url` for precedence
+            if self.prefer_canonical
+                && let Some((canonical, kind)) = canonical::canonical_url(&url, &html)
+                && canonical != url
+            {
+                hops.push(Hop {
+                    status,
+                    url: canonical.to_string(),
+                    kind,
+                    elapsed: Duration::ZERO,
+                    title: None,
+                });
+            }
+
+            // The page may advertise its canonical URL via oEmbed
+            if self.oembed
+                && let Some(hop) = oembed::discover(self, client, &start_url, &url, &html).await
+            {
+                hops.push(hop);
+            }
+
+            return Ok(hops);
+        }
+    }
+}
+
+// The URL that a redirect from `url` to `target` (which may be relative) leads to, as long
+// as it is on the web
+//
+// A page can put anything in a meta refresh (or header), including a `javascript:` or
+// `data:` URL, which a browser would refuse to follow.  If we followed it, it would be
+// what we reported (and copied to the clipboard) as the link's destination.
+fn redirect_target(url: &Url, target: &str) -> Result<Url, ResolveError> {
+    let target = url.join(target)?;
+    if !matches!(target.scheme(), "http" | "https") {
+        tracing::warn!(
+            scheme = target.scheme(),
+            "refusing redirect to unsupported scheme"
+        );
+        return Err(ResolveError::UnsupportedScheme(target.scheme().to_string()));
+    }
+    Ok(target)
+}
+
+// Extract the URLs of all meta refresh tags, in document order, like:
+// <meta http-equiv="refresh" content="0;url=https://example.com">
+// TODO: what about window.href being set?  Is that ever used?
+//
+// A browser follows the first of these, but pages sometimes give more than one (e.g., a
+// second in case the first is not understood).  We parse the page properly, so attributes
+// may come in any order, be quoted or not, and contain entities (`&amp;`).  As we do not
+// run JavaScript, `<noscript>` is parsed as a browser with JavaScript would: as text, so
+// a refresh meant only for browsers without JavaScript (e.g., Facebook's) is ignored.
+//
+// This is given untrusted input from the network, so must never panic
+pub fn extract_meta_refreshes(html: &str) -> Vec<String> {
+    let document = scraper::Html::parse_document(html);
+    let selector = scraper::Selector::parse("meta[http-equiv][content]")
+        .expect("meta refresh selector is valid");
+    document
+        .select(&selector)
+        .filter(|meta| {
+            meta.value()
+                .attr("http-equiv")
+                .is_some_and(|equiv| equiv.trim().eq_ignore_ascii_case("refresh"))
+        })
+        .filter_map(|meta| refresh_url(meta.value().attr("content")?))
+        .collect()
+}
+
+// Extract the URL of the first meta refresh tag, which is the one a browser follows; see
+// `extract_meta_refreshes`
+pub fn extract_meta_refresh(html: &str) -> Option<String> {
+    extract_meta_refreshes(html).into_iter().next()
+}
+
+// Extract the URL from the value of a refresh, as given by a meta refresh's `content`, or
+// a `Refresh` header, e.g.:
+// 0;url=https://example.com
+// 0; URL = 'https://example.com'
+//
+// This follows the HTML standard's parsing of refreshes, which browsers implement: a
+// delay, a separator, then the URL, which may be preceded by `url=` and quoted
+fn refresh_url(value: &str) -> Option<String> {
+    let value = value.trim_start_matches(|c: char| c.is_ascii_whitespace());
+
+    // The delay, which we ignore
+    let rest = value.trim_start_matches(|c: char| c.is_ascii_digit());
+    if rest.len() == value.len() && !rest.starts_with('.') {
+        return None;
+    }
+    let rest = rest.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+
+    // The separator
+    if !rest.starts_with(|c: char| c == ';' || c == ',' || c.is_ascii_whitespace()) {
+        return None;
+    }
+    let rest = rest.trim_start_matches(|c: char| c.is_ascii_whitespace());
+    let rest = rest.strip_prefix([';', ',']).unwrap_or(rest);
+    let rest = rest.trim_start_matches(|c: char| c.is_ascii_whitespace());
+
+    // An optional `url=`, with whitespace allowed around the `=`
+    let rest = rest
+        .get(..3)
+        .filter(|prefix| prefix.eq_ignore_ascii_case("url"))
+        .and_then(|_| {
+            rest[3..]
+                .trim_start_matches(|c: char| c.is_ascii_whitespace())
+                .strip_prefix('=')
+        })
+        .map_or(rest, |url| {
+            url.trim_start_matches(|c: char| c.is_ascii_whitespace())
+        });
+
+    // The URL, which may be quoted
+    let url = match rest.chars().next() {
+        Some(quote @ ('"' | '\'')) => {
+            let rest = &rest[1..];
+            rest.split(quote).next().unwrap_or(rest)
+        }
+        _ => rest,
+    };
+    let url = url.trim();
+    (!url.is_empty()).then(|| url.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn resolve(url: &str) -> ResolveOutput {
+        Resolver::new()?.resolve(url).await
+    }
+
+    mod builder {
+        use super::*;
+
+        #[test]
+        fn test_max_redirects() {
+            let resolver = Resolver::builder()
+                .max_redirects(3)
+                .timeout(Duration::from_secs(5))
+                .user_agent("rsl-test")
+                .build()
+                .expect("built");
+            assert_eq!(resolver.max_redirects, 3);
+        }
+
+        struct Example;
+
+        impl UrlResolver for Example {
+            fn name(&self) -> &'static str {
+                "Example"
+            }
+
+            fn resolve<'a>(
+                &'a self,
+                _client: &'a reqwest::Client,
+                url: &'a Url,
+            ) -> Option<BoxFuture<'a, ResolveOutput>> {
+                (url.host_str() == Some("example.com")).then(|| {
+                    Box::pin(async { Ok("https://example.com/resolved".to_string()) }) as _
+                })
+            }
+        }
+
+        #[tokio::test]
+        async fn test_url_resolver() {
+            let resolver = Resolver::builder()
+                .url_resolver(Example)
+                .build()
+                .expect("built");
+            assert_eq!(resolver.url_resolvers.len(), 3);
+            let resolution = resolver
+                .resolve_traced("https://example.com/s/abc")
+                .await
+                .expect("resolved");
+            assert_eq!(resolution.hops.len(), 1);
+            assert_eq!(resolution.hops[0].kind, HopKind::Api);
+            assert_eq!(resolution.url, "https://example.com/resolved");
+        }
+
+        #[test]
+        fn test_prefer_canonical() {
+            let resolver = Resolver::builder().build().expect("built");
+            assert!(resolver.prefer_canonical);
+            let resolver = Resolver::builder()
+                .prefer_canonical(false)
+                .build()
+                .expect("built");
+            assert!(!resolver.prefer_canonical);
+        }
+
+        #[test]
+        fn test_request_strategy() {
+            let resolver = Resolver::builder()
+                .request_strategy(RequestStrategy::Head)
+                .domain_request_strategy("facebook.com", RequestStrategy::Get)
+                .domain_request_strategy("m.facebook.com", RequestStrategy::Range)
+                .build()
+                .expect("built");
+            let strategy =
+                |url| resolver.request_strategy_for(&Url::parse(url).expect("valid url"));
+            assert_eq!(strategy("https://example.com/"), RequestStrategy::Head);
+            assert_eq!(strategy("https://www.facebook.com/"), RequestStrategy::Get);
+            assert_eq!(strategy("https://m.facebook.com/"), RequestStrategy::Range);
+            assert_eq!(strategy("https://notfacebook.com/"), RequestStrategy::Head);
+        }
+
+        #[test]
+        fn test_shortener_request_strategy() {
+            let resolver = Resolver::builder()
+                .domain_request_strategy("t.co", RequestStrategy::Range)
+                .build()
+                .expect("built");
+            let strategy =
+                |url| resolver.request_strategy_for(&Url::parse(url).expect("valid url"));
+            assert_eq!(strategy("https://bit.ly/3abcdef"), RequestStrategy::Head);
+            assert_eq!(strategy("https://lnkd.in/gAbCdEf"), RequestStrategy::Get);
+            assert_eq!(strategy("https://t.co/abc"), RequestStrategy::Range);
+            assert_eq!(strategy("https://example.com/"), RequestStrategy::Get);
+        }
+
+        #[test]
+        fn test_without_locale_subdomain() {
+            let strip = |url| {
+                without_locale_subdomain(&Url::parse(url).expect("valid url"))
+                    .map(|url| url.to_string())
+            };
+            assert_eq!(
+                strip("https://de-de.facebook.com/groups/123/").as_deref(),
+                Some("https://www.facebook.com/groups/123/")
+            );
+            assert_eq!(
+                strip("https://en-GB.facebook.com/").as_deref(),
+                Some("https://www.facebook.com/")
+            );
+            assert_eq!(strip("https://www.facebook.com/"), None);
+            assert_eq!(strip("https://de.wikipedia.org/wiki/Rust"), None);
+            assert_eq!(strip("https://en-gb.m.example.com/"), None);
+        }
+
+        #[test]
+        fn test_matches_domain() {
+            assert!(matches_domain("facebook.com", "facebook.com"));
+            assert!(matches_domain("www.Facebook.com", "facebook.com"));
+            assert!(!matches_domain("notfacebook.com", "facebook.com"));
+            assert!(!matches_domain("facebook.com.evil.org", "facebook.com"));
+        }
+
+        #[test]
+        fn test_api_resolution() {
+            let resolver = Resolver::builder()
+                .api_resolution(false)
+                .build()
+                .expect("built");
+            assert!(resolver.url_resolvers.is_empty());
+        }
+    }
+
+    mod sources {
+        use super::*;
+
+        mod reddit {
+            use super::*;
+
+            mod posts {
+                use super::*;
+
+                #[tokio::test]
+                async fn test_identity() {
+                    let url = "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m/";
+                    let result = resolve(url).await;
+                    assert!(result.is_ok());
+                    assert_eq!(url, result.expect("resolved"));
+                }
+
+                #[tokio::test]
+                async fn test_share_link() {
+                    let url = "https://www.reddit.com/r/AskTheWorld/s/mONZu40JNk";
+                    let result = resolve(url).await;
+                    assert!(result.is_ok());
+                    let expected = "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m/";
+                    assert!(result.expect("resolved").starts_with(expected));
+                }
+            }
+
+            mod comments {
+                use super::*;
+
+                #[tokio::test]
+                async fn test_identity() {
+                    let url =
+                        "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m/comment/nxfc5ci/";
+                    let result = resolve(url).await;
+                    assert!(result.is_ok());
+                    assert_eq!(url, result.expect("resolved"));
+                }
+
+                #[tokio::test]
+                async fn test_share_link() {
+                    let url = "https://www.reddit.com/r/AskTheWorld/s/5dTzVW0T3w";
+                    let result = resolve(url).await;
+                    assert!(result.is_ok());
+                    let expected = "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m/";
+                    assert!(result.expect("resolved").starts_with(expected));
+                }
+            }
+        }
+
+        mod facebook {
+            use super::*;
+
+            mod posts {
+                use super::*;
+
+                #[tokio::test]
+                async fn test_identity() {
+                    let url = "https://www.facebook.com/MoreFMWellington/posts/pfbid0vdnCZ6brToAep5XKfrM7FJBuMcuzsg64y896v4Ce2DJefNKGqe8mYhiJvAZwA5SGl";
+                    let result = resolve(url).await;
+                    assert!(result.is_ok());
+                    assert!(result.expect("resolved").starts_with(url));
+                }
+
+                // https://www.facebook.com/share/p/187BayNfDu/ -> https://www.facebook.com/MoreFMWellington/posts/pfbid0vdnCZ6brToAep5XKfrM7FJBuMcuzsg64y896v4Ce2DJefNKGqe8mYhiJvAZwA5SGl?rdid=meEnrHMlBkOtDSrX
+                // https://www.facebook.com/share/p/1BgeVKA3em/ -> https://www.facebook.com/rnznewzealand/posts/pfbid0jYRXtR6dGzJeAgLzpYA2ovWwuqtcVRnCMt5TWRmjcwDBPV4yBcNYLr1nwKhKupiPl?rdid=yhfTkLFfmiUYezxF // venezuela
+                // https://www.facebook.com/share/p/1BQ1Th6jhw/?mibextid=wwXIfr -> https://www.facebook.com/groups/vicdeals/permalink/25652124024437331/ // elliot
+                // https://www.facebook.com/share/p/1JZjfaFSrS/ -> https://www.facebook.com/MCDONewt/posts/pfbid02rgYgaUWkPGcXxMBwgntDtzhYtbmXmCLVrqdguvFS2pnSQRYan1MW5yyfwX1ZqjR1l?rdid=fPrLfmze1NgA6Pqj
+                // https://www.facebook.com/share/v/1866jpsdXs/?mibextid=wwXIfr -> https://www.facebook.com/groups/vicdeals/permalink/25658677397115327/
+                // https://www.facebook.com/share/1JigZ3TAud/?mibextid=wwXIfr -> https://www.facebook.com/photo.php?fbid=1279617124197361&set=a.301086902050393&type=3
+
+                #[tokio::test]
+                async fn test_basic() {
+                    // NOTE: MY OWN
+                    let url = "https://www.facebook.com/share/v/1866jpsdXs/?mibextid=wwXIfr";
+                    let result = resolve(url).await;
+                    let expected =
+                        "https://www.facebook.com/groups/vicdeals/permalink/25658677397115327/";
+                    assert!(result.expect("resolved").starts_with(expected))
+                }
+            }
+
+            mod story {
+                use super::*;
+
+                #[tokio::test]
+                async fn test_identity() {
+                    let url = "https://www.facebook.com/permalink.php?story_fbid=pfbid02mNMcJYekXP4bnUFkWguBsNddw6GkLHrWZG4ENa23x2h3G2SbbMeJRHByXuxhjKj1l&id=100088004222911";
+                    let result = resolve(url).await;
+                    assert!(result.is_ok());
+                    assert!(result.expect("resolved").starts_with(url));
+                }
+
+                #[tokio::test]
+                async fn test_basic() {
+                    let url = "https://www.facebook.com/share/p/1FKNBd86BV/";
+                    let result = resolve(url).await;
+                    let expected = "https://www.facebook.com/permalink.php?story_fbid=pfbid02mNMcJYekXP4bnUFkWguBsNddw6GkLHrWZG4ENa23x2h3G2SbbMeJRHByXuxhjKj1l&id=100088004222911";
+                    assert!(result.expect("resolved").starts_with(expected))
+                }
+            }
+
+            mod reels {
+                use super::*;
+
+                #[tokio::test]
+                async fn test_identity() {
+                    let url = "https://www.facebook.com/reel/1309748351194528";
+                    let result = resolve(url).await;
+                    assert!(result.is_ok());
+                    assert!(result.expect("resolved").starts_with(url));
+                }
+
+                #[tokio::test]
+                async fn test_basic() {
+                    let url = "https://www.facebook.com/share/r/14QeSSeP3nu/";
+                    let result = resolve(url).await;
+                    let expected = "https://www.facebook.com/reel/1309748351194528";
+                    assert!(result.expect("resolved").starts_with(expected))
+                }
+                // https://www.facebook.com/share/r/1AZhvx3n72/ -> https://www.facebook.com/reel/1605919000854039/?rdid=VxhE0u0GlwyGLnFD&share_url=https%3A%2F%2Fwww.facebook.com%2Fshare%2Fr%2F1AZhvx3n72%2F // reel
+            }
+
+            mod comments {
+                use super::*;
+
+                #[tokio::test]
+                async fn test_identity() {
+                    let url = "https://www.facebook.com/groups/vicdeals/permalink/25654608820855518/?comment_id=25654673274182406";
+                    let result = resolve(url).await;
+                    assert!(result.is_ok());
+                    assert!(result.expect("resolved").starts_with(url));
+                }
+
+                #[tokio::test]
+                async fn test_basic() {
+                    let url = "https://www.facebook.com/share/17cLnKdQth/";
+                    let result = resolve(url).await;
+                    let expected = "https://www.facebook.com/groups/vicdeals/permalink/25654608820855518/?comment_id=25654673274182406";
+                    assert!(result.expect("resolved").starts_with(expected))
+                }
+            }
+
+            mod photos {
+                use super::*;
+
+                #[tokio::test]
+                async fn test_identity() {
+                    let url = "https://www.facebook.com/photo.php?fbid=1279617124197361";
+                    let result = resolve(url).await;
+                    assert!(result.is_ok());
+                    assert!(result.expect("resolved").starts_with(url));
+                }
+
+                #[tokio::test]
+                async fn test_basic() {
+                    let url = "https://www.facebook.com/share/1JigZ3TAud/?mibextid=wwXIfr";
+                    let result = resolve(url).await;
+                    let expected = "https://www.facebook.com/photo.php?fbid=1279617124197361&set=a.301086902050393&type=3";
+                    assert!(result.expect("resolved").starts_with(expected))
+                }
+            }
+        }
+
+        mod instagram {
+            use super::*;
+
+            #[tokio::test]
+            async fn test_identity() {
+                let url = "https://www.instagram.com/p/DS8F57NjS_S";
+                let result = resolve(url).await;
+                assert!(result.is_ok());
+                assert!(result.expect("resolved").starts_with(url));
+            }
+
+            #[tokio::test]
+            async fn test_basic() {
+                let url = "https://www.instagram.com/p/DS8F57NjS_S/?igsh=MWxidXNpbWV6djIxcQ==";
+                let result = resolve(url).await;
+                let expected = "https://www.instagram.com/p/DS8F57NjS_S";
+                assert!(result.expect("resolved").starts_with(expected))
+            }
+        }
+
+        mod linkedin {
+            use super::*;
+
+            #[tokio::test]
+            async fn test_identity() {
+                let url = "https://www.linkedin.com/posts/robert-a-saigh-7b2b05359_i-have-warned-about-this-before-ai-is-activity-7415393290201534464-seUj";
+                let result = resolve(url).await;
+                assert!(result.is_ok());
+                assert!(result.expect("resolved").starts_with(url));
+            }
+
+            #[tokio::test]
+            async fn test_basic() {
+                let url = "https://www.linkedin.com/posts/robert-a-saigh-7b2b05359_i-have-warned-about-this-before-ai-is-activity-7415393290201534464-seUj?utm_source=share&utm_medium=member_desktop&rcm=ACoAAGLnhZIB3taWr06FFIEsRcT5ekzKWtFC83A";
+                let result = resolve(url).await;
+                let expected = "https://www.linkedin.com/posts/robert-a-saigh-7b2b05359_i-have-warned-about-this-before-ai-is-activity-7415393290201534464-seUj";
+                assert!(result.expect("resolved").starts_with(expected))
+            }
+        }
+    }
+
+    mod meta_refresh {
+        use super::*;
+
+        #[test]
+        fn test_basic() {
+            let html = r#"<meta http-equiv="refresh" content="0;url=https://example.com">"#;
+            let result = extract_meta_refresh(html);
+            assert_eq!(result, Some("https://example.com".to_string()));
+        }
+
+        #[test]
+        fn test_with_delay() {
+            let html = r#"<meta http-equiv="refresh" content="5;url=https://example.com/page">"#;
+            let result = extract_meta_refresh(html);
+            assert_eq!(result, Some("https://example.com/page".to_string()));
+        }
+
+        #[test]
+        fn test_single_quotes() {
+            let html = r#"<meta http-equiv='refresh' content='0;url=https://example.com'>"#;
+            let result = extract_meta_refresh(html);
+            assert_eq!(result, Some("https://example.com".to_string()));
+        }
+
+        #[test]
+        fn test_no_quotes() {
+            let html = r#"<meta http-equiv=refresh content=0;url=https://example.com>"#;
+            let result = extract_meta_refresh(html);
+            assert_eq!(result, Some("https://example.com".to_string()));
+        }
+
+        #[test]
+        fn test_uppercase() {
+            let html = r#"<META HTTP-EQUIV="REFRESH" CONTENT="0;URL=https://example.com">"#;
+            let result = extract_meta_refresh(html);
+            assert_eq!(result, Some("https://example.com".to_string()));
+        }
+
+        #[test]
+        fn test_mixed_case() {
+            let html = r#"<Meta Http-Equiv="Refresh" Content="0;Url=https://example.com">"#;
+            let result = extract_meta_refresh(html);
+            assert_eq!(result, Some("https://example.com".to_string()));
+        }
+
+        #[test]
+        fn test_with_spaces() {
+            let html = r#"<meta http-equiv="refresh" content="0; url = https://example.com ">"#;
+            let result = extract_meta_refresh(html);
+            assert_eq!(result, Some("https://example.com".to_string()));
+        }
+
+        #[test]
+        fn test_quoted_url() {
+            let html = r#"<meta http-equiv="refresh" content="0; URL='https://example.com/a b'">"#;
+            let result = extract_meta_refresh(html);
+            assert_eq!(result, Some("https://example.com/a b".to_string()));
+        }
+
+        #[test]
+        fn test_without_url_prefix() {
+            let html = r#"<meta http-equiv="refresh" content="0, https://example.com">"#;
+            let result = extract_meta_refresh(html);
+            assert_eq!(result, Some("https://example.com".to_string()));
+        }
+
+        #[test]
+        fn test_attribute_order() {
+            let html = r#"<meta content="0;url=https://example.com" http-equiv="refresh">"#;
+            let result = extract_meta_refresh(html);
+            assert_eq!(result, Some("https://example.com".to_string()));
+        }
+
+        #[test]
+        fn test_entities() {
+            let html = r#"<meta http-equiv="refresh" content="0;url=https://example.com/?a=1&amp;b=2&#38;c=3">"#;
+            let result = extract_meta_refresh(html);
+            assert_eq!(result, Some("https://example.com/?a=1&b=2&c=3".to_string()));
+        }
+
+        #[test]
+        fn test_after_other_meta_tags() {
+            let html = r#"
+                <meta charset="utf-8">
+                <meta name="viewport" content="width=device-width">
+                <meta http-equiv="refresh" content="0;url=https://example.com">
+            "#;
+            let result = extract_meta_refresh(html);
+            assert_eq!(result, Some("https://example.com".to_string()));
+        }
+
+        #[test]
+        fn test_multiple() {
+            let html = r#"
+                <meta http-equiv="refresh" content="0;url=https://example.com/first">
+                <meta http-equiv="refresh" content="5">
+                <meta http-equiv="refresh" content="1;url=/second">
+            "#;
+            assert_eq!(
+                extract_meta_refreshes(html),
+                vec!["https://example.com/first", "/second"]
+            );
+            let result = extract_meta_refresh(html);
+            assert_eq!(result, Some("https://example.com/first".to_string()));
+        }
+
+        #[test]
+        fn test_noscript() {
+            let html = r#"<noscript><meta http-equiv="refresh" content="0;url=/no-js"></noscript>"#;
+            let result = extract_meta_refresh(html);
+            assert_eq!(result, None);
+        }
+
+        #[test]
+        fn test_refresh_url() {
+            assert_eq!(refresh_url("0;url=/a").as_deref(), Some("/a"));
+            assert_eq!(refresh_url(" 1.5 ; URL=\"/a\"").as_deref(), Some("/a"));
+            assert_eq!(refresh_url("0;"), None);
+            assert_eq!(refresh_url("5"), None);
+            assert_eq!(refresh_url("url=/a"), None);
+        }
+
+        #[test]
+        fn test_in_full_html() {
+            let html = r#"
+                <!DOCTYPE html>
+                <html>
+                <head>
+                    <title>Redirect</title>
+                    <meta http-equiv="refresh" content="0;url=https://example.com">
+                </head>
+                <body>Redirecting...</body>
+                </html>
+            "#;
+            let result = extract_meta_refresh(html);
+            assert_eq!(result, Some("https://example.com".to_string()));
+        }
+
+        #[test]
+        fn test_no_meta_refresh() {
+            let html = r#"<html><body>No redirect here</body></html>"#;
+            let result = extract_meta_refresh(html);
+            assert_eq!(result, None);
+        }
+
+        #[test]
+        fn test_meta_without_refresh() {
+            let html = r#"<meta charset="utf-8">"#;
+            let result = extract_meta_refresh(html);
+            assert_eq!(result, None);
+        }
+
+        #[test]
+        fn test_without_url() {
+            let html = r#"<meta http-equiv="refresh" content="5">"#;
+            let result = extract_meta_refresh(html);
+            assert_eq!(result, None);
+        }
+
+        #[test]
+        fn test_with_relative_url() {
+            let html = r#"<meta http-equiv="refresh" content="0;url=/relative/path">"#;
+            let result = extract_meta_refresh(html);
+            assert_eq!(result, Some("/relative/path".to_string()));
+        }
+
+        #[test]
+        fn test_with_url_with_query_params() {
+            let html = r#"<meta http-equiv="refresh" content="0;url=https://example.com?foo=bar&baz=qux">"#;
+            let result = extract_meta_refresh(html);
+            assert_eq!(
+                result,
+                Some("https://example.com?foo=bar&baz=qux".to_string())
+            );
+        }
+
+        #[test]
+        fn test_empty_html() {
+            let html = "";
+            let result = extract_meta_refresh(html);
+            assert_eq!(result, None);
+        }
+
+        #[test]
+        fn test_malformed_meta_tag() {
+            let html = r#"<meta http-equiv="refresh" content=">"#;
+            let result = extract_meta_refresh(html);
+            assert_eq!(result, None);
+        }
+
+        #[test]
+        fn test_non_ascii() {
+            // Lowercasing "İ" changes its length in bytes, which used to shift our offsets
+            let html = r#"<title>İİ</title><meta http-equiv="refresh" content="0;url=https://example.com/ü">"#;
+            let result = extract_meta_refresh(html);
+            assert_eq!(result, Some("https://example.com/ü".to_string()));
+        }
+
+        proptest::proptest! {
+            #[test]
+            fn test_never_panics(html in ".*") {
+                let _ = extract_meta_refresh(&html);
+            }
+
+            #[test]
+            fn test_never_panics_in_tag(prefix in ".*", content in ".*", suffix in ".*") {
+                let html = format!(
+                    r#"{}<meta http-equiv="refresh" content={}>{}"#,
+                    prefix, content, suffix
+                );
+                let _ = extract_meta_refresh(&html);
+            }
+        }
+    }
+
+    mod https_upgrade {
+        use super::*;
+
+        fn hop(url: &str) -> Hop {
+            Hop {
+                status: StatusCode::OK,
+                url: url.to_string(),
+                kind: HopKind::Start,
+                elapsed: Duration::ZERO,
+                title: None,
+            }
+        }
+
+        // The URL `url` would be upgraded to, if the hosts in `hsts_hosts` sent HSTS
+        async fn upgrade(url: &str, hsts_hosts: &[&str]) -> Option<String> {
+            let resolver = Resolver::new().expect("resolver");
+            let hsts_hosts = hsts_hosts.iter().map(|host| host.to_string()).collect();
+            resolver
+                .upgrade_to_https(resolver.client(), &[hop(url)], &hsts_hosts)
+                .await
+                .map(|hop| hop.url)
+        }
+
+        #[tokio::test]
+        async fn test_hsts() {
+            assert_eq!(
+                upgrade("http://example.com/a?b=c", &["example.com"])
+                    .await
+                    .as_deref(),
+                Some("https://example.com/a?b=c")
+            );
+
+            // Already secure, or on a port that would not serve HTTPS
+            assert_eq!(
+                upgrade("https://example.com/a", &["example.com"]).await,
+                None
+            );
+            assert_eq!(
+                upgrade("http://example.com:8080/a", &["example.com"]).await,
+                None
+            );
+        }
+    }
+
+    mod retry_after {
+        use super::*;
+
+        fn retry_after(value: &str) -> Option<Duration> {
+            let mut headers = header::HeaderMap::new();
+            headers.insert(
+                header::RETRY_AFTER,
+                header::HeaderValue::from_str(value).expect("valid header"),
+            );
+            super::retry_after(&headers)
+        }
+
+        #[test]
+        fn test_seconds() {
+            assert_eq!(retry_after("120"), Some(Duration::from_secs(120)));
+        }
+
+        #[test]
+        fn test_date() {
+            let date = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(600));
+            let delay = retry_after(&date).expect("delay");
+            assert!(delay > Duration::from_secs(590) && delay <= Duration::from_secs(600));
+
+            // A date in the past means we may retry straight away
+            assert_eq!(
+                retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+                Some(Duration::ZERO)
+            );
+        }
+
+        #[test]
+        fn test_invalid() {
+            assert_eq!(retry_after("soon"), None);
+        }
+    }
+
+    mod circuit_breaker {
+        use super::*;
+
+        #[test]
+        fn test_opens_after_failures() {
+            let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+            let url = Url::parse("https://Example.com/a").expect("valid url");
+            let other = Url::parse("https://example.org/a").expect("valid url");
+
+            breaker.record(&url, true);
+            assert!(breaker.check(&url).is_ok());
+            breaker.record(&url, true);
+            assert!(matches!(
+                breaker.check(&url),
+                Err(ResolveError::CircuitOpen(host)) if host == "example.com"
+            ));
+            assert!(breaker.check(&other).is_ok());
+        }
+
+        #[test]
+        fn test_success_resets() {
+            let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+            let url = Url::parse("https://example.com/a").expect("valid url");
+            breaker.record(&url, true);
+            breaker.record(&url, false);
+            breaker.record(&url, true);
+            assert!(breaker.check(&url).is_ok());
+        }
+
+        #[test]
+        fn test_cooldown() {
+            let breaker = CircuitBreaker::new(1, Duration::ZERO);
+            let url = Url::parse("https://example.com/a").expect("valid url");
+            breaker.record(&url, true);
+            assert!(breaker.check(&url).is_ok());
+        }
+    }
+
+    mod host_limits {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_limits_each_host() {
+            let limits = HostLimits::new(1);
+            let facebook = Url::parse("https://www.facebook.com/a").expect("valid url");
+            let facebook_upper = Url::parse("https://WWW.facebook.com/b").expect("valid url");
+            let reddit = Url::parse("https://www.reddit.com/a").expect("valid url");
+            let wait = Duration::from_millis(50);
+
+            let permit = limits.acquire(&facebook).await;
+            assert!(permit.is_some());
+
+            // Another host is unaffected, but the same host must wait
+            let other = limits.acquire(&reddit);
+            assert!(tokio::time::timeout(wait, other).await.is_ok());
+            let same = limits.acquire(&facebook_upper);
+            assert!(tokio::time::timeout(wait, same).await.is_err());
+
+            drop(permit);
+            let same = limits.acquire(&facebook_upper);
+            assert!(tokio::time::timeout(wait, same).await.is_ok());
+        }
+    }
+
+    mod body {
+        use super::*;
+
+        #[test]
+        fn test_find_head_end() {
+            let body = b"<html><HEAD><title>x</title></Head><body>";
+            assert_eq!(find_head_end(body, 0), Some(35));
+            assert_eq!(&body[..35], b"<html><HEAD><title>x</title></Head>");
+            assert_eq!(find_head_end(body, 30), None);
+            assert_eq!(find_head_end(body, 100), None);
+            assert_eq!(find_head_end(b"<html><body>", 0), None);
+        }
+    }
+
+    mod trace {
+        use super::*;
+
+        #[test]
+        fn test_hop_display() {
+            let hop = Hop {
+                status: StatusCode::MOVED_PERMANENTLY,
+                url: "https://www.reddit.com/r/AskTheWorld/s/mONZu40JNk".to_string(),
+                kind: HopKind::Start,
+                elapsed: Duration::ZERO,
+                title: None,
+            };
+            assert_eq!(
+                hop.to_string(),
+                "301 https://www.reddit.com/r/AskTheWorld/s/mONZu40JNk (start)"
+            );
+        }
+
+        #[test]
+        fn test_final_url() {
+            let hops = [
+                Hop {
+                    status: StatusCode::FOUND,
+                    url: "https://example.com/a".to_string(),
+                    kind: HopKind::Start,
+                    elapsed: Duration::ZERO,
+                    title: None,
+                },
+                Hop {
+                    status: StatusCode::OK,
+                    url: "https://example.com/b".to_string(),
+                    kind: HopKind::MetaRefresh,
+                    elapsed: Duration::ZERO,
+                    title: None,
+                },
+            ];
+            assert_eq!(final_url(&hops), "https://example.com/b");
+        }
+
+        #[test]
+        fn test_final_status() {
+            let hop = |status, kind| Hop {
+                status,
+                url: "https://www.reddit.com/r/rust/comments/abc/".to_string(),
+                kind,
+                elapsed: Duration::ZERO,
+                title: None,
+            };
+            let hops = [
+                hop(StatusCode::MOVED_PERMANENTLY, HopKind::Start),
+                hop(StatusCode::OK, HopKind::HttpRedirect),
+                hop(StatusCode::OK, HopKind::Unavailable),
+            ];
+            assert_eq!(final_status(&hops), Some(StatusCode::OK));
+            assert!(is_dead(&hops));
+            assert!(!is_dead(&hops[..2]));
+            assert!(is_dead(&[hop(StatusCode::NOT_FOUND, HopKind::Start)]));
+            assert_eq!(final_status(&[]), None);
+        }
+
+        #[test]
+        fn test_hop_title() {
+            let hop = Hop {
+                status: StatusCode::OK,
+                url: "https://www.youtube.com/watch?v=dQw4w9WgXcQ".to_string(),
+                kind: HopKind::OEmbed,
+                elapsed: Duration::ZERO,
+                title: Some("Never Gonna Give You Up".to_string()),
+            };
+            assert_eq!(
+                hop.to_string(),
+                "200 https://www.youtube.com/watch?v=dQw4w9WgXcQ (oEmbed) \"Never Gonna Give You Up\""
+            );
+        }
+    }
+
+    mod errors {
+        use super::*;
+
+        #[test]
+        fn test_retryable() {
+            assert!(ResolveError::Timeout.is_retryable());
+            assert!(ResolveError::Http(StatusCode::TOO_MANY_REQUESTS).is_retryable());
+            assert!(ResolveError::Http(StatusCode::BAD_GATEWAY).is_retryable());
+            assert!(
+                ResolveError::RetryAfter(StatusCode::SERVICE_UNAVAILABLE, Duration::ZERO)
+                    .is_retryable()
+            );
+            assert!(!ResolveError::TooManyRedirects.is_retryable());
+            assert!(!ResolveError::InvalidUrl(url::ParseError::EmptyHost).is_retryable());
+        }
+
+        #[tokio::test]
+        async fn test_invalid_url() {
+            let result = resolve("not a valid url").await;
+            assert!(matches!(result, Err(ResolveError::InvalidUrl(_))));
+        }
+
+        #[tokio::test]
+        async fn test_invalid_scheme() {
+            let result = resolve("hxxp://example.com").await;
+            assert!(result.is_err());
+        }
+
+        #[tokio::test]
+        async fn test_dns_failure() {
+            let result = resolve("5792d248-2714-4923-8aa4-6c8ff4016a44.govt.nz").await;
+            assert!(result.is_err());
+        }
+    }
+
+    // Resolution against a local server, so that we can test how we handle redirects and
+    // failures without depending on how real sites behave (or on the network at all)
+    mod mock {
+        use wiremock::{
+            Mock, MockServer, ResponseTemplate,
+            matchers::{header, header_regex, method, path},
+        };
+
+        use super::*;
+
+        // A resolver that only follows redirects, and does not retry unless asked
+        fn resolver() -> ResolverBuilder {
+            Resolver::builder()
+                .allow_private_networks(true)
+                .api_resolution(false)
+                .oembed(false)
+                .max_retries(0)
+        }
+
+        fn redirect(to: &str) -> ResponseTemplate {
+            ResponseTemplate::new(301).insert_header("Location", to)
+        }
+
+        fn page(head: &str) -> ResponseTemplate {
+            ResponseTemplate::new(200).set_body_raw(
+                format!("<html><head>{}</head><body></body></html>", head),
+                "text/html",
+            )
+        }
+
+        async fn mount(server: &MockServer, at: &str, response: ResponseTemplate) {
+            Mock::given(path(at))
+                .respond_with(response)
+                .mount(server)
+                .await;
+        }
+
+        fn kinds(resolution: &Resolution) -> Vec<HopKind> {
+            resolution.hops.iter().map(|hop| hop.kind).collect()
+        }
+
+        #[tokio::test]
+        async fn test_redirect_chain() {
+            let server = MockServer::start().await;
+            mount(&server, "/a", redirect(&format!("{}/b", server.uri()))).await;
+            mount(&server, "/b", redirect("/c")).await;
+            mount(&server, "/c", page("<title>C</title>")).await;
+
+            let resolver = resolver().build().expect("built");
+            let resolution = resolver
+                .resolve_traced(&format!("{}/a", server.uri()))
+                .await
+                .expect("resolved");
+            assert_eq!(resolution.url, format!("{}/c", server.uri()));
+            assert_eq!(
+                kinds(&resolution),
+                [HopKind::Start, HopKind::HttpRedirect, HopKind::HttpRedirect]
+            );
+            assert_eq!(resolution.hops[0].status, StatusCode::MOVED_PERMANENTLY);
+            assert_eq!(resolution.hops[2].status, StatusCode::OK);
+        }
+
+        #[tokio::test]
+        async fn test_same_site() {
+            let server = MockServer::start().await;
+            let port = server.address().port();
+            mount(&server, "/a", redirect("/b")).await;
+            mount(
+                &server,
+                "/b",
+                redirect(&format!("http://localhost:{}/c", port)),
+            )
+            .await;
+            Mock::given(path("/c"))
+                .respond_with(page(""))
+                .expect(0)
+                .mount(&server)
+                .await;
+
+            // The redirect within the site is followed, but not the one off it
+            let resolver = resolver().same_site(true).build().expect("built");
+            let result = resolver.resolve(&format!("{}/a", server.uri())).await;
+            assert!(
+                matches!(&result, Err(ResolveError::OffSite(url)) if url == &format!("http://localhost:{}/c", port)),
+                "{:?}",
+                result
+            );
+        }
+
+        #[tokio::test]
+        async fn test_same_site_oembed() {
+            let server = MockServer::start().await;
+            let port = server.address().port();
+            mount(
+                &server,
+                "/a",
+                page(&format!(
+                    r#"<link rel="alternate" type="application/json+oembed" href="http://localhost:{}/oembed">"#,
+                    port
+                )),
+            )
+            .await;
+            Mock::given(path("/oembed"))
+                .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"title":"A"}"#))
+                .expect(0)
+                .mount(&server)
+                .await;
+
+            let resolver = resolver()
+                .oembed(true)
+                .same_site(true)
+                .build()
+                .expect("built");
+            let resolution = resolver
+                .resolve_traced(&format!("{}/a", server.uri()))
+                .await
+                .expect("resolved");
+            assert_eq!(kinds(&resolution), [HopKind::Start]);
+        }
+
+        #[tokio::test]
+        async fn test_private_network() {
+            let server = MockServer::start().await;
+            Mock::given(path("/a"))
+                .respond_with(page(""))
+                .expect(0)
+                .mount(&server)
+                .await;
+
+            // The mock server is on loopback, like anything on the machine we run on
+            let resolver = resolver()
+                .allow_private_networks(false)
+                .build()
+                .expect("built");
+            let result = resolver.resolve(&format!("{}/a", server.uri())).await;
+            assert!(
+                matches!(&result, Err(ResolveError::PrivateNetwork(_))),
+                "{:?}",
+                result
+            );
+        }
+
+        // A proxy is sent the whole URL, so the mock server can stand in for one.  The
+        // `.invalid` domain never resolves (RFC 6761), so only the proxy can reach it
+        fn proxied(server: &MockServer) -> ResolverBuilder {
+            resolver()
+                .proxy(Url::parse(&server.uri()).expect("valid url"))
+                .https_upgrade(false)
+        }
+
+        #[tokio::test]
+        async fn test_proxy() {
+            let proxy = MockServer::start().await;
+            Mock::given(path("/a"))
+                .respond_with(page(""))
+                .expect(1)
+                .mount(&proxy)
+                .await;
+
+            let resolver = proxied(&proxy).build().expect("built");
+            let resolved = resolver
+                .resolve("http://rsl.invalid/a")
+                .await
+                .expect("resolved");
+            assert_eq!(resolved, "http://rsl.invalid/a");
+        }
+
+        #[tokio::test]
+        async fn test_proxy_private_network() {
+            let proxy = MockServer::start().await;
+            Mock::given(path("/a"))
+                .respond_with(page(""))
+                .expect(0)
+                .mount(&proxy)
+                .await;
+
+            // The proxy itself is on loopback, which is allowed, but it would look up the
+            // link's host for us, so a host we cannot vet ourselves must not be requested
+            let resolver = proxied(&proxy)
+                .allow_private_networks(false)
+                .build()
+                .expect("built");
+            let result = resolver.resolve("http://rsl.invalid/a").await;
+            assert!(
+                matches!(&result, Err(ResolveError::PrivateNetwork(_))),
+                "{:?}",
+                result
+            );
+        }
+
+        #[tokio::test]
+        async fn test_head_strategy() {
+            let server = MockServer::start().await;
+            Mock::given(method("HEAD"))
+                .and(path("/a"))
+                .respond_with(redirect("/b"))
+                .expect(1)
+                .mount(&server)
+                .await;
+            Mock::given(method("HEAD"))
+                .and(path("/b"))
+                .respond_with(ResponseTemplate::new(200).insert_header("Content-Type", "image/png"))
+                .expect(1)
+                .mount(&server)
+                .await;
+
+            // Neither URL should be fetched with a GET, as the final one is not HTML
+            let resolver = resolver()
+                .request_strategy(RequestStrategy::Head)
+                .build()
+                .expect("built");
+            let resolved = resolver
+                .resolve(&format!("{}/a", server.uri()))
+                .await
+                .expect("resolved");
+            assert_eq!(resolved, format!("{}/b", server.uri()));
+        }
+
+        #[tokio::test]
+        async fn test_meta_refresh() {
+            let server = MockServer::start().await;
+            mount(
+                &server,
+                "/a",
+                page(r#"<meta http-equiv="refresh" content="0; url=/b">"#),
+            )
+            .await;
+            mount(&server, "/b", page("")).await;
+
+            let resolver = resolver().build().expect("built");
+            let resolution = resolver
+                .resolve_traced(&format!("{}/a", server.uri()))
+                .await
+                .expect("resolved");
+            assert_eq!(resolution.url, format!("{}/b", server.uri()));
+            assert_eq!(kinds(&resolution), [HopKind::Start, HopKind::MetaRefresh]);
+        }
+
+        #[tokio::test]
+        async fn test_domain_policy() {
+            let server = MockServer::start().await;
+            Mock::given(path("/a"))
+                .and(header("User-Agent", "rsl-test"))
+                .and(header("X-Test", "yes"))
+                .respond_with(page(r#"<meta http-equiv="refresh" content="0; url=/b">"#))
+                .expect(1)
+                .mount(&server)
+                .await;
+            Mock::given(path("/b"))
+                .respond_with(page(""))
+                .expect(0)
+                .mount(&server)
+                .await;
+
+            let host = server.address().ip().to_string();
+            let resolver = resolver()
+                .domain_policy(
+                    &host,
+                    DomainPolicy {
+                        meta_refresh: Some(false),
+                        user_agent: Some("rsl-test".to_string()),
+                        headers: [("X-Test".to_string(), "yes".to_string())].into(),
+                        ..DomainPolicy::default()
+                    },
+                )
+                .build()
+                .expect("built");
+            let resolved = resolver
+                .resolve(&format!("{}/a", server.uri()))
+                .await
+                .expect("resolved");
+            assert_eq!(resolved, format!("{}/a", server.uri()));
+        }
+
+        #[tokio::test]
+        async fn test_unsupported_scheme() {
+            let server = MockServer::start().await;
+            mount(
+                &server,
+                "/a",
+                page(r#"<meta http-equiv="refresh" content="0; url=javascript:alert(document.cookie)">"#),
+            )
+            .await;
+            mount(
+                &server,
+                "/b",
+                redirect("data:text/html,<script>alert(1)</script>"),
+            )
+            .await;
+
+            let resolver = resolver().build().expect("built");
+            for (at, scheme) in [("/a", "javascript"), ("/b", "data")] {
+                let result = resolver.resolve(&format!("{}{}", server.uri(), at)).await;
+                assert!(
+                    matches!(&result, Err(ResolveError::UnsupportedScheme(s)) if s == scheme),
+                    "{:?}",
+                    result
+                );
+            }
+        }
+
+        // An API that answers (or fails) after a delay
+        struct SlowApi(Duration, Option<&'static str>);
+
+        impl UrlResolver for SlowApi {
+            fn name(&self) -> &'static str {
+                "SlowApi"
+            }
+
+            fn resolve<'a>(
+                &'a self,
+                _client: &'a reqwest::Client,
+                _url: &'a Url,
+            ) -> Option<BoxFuture<'a, ResolveOutput>> {
+                Some(Box::pin(async move {
+                    tokio::time::sleep(self.0).await;
+                    self.1
+                        .map(str::to_string)
+                        .ok_or(ResolveError::Api("SlowApi"))
+                }))
+            }
+        }
+
+        #[tokio::test]
+        async fn test_race() {
+            let server = MockServer::start().await;
+            mount(&server, "/fast", page("")).await;
+            mount(&server, "/slow", page("").set_delay(Duration::from_secs(2))).await;
+            let api = "https://example.com/api";
+
+            // Following redirects wins
+            let resolver = resolver()
+                .url_resolver(SlowApi(Duration::from_secs(2), Some(api)))
+                .build()
+                .expect("built");
+            let start = Instant::now();
+            let resolution = resolver
+                .resolve_traced(&format!("{}/fast", server.uri()))
+                .await
+                .expect("resolved");
+            assert_eq!(resolution.url, format!("{}/fast", server.uri()));
+            assert!(start.elapsed() < Duration::from_secs(1));
+
+            // The API wins
+            let resolver = resolver()
+                .url_resolver(SlowApi(Duration::ZERO, Some(api)))
+                .build()
+                .expect("built");
+            let start = Instant::now();
+            let resolution = resolver
+                .resolve_traced(&format!("{}/slow", server.uri()))
+                .await
+                .expect("resolved");
+            assert_eq!(kinds(&resolution), [HopKind::Api]);
+            assert!(start.elapsed() < Duration::from_secs(1));
+
+            // The API fails, so we wait for the redirects
+            let resolver = resolver()
+                .url_resolver(SlowApi(Duration::ZERO, None))
+                .build()
+                .expect("built");
+            let resolution = resolver
+                .resolve_traced(&format!("{}/slow", server.uri()))
+                .await
+                .expect("resolved");
+            assert_eq!(resolution.url, format!("{}/slow", server.uri()));
+        }
+
+        #[tokio::test]
+        async fn test_no_race() {
+            let server = MockServer::start().await;
+            Mock::given(path("/a"))
+                .respond_with(page(""))
+                .expect(0)
+                .mount(&server)
+                .await;
+
+            // Without racing, we only follow redirects if the API fails
+            let resolver = resolver()
+                .race(false)
+                .url_resolver(SlowApi(
+                    Duration::from_millis(100),
+                    Some("https://example.com/api"),
+                ))
+                .build()
+                .expect("built");
+            let resolved = resolver
+                .resolve(&format!("{}/a", server.uri()))
+                .await
+                .expect("resolved");
+            assert_eq!(resolved, "https://example.com/api");
+        }
+
+        #[tokio::test]
+        async fn test_request_headers() {
+            let server = MockServer::start().await;
+            Mock::given(path("/a"))
+                .and(header("accept-language", "en"))
+                .and(header_regex("accept-encoding", r"\bbr\b"))
+                .and(header_regex("accept-encoding", r"\bzstd\b"))
+                .respond_with(page(""))
+                .mount(&server)
+                .await;
+
+            // Otherwise, we would get a 404
+            let resolver = resolver().build().expect("built");
+            let resolution = resolver
+                .resolve_traced(&format!("{}/a", server.uri()))
+                .await
+                .expect("resolved");
+            assert_eq!(resolution.status(), Some(StatusCode::OK));
+        }
+
+        #[tokio::test]
+        async fn test_dead_links() {
+            let server = MockServer::start().await;
+            mount(&server, "/a", redirect("/gone")).await;
+            mount(&server, "/gone", ResponseTemplate::new(410)).await;
+            mount(&server, "/b", page("<title>B</title>")).await;
+
+            let resolver = resolver().build().expect("built");
+            let resolution = resolver
+                .resolve_traced(&format!("{}/a", server.uri()))
+                .await
+                .expect("resolved");
+            assert_eq!(resolution.status(), Some(StatusCode::GONE));
+            assert!(resolution.is_dead());
+
+            let resolution = resolver
+                .resolve_traced(&format!("{}/b", server.uri()))
+                .await
+                .expect("resolved");
+            assert_eq!(resolution.status(), Some(StatusCode::OK));
+            assert!(!resolution.is_dead());
+        }
+
+        #[tokio::test]
+        async fn test_refresh_header() {
+            let server = MockServer::start().await;
+            mount(
+                &server,
+                "/a",
+                ResponseTemplate::new(200).insert_header("Refresh", "0; url=/b"),
+            )
+            .await;
+            mount(&server, "/b", page("")).await;
+            mount(
+                &server,
+                "/loop",
+                ResponseTemplate::new(200).insert_header("Refresh", "0; URL=/loop"),
+            )
+            .await;
+
+            let resolver = resolver().build().expect("built");
+            let resolution = resolver
+                .resolve_traced(&format!("{}/a", server.uri()))
+                .await
+                .expect("resolved");
+            assert_eq!(resolution.url, format!("{}/b", server.uri()));
+            assert_eq!(kinds(&resolution), [HopKind::Start, HopKind::RefreshHeader]);
+
+            let result = resolver.resolve(&format!("{}/loop", server.uri())).await;
+            assert!(matches!(result, Err(ResolveError::MetaRefreshLoop)));
+        }
+
+        #[tokio::test]
+        async fn test_too_many_redirects() {
+            let server = MockServer::start().await;
+            mount(&server, "/loop", redirect("/loop")).await;
+
+            let resolver = resolver().max_redirects(3).build().expect("built");
+            let result = resolver.resolve(&format!("{}/loop", server.uri())).await;
+            assert!(matches!(result, Err(ResolveError::TooManyRedirects)));
+        }
+
+        #[tokio::test]
+        async fn test_meta_refresh_loop() {
+            let server = MockServer::start().await;
+            mount(
+                &server,
+                "/loop",
+                page(r#"<meta http-equiv="refresh" content="0; url=/loop">"#),
+            )
+            .await;
+
+            let resolver = resolver().build().expect("built");
+            let result = resolver.resolve(&format!("{}/loop", server.uri())).await;
+            assert!(matches!(result, Err(ResolveError::MetaRefreshLoop)));
+        }
+
+        #[tokio::test]
+        async fn test_server_error() {
+            let server = MockServer::start().await;
+            mount(&server, "/a", ResponseTemplate::new(503)).await;
+
+            let resolver = resolver().build().expect("built");
+            let result = resolver.resolve(&format!("{}/a", server.uri())).await;
+            assert!(matches!(
+                result,
+                Err(ResolveError::Http(StatusCode::SERVICE_UNAVAILABLE))
+            ));
+        }
+
+        #[tokio::test]
+        async fn test_server_error_retried() {
+            let server = MockServer::start().await;
+            Mock::given(path("/a"))
+                .respond_with(ResponseTemplate::new(502))
+                .up_to_n_times(1)
+                .expect(1)
+                .mount(&server)
+                .await;
+            mount(&server, "/a", page("")).await;
+
+            let resolver = resolver().max_retries(1).build().expect("built");
+            let resolved = resolver
+                .resolve(&format!("{}/a", server.uri()))
+                .await
+                .expect("resolved");
+            assert_eq!(resolved, format!("{}/a", server.uri()));
+        }
+
+        #[tokio::test]
+        async fn test_server_error_exhausted() {
+            let server = MockServer::start().await;
+            Mock::given(path("/a"))
+                .respond_with(ResponseTemplate::new(503))
+                .expect(2)
+                .mount(&server)
+                .await;
+
+            let resolver = resolver().max_retries(1).build().expect("built");
+            let result = resolver.resolve(&format!("{}/a", server.uri())).await;
+            assert!(matches!(
+                result,
+                Err(ResolveError::Http(StatusCode::SERVICE_UNAVAILABLE))
+            ));
+        }
+
+        #[tokio::test]
+        async fn test_client_error_not_retried() {
+            let server = MockServer::start().await;
+            Mock::given(path("/a"))
+                .respond_with(ResponseTemplate::new(404))
+                .expect(1)
+                .mount(&server)
+                .await;
+
+            // A 404 is an answer (a dead link), not a failure, so is not asked again
+            let resolver = resolver().max_retries(3).build().expect("built");
+            let resolution = resolver
+                .resolve_traced(&format!("{}/a", server.uri()))
+                .await
+                .expect("resolved");
+            assert_eq!(resolution.status(), Some(StatusCode::NOT_FOUND));
+        }
+
+        // A server that hangs up on the first request without answering, and answers the
+        // rest (which wiremock cannot do), returning its URL
+        async fn hang_up_once() -> String {
+            use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+                .await
+                .expect("bound");
+            let address = listener.local_addr().expect("address");
+            tokio::spawn(async move {
+                let mut first = true;
+                while let Ok((mut stream, _)) = listener.accept().await {
+                    let _ = stream.read(&mut [0; 4096]).await;
+                    if !std::mem::take(&mut first) {
+                        let _ = stream
+                            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                            .await;
+                    }
+                }
+            });
+            format!("http://{}/a", address)
+        }
+
+        #[tokio::test]
+        async fn test_connection_reset() {
+            let url = hang_up_once().await;
+            let result = resolver().build().expect("built").resolve(&url).await;
+            assert!(
+                matches!(&result, Err(e @ ResolveError::Request(_)) if e.is_retryable()),
+                "{:?}",
+                result
+            );
+        }
+
+        #[tokio::test]
+        async fn test_connection_reset_retried() {
+            let url = hang_up_once().await;
+            let resolver = resolver().max_retries(1).build().expect("built");
+            let resolved = resolver.resolve(&url).await.expect("resolved");
+            assert_eq!(resolved, url);
+        }
+
+        #[tokio::test]
+        async fn test_retry_after() {
+            let server = MockServer::start().await;
+            Mock::given(path("/a"))
+                .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+                .up_to_n_times(1)
+                .mount(&server)
+                .await;
+            mount(&server, "/a", page("")).await;
+
+            // We wait as long as we are asked to (i.e., not at all) before trying again
+            let resolver = resolver().max_retries(1).build().expect("built");
+            let start = Instant::now();
+            let resolved = resolver
+                .resolve(&format!("{}/a", server.uri()))
+                .await
+                .expect("resolved");
+            assert_eq!(resolved, format!("{}/a", server.uri()));
+            assert!(start.elapsed() < Duration::from_secs(1));
+        }
+
+        #[tokio::test]
+        async fn test_retry_after_exhausted() {
+            let server = MockServer::start().await;
+            mount(
+                &server,
+                "/a",
+                ResponseTemplate::new(429).insert_header("Retry-After", "120"),
+            )
+            .await;
+
+            let resolver = resolver().build().expect("built");
+            let result = resolver.resolve(&format!("{}/a", server.uri())).await;
+            assert!(matches!(
+                result,
+                Err(ResolveError::RetryAfter(StatusCode::TOO_MANY_REQUESTS, delay))
+                    if delay == Duration::from_secs(120)
+            ));
+        }
+
+        #[tokio::test]
+        async fn test_timeout() {
+            let server = MockServer::start().await;
+            mount(&server, "/slow", page("").set_delay(Duration::from_secs(2))).await;
+
+            let resolver = resolver()
+                .timeout(Duration::from_millis(200))
+                .build()
+                .expect("built");
+            let result = resolver.resolve(&format!("{}/slow", server.uri())).await;
+            assert!(matches!(result, Err(ResolveError::Timeout)));
+        }
+
+        #[tokio::test]
+        async fn test_connection_refused() {
+            // Find a port that nothing is listening on
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bound");
+            let port = listener.local_addr().expect("address").port();
+            drop(listener);
+
+            let resolver = resolver().build().expect("built");
+            let result = resolver
+                .resolve(&format!("http://127.0.0.1:{}/a", port))
+                .await;
+            assert!(matches!(result, Err(ResolveError::Connect(_))));
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use std::time::Instant;
+
+use reqwest::StatusCode;
+use url::Url;
+
+use super::{Hop, HopKind, Resolver, html, read_body, same_site, ssrf};
+use crate::redact;
+
+const OEMBED_TYPE: &str = "application/json+oembed";
+
+// The fields of an oEmbed response that we use; see <https://oembed.com/#section2.3>
+struct OEmbed {
+    kind: Option<String>,
+    url: Option<String>,
+    title: Option<String>,
+}
+
+impl OEmbed {
+    fn parse(body: &[u8]) -> Option<Self> {
+        let json: serde_json::Value = serde_json::from_slice(body).ok()?;
+        let field = |name: &str| json[name].as_str().map(str::to_string);
+        Some(OEmbed {
+            kind: field("type"),
+            url: field("url"),
+            title: field("title"),
+        })
+    }
+}
+
+// The oEmbed endpoint advertised by the page, e.g.:
+// <link rel="alternate" type="application/json+oembed" href="https://example.com/oembed?url=...">
+fn extract_oembed_link(html: &str) -> Option<String> {
+    html::tags(html, "link").into_iter().find_map(|tag| {
+        let rel = html::attribute(tag, "rel")?;
+        let kind = html::attribute(tag, "type")?;
+        (rel.eq_ignore_ascii_case("alternate") && kind.eq_ignore_ascii_case(OEMBED_TYPE))
+            .then(|| html::attribute(tag, "href"))?
+    })
+}
+
+// The canonical URL given by an oEmbed response for `page`
+//
+// Photo responses use `url` for the image itself, so in that case (or if the response
+// has no `url`) we fall back to the `url` the endpoint was asked about, which sites fill
+// in with the page's permalink.
+fn canonical_url(page: &Url, endpoint: &Url, oembed: &OEmbed) -> Option<Url> {
+    let url = oembed
+        .url
+        .clone()
+        .filter(|_| oembed.kind.as_deref() != Some("photo"))
+        .or_else(|| {
+            endpoint
+                .query_pairs()
+                .find(|(k, _)| k == "url")
+                .map(|(_, v)| v.into_owned())
+        })?;
+    page.join(&url).ok().filter(|url| same_site(url, page))
+}
+
+// Look for an oEmbed endpoint on the page at `page` (which we reached from `start_url`),
+// and if there is one, fetch it for the canonical URL and title of the page.  The
+// endpoint is held to the same restrictions as the redirects we follow
+//
+// Many platforms give the clean permalink in oEmbed even when the page itself is a
+// soft-login wall.  This is only an additional signal: if the page has no endpoint, or
+// it fails, we stick with the URL we were redirected to.
+pub(super) async fn discover(
+    resolver: &Resolver,
+    client: &reqwest::Client,
+    start_url: &Url,
+    page: &Url,
+    html: &str,
+) -> Option<Hop> {
+    // The endpoint is given by the page, so may be anywhere
+    let endpoint = page.join(&extract_oembed_link(html)?).ok()?;
+    if !resolver.allow_private_networks
+        && (!ssrf::is_allowed(&endpoint)
+            || (resolver.proxied && !ssrf::resolves_publicly(&endpoint).await))
+    {
+        tracing::debug!(
+            endpoint = %redact::url(endpoint.as_str()),
+            "refusing oEmbed endpoint on a private network"
+        );
+        return None;
+    }
+    if resolver.same_site
+        && !same_site(&endpoint, start_url)
+        && endpoint.host_str() != start_url.host_str()
+    {
+        tracing::debug!(
+            endpoint = %redact::url(endpoint.as_str()),
+            "refusing oEmbed endpoint on another site"
+        );
+        return None;
+    }
+    if let Some(breaker) = &resolver.circuit_breaker
+        && breaker.check(&endpoint).is_err()
+    {
+        return None;
+    }
+
+    let _permit = resolver.host_limits.acquire(&endpoint).await;
+    let start = Instant::now();
+    let response = match client.get(endpoint.clone()).send().await {
+        Ok(response) if response.status().is_success() => response,
+        Ok(response) => {
+            tracing::debug!(
+                endpoint = %redact::url(endpoint.as_str()),
+                status = response.status().as_u16(),
+                "oEmbed request failed"
+            );
+            return None;
+        }
+        Err(e) => {
+            tracing::debug!(
+                endpoint = %redact::url(endpoint.as_str()),
+                error = %e.without_url(),
+                "oEmbed request failed"
+            );
+            return None;
+        }
+    };
+    let body = read_body(response, resolver.max_body_size).await.ok()?;
+    let oembed = OEmbed::parse(&body)?;
+    let url = canonical_url(page, &endpoint, &oembed).unwrap_or_else(|| page.clone());
+    if url == *page && oembed.title.is_none() {
+        return None;
+    }
+    Some(Hop {
+        status: StatusCode::OK,
+        url: url.to_string(),
+        kind: HopKind::OEmbed,
+        elapsed: start.elapsed(),
+        title: oembed.title,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_oembed_link() {
+        let html = r#"
+            <head>
+                <link rel="alternate" type="application/json+oembed" href="https://www.youtube.com/oembed?format=json&amp;url=https%3A%2F%2Fwww.youtube.com%2Fwatch%3Fv%3DdQw4w9WgXcQ" title="Video">
+                <link rel="alternate" type="text/xml+oembed" href="https://www.youtube.com/oembed?format=xml">
+            </head>
+        "#;
+        assert_eq!(
+            extract_oembed_link(html),
+            Some("https://www.youtube.com/oembed?format=json&url=https%3A%2F%2Fwww.youtube.com%2Fwatch%3Fv%3DdQw4w9WgXcQ".to_string())
+        );
+        assert_eq!(extract_oembed_link("<head></head>"), None);
+    }
+
+    #[test]
+    fn test_canonical_url() {
+        let page =
+            Url::parse("https://www.youtube.com/watch?v=dQw4w9WgXcQ&pp=abc").expect("valid url");
+        let endpoint = Url::parse(
+            "https://www.youtube.com/oembed?url=https%3A%2F%2Fwww.youtube.com%2Fwatch%3Fv%3DdQw4w9WgXcQ",
+        )
+        .expect("valid url");
+        let oembed = |kind: &str, url: Option<&str>| OEmbed {
+            kind: Some(kind.to_string()),
+            url: url.map(str::to_string),
+            title: None,
+        };
+
+        // From the response
+        let canonical = canonical_url(
+            &page,
+            &endpoint,
+            &oembed("rich", Some("https://youtube.com/watch?v=dQw4w9WgXcQ")),
+        );
+        assert_eq!(
+            canonical.expect("canonical").as_str(),
+            "https://youtube.com/watch?v=dQw4w9WgXcQ"
+        );
+
+        // From the endpoint, as photos give the image URL
+        let canonical = canonical_url(
+            &page,
+            &endpoint,
+            &oembed("photo", Some("https://i.ytimg.com/a.jpg")),
+        );
+        assert_eq!(
+            canonical.expect("canonical").as_str(),
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ"
+        );
+
+        // Not another site
+        let canonical = canonical_url(
+            &page,
+            &endpoint,
+            &oembed("rich", Some("https://example.com/")),
+        );
+        assert_eq!(canonical, None);
+    }
+}
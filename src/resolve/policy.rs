@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+
+use reqwest::header::{self, HeaderMap, HeaderName, HeaderValue};
+use url::Url;
+
+use super::{RequestStrategy, matches_domain};
+
+// How to resolve URLs on a particular domain (and its subdomains), where it needs
+// different handling from the rest
+//
+// Platforms differ wildly (Facebook wants cookies, a full GET, and a browser's headers,
+// while Reddit answers a HEAD with a plain redirect), so a single policy for everything
+// means compromising somewhere.  Every setting is optional, and only overrides the
+// resolver's own where given.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct DomainPolicy {
+    // Maximum number of redirects to follow from a URL on the domain
+    pub max_redirects: Option<usize>,
+    // Whether to follow meta refreshes (and `Refresh` headers) on the domain's pages
+    pub meta_refresh: Option<bool>,
+    // How to request the domain's URLs; see `RequestStrategy`
+    pub request_strategy: Option<RequestStrategy>,
+    // User agent to send to the domain, rather than the resolver's
+    pub user_agent: Option<String>,
+    // Additional headers to send to the domain, which override the resolver's own
+    pub headers: BTreeMap<String, String>,
+}
+
+// The policies for each domain given, in the order they were added
+#[derive(Clone, Debug, Default)]
+pub(super) struct DomainPolicies(Vec<(String, DomainPolicy)>);
+
+impl DomainPolicies {
+    pub(super) fn add(&mut self, domain: impl Into<String>, policy: DomainPolicy) {
+        self.0.push((domain.into(), policy));
+    }
+
+    // The policies that apply to `url`, from the least specific domain to the most (and,
+    // for the same domain, in the order they were added)
+    fn matching(&self, url: &Url) -> Vec<&DomainPolicy> {
+        let host = url.host_str().unwrap_or_default();
+        let mut matching: Vec<_> = self
+            .0
+            .iter()
+            .filter(|(domain, _)| matches_domain(host, domain))
+            .collect();
+        matching.sort_by_key(|(domain, _)| domain.len());
+        matching.into_iter().map(|(_, policy)| policy).collect()
+    }
+
+    // The setting that applies to `url`, from the most specific policy that gives one
+    pub(super) fn get<T>(
+        &self,
+        url: &Url,
+        setting: impl Fn(&DomainPolicy) -> Option<T>,
+    ) -> Option<T> {
+        self.matching(url).into_iter().rev().find_map(setting)
+    }
+
+    // The headers to send with a request for `url`.  As with other settings, the most
+    // specific policy wins where several give the same header
+    pub(super) fn headers(&self, url: &Url) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for policy in self.matching(url) {
+            for (name, value) in &policy.headers {
+                match (
+                    HeaderName::from_bytes(name.as_bytes()),
+                    HeaderValue::from_str(value),
+                ) {
+                    (Ok(name), Ok(value)) => {
+                        headers.insert(name, value);
+                    }
+                    _ => tracing::debug!(header = %name, "ignoring invalid header"),
+                }
+            }
+            if let Some(user_agent) = &policy.user_agent
+                && let Ok(user_agent) = HeaderValue::from_str(user_agent)
+            {
+                headers.insert(header::USER_AGENT, user_agent);
+            }
+        }
+        headers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policies() -> DomainPolicies {
+        let mut policies = DomainPolicies::default();
+        policies.add(
+            "facebook.com",
+            DomainPolicy {
+                max_redirects: Some(10),
+                meta_refresh: Some(false),
+                user_agent: Some("facebookexternalhit/1.1".to_string()),
+                headers: BTreeMap::from([
+                    ("Sec-Fetch-Site".to_string(), "none".to_string()),
+                    ("DNT".to_string(), "1".to_string()),
+                ]),
+                ..DomainPolicy::default()
+            },
+        );
+        policies.add(
+            "m.facebook.com",
+            DomainPolicy {
+                meta_refresh: Some(true),
+                headers: BTreeMap::from([("DNT".to_string(), "0".to_string())]),
+                ..DomainPolicy::default()
+            },
+        );
+        policies
+    }
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).expect("valid url")
+    }
+
+    #[test]
+    fn test_get() {
+        let policies = policies();
+        let www = url("https://www.facebook.com/share/abc");
+        let m = url("https://m.facebook.com/story.php");
+        assert_eq!(policies.get(&www, |p| p.meta_refresh), Some(false));
+        assert_eq!(policies.get(&m, |p| p.meta_refresh), Some(true));
+
+        // A setting the most specific policy does not give comes from the next
+        assert_eq!(policies.get(&m, |p| p.max_redirects), Some(10));
+        assert_eq!(
+            policies.get(&url("https://www.reddit.com/"), |p| p.max_redirects),
+            None
+        );
+    }
+
+    #[test]
+    fn test_headers() {
+        let policies = policies();
+        let headers = policies.headers(&url("https://m.facebook.com/story.php"));
+        assert_eq!(headers["dnt"], "0");
+        assert_eq!(headers["sec-fetch-site"], "none");
+        assert_eq!(headers[header::USER_AGENT], "facebookexternalhit/1.1");
+        assert!(policies.headers(&url("https://example.com/")).is_empty());
+    }
+}
@@ -0,0 +1,96 @@
+use futures::future::BoxFuture;
+use reqwest::header::{self, HeaderValue};
+use url::Url;
+
+use super::{ResolveError, ResolveOutput, UrlResolver};
+
+// Resolves Reddit share links (`/r/<subreddit>/s/<token>`)
+//
+// Reddit's share endpoint redirects straight to the post or comment's permalink.  By
+// asking for JSON, as Reddit's own apps do, we can read the permalink from the redirect
+// without being served (or having to download) the HTML page, which is where Reddit's
+// bot detection lives.
+pub struct RedditResolver;
+
+// Whether the URL is a share link, of the form `/r/<subreddit>/s/<token>` (or
+// `/u/<user>/s/<token>` for posts on a user's profile)
+fn is_share_link(url: &Url) -> bool {
+    let host = url.host_str().unwrap_or_default();
+    if host != "reddit.com" && !host.ends_with(".reddit.com") {
+        return false;
+    }
+    let segments: Vec<_> = url
+        .path_segments()
+        .map(|s| s.filter(|s| !s.is_empty()).collect())
+        .unwrap_or_default();
+    matches!(segments.as_slice(), ["r" | "u" | "user", _, "s", _])
+}
+
+impl UrlResolver for RedditResolver {
+    fn name(&self) -> &'static str {
+        "RedditResolver"
+    }
+
+    fn resolve<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        url: &'a Url,
+    ) -> Option<BoxFuture<'a, ResolveOutput>> {
+        if !is_share_link(url) {
+            return None;
+        }
+        Some(Box::pin(async move {
+            let response = client
+                .get(url.clone())
+                .header(header::ACCEPT, HeaderValue::from_static("application/json"))
+                .send()
+                .await?;
+            let status = response.status();
+            let location = response
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|v| v.to_str().ok())
+                .filter(|_| status.is_redirection())
+                .ok_or(ResolveError::Api(self.name()))?;
+            let mut permalink = url.join(location)?;
+
+            // The permalink carries the sharer's ID in its query, which the cleaner would
+            // only remove again
+            permalink.set_query(None);
+            Ok(permalink.to_string())
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_share_link() {
+        let share_link = |url| is_share_link(&Url::parse(url).expect("valid url"));
+        assert!(share_link(
+            "https://www.reddit.com/r/AskTheWorld/s/mONZu40JNk"
+        ));
+        assert!(share_link("https://reddit.com/u/someone/s/mONZu40JNk/"));
+        assert!(!share_link(
+            "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m/"
+        ));
+        assert!(!share_link(
+            "https://www.notreddit.com/r/AskTheWorld/s/mONZu40JNk"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_share_link() {
+        let url =
+            Url::parse("https://www.reddit.com/r/AskTheWorld/s/mONZu40JNk").expect("valid url");
+        let resolver = crate::Resolver::new().expect("resolver");
+        let result = RedditResolver
+            .resolve(resolver.client(), &url)
+            .expect("share link")
+            .await;
+        let expected = "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m/";
+        assert!(result.expect("resolved").starts_with(expected));
+    }
+}
@@ -0,0 +1,121 @@
+use url::Url;
+
+use super::{RequestStrategy, matches_domain};
+
+// How a link shortener gets us to its destination
+#[derive(Clone, Copy, Debug, PartialEq, Eq, derive_more::Display)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum ShortenerBehaviour {
+    // A plain HTTP redirect, which needs nothing but the headers
+    #[display("HTTP redirect")]
+    Redirect,
+    // An HTTP redirect, but only once cookies set on an earlier hop are sent back
+    #[display("redirect needing cookies")]
+    Cookies,
+    // An interstitial page that redirects in JavaScript, which following redirects
+    // cannot get past (see `BrowserResolver`)
+    #[display("JavaScript redirect")]
+    JavaScript,
+}
+
+// A known link shortener
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Shortener {
+    pub domain: &'static str,
+    pub behaviour: ShortenerBehaviour,
+}
+
+impl Shortener {
+    // The cheapest way to request the shortener's links: a pure redirect only needs its
+    // headers, so we need not download its body
+    pub fn request_strategy(&self) -> Option<RequestStrategy> {
+        match self.behaviour {
+            ShortenerBehaviour::Redirect => Some(RequestStrategy::Head),
+            ShortenerBehaviour::Cookies | ShortenerBehaviour::JavaScript => None,
+        }
+    }
+}
+
+const fn shortener(domain: &'static str, behaviour: ShortenerBehaviour) -> Shortener {
+    Shortener { domain, behaviour }
+}
+
+// Link shorteners we know how to handle, and how each behaves
+//
+// This is deliberately small: it lists popular shorteners, not every host that
+// redirects.  A link on a host that is not listed is still resolved as usual.
+const SHORTENERS: &[Shortener] = {
+    use ShortenerBehaviour::*;
+    &[
+        shortener("amzn.to", Redirect),
+        shortener("app.link", JavaScript),
+        shortener("bit.ly", Redirect),
+        shortener("bitly.com", Redirect),
+        shortener("buff.ly", Redirect),
+        shortener("cutt.ly", Redirect),
+        shortener("dlvr.it", Redirect),
+        shortener("fb.me", Cookies),
+        shortener("is.gd", Redirect),
+        shortener("j.mp", Redirect),
+        shortener("lnkd.in", Cookies),
+        shortener("ow.ly", Redirect),
+        shortener("pin.it", Redirect),
+        shortener("rb.gy", Redirect),
+        shortener("rebrand.ly", Redirect),
+        shortener("redd.it", Redirect),
+        shortener("shorturl.at", Redirect),
+        shortener("spoti.fi", JavaScript),
+        shortener("t.co", Redirect),
+        shortener("t.ly", Redirect),
+        shortener("tiny.cc", Redirect),
+        shortener("tinyurl.com", Redirect),
+        shortener("v.gd", Redirect),
+        shortener("vm.tiktok.com", Redirect),
+        shortener("youtu.be", Redirect),
+    ]
+};
+
+// The shortener that `url` is on, if it is a known one
+pub fn shortener_for(url: &Url) -> Option<&'static Shortener> {
+    let host = url.host_str()?;
+    SHORTENERS
+        .iter()
+        .find(|shortener| matches_domain(host, shortener.domain))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn behaviour(url: &str) -> Option<ShortenerBehaviour> {
+        shortener_for(&Url::parse(url).expect("valid url")).map(|s| s.behaviour)
+    }
+
+    #[test]
+    fn test_shortener_for() {
+        assert_eq!(
+            behaviour("https://bit.ly/3abcdef"),
+            Some(ShortenerBehaviour::Redirect)
+        );
+        assert_eq!(
+            behaviour("https://Lnkd.in/gAbCdEf"),
+            Some(ShortenerBehaviour::Cookies)
+        );
+        assert_eq!(
+            behaviour("https://open.app.link/abc"),
+            Some(ShortenerBehaviour::JavaScript)
+        );
+        assert_eq!(behaviour("https://www.reddit.com/r/rust/"), None);
+        assert_eq!(behaviour("https://notbit.ly/abc"), None);
+    }
+
+    #[test]
+    fn test_sorted() {
+        assert!(SHORTENERS.is_sorted_by_key(|shortener| shortener.domain));
+    }
+}
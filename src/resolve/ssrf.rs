@@ -0,0 +1,252 @@
+use std::{
+    env,
+    error::Error,
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use url::{Host, Url};
+
+// Protection against server-side request forgery
+//
+// A share link is untrusted input, and if we are run as a service, following it means
+// making requests from inside someone else's network.  A malicious link (or a redirect
+// from one) could point at a router's admin page, or a cloud metadata endpoint (e.g.,
+// 169.254.169.254), and use our response to probe what is there.  So unless told
+// otherwise (see `ResolverBuilder::allow_private_networks`), we only request URLs on the
+// public internet, over the standard HTTP ports.
+
+// Whether `url` may be requested: it is on the standard port for its scheme, and not on
+// a private, loopback, or link-local address (or a name that always means one, like
+// `localhost`)
+//
+// Other names are checked when they are looked up; see `PublicDns`.
+pub(super) fn is_allowed(url: &Url) -> bool {
+    // The `url` crate drops a port that is the default for the scheme
+    if !matches!(url.scheme(), "http" | "https") || url.port().is_some() {
+        return false;
+    }
+    match url.host() {
+        Some(Host::Domain(domain)) => {
+            let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+            domain != "localhost" && !domain.ends_with(".localhost")
+        }
+        Some(Host::Ipv4(ip)) => is_public(ip.into()),
+        Some(Host::Ipv6(ip)) => is_public(ip.into()),
+        None => false,
+    }
+}
+
+// Whether `ip` is on the public internet
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_v4(ip),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // "This network" (0.0.0.0/8), and carrier-grade NAT (100.64.0.0/10)
+        || a == 0
+        || (a == 100 && (b & 0b1100_0000) == 64))
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let segments = ip.segments();
+    let embedded = |high: u16, low: u16| Ipv4Addr::from((u32::from(high) << 16) | u32::from(low));
+    match segments {
+        // NAT64 (64:ff9b::/96) and 6to4 (2002::/16) addresses lead to the IPv4 address
+        // embedded in them, so are only as public as it is
+        [0x64, 0xff9b, 0, 0, 0, 0, high, low] | [0x2002, high, low, ..] => {
+            is_public_v4(embedded(high, low))
+        }
+        // Documentation (2001:db8::/32)
+        [0x2001, 0xdb8, ..] => false,
+        _ => {
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_unique_local()
+                || ip.is_unicast_link_local()
+                || ip.is_multicast())
+        }
+    }
+}
+
+// The error given when a name only resolves to addresses we will not connect to
+#[derive(Debug)]
+pub(super) struct PrivateAddress(pub(super) String);
+
+impl fmt::Display for PrivateAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} only resolves to private addresses", self.0)
+    }
+}
+
+impl Error for PrivateAddress {}
+
+// Looks up names as usual, but drops any private addresses they resolve to, so that a
+// public-looking name cannot lead us into a private network
+//
+// The exception is a proxy we were told to use, which is often on a private network (as
+// a corporate proxy is), and is not a link's to choose.  Through a proxy, it is the proxy
+// that looks up the hosts of the links themselves, so they never get here; see
+// `resolves_publicly` for how those are checked instead.
+pub(super) struct PublicDns {
+    pub(super) proxies: Vec<String>,
+}
+
+impl Resolve for PublicDns {
+    fn resolve(&self, name: Name) -> Resolving {
+        let proxy = self
+            .proxies
+            .iter()
+            .any(|proxy| proxy.eq_ignore_ascii_case(name.as_str()));
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|addr| proxy || is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                tracing::warn!(host, "refusing to connect to private address");
+                return Err(Box::new(PrivateAddress(host)) as Box<dyn Error + Send + Sync>);
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+// Whether the host of `url` only resolves to public addresses, looking it up ourselves
+//
+// A request through a proxy does not look up its host here (the proxy does), so
+// `PublicDns` cannot drop private addresses from it.  Instead, before requesting a URL
+// through a proxy, we look its host up first, and refuse it if any of its addresses is
+// private.  A host we cannot look up at all is refused too, as we cannot tell where the
+// proxy would take us.  The proxy may still get a different answer than we did (e.g., if
+// the name's owner changes it in between), so this is not as strong as `PublicDns`.
+pub(super) async fn resolves_publicly(url: &Url) -> bool {
+    // Addresses given directly are checked by `is_allowed`
+    let Some(Host::Domain(domain)) = url.host() else {
+        return true;
+    };
+    match tokio::net::lookup_host((domain, 0)).await {
+        Ok(addrs) => {
+            let addrs: Vec<SocketAddr> = addrs.collect();
+            !addrs.is_empty() && addrs.iter().all(|addr| is_public(addr.ip()))
+        }
+        Err(e) => {
+            tracing::debug!(host = domain, error = %e, "could not look up host");
+            false
+        }
+    }
+}
+
+// The hosts of the proxies we may connect through: the one given, or else those given by
+// the environment (see `ResolverBuilder::proxy`)
+pub(super) fn proxy_hosts(proxy: Option<&Url>) -> Vec<String> {
+    let env_proxies = || {
+        ["HTTP_PROXY", "HTTPS_PROXY", "ALL_PROXY"]
+            .iter()
+            .flat_map(|var| [var.to_string(), var.to_ascii_lowercase()])
+            .filter_map(|var| env::var(var).ok())
+            .filter_map(|proxy| Url::parse(&proxy).ok())
+            .collect()
+    };
+    let proxies: Vec<Url> = proxy.map_or_else(env_proxies, |proxy| vec![proxy.clone()]);
+    proxies
+        .iter()
+        .filter_map(|proxy| proxy.host_str())
+        .map(str::to_string)
+        .collect()
+}
+
+// Whether a request failed because `PublicDns` refused to resolve its host
+pub(super) fn is_private_address(e: &reqwest::Error) -> bool {
+    let mut source = e.source();
+    while let Some(e) = source {
+        if e.is::<PrivateAddress>() {
+            return true;
+        }
+        source = e.source();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowed(url: &str) -> bool {
+        is_allowed(&Url::parse(url).expect("valid url"))
+    }
+
+    #[test]
+    fn test_is_allowed() {
+        assert!(allowed("https://www.reddit.com/r/rust/"));
+        assert!(allowed("http://example.com:80/"));
+        assert!(allowed("https://93.184.215.14/"));
+        assert!(allowed("https://[2606:2800:21f:cb07:6820:80da:af6b:8b2c]/"));
+    }
+
+    #[test]
+    fn test_private() {
+        assert!(!allowed("http://127.0.0.1/"));
+        assert!(!allowed("http://10.0.0.1/"));
+        assert!(!allowed("http://192.168.1.1/admin"));
+        assert!(!allowed("http://172.16.0.1/"));
+        assert!(!allowed("http://169.254.169.254/latest/meta-data/"));
+        assert!(!allowed("http://100.64.0.1/"));
+        assert!(!allowed("http://0.0.0.0/"));
+        assert!(!allowed("http://[::1]/"));
+        assert!(!allowed("http://[fd00::1]/"));
+        assert!(!allowed("http://[fe80::1]/"));
+        assert!(!allowed("http://[::ffff:127.0.0.1]/"));
+        assert!(!allowed("http://localhost/"));
+        assert!(!allowed("http://api.localhost./"));
+    }
+
+    #[test]
+    fn test_nat64() {
+        assert!(!allowed("http://[64:ff9b::7f00:1]/"));
+        assert!(!allowed("http://[64:ff9b::a9fe:a9fe]/"));
+        assert!(allowed("http://[64:ff9b::5db8:d70e]/"));
+    }
+
+    #[test]
+    fn test_6to4() {
+        assert!(!allowed("http://[2002:7f00:1::1]/"));
+        assert!(!allowed("http://[2002:c0a8:101::]/"));
+        assert!(allowed("http://[2002:5db8:d70e::1]/"));
+    }
+
+    #[test]
+    fn test_documentation() {
+        assert!(!allowed("http://[2001:db8::1]/"));
+        assert!(!allowed("http://[2001:db8:ffff::]/"));
+    }
+
+    #[test]
+    fn test_proxy_hosts() {
+        let proxy = Url::parse("socks5://proxy.corp.example:1080").expect("valid url");
+        assert_eq!(proxy_hosts(Some(&proxy)), ["proxy.corp.example"]);
+    }
+
+    #[test]
+    fn test_ports() {
+        assert!(!allowed("https://example.com:8443/"));
+        assert!(!allowed("http://example.com:22/"));
+        assert!(!allowed("http://example.com:443/"));
+    }
+}
@@ -0,0 +1,89 @@
+use url::Url;
+
+use super::matches_domain;
+
+// What platforms say in place of a post that has been removed (or made private)
+//
+// These pages are served with a 200, so unlike a 404 or 410 we can only tell that the
+// link is dead from what they say.  We only see as much of the page as we read for its
+// head, so this is a best effort, matched case-insensitively.
+const MARKERS: &[(&str, &[&str])] = &[
+    (
+        "facebook.com",
+        &[
+            "this content isn't available",
+            "this page isn't available",
+            "content not found",
+        ],
+    ),
+    (
+        "reddit.com",
+        &[
+            "sorry, this post has been removed",
+            "sorry, this post was deleted",
+            "[removed by moderator]",
+            "[ removed by reddit ]",
+        ],
+    ),
+];
+
+// Whether the page at `url` says that its content is no longer available
+pub(super) fn is_unavailable(url: &Url, html: &str) -> bool {
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    let Some((_, markers)) = MARKERS
+        .iter()
+        .find(|(domain, _)| matches_domain(host, domain))
+    else {
+        return false;
+    };
+
+    // Apostrophes may be curly, or escaped
+    let html = html
+        .to_ascii_lowercase()
+        .replace(['\u{2019}', '\u{2018}'], "'")
+        .replace("&#039;", "'")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'");
+    markers.iter().any(|marker| html.contains(marker))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unavailable(url: &str, html: &str) -> bool {
+        is_unavailable(&Url::parse(url).expect("valid url"), html)
+    }
+
+    #[test]
+    fn test_is_unavailable() {
+        assert!(unavailable(
+            "https://www.facebook.com/groups/123/posts/456/",
+            "<title>This content isn\u{2019}t available right now</title>"
+        ));
+        assert!(unavailable(
+            "https://www.facebook.com/123",
+            "<meta name=\"description\" content=\"This content isn&#039;t available\">"
+        ));
+        assert!(unavailable(
+            "https://www.reddit.com/r/rust/comments/abc/title/",
+            "<title>Sorry, this post has been removed by the moderators</title>"
+        ));
+    }
+
+    #[test]
+    fn test_available() {
+        assert!(!unavailable(
+            "https://www.reddit.com/r/rust/comments/abc/title/",
+            "<title>Announcing Rust 1.90</title>"
+        ));
+
+        // Markers only count on the platform that uses them
+        assert!(!unavailable(
+            "https://example.com/post",
+            "<title>Content not found</title>"
+        ));
+    }
+}
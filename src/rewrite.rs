@@ -0,0 +1,150 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs,
+    io::{self, Read},
+    path::PathBuf,
+};
+
+use crate::{
+    clean::{CleanUrlError, find_urls},
+    clipboard,
+    history::{self, History},
+    process::{self, ProcessError, ProcessOptions},
+    redact, report,
+    resolve::Resolver,
+};
+
+// Resolve and clean every supported link in each file, either printing the result to
+// standard output or modifying the files in place
+//
+// Without any files, the text is read from standard input (e.g., a message pasted with
+// several share links in it), and the result is copied to the clipboard as well as
+// printed, ready to be pasted back in place of the original.
+pub async fn run(
+    resolver: &Resolver,
+    files: &[PathBuf],
+    in_place: bool,
+    options: &ProcessOptions,
+    saved_history: Option<&History>,
+) -> Result<(), Box<dyn Error>> {
+    // Share links are often repeated across (and within) files, so we only process
+    // each one once
+    let mut cache = HashMap::new();
+
+    if files.is_empty() {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text)?;
+        let rewritten = rewrite_text(resolver, &text, &mut cache, options, saved_history).await;
+        print!("{}", rewritten);
+        clipboard::copy(rewritten.trim_end())?;
+        return Ok(());
+    }
+
+    for file in files {
+        let text = fs::read_to_string(file)?;
+        let rewritten = rewrite_text(resolver, &text, &mut cache, options, saved_history).await;
+
+        if in_place {
+            if rewritten != text {
+                fs::write(file, rewritten)?;
+            }
+        } else {
+            print!("{}", rewritten);
+        }
+    }
+
+    Ok(())
+}
+
+// Replace each supported URL in `text` with its cleaned form, leaving everything else
+// (including unsupported links and surrounding Markdown) untouched
+//
+// `cache` maps input URLs to their replacement, or `None` if the URL should be left as-is.
+pub async fn rewrite_text(
+    resolver: &Resolver,
+    text: &str,
+    cache: &mut HashMap<String, Option<String>>,
+    options: &ProcessOptions,
+    saved_history: Option<&History>,
+) -> String {
+    let mut rewritten = String::with_capacity(text.len());
+    let mut last = 0;
+
+    for (start, end) in find_urls(text) {
+        let url = &text[start..end];
+        if !cache.contains_key(url) {
+            let replacement = replacement_for(resolver, url, options, saved_history).await;
+            cache.insert(url.to_string(), replacement);
+        }
+
+        rewritten.push_str(&text[last..start]);
+        match &cache[url] {
+            Some(cleaned) => rewritten.push_str(cleaned),
+            None => rewritten.push_str(url),
+        }
+        last = end;
+    }
+
+    rewritten.push_str(&text[last..]);
+    rewritten
+}
+
+// Process a single URL found in text, returning its replacement if it is supported
+async fn replacement_for(
+    resolver: &Resolver,
+    url: &str,
+    options: &ProcessOptions,
+    saved_history: Option<&History>,
+) -> Option<String> {
+    let result = process::process(resolver, url, options).await;
+    report::report(&result);
+    match result {
+        Ok(processed) => {
+            if let Some(saved_history) = saved_history {
+                history::record(saved_history, &processed);
+            }
+            Some(processed.cleaned)
+        }
+        // Most links in a document will not be share links for a supported platform;
+        // this is expected, so we leave them be quietly
+        Err(ProcessError::Clean(
+            CleanUrlError::UnknownDomain
+            | CleanUrlError::UnsupportedUrlHost
+            | CleanUrlError::UnsupportedUrlPath,
+            _,
+        )) => None,
+        Err(e) => {
+            tracing::warn!(url = %redact::url(url), error = %e, "could not rewrite");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rewrite_message() {
+        // Full post URLs are cleaned without touching the network
+        let resolver = Resolver::new().expect("resolver");
+        let text = "Two for you:\n\
+            https://www.reddit.com/r/rust/comments/abc123/title/?share_id=xyz and\n\
+            https://www.reddit.com/r/rust/comments/def456/?utm_source=share.\n";
+        let rewritten = rewrite_text(
+            &resolver,
+            text,
+            &mut HashMap::new(),
+            &ProcessOptions::default(),
+            None,
+        )
+        .await;
+        assert_eq!(
+            rewritten,
+            "Two for you:\n\
+            https://www.reddit.com/r/rust/comments/abc123 and\n\
+            https://www.reddit.com/r/rust/comments/def456.\n"
+        );
+    }
+}
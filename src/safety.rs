@@ -0,0 +1,165 @@
+use std::error::Error;
+
+use reqwest::header;
+use url::Url;
+
+use crate::clean;
+
+// Google Safe Browsing's Lookup API; see:
+//   <https://developers.google.com/safe-browsing/v4/lookup-api>
+const SAFE_BROWSING_ENDPOINT: &str = "https://safebrowsing.googleapis.com/v4/threatMatches:find";
+const SAFE_BROWSING_THREATS: &[&str] = &[
+    "MALWARE",
+    "SOCIAL_ENGINEERING",
+    "UNWANTED_SOFTWARE",
+    "POTENTIALLY_HARMFUL_APPLICATION",
+];
+
+// Top-level domains that are either free to register, and so are overrun with throwaway
+// phishing sites, or look like file extensions (e.g., `setup.zip`)
+const SUSPICIOUS_TLDS: &[&str] = &["cf", "ga", "gq", "ml", "tk", "mov", "zip"];
+
+// Reasons to think a link may be malicious
+#[derive(Clone, Debug, PartialEq, Eq, derive_more::Display)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Threat {
+    #[display("host {_0} mixes lookalike scripts")]
+    Homograph(String),
+    #[display("link contains credentials, which can disguise its real host")]
+    Credentials,
+    #[display("top-level domain .{_0} is widely used for phishing")]
+    SuspiciousTld(String),
+    #[display("listed by Google Safe Browsing as {_0}")]
+    SafeBrowsing(String),
+}
+
+// Screen a link against local phishing heuristics, without touching the network
+//
+// These will not catch most malicious links, only ones dressed up to look like somewhere
+// they are not: a host spelled with lookalike letters from another script (see
+// `clean::is_mixed_script`), or a link like `https://www.paypal.com@example.com/`, which
+// goes to the host after the `@`.
+pub fn screen(url: &Url) -> Vec<Threat> {
+    let mut threats = Vec::new();
+    let Some(host) = url.host_str() else {
+        return threats;
+    };
+
+    let unicode = clean::to_unicode(host);
+    if clean::is_mixed_script(&unicode) {
+        threats.push(Threat::Homograph(unicode));
+    }
+
+    if !url.username().is_empty() || url.password().is_some() {
+        threats.push(Threat::Credentials);
+    }
+
+    if let Some(tld) = host.trim_end_matches('.').rsplit('.').next()
+        && SUSPICIOUS_TLDS.contains(&tld)
+    {
+        threats.push(Threat::SuspiciousTld(tld.to_string()));
+    }
+
+    threats
+}
+
+// Look a link up in Google Safe Browsing, returning the threats it is listed under
+pub async fn safe_browsing(
+    client: &reqwest::Client,
+    key: &str,
+    url: &str,
+) -> Result<Vec<Threat>, Box<dyn Error>> {
+    let body = serde_json::json!({
+        "client": {
+            "clientId": env!("CARGO_PKG_NAME"),
+            "clientVersion": env!("CARGO_PKG_VERSION"),
+        },
+        "threatInfo": {
+            "threatTypes": SAFE_BROWSING_THREATS,
+            "platformTypes": ["ANY_PLATFORM"],
+            "threatEntryTypes": ["URL"],
+            "threatEntries": [{ "url": url }],
+        },
+    });
+    // The key goes in a header rather than the query string, so that it doesn't end up in
+    // the URL that reqwest attaches to its errors (and so in our logs)
+    let response = client
+        .post(SAFE_BROWSING_ENDPOINT)
+        .header("X-Goog-Api-Key", key)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(body.to_string())
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.without_url())?;
+    let response: serde_json::Value =
+        serde_json::from_str(&response.text().await.map_err(|e| e.without_url())?)?;
+    Ok(safe_browsing_threats(&response))
+}
+
+// The threats in a Safe Browsing response, which is empty (`{}`) if there are none
+fn safe_browsing_threats(response: &serde_json::Value) -> Vec<Threat> {
+    let mut threats: Vec<_> = response["matches"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|m| m["threatType"].as_str())
+        .map(|threat| Threat::SafeBrowsing(threat.to_ascii_lowercase().replace('_', " ")))
+        .collect();
+    threats.dedup();
+    threats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screened(url: &str) -> Vec<Threat> {
+        screen(&Url::parse(url).expect("valid url"))
+    }
+
+    #[test]
+    fn test_screen() {
+        assert_eq!(
+            screened("https://\u{0430}pple.com/"),
+            [Threat::Homograph("\u{0430}pple.com".to_string())]
+        );
+        assert_eq!(
+            screened("https://www.paypal.com@example.com/"),
+            [Threat::Credentials]
+        );
+        assert_eq!(
+            screened("https://github.com@setup.zip/"),
+            [
+                Threat::Credentials,
+                Threat::SuspiciousTld("zip".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_screen_safe() {
+        assert!(screened("https://www.reddit.com/r/rust/comments/1q2rw7m").is_empty());
+        assert!(screened("https://bücher.de/").is_empty());
+        // Only the top-level domain counts
+        assert!(screened("https://zip.example.com/").is_empty());
+    }
+
+    #[test]
+    fn test_safe_browsing_threats() {
+        let response = serde_json::json!({
+            "matches": [
+                {
+                    "threatType": "SOCIAL_ENGINEERING",
+                    "platformType": "ANY_PLATFORM",
+                    "threat": { "url": "https://example.com/" },
+                },
+            ],
+        });
+        assert_eq!(
+            safe_browsing_threats(&response),
+            [Threat::SafeBrowsing("social engineering".to_string())]
+        );
+        assert!(safe_browsing_threats(&serde_json::json!({})).is_empty());
+    }
+}
@@ -0,0 +1,34 @@
+use std::{fmt::Display, time::Duration};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+// Serialize a value that does not implement `Serialize` (e.g., an error from another
+// crate) as its `Display` representation
+pub(crate) fn display<T: Display, S: Serializer>(
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+// Serialize a duration as a whole number of milliseconds
+pub(crate) fn millis<S: Serializer>(value: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(value.as_millis() as u64)
+}
+
+// Deserialize a duration serialized with `millis`
+pub(crate) fn from_millis<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Duration, D::Error> {
+    u64::deserialize(deserializer).map(Duration::from_millis)
+}
+
+// Serialize an enum variant as its first field alone, for a variant that carries extra
+// context (e.g., an explanation alongside an error) that is of no use once serialized
+pub(crate) fn first<T: Serialize, U, S: Serializer>(
+    value: &T,
+    _: &U,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    value.serialize(serializer)
+}
@@ -0,0 +1,176 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+// Destination for results: either standard output, or a file (with `--output`)
+pub enum Sink {
+    Stdout,
+    File(AtomicFile),
+}
+
+impl Sink {
+    pub fn new(path: Option<&Path>, append: bool) -> io::Result<Self> {
+        match path {
+            Some(path) => Ok(Sink::File(AtomicFile::create(path, append)?)),
+            None => Ok(Sink::Stdout),
+        }
+    }
+
+    // Write a single output record, terminated by `delimiter`
+    //
+    // Records written to standard output are flushed immediately, so that results appear
+    // as soon as they are ready.
+    pub fn write_record(&mut self, s: &str, delimiter: u8) -> io::Result<()> {
+        self.write_all(s.as_bytes())?;
+        self.write_all(&[delimiter])?;
+        match self {
+            Sink::Stdout => self.flush(),
+            Sink::File(_) => Ok(()),
+        }
+    }
+
+    // Finish writing output; for files, this is when the results become visible at the
+    // destination path
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            Sink::Stdout => Ok(()),
+            Sink::File(file) => file.commit(),
+        }
+    }
+}
+
+// A file that is written to a temporary path alongside its destination, and then
+// atomically renamed into place once complete
+//
+// This means that an interrupted run never leaves a truncated or half-written file at
+// the destination; the previous contents (if any) are left intact.
+pub struct AtomicFile {
+    path: PathBuf,
+    tmp: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl AtomicFile {
+    // Create a new file to be written to `path`.  With `append`, the existing contents
+    // of `path` (if any) are kept, and new records are added after them
+    pub fn create(path: &Path, append: bool) -> io::Result<Self> {
+        let name = path
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file path"))?;
+        let tmp = path.with_file_name(format!(
+            ".{}.{}.tmp",
+            name.to_string_lossy(),
+            std::process::id()
+        ));
+
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        if append {
+            match File::open(path) {
+                Ok(mut existing) => {
+                    io::copy(&mut existing, &mut writer)?;
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(AtomicFile {
+            path: path.to_path_buf(),
+            tmp,
+            writer,
+        })
+    }
+
+    // Flush the file to disk and move it into place
+    pub fn commit(mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        self.writer.get_ref().sync_all()?;
+        fs::rename(&self.tmp, &self.path)
+    }
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Sink::Stdout => io::stdout().write(buf),
+            Sink::File(file) => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Sink::Stdout => io::stdout().flush(),
+            Sink::File(file) => file.flush(),
+        }
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    // If the file was never committed, clean up after ourselves
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.tmp);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("rsl-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_commit() {
+        let path = path("commit.txt");
+        let mut file = AtomicFile::create(&path, false).expect("created");
+        file.write_all(b"https://example.com/a\n").expect("written");
+        assert!(!path.exists());
+        file.commit().expect("committed");
+        assert_eq!(
+            fs::read_to_string(&path).expect("read"),
+            "https://example.com/a\n"
+        );
+        fs::remove_file(&path).expect("removed");
+    }
+
+    #[test]
+    fn test_append() {
+        let path = path("append.txt");
+        fs::write(&path, "https://example.com/a\n").expect("written");
+        let mut file = AtomicFile::create(&path, true).expect("created");
+        file.write_all(b"https://example.com/b\n").expect("written");
+        file.commit().expect("committed");
+        assert_eq!(
+            fs::read_to_string(&path).expect("read"),
+            "https://example.com/a\nhttps://example.com/b\n"
+        );
+        fs::remove_file(&path).expect("removed");
+    }
+
+    #[test]
+    fn test_uncommitted() {
+        let path = path("uncommitted.txt");
+        fs::write(&path, "https://example.com/a\n").expect("written");
+        let mut file = AtomicFile::create(&path, false).expect("created");
+        file.write_all(b"https://example.com/b\n").expect("written");
+        drop(file);
+        assert_eq!(
+            fs::read_to_string(&path).expect("read"),
+            "https://example.com/a\n"
+        );
+        fs::remove_file(&path).expect("removed");
+    }
+}
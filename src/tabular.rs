@@ -0,0 +1,197 @@
+use std::{
+    collections::{HashMap, HashSet},
+    convert::Infallible,
+    error::Error,
+    io::Read,
+    pin::pin,
+    str::FromStr,
+};
+
+use csv::StringRecord;
+use futures::StreamExt;
+
+use crate::{
+    batch,
+    exit::ExitCode,
+    history::{self, History},
+    process::ProcessOptions,
+    progress::Progress,
+    report,
+    resolve::Resolver,
+    sink::Sink,
+};
+
+// Name of the column used for URLs if none is specified (compared case-insensitively)
+const DEFAULT_COLUMN_NAME: &str = "url";
+
+// Which column of a CSV/TSV file holds URLs: either a header name, or a 1-based index
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Column {
+    Name(String),
+    Index(usize),
+}
+
+impl FromStr for Column {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<usize>() {
+            Ok(n) if n >= 1 => Ok(Column::Index(n - 1)),
+            _ => Ok(Column::Name(s.to_string())),
+        }
+    }
+}
+
+// Options controlling how a CSV/TSV file is processed
+pub struct TableOptions {
+    // Byte separating fields (a comma for CSV, or a tab for TSV)
+    pub delimiter: u8,
+    // Column holding URLs; if not given, we look for a column named "url", or use the
+    // first column
+    pub column: Option<Column>,
+    // Maximum number of URLs to resolve at once
+    pub concurrency: usize,
+    // Options passed through to each URL's processing
+    pub process: ProcessOptions,
+}
+
+// Resolve and clean a column of URLs in a CSV/TSV file, writing the original rows back
+// out with added `resolved` and `cleaned` columns
+//
+// Rows whose URL could not be processed are kept, with the added columns left empty.
+pub async fn run(
+    resolver: &Resolver,
+    input: impl Read,
+    options: &TableOptions,
+    mut sink: Sink,
+    saved_history: Option<&History>,
+) -> Result<ExitCode, Box<dyn Error>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(options.delimiter)
+        .flexible(true)
+        .from_reader(input);
+    let mut headers = reader.headers()?.clone();
+    let column = find_column(&headers, options.column.as_ref())
+        .ok_or_else(|| format!("no such column: {:?}", options.column))?;
+    let rows = reader.records().collect::<Result<Vec<_>, _>>()?;
+
+    // Spreadsheets often repeat the same link across rows, so we only process each
+    // distinct URL once
+    let mut seen = HashSet::new();
+    let urls: Vec<String> = rows
+        .iter()
+        .filter_map(|row| row.get(column).map(str::trim))
+        .filter(|url| !url.is_empty() && seen.insert(*url))
+        .map(str::to_string)
+        .collect();
+
+    let mut results = pin!(batch::process_all(
+        resolver,
+        &urls,
+        options.concurrency,
+        &options.process
+    ));
+    let mut progress = Progress::new(urls.len());
+    let mut code = ExitCode::Success;
+    let mut processed = HashMap::new();
+    while let Some((url, result)) = results.next().await {
+        progress.record(&result);
+        progress.suspend(|| report::report(&result));
+        match result {
+            Ok(p) => {
+                if let Some(saved_history) = saved_history {
+                    history::record(saved_history, &p);
+                }
+                processed.insert(url.as_str(), (p.resolved, p.cleaned));
+            }
+            Err(e) => {
+                progress.eprintln(&format!("Error: {}: {}", url, e));
+                if code == ExitCode::Success {
+                    code = ExitCode::from(&e);
+                }
+            }
+        }
+    }
+    progress.finish();
+
+    // Write the original rows back out, with the results appended.  Rows may be ragged,
+    // so we pad them to the width of the header first, so that the new columns line up
+    let width = headers.len();
+    headers.push_field("resolved");
+    headers.push_field("cleaned");
+
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(options.delimiter)
+        .flexible(true)
+        .from_writer(&mut sink);
+    writer.write_record(&headers)?;
+    for mut row in rows {
+        let (resolved, cleaned) = row
+            .get(column)
+            .and_then(|url| processed.get(url.trim()))
+            .cloned()
+            .unwrap_or_default();
+        while row.len() < width {
+            row.push_field("");
+        }
+        row.push_field(&resolved);
+        row.push_field(&cleaned);
+        writer.write_record(&row)?;
+    }
+    writer.flush()?;
+    drop(writer);
+
+    sink.finish()?;
+    Ok(code)
+}
+
+// Find the index of the column holding URLs
+fn find_column(headers: &StringRecord, column: Option<&Column>) -> Option<usize> {
+    match column {
+        Some(Column::Index(i)) => (*i < headers.len()).then_some(*i),
+        Some(Column::Name(name)) => headers.iter().position(|h| h == name),
+        None => Some(
+            headers
+                .iter()
+                .position(|h| h.trim().eq_ignore_ascii_case(DEFAULT_COLUMN_NAME))
+                .unwrap_or(0),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers() -> StringRecord {
+        StringRecord::from(vec!["name", "URL", "link"])
+    }
+
+    #[test]
+    fn test_column_from_str() {
+        assert_eq!("2".parse(), Ok(Column::Index(1)));
+        assert_eq!("link".parse(), Ok(Column::Name("link".to_string())));
+        assert_eq!("0".parse(), Ok(Column::Name("0".to_string())));
+    }
+
+    #[test]
+    fn test_find_column() {
+        assert_eq!(find_column(&headers(), None), Some(1));
+        assert_eq!(
+            find_column(&headers(), Some(&Column::Name("link".to_string()))),
+            Some(2)
+        );
+        assert_eq!(find_column(&headers(), Some(&Column::Index(0))), Some(0));
+        assert_eq!(find_column(&headers(), Some(&Column::Index(3))), None);
+        assert_eq!(
+            find_column(&headers(), Some(&Column::Name("missing".to_string()))),
+            None
+        );
+    }
+
+    #[test]
+    fn test_find_column_default() {
+        let headers = StringRecord::from(vec!["link", "notes"]);
+        assert_eq!(find_column(&headers, None), Some(0));
+    }
+}
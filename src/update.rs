@@ -0,0 +1,196 @@
+use std::{
+    error::Error,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use minisign_verify::{PublicKey, Signature};
+use reqwest::StatusCode;
+use url::Url;
+
+use crate::{clean::TrackingParams, exit::ExitCode, sink::AtomicFile};
+
+const RULES_FILE_NAME: &str = "tracking-params.txt";
+
+// Where `rsl update-rules` downloads the tracking parameter list from.  This is pinned to
+// the list published in this repository, next to its minisign signature
+pub const RULES_URL: &str =
+    "https://raw.githubusercontent.com/jakewilliami/rsl/main/data/tracking-params.txt";
+
+// The key the published list must be signed with
+//
+// A hash published next to the list would only catch a corrupted download, as whoever
+// could change the list could change its hash too.  The secret half of this key is kept
+// by the maintainers, not on the server, so a list is only used if they signed it.  To
+// publish a new list, sign it with:
+//
+//     minisign -S -s rsl-rules.key -m data/tracking-params.txt
+const RULES_PUBLIC_KEY: &str = "RWQiRW+rCTli0suVQ+MP5t/JHuHYIRes9prseEPW4y4cVnYLGUCQTiHb";
+
+// Error type for updating the tracking parameter list
+#[derive(Debug, derive_more::Display)]
+pub enum UpdateError {
+    #[display("could not download rules: {_0}")]
+    Request(reqwest::Error),
+    #[display("could not download rules: server returned HTTP {_0}")]
+    Http(StatusCode),
+    #[display("downloaded rules are not signed by the maintainers: {_0}")]
+    Signature(minisign_verify::Error),
+    #[display("downloaded rules are empty")]
+    Empty,
+    Io(io::Error),
+    #[display("could not determine data directory")]
+    NoDataDir,
+}
+
+impl Error for UpdateError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            UpdateError::Request(e) => Some(e),
+            UpdateError::Signature(e) => Some(e),
+            UpdateError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for UpdateError {
+    fn from(e: reqwest::Error) -> Self {
+        UpdateError::Request(e)
+    }
+}
+
+impl From<io::Error> for UpdateError {
+    fn from(e: io::Error) -> Self {
+        UpdateError::Io(e)
+    }
+}
+
+// Where the downloaded list is kept, under the platform's data directory (e.g.,
+// `~/.local/share/rsl/tracking-params.txt` on Linux)
+pub fn default_path() -> Result<PathBuf, UpdateError> {
+    let data_dir = dirs::data_dir().ok_or(UpdateError::NoDataDir)?;
+    Ok(data_dir.join(clap::crate_name!()).join(RULES_FILE_NAME))
+}
+
+// The tracking parameters downloaded by the last `rsl update-rules`, if any.  Without
+// them, we use the list built in to this release
+pub fn load(path: &Path) -> TrackingParams {
+    match fs::read_to_string(path) {
+        Ok(list) => TrackingParams::parse(&list),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => TrackingParams::default(),
+        Err(e) => {
+            tracing::warn!(error = %e, "could not load downloaded tracking parameters");
+            TrackingParams::default()
+        }
+    }
+}
+
+async fn download(client: &reqwest::Client, url: &str) -> Result<String, UpdateError> {
+    let response = client.get(url).send().await?;
+    if !response.status().is_success() {
+        return Err(UpdateError::Http(response.status()));
+    }
+    Ok(response.text().await?)
+}
+
+// Check that `list` was signed with `RULES_PUBLIC_KEY`, given its minisign `signature`
+fn verify(list: &str, signature: &str) -> Result<(), minisign_verify::Error> {
+    let public_key = PublicKey::from_base64(RULES_PUBLIC_KEY)?;
+    let signature = Signature::decode(signature)?;
+    public_key.verify(list.as_bytes(), &signature, false)
+}
+
+// Download the tracking parameter list from `url`, check its signature (at `url` with
+// `.minisig` appended), and save it to `path`, returning what it contains
+//
+// The list is only replaced once it has been checked, so a failed update leaves the
+// previous one (or the built-in list) in use.
+pub async fn update(
+    client: &reqwest::Client,
+    url: &str,
+    path: &Path,
+) -> Result<TrackingParams, UpdateError> {
+    let list = download(client, url).await?;
+    let signature = download(client, &format!("{}.minisig", url)).await?;
+    verify(&list, &signature).map_err(UpdateError::Signature)?;
+    let params = TrackingParams::parse(&list);
+    if params.is_empty() {
+        return Err(UpdateError::Empty);
+    }
+
+    // Written alongside and moved into place, so that we never leave a partial list behind
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = AtomicFile::create(path, false)?;
+    file.write_all(list.as_bytes())?;
+    file.commit()?;
+    Ok(params)
+}
+
+// Download the list to its default path for `rsl update-rules`, through `proxy` if given
+pub async fn run(proxy: Option<&Url>) -> ExitCode {
+    let client = match proxy {
+        Some(proxy) => reqwest::Proxy::all(proxy.as_str())
+            .and_then(|proxy| reqwest::Client::builder().proxy(proxy).build()),
+        None => Ok(reqwest::Client::new()),
+    };
+    let client = match client {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::Failure;
+        }
+    };
+    let result = match default_path() {
+        Ok(path) => update(&client, RULES_URL, &path)
+            .await
+            .map(|params| (params, path)),
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok((params, path)) => {
+            println!(
+                "Downloaded {} tracking parameters to {}",
+                params.len(),
+                path.display()
+            );
+            ExitCode::Success
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::Failure
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUBLISHED: &str = include_str!("../data/tracking-params.txt");
+    const PUBLISHED_SIGNATURE: &str = include_str!("../data/tracking-params.txt.minisig");
+
+    #[test]
+    fn test_published_signature() {
+        // If this fails, sign the list again; see `RULES_PUBLIC_KEY`
+        assert!(verify(PUBLISHED, PUBLISHED_SIGNATURE).is_ok());
+        assert!(verify(&format!("{}evil\n", PUBLISHED), PUBLISHED_SIGNATURE).is_err());
+    }
+
+    #[test]
+    fn test_load() {
+        let dir = std::env::temp_dir().join(format!("rsl-update-{}", std::process::id()));
+        let path = dir.join(RULES_FILE_NAME);
+        assert_eq!(load(&path), TrackingParams::default());
+
+        fs::create_dir_all(&dir).expect("created");
+        fs::write(&path, "# New\nnew_click_id\n").expect("written");
+        let params = load(&path);
+        assert!(params.contains("new_click_id"));
+        assert!(params.contains("fbclid"));
+        fs::remove_dir_all(&dir).expect("removed");
+    }
+}
@@ -0,0 +1,105 @@
+use std::error::Error;
+
+use reqwest::StatusCode;
+use url::Url;
+
+use crate::resolve::{ResolveError, Resolver};
+
+// Reasons that a cleaned URL may no longer work
+#[derive(Debug, derive_more::Display)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum VerifyError {
+    #[display("request failed: {_0}")]
+    Request(ResolveError),
+    #[display("no response")]
+    NoResponse,
+    #[display("returned HTTP {_0}")]
+    Status(
+        #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serialize::display"))]
+        StatusCode,
+    ),
+    #[display("redirected to {_0}")]
+    Redirected(String),
+    #[display("page title changed to {_0:?}")]
+    TitleChanged(String),
+}
+
+impl Error for VerifyError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            VerifyError::Request(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+// Re-fetch a cleaned URL to check that cleaning did not break it
+//
+// The cleaned URL should return a successful status, and should not redirect somewhere
+// other than where the original link resolved to.  This catches cleaners that strip a
+// parameter the site needs (e.g., a video ID), in which case the site will often
+// redirect to its home page or return an error.
+//
+// Given the title of the resolved page, the cleaned page should have the same title, if
+// it has one, which catches sites that show a different page at the same location
+// (e.g., a search page without its query).
+pub async fn verify(
+    resolver: &Resolver,
+    resolved: &str,
+    cleaned: &str,
+    title: Option<&str>,
+) -> Result<(), VerifyError> {
+    let resolution = resolver
+        .resolve_traced(cleaned)
+        .await
+        .map_err(VerifyError::Request)?;
+    let Some(last) = resolution.hops.last() else {
+        return Err(VerifyError::NoResponse);
+    };
+
+    if !last.status.is_success() {
+        return Err(VerifyError::Status(last.status));
+    }
+
+    if !same_location(&last.url, resolved) && !same_location(&last.url, cleaned) {
+        return Err(VerifyError::Redirected(last.url.clone()));
+    }
+
+    if let (Some(expected), Some(actual)) = (title, &last.title)
+        && expected != actual
+    {
+        return Err(VerifyError::TitleChanged(actual.clone()));
+    }
+
+    Ok(())
+}
+
+// Whether two URLs point at the same host and path, ignoring the query, fragment, and
+// any trailing slash
+fn same_location(a: &str, b: &str) -> bool {
+    let (Ok(a), Ok(b)) = (Url::parse(a), Url::parse(b)) else {
+        return false;
+    };
+    a.host_str() == b.host_str() && a.path().trim_end_matches('/') == b.path().trim_end_matches('/')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_location() {
+        assert!(same_location(
+            "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m/?share_id=abc",
+            "https://www.reddit.com/r/AskTheWorld/comments/1q2rw7m",
+        ));
+        assert!(!same_location(
+            "https://www.youtube.com/",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+        ));
+        assert!(!same_location(
+            "https://m.facebook.com/reel/1309748351194528",
+            "https://www.facebook.com/reel/1309748351194528",
+        ));
+    }
+}
@@ -0,0 +1,47 @@
+use wasm_bindgen::prelude::*;
+
+use crate::clean::{self, CleanOptions, Explanation, FragmentPolicy};
+
+// JavaScript interface to the cleaning rules, for use in the browser (e.g., by an
+// extension).  Resolution is left to the caller, as it is not possible to follow
+// redirects manually from a browser
+//
+// Build with `wasm-pack build --target web`.
+
+// Clean a (resolved) URL using the default options
+#[wasm_bindgen]
+pub fn clean(url: &str) -> Result<String, JsError> {
+    clean::clean_url(url)
+        .map(String::from)
+        .map_err(|e| JsError::new(&e.to_string()))
+}
+
+// Clean a (resolved) URL, overriding the parameters kept or stripped, and how fragments
+// are handled ("keep", "strip", or "auto")
+#[wasm_bindgen(js_name = cleanWithOptions)]
+pub fn clean_with_options(
+    url: &str,
+    keep_params: Vec<String>,
+    strip_params: Vec<String>,
+    fragments: &str,
+) -> Result<String, JsError> {
+    let fragments: FragmentPolicy = fragments.parse()?;
+    let options = CleanOptions {
+        keep_params,
+        strip_params,
+        fragments,
+        ..Default::default()
+    };
+    clean::clean_url_explained(url, &options, &mut Explanation::default())
+        .map(String::from)
+        .map_err(|e| JsError::new(&e.to_string()))
+}
+
+// Every domain with a dedicated cleaner
+#[wasm_bindgen(js_name = supportedDomains)]
+pub fn supported_domains() -> Vec<String> {
+    clean::supported_platforms()
+        .into_iter()
+        .map(|platform| platform.domain)
+        .collect()
+}